@@ -0,0 +1,653 @@
+//! Evaluation of the alert rules of a service against changes to its state, and the state an
+//! alert goes through once its rule fires.
+use actix::Addr;
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use chrono::{DateTime, Duration, Utc};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        Acknowledgement, AlertRule, AlertRuleRepr, AlertState, AlertStatus, AlertTrigger, DbFind,
+        EscalationTier, Event, EventKind, HealthStatus, Service,
+    },
+    PgPool,
+};
+use serde::Serialize;
+
+use crate::{
+    settings::AlertSettings,
+    websocket::{Publish, Server},
+    Error,
+};
+
+/// A change to a service seen by the websocket server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The service opened a session.
+    Connected,
+    /// The service's session closed or timed out.
+    Disconnected,
+    Health {
+        from: HealthStatus,
+        to: HealthStatus,
+    },
+    /// The service started running another config than it last reported.
+    Config {
+        assigned: Option<String>,
+        running: String,
+    },
+    /// The service reported a metric. `previous` is the last value it reported, if any.
+    Metric {
+        name: String,
+        previous: Option<f64>,
+        value: f64,
+    },
+}
+
+/// A notification about an alert: that it fired, resolved or escalated.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub alert_rule_id: i32,
+    pub asset_group_id: i32,
+    /// `on` of the rule.
+    pub trigger: &'static str,
+    pub service_name: String,
+    pub address: String,
+    /// Notifications with the same key in an asset group are about the same alert.
+    pub dedup_key: String,
+    pub status: AlertStatus,
+    pub sender: String,
+    pub recipients: Vec<String>,
+    /// Names of the notification channels of the rule.
+    pub channels: Vec<String>,
+    pub message: String,
+}
+impl Alert {
+    /// A firing alert of a rule on a service.
+    pub fn new(alert_rule: &AlertRule, service: &Service, message: String) -> Self {
+        let rule = &alert_rule.rule;
+        Self {
+            alert_rule_id: alert_rule.alert_rule_id,
+            asset_group_id: alert_rule.asset_group_id,
+            trigger: rule.trigger.name(),
+            service_name: service.name.clone(),
+            address: service.address.clone(),
+            dedup_key: rule.dedup_key(&service.address),
+            status: AlertStatus::Firing,
+            sender: rule.sender.clone(),
+            recipients: rule.recipients.clone(),
+            channels: rule.channels.clone(),
+            message,
+        }
+    }
+
+    /// The alert, resolved.
+    pub fn resolved(self) -> Self {
+        Self {
+            status: AlertStatus::Resolved,
+            message: format!("Resolved: {}", self.message),
+            ..self
+        }
+    }
+
+    /// The alert, sent to an escalation tier rather than to the rule's own recipients.
+    pub fn escalated(self, tier: &EscalationTier) -> Self {
+        Self {
+            recipients: tier.recipients.clone(),
+            channels: tier.channels.clone(),
+            message: format!("Unacknowledged: {}", self.message),
+            ..self
+        }
+    }
+
+    /// The one notice sent when the alert starts flapping, before it is held back.
+    pub fn flapping(self) -> Self {
+        Self {
+            message: format!("Flapping, held back until it settles: {}", self.message),
+            ..self
+        }
+    }
+}
+
+/// Whether `value` is past a threshold, where `above` says on which side.
+fn past(value: f64, threshold: f64, above: bool) -> bool {
+    if above {
+        value > threshold
+    } else {
+        value < threshold
+    }
+}
+
+/// Whether a health rule with `targets` holds for a service at `health`: any of the targets,
+/// or anything but healthy without any.
+fn targeted(targets: &[HealthStatus], health: HealthStatus) -> bool {
+    if targets.is_empty() {
+        health != HealthStatus::Healthy
+    } else {
+        targets.contains(&health)
+    }
+}
+
+/// Describe why a rule fires for a change, or `None` if it does not. Metric rules only fire
+/// when the value crosses a threshold, not while it stays past it.
+fn describe(trigger: &AlertTrigger, service: &Service, change: &Change) -> Option<String> {
+    let who = format!("{} ({})", service.name, service.address);
+    match (trigger, change) {
+        (AlertTrigger::Disconnect, Change::Disconnected) => Some(format!("{} disconnected", who)),
+        (AlertTrigger::Health { to: targets }, Change::Health { from, to })
+            if from != to && targeted(targets, *to) =>
+        {
+            Some(format!("{} went from {:?} to {:?}", who, from, to))
+        }
+        (AlertTrigger::ConfigDrift, Change::Config { assigned, running })
+            if assigned.as_ref() != Some(running) =>
+        {
+            Some(format!(
+                "{} is running config '{}' instead of {}",
+                who,
+                running,
+                match assigned {
+                    Some(assigned) => format!("'{}'", assigned),
+                    None => "none".to_string(),
+                }
+            ))
+        }
+        (
+            AlertTrigger::Metric {
+                metric,
+                above,
+                below,
+            },
+            Change::Metric {
+                name,
+                previous,
+                value,
+            },
+        ) if metric == name => {
+            let crossed = |threshold: Option<f64>, is_above| {
+                let threshold = threshold?;
+                let was_past = previous.is_some_and(|previous| past(previous, threshold, is_above));
+                if past(*value, threshold, is_above) && !was_past {
+                    Some(threshold)
+                } else {
+                    None
+                }
+            };
+            if let Some(threshold) = crossed(*above, true) {
+                Some(format!(
+                    "{} reported {} {} above {}",
+                    who, name, value, threshold
+                ))
+            } else {
+                crossed(*below, false).map(|threshold| {
+                    format!("{} reported {} {} below {}", who, name, value, threshold)
+                })
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether a change means the condition of a rule no longer holds.
+fn clears(trigger: &AlertTrigger, change: &Change) -> bool {
+    match (trigger, change) {
+        (AlertTrigger::Disconnect, Change::Connected) => true,
+        (AlertTrigger::Health { to: targets }, Change::Health { to, .. }) => {
+            !targeted(targets, *to)
+        }
+        (AlertTrigger::ConfigDrift, Change::Config { assigned, running }) => {
+            assigned.as_ref() == Some(running)
+        }
+        (
+            AlertTrigger::Metric {
+                metric,
+                above,
+                below,
+            },
+            Change::Metric { name, value, .. },
+        ) if metric == name => {
+            !above.is_some_and(|threshold| past(*value, threshold, true))
+                && !below.is_some_and(|threshold| past(*value, threshold, false))
+        }
+        _ => false,
+    }
+}
+
+/// Get the alerts fired by a change to a service, in the order of its rules.
+pub fn evaluate(rules: &[AlertRule], service: &Service, change: &Change) -> Vec<Alert> {
+    rules
+        .iter()
+        .filter_map(|alert_rule| {
+            let message = describe(&alert_rule.rule.trigger, service, change)?;
+            Some(Alert::new(alert_rule, service, message))
+        })
+        .collect()
+}
+
+/// Get the rules whose alerts a change to a service resolves.
+pub fn resolve<'a>(rules: &'a [AlertRule], change: &Change) -> Vec<&'a AlertRule> {
+    rules
+        .iter()
+        .filter(|alert_rule| clears(&alert_rule.rule.trigger, change))
+        .collect()
+}
+
+fn seconds(seconds: u64) -> Duration {
+    Duration::seconds(seconds as i64)
+}
+
+/// Whether a pending alert has held for the `for` of its rule.
+pub fn due_to_fire(rule: &AlertRuleRepr, state: &AlertState, now: DateTime<Utc>) -> bool {
+    state.status == AlertStatus::Pending && now - state.since >= seconds(rule.for_seconds.into())
+}
+
+/// The escalation tier of a rule to notify next about a firing alert, once it is due. Alerts
+/// that are acknowledged or flapping are not escalated.
+pub fn due_tier<'a>(
+    rule: &'a AlertRuleRepr,
+    state: &AlertState,
+    now: DateTime<Utc>,
+) -> Option<&'a EscalationTier> {
+    if state.status != AlertStatus::Firing || state.acknowledged_at.is_some() || state.flapping {
+        return None;
+    }
+    let tier = rule.escalate.get(state.escalations as usize)?;
+    if now - state.fired_at? >= seconds(tier.after.into()) {
+        Some(tier)
+    } else {
+        None
+    }
+}
+
+/// Note that an alert fired or resolved at `now`, forgetting what happened before the flap
+/// window. Returns whether the alert changed often enough within the window to be flapping.
+pub fn note_transition(
+    state: &mut AlertState,
+    now: DateTime<Utc>,
+    settings: &AlertSettings,
+) -> bool {
+    let window = seconds(settings.flap_window);
+    state.transitions.retain(|&at| now - at < window);
+    state.transitions.push(now);
+    settings.flap_threshold > 0 && state.transitions.len() >= settings.flap_threshold
+}
+
+/// Whether a flapping alert has not fired or resolved for a whole flap window.
+pub fn settled(state: &AlertState, now: DateTime<Utc>, settings: &AlertSettings) -> bool {
+    let window = seconds(settings.flap_window);
+    state.flapping && state.transitions.iter().all(|&at| now - at >= window)
+}
+
+/// Lists the alerts of an asset group that ever fired or were pending, most recent first.
+pub async fn list(path: web::Path<i32>, pool: Data<PgPool>) -> Result<HttpResponse, Error> {
+    let conn = get_conn(&pool)?;
+    let states = AlertState::get_group(&conn, path.into_inner())?;
+    Ok(HttpResponse::Ok().json(states))
+}
+
+/// Acknowledges a firing alert, which stops its escalation until it fires again.
+pub async fn acknowledge(
+    path: web::Path<(i32, i32)>,
+    body: web::Json<Acknowledgement>,
+    pool: Data<PgPool>,
+    server: Data<Addr<Server>>,
+) -> Result<HttpResponse, Error> {
+    let (asset_group_id, alert_state_id) = path.into_inner();
+    let conn = get_conn(&pool)?;
+    let mut state = AlertState::find(&conn, alert_state_id)?;
+    if state.asset_group_id != asset_group_id {
+        return Err(Error::NotFound(format!(
+            "Alert {} not found",
+            alert_state_id
+        )));
+    }
+    if state.status != AlertStatus::Firing {
+        return Err(Error::Conflict(format!(
+            "Alert {} is not firing",
+            alert_state_id
+        )));
+    }
+    let by = body.into_inner().by;
+    state.acknowledged_at = Some(Utc::now());
+    state.acknowledged_by = by.clone();
+    let state = state.save(&conn)?;
+
+    let event = Event::new(
+        asset_group_id,
+        EventKind::AlertAcknowledged {
+            address: state.address.clone(),
+            alert_rule_id: state.alert_rule_id,
+            by,
+        },
+    );
+    event.log(&conn)?;
+    server.do_send(Publish(event));
+    Ok(HttpResponse::Ok().json(state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(alert_rule_id: i32, trigger: AlertTrigger) -> AlertRule {
+        AlertRule {
+            alert_rule_id,
+            asset_group_id: 1,
+            service_id: 1,
+            rule: AlertRuleRepr {
+                trigger,
+                sender: "pr0t0nalerts@gmail.com".to_string(),
+                recipients: vec!["joshikatsu@gmail.com".to_string()],
+                channels: vec![],
+                key: None,
+                for_seconds: 0,
+                escalate: vec![],
+            },
+        }
+    }
+
+    fn state(status: AlertStatus, at: DateTime<Utc>) -> AlertState {
+        AlertState {
+            alert_state_id: 1,
+            asset_group_id: 1,
+            dedup_key: "localhost:5678/disconnect".to_string(),
+            alert_rule_id: 1,
+            address: "localhost:5678".to_string(),
+            status,
+            message: "camera (localhost:5678) disconnected".to_string(),
+            since: at,
+            fired_at: Some(at).filter(|_| status == AlertStatus::Firing),
+            resolved_at: None,
+            acknowledged_at: None,
+            acknowledged_by: None,
+            escalations: 0,
+            flapping: false,
+            transitions: vec![],
+        }
+    }
+
+    fn service() -> Service {
+        Service {
+            service_id: 1,
+            asset_group_id: 1,
+            name: "camera".to_string(),
+            address: "localhost:5678".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn fired(rules: &[AlertRule], change: Change) -> Vec<i32> {
+        evaluate(rules, &service(), &change)
+            .into_iter()
+            .map(|alert| alert.alert_rule_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let rules = vec![
+            rule(1, AlertTrigger::Disconnect),
+            rule(2, AlertTrigger::Health { to: vec![] }),
+            rule(
+                3,
+                AlertTrigger::Health {
+                    to: vec![HealthStatus::Critical],
+                },
+            ),
+            rule(4, AlertTrigger::ConfigDrift),
+        ];
+        assert_eq!(fired(&rules, Change::Disconnected), vec![1]);
+        let health = |from, to| Change::Health { from, to };
+        assert_eq!(
+            fired(&rules, health(HealthStatus::Healthy, HealthStatus::Warning)),
+            vec![2]
+        );
+        assert_eq!(
+            fired(
+                &rules,
+                health(HealthStatus::Warning, HealthStatus::Critical)
+            ),
+            vec![2, 3]
+        );
+        assert!(fired(
+            &rules,
+            health(HealthStatus::Critical, HealthStatus::Critical)
+        )
+        .is_empty());
+
+        let alerts = evaluate(
+            &rules,
+            &service(),
+            &Change::Config {
+                assigned: Some("camera-config".to_string()),
+                running: "old-config".to_string(),
+            },
+        );
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(
+            alerts[0].message,
+            "camera (localhost:5678) is running config 'old-config' instead of 'camera-config'"
+        );
+        assert_eq!(alerts[0].recipients, vec!["joshikatsu@gmail.com"]);
+    }
+
+    #[test]
+    fn test_metric_thresholds() {
+        let rules = vec![
+            rule(
+                1,
+                AlertTrigger::Metric {
+                    metric: "cpu".to_string(),
+                    above: Some(0.9),
+                    below: None,
+                },
+            ),
+            rule(
+                2,
+                AlertTrigger::Metric {
+                    metric: "fps".to_string(),
+                    above: Some(60.0),
+                    below: Some(10.0),
+                },
+            ),
+        ];
+        let metric = |name: &str, previous, value| Change::Metric {
+            name: name.to_string(),
+            previous,
+            value,
+        };
+        assert_eq!(fired(&rules, metric("cpu", None, 0.95)), vec![1]);
+        assert!(fired(&rules, metric("cpu", None, 0.5)).is_empty());
+        // Staying above the threshold does not fire again.
+        assert!(fired(&rules, metric("cpu", Some(0.95), 0.99)).is_empty());
+        assert_eq!(fired(&rules, metric("cpu", Some(0.5), 0.95)), vec![1]);
+
+        assert_eq!(fired(&rules, metric("fps", Some(30.0), 5.0)), vec![2]);
+        assert_eq!(fired(&rules, metric("fps", Some(5.0), 61.0)), vec![2]);
+        assert!(fired(&rules, metric("fps", Some(5.0), 30.0)).is_empty());
+        assert!(fired(&rules, metric("memory", None, 1e9)).is_empty());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let rules = vec![
+            rule(1, AlertTrigger::Disconnect),
+            rule(2, AlertTrigger::Health { to: vec![] }),
+            rule(
+                3,
+                AlertTrigger::Health {
+                    to: vec![HealthStatus::Critical],
+                },
+            ),
+            rule(4, AlertTrigger::ConfigDrift),
+            rule(
+                5,
+                AlertTrigger::Metric {
+                    metric: "fps".to_string(),
+                    above: Some(60.0),
+                    below: Some(10.0),
+                },
+            ),
+        ];
+        let resolved = |change| -> Vec<i32> {
+            resolve(&rules, &change)
+                .into_iter()
+                .map(|alert_rule| alert_rule.alert_rule_id)
+                .collect()
+        };
+        assert_eq!(resolved(Change::Connected), vec![1]);
+        assert!(resolved(Change::Disconnected).is_empty());
+        let health = |from, to| Change::Health { from, to };
+        assert_eq!(
+            resolved(health(HealthStatus::Critical, HealthStatus::Healthy)),
+            vec![2, 3]
+        );
+        assert_eq!(
+            resolved(health(HealthStatus::Critical, HealthStatus::Warning)),
+            vec![3]
+        );
+        // Rules without targets fire on anything but healthy.
+        assert_eq!(
+            fired(&rules, health(HealthStatus::Warning, HealthStatus::Healthy)),
+            Vec::<i32>::new()
+        );
+
+        let config = |running: &str| Change::Config {
+            assigned: Some("camera-config".to_string()),
+            running: running.to_string(),
+        };
+        assert_eq!(resolved(config("camera-config")), vec![4]);
+        assert!(resolved(config("old-config")).is_empty());
+        assert!(fired(&rules, config("camera-config")).is_empty());
+
+        let fps = |value| Change::Metric {
+            name: "fps".to_string(),
+            previous: Some(5.0),
+            value,
+        };
+        assert_eq!(resolved(fps(30.0)), vec![5]);
+        assert!(resolved(fps(5.0)).is_empty());
+        assert!(resolved(fps(61.0)).is_empty());
+    }
+
+    #[test]
+    fn test_dedup_key() {
+        let mut alert_rule = rule(1, AlertTrigger::Disconnect);
+        let alert = Alert::new(&alert_rule, &service(), "down".to_string());
+        assert_eq!(alert.dedup_key, r#"localhost:5678/{"on":"disconnect"}"#);
+        assert_eq!(alert.status, AlertStatus::Firing);
+        alert_rule.rule.key = Some("lobby".to_string());
+        let alert = Alert::new(&alert_rule, &service(), "down".to_string());
+        assert_eq!(alert.dedup_key, "lobby");
+        let resolved = alert.resolved();
+        assert_eq!(resolved.status, AlertStatus::Resolved);
+        assert_eq!(resolved.message, "Resolved: down");
+    }
+
+    #[test]
+    fn test_for_and_escalation() {
+        let now = Utc::now();
+        let mut alert_rule = rule(1, AlertTrigger::Disconnect).rule;
+        alert_rule.for_seconds = 60;
+        alert_rule.escalate = vec![
+            EscalationTier {
+                after: 300,
+                recipients: vec![],
+                channels: vec!["manager".to_string()],
+            },
+            EscalationTier {
+                after: 900,
+                recipients: vec![],
+                channels: vec!["director".to_string()],
+            },
+        ];
+
+        let pending = state(AlertStatus::Pending, now);
+        assert!(!due_to_fire(
+            &alert_rule,
+            &pending,
+            now + Duration::seconds(59)
+        ));
+        assert!(due_to_fire(
+            &alert_rule,
+            &pending,
+            now + Duration::seconds(60)
+        ));
+        assert_eq!(
+            due_tier(&alert_rule, &pending, now + Duration::hours(1)),
+            None
+        );
+
+        let mut firing = state(AlertStatus::Firing, now);
+        assert!(!due_to_fire(&alert_rule, &firing, now + Duration::hours(1)));
+        assert_eq!(due_tier(&alert_rule, &firing, now), None);
+        assert_eq!(
+            due_tier(&alert_rule, &firing, now + Duration::seconds(300)),
+            Some(&alert_rule.escalate[0])
+        );
+        firing.escalations = 1;
+        assert_eq!(
+            due_tier(&alert_rule, &firing, now + Duration::seconds(300)),
+            None
+        );
+        assert_eq!(
+            due_tier(&alert_rule, &firing, now + Duration::seconds(900)),
+            Some(&alert_rule.escalate[1])
+        );
+        firing.escalations = 2;
+        assert_eq!(
+            due_tier(&alert_rule, &firing, now + Duration::days(1)),
+            None
+        );
+
+        // Acknowledged alerts are not escalated.
+        let mut acknowledged = state(AlertStatus::Firing, now);
+        acknowledged.acknowledged_at = Some(now);
+        assert_eq!(
+            due_tier(&alert_rule, &acknowledged, now + Duration::days(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_flapping() {
+        let settings = AlertSettings {
+            flap_window: 60,
+            flap_threshold: 3,
+            ..Default::default()
+        };
+        let now = Utc::now();
+        let mut alert = state(AlertStatus::Firing, now);
+        assert!(!note_transition(&mut alert, now, &settings));
+        assert!(!note_transition(
+            &mut alert,
+            now + Duration::seconds(10),
+            &settings
+        ));
+        assert!(note_transition(
+            &mut alert,
+            now + Duration::seconds(20),
+            &settings
+        ));
+
+        // Transitions before the window are forgotten.
+        let later = now + Duration::seconds(75);
+        assert!(!note_transition(&mut alert, later, &settings));
+        assert_eq!(alert.transitions.len(), 2);
+
+        alert.flapping = true;
+        assert!(!settled(&alert, later + Duration::seconds(59), &settings));
+        assert!(settled(&alert, later + Duration::seconds(60), &settings));
+
+        let never = AlertSettings {
+            flap_threshold: 0,
+            ..settings
+        };
+        let mut alert = state(AlertStatus::Firing, now);
+        for _ in 0..10 {
+            assert!(!note_transition(&mut alert, now, &never));
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use diesel::{Connection, PgConnection};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{AssetGroup, AssetGroupChanges, AssetGroupRepr, AssetGroupSummary, DbDelete, DbFind},
+    PgPool,
+};
+use serde::Deserialize;
+
+use crate::Error;
+
+fn validate_name(name: &str) -> Result<(), Error> {
+    if name.trim().is_empty() {
+        return Err(Error::InvalidField {
+            field: "name".to_string(),
+            message: "Asset group name must not be empty".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn find(conn: &PgConnection, asset_group_id: i32) -> Result<AssetGroup, Error> {
+    AssetGroup::find(conn, asset_group_id).map_err(|err| match err {
+        diesel::result::Error::NotFound => {
+            Error::NotFound(format!("Asset group {} not found", asset_group_id))
+        }
+        err => err.into(),
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteQuery {
+    /// Also delete the services, configs and edges of the asset group.
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+/// Lists asset groups with service counts and aggregate health.
+pub async fn list(pool: Data<PgPool>) -> Result<HttpResponse, Error> {
+    let conn = get_conn(&pool)?;
+    Ok(HttpResponse::Ok().json(AssetGroupSummary::get_all(&conn)?))
+}
+
+pub async fn create(
+    asset_group: web::Json<AssetGroupRepr>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    validate_name(&asset_group.name)?;
+    let conn = get_conn(&pool)?;
+    let inserted = asset_group.insert(&conn)?;
+    Ok(HttpResponse::Created().json(inserted))
+}
+
+pub async fn show(path: web::Path<i32>, pool: Data<PgPool>) -> Result<HttpResponse, Error> {
+    let asset_group_id = path.into_inner();
+    let conn = get_conn(&pool)?;
+    find(&conn, asset_group_id)?;
+    Ok(HttpResponse::Ok().json(AssetGroupSummary::get(&conn, asset_group_id)?))
+}
+
+/// Updates the fields given in the body, leaving the others unchanged.
+pub async fn update(
+    path: web::Path<i32>,
+    changes: web::Json<AssetGroupChanges>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let asset_group_id = path.into_inner();
+    if let Some(name) = &changes.name {
+        validate_name(name)?;
+    }
+    let conn = get_conn(&pool)?;
+    find(&conn, asset_group_id)?;
+    let updated = AssetGroup::patch(&conn, asset_group_id, &changes)?;
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// Deletes an asset group. Groups that still own services, configs or edges are only deleted
+/// with `?cascade=true`.
+pub async fn delete(
+    path: web::Path<i32>,
+    query: web::Query<DeleteQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let asset_group_id = path.into_inner();
+    let conn = get_conn(&pool)?;
+    conn.transaction::<_, Error, _>(|| {
+        // Lock the asset group so nothing is added to it between the check and the delete.
+        if AssetGroup::lock_revision(&conn, asset_group_id)?.is_none() {
+            return Err(Error::NotFound(format!(
+                "Asset group {} not found",
+                asset_group_id
+            )));
+        }
+        let dependents = AssetGroup::get_dependents(&conn, asset_group_id)?;
+        if !query.cascade && !dependents.is_empty() {
+            let counts: Vec<String> = dependents
+                .counts()
+                .into_iter()
+                .map(|(kind, count)| format!("{} {}", count, kind))
+                .collect();
+            return Err(Error::Conflict(format!(
+                "Asset group {} still has {}; pass cascade=true to delete them too",
+                asset_group_id,
+                counts.join(", ")
+            )));
+        }
+        AssetGroup::delete(&conn, asset_group_id)?;
+        Ok(())
+    })?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,136 @@
+use std::convert::TryFrom;
+
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use diesel::Connection;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{AssetRepr, Config, ConfigRepr, ConfigUsageRepr, DbDelete, ServiceLink},
+    PgPool,
+};
+use serde::Deserialize;
+
+use crate::Error;
+
+/// Check a config. `path` is prefixed to the field names in errors, e.g. `configs/0/`.
+pub(crate) fn validate(config: &ConfigRepr, path: &str) -> Result<(), Error> {
+    if config.name.trim().is_empty() {
+        return Err(Error::InvalidField {
+            field: format!("{}name", path),
+            message: "Config name must not be empty".to_string(),
+        });
+    }
+    if !config.json_config.is_object() {
+        return Err(Error::InvalidField {
+            field: format!("{}json_config", path),
+            message: format!("Config '{}' must be a JSON object", config.name),
+        });
+    }
+    Ok(())
+}
+
+fn not_found(name: &str) -> Error {
+    Error::NotFound(format!("Config '{}' not found", name))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteQuery {
+    /// Delete the config even if services use it, leaving them without a config.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Lists the configs of an asset group with the services using each.
+pub async fn list(path: web::Path<i32>, pool: Data<PgPool>) -> Result<HttpResponse, Error> {
+    let conn = get_conn(&pool)?;
+    let configs = ConfigUsageRepr::get_group(&conn, path.into_inner())?;
+    Ok(HttpResponse::Ok().json(configs))
+}
+
+pub async fn create(
+    path: web::Path<i32>,
+    mut config: web::Json<ConfigRepr>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let asset_group_id = path.into_inner();
+    validate(&config, "")?;
+    let conn = get_conn(&pool)?;
+    // Config names are unique, so an existing name is refused with 409 by the database.
+    let inserted = config.insert(&conn, asset_group_id)?;
+    Ok(HttpResponse::Created().json(ConfigRepr::try_from(inserted)?))
+}
+
+pub async fn show(
+    path: web::Path<(i32, String)>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let (asset_group_id, name) = path.into_inner();
+    let conn = get_conn(&pool)?;
+    let config =
+        Config::find_by_name(&conn, asset_group_id, &name)?.ok_or_else(|| not_found(&name))?;
+    Ok(HttpResponse::Ok().json(ConfigRepr::try_from(config)?))
+}
+
+/// Replaces a config. Renaming is allowed by giving a new name in the body.
+pub async fn update(
+    path: web::Path<(i32, String)>,
+    config: web::Json<ConfigRepr>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let (asset_group_id, name) = path.into_inner();
+    validate(&config, "")?;
+    let conn = get_conn(&pool)?;
+    let mut existing =
+        Config::find_by_name(&conn, asset_group_id, &name)?.ok_or_else(|| not_found(&name))?;
+    config.try_merge_asset(&mut existing)?;
+    existing.update(&conn)?;
+    Ok(HttpResponse::Ok().json(ConfigRepr::try_from(existing)?))
+}
+
+/// Lists the services using a config.
+pub async fn used_by(
+    path: web::Path<(i32, String)>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let (asset_group_id, name) = path.into_inner();
+    let conn = get_conn(&pool)?;
+    let config =
+        Config::find_by_name(&conn, asset_group_id, &name)?.ok_or_else(|| not_found(&name))?;
+    let services: Vec<ServiceLink> = config
+        .get_services(&conn)?
+        .into_iter()
+        .map(ServiceLink::from)
+        .collect();
+    Ok(HttpResponse::Ok().json(services))
+}
+
+/// Deletes a config. Configs used by services are only deleted with `?force=true`, since the
+/// database would otherwise silently clear the config of those services.
+pub async fn delete(
+    path: web::Path<(i32, String)>,
+    query: web::Query<DeleteQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let (asset_group_id, name) = path.into_inner();
+    let conn = get_conn(&pool)?;
+    conn.transaction::<_, Error, _>(|| {
+        // Lock the config so no service starts using it between the check and the delete.
+        let config =
+            Config::lock_by_name(&conn, asset_group_id, &name)?.ok_or_else(|| not_found(&name))?;
+        let services = config.get_services(&conn)?;
+        if !query.force && !services.is_empty() {
+            let addresses: Vec<&str> = services.iter().map(|s| s.address.as_str()).collect();
+            return Err(Error::Conflict(format!(
+                "Config '{}' is used by {}; pass force=true to delete it and leave them \
+                 without a config",
+                name,
+                addresses.join(", ")
+            )));
+        }
+        Config::delete(&conn, config.config_id)?;
+        Ok(())
+    })?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,5 +1,5 @@
-use actix_web::{self, error::ResponseError, HttpResponse};
-use serde::{Deserialize, Serialize};
+use actix_web::{self, error::ResponseError, http::StatusCode, HttpResponse};
+pub use pr0t0n_orch_db::models::{ErrorCode, ErrorDetail, ErrorResponse};
 
 #[derive(Debug)]
 pub enum Error {
@@ -12,15 +12,52 @@ pub enum Error {
     Pr0t0nDbError(pr0t0n_orch_db::Error),
     NotFound(String),
     UnprocessableEntity(String),
+    /// A field of the request body is invalid. `field` is its path, e.g. `services/2/address`.
+    InvalidField {
+        field: String,
+        message: String,
+    },
+    Conflict(String),
+    UnsupportedMediaType(String),
     BlockingError(String),
     SerdeJsonError(serde_json::Error),
+    SerdeYamlError(serde_yaml::Error),
     Forbidden,
+    /// The server settings are unusable. Only reported at startup.
+    InvalidSettings(String),
+    Smtp(lettre::smtp::error::Error),
+    Email(lettre_email::error::Error),
+    Http(ureq::Error),
+    /// A webhook responded with a status other than 2xx.
+    WebhookStatus(u16),
+    /// An alert rule names a notification channel the server does not have.
+    UnknownChannel(String),
 }
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
         Self::SerdeJsonError(err)
     }
 }
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::SerdeYamlError(err)
+    }
+}
+impl From<lettre::smtp::error::Error> for Error {
+    fn from(err: lettre::smtp::error::Error) -> Self {
+        Self::Smtp(err)
+    }
+}
+impl From<lettre_email::error::Error> for Error {
+    fn from(err: lettre_email::error::Error) -> Self {
+        Self::Email(err)
+    }
+}
+impl From<ureq::Error> for Error {
+    fn from(err: ureq::Error) -> Self {
+        Self::Http(err)
+    }
+}
 impl From<r2d2::Error> for Error {
     fn from(e: r2d2::Error) -> Self {
         Self::R2D2(e)
@@ -47,48 +84,138 @@ impl std::fmt::Display for Error {
     }
 }
 
-// User-friendly error messages
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ErrorResponse {
-    pub errors: Vec<String>,
-}
-impl From<&str> for ErrorResponse {
-    fn from(error: &str) -> Self {
-        ErrorResponse {
-            errors: vec![error.into()],
-        }
-    }
+/// Path of the field a unique violation is about, taken from Postgres' `Key (a, b)=(..)` detail.
+/// For composite keys the last column is used, as the leading ones only scope it.
+fn unique_violation_field(details: &str) -> Option<String> {
+    let columns = details.strip_prefix("Key (")?.split(")=").next()?;
+    columns
+        .split(',')
+        .map(str::trim)
+        .next_back()
+        .map(str::to_string)
 }
-impl From<&String> for ErrorResponse {
-    fn from(error: &String) -> Self {
-        ErrorResponse {
-            errors: vec![error.into()],
+
+fn db_error_detail(err: &pr0t0n_orch_db::Error) -> Option<(StatusCode, ErrorDetail)> {
+    use diesel::result::{DatabaseErrorKind, Error as DieselError};
+    use pr0t0n_orch_db::Error as DbError;
+
+    match err {
+        DbError::DatabaseError(DieselError::NotFound) => Some((
+            StatusCode::NOT_FOUND,
+            ErrorDetail::new(ErrorCode::NotFound, "Not found"),
+        )),
+        DbError::DatabaseError(DieselError::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            info,
+        )) => {
+            let mut detail = ErrorDetail::new(
+                ErrorCode::UniqueViolation,
+                info.details().unwrap_or_else(|| info.message()),
+            );
+            detail.field = info
+                .column_name()
+                .map(str::to_string)
+                .or_else(|| unique_violation_field(info.details()?));
+            Some((StatusCode::CONFLICT, detail))
         }
+        DbError::DatabaseSyncError(message) => Some((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorDetail::new(ErrorCode::SyncFailed, message.as_str()),
+        )),
+        DbError::InvalidEnumValue(message) => Some((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorDetail::new(ErrorCode::InvalidEnumValue, message.as_str()),
+        )),
+        DbError::InvalidQuery(message) => Some((
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::new(ErrorCode::BadRequest, message.as_str()),
+        )),
+        _ => None,
     }
 }
-impl From<Vec<String>> for ErrorResponse {
-    fn from(error: Vec<String>) -> Self {
-        ErrorResponse { errors: error }
+
+impl Error {
+    /// Status and machine-readable detail sent to the client, or `None` for internal errors.
+    fn detail(&self) -> Option<(StatusCode, ErrorDetail)> {
+        let (status, code, message) = match self {
+            Error::BadRequest(message) => (StatusCode::BAD_REQUEST, ErrorCode::BadRequest, message),
+            Error::NotFound(message) => (StatusCode::NOT_FOUND, ErrorCode::NotFound, message),
+            Error::UnprocessableEntity(message) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed,
+                message,
+            ),
+            Error::InvalidField { field, message } => {
+                let detail = ErrorDetail::new(ErrorCode::ValidationFailed, message.as_str());
+                return Some((
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    detail.with_field(field.as_str()),
+                ));
+            }
+            Error::Conflict(message) => (StatusCode::CONFLICT, ErrorCode::Conflict, message),
+            Error::UnsupportedMediaType(message) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorCode::UnsupportedMediaType,
+                message,
+            ),
+            Error::Forbidden => {
+                let detail = ErrorDetail::new(ErrorCode::Forbidden, "Forbidden");
+                return Some((StatusCode::FORBIDDEN, detail));
+            }
+            Error::Pr0t0nDbError(err) => return db_error_detail(err),
+            _ => return None,
+        };
+        Some((status, ErrorDetail::new(code, message.as_str())))
     }
 }
+
 impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        self.detail()
+            .map(|(status, _)| status)
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
     fn error_response(&self) -> HttpResponse {
-        match self {
-            Error::BadRequest(error) => {
-                HttpResponse::BadRequest().json::<ErrorResponse>(error.into())
-            }
-            Error::NotFound(message) => {
-                HttpResponse::NotFound().json::<ErrorResponse>(message.into())
-            }
-            Error::UnprocessableEntity(message) => {
-                HttpResponse::UnprocessableEntity().json::<ErrorResponse>(message.into())
-            }
-            Error::Forbidden => HttpResponse::Forbidden().json::<ErrorResponse>("Forbidden".into()),
-            _ => {
+        match self.detail() {
+            Some((status, detail)) => HttpResponse::build(status).json(ErrorResponse::from(detail)),
+            None => {
                 error!("Internal server error: {:?}", self);
-                HttpResponse::InternalServerError()
-                    .json::<ErrorResponse>("Internal Server Error".into())
+                HttpResponse::InternalServerError().json(ErrorResponse::from(ErrorDetail::new(
+                    ErrorCode::Internal,
+                    "Internal Server Error",
+                )))
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique_violation_field() {
+        assert_eq!(
+            unique_violation_field("Key (asset_group_id, name)=(1, camera) already exists."),
+            Some("name".to_string())
+        );
+        assert_eq!(
+            unique_violation_field("Key (address)=(localhost:1) already exists."),
+            Some("address".to_string())
+        );
+        assert_eq!(unique_violation_field("Something else"), None);
+    }
+
+    #[test]
+    fn test_status_codes() {
+        let not_found: Error = diesel::result::Error::NotFound.into();
+        assert_eq!(not_found.status_code(), StatusCode::NOT_FOUND);
+        let sync: Error = pr0t0n_orch_db::Error::DatabaseSyncError("Oops".to_string()).into();
+        assert_eq!(sync.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        let invalid: Error = pr0t0n_orch_db::Error::invalid_enum("Oops".to_string()).into();
+        assert_eq!(invalid.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        let pool = Error::Unknown("Oops".to_string());
+        assert_eq!(pool.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
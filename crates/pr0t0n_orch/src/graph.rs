@@ -0,0 +1,321 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{AssetGroup, HealthStatus, Service, ServiceEdge, ServiceType},
+    PgPool,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Output formats for the service graph.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    #[default]
+    Dot,
+    Mermaid,
+    Ascii,
+}
+impl GraphFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Dot => "text/vnd.graphviz; charset=utf-8",
+            Self::Mermaid | Self::Ascii => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphQuery {
+    #[serde(default)]
+    pub format: GraphFormat,
+}
+
+/// Renders the service graph of an asset group.
+pub async fn graph(
+    path: web::Path<i32>,
+    query: web::Query<GraphQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let asset_group_id = path.into_inner();
+    let conn = get_conn(&pool)?;
+    if AssetGroup::get_revision(&conn, asset_group_id)?.is_none() {
+        return Err(Error::NotFound(format!(
+            "Asset group {} not found",
+            asset_group_id
+        )));
+    }
+    let (services, edges) = Service::get_graph(&conn, asset_group_id)?;
+    let rendered = render(&services, &edges, query.format);
+    Ok(HttpResponse::Ok()
+        .content_type(query.format.content_type())
+        .body(rendered))
+}
+
+/// Renders services as nodes and edges between them in the given format.
+pub fn render(services: &[Service], edges: &[ServiceEdge], format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => render_dot(services, edges),
+        GraphFormat::Mermaid => render_mermaid(services, edges),
+        GraphFormat::Ascii => render_ascii(services, edges),
+    }
+}
+
+fn node_id(service_id: i32) -> String {
+    format!("s{}", service_id)
+}
+
+fn health_color(health_status: HealthStatus) -> &'static str {
+    match health_status {
+        HealthStatus::Healthy => "#4caf50",
+        HealthStatus::Warning => "#ffc107",
+        HealthStatus::Critical => "#f44336",
+        HealthStatus::Disconnected => "#9e9e9e",
+        HealthStatus::Unknown => "#bdbdbd",
+    }
+}
+
+fn health_class(health_status: HealthStatus) -> &'static str {
+    match health_status {
+        HealthStatus::Healthy => "healthy",
+        HealthStatus::Warning => "warning",
+        HealthStatus::Critical => "critical",
+        HealthStatus::Disconnected => "disconnected",
+        HealthStatus::Unknown => "unknown",
+    }
+}
+
+fn dot_shape(service_type: ServiceType) -> &'static str {
+    match service_type {
+        ServiceType::Input => "invhouse",
+        ServiceType::Processor => "box",
+        ServiceType::Output => "house",
+        ServiceType::None => "ellipse",
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn render_dot(services: &[Service], edges: &[ServiceEdge]) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph services {{").unwrap();
+    writeln!(out, "  rankdir=LR;").unwrap();
+    writeln!(out, "  node [style=filled];").unwrap();
+    for service in services {
+        writeln!(
+            out,
+            "  {} [label=\"{}\\n{}\", shape={}, fillcolor=\"{}\"];",
+            node_id(service.service_id),
+            dot_escape(&service.name),
+            dot_escape(&service.address),
+            dot_shape(service.service_type),
+            health_color(service.health_status),
+        )
+        .unwrap();
+    }
+    for edge in edges {
+        writeln!(
+            out,
+            "  {} -> {};",
+            node_id(edge.input_service_id),
+            node_id(edge.output_service_id)
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn mermaid_node(service: &Service) -> String {
+    let label = format!("{}<br/>{}", service.name, service.address).replace('"', "#quot;");
+    let id = node_id(service.service_id);
+    match service.service_type {
+        ServiceType::Input => format!("{}>\"{}\"]", id, label),
+        ServiceType::Processor => format!("{}[\"{}\"]", id, label),
+        ServiceType::Output => format!("{}[(\"{}\")]", id, label),
+        ServiceType::None => format!("{}(\"{}\")", id, label),
+    }
+}
+
+fn render_mermaid(services: &[Service], edges: &[ServiceEdge]) -> String {
+    let mut out = String::new();
+    writeln!(out, "flowchart LR").unwrap();
+    for health_status in &[
+        HealthStatus::Healthy,
+        HealthStatus::Warning,
+        HealthStatus::Critical,
+        HealthStatus::Disconnected,
+        HealthStatus::Unknown,
+    ] {
+        writeln!(
+            out,
+            "  classDef {} fill:{}",
+            health_class(*health_status),
+            health_color(*health_status)
+        )
+        .unwrap();
+    }
+    for service in services {
+        writeln!(out, "  {}", mermaid_node(service)).unwrap();
+        writeln!(
+            out,
+            "  class {} {}",
+            node_id(service.service_id),
+            health_class(service.health_status)
+        )
+        .unwrap();
+    }
+    for edge in edges {
+        writeln!(
+            out,
+            "  {} --> {}",
+            node_id(edge.input_service_id),
+            node_id(edge.output_service_id)
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn ascii_node(service: &Service) -> String {
+    let (open, close) = match service.service_type {
+        ServiceType::Input => (">", ">"),
+        ServiceType::Processor => ("[", "]"),
+        ServiceType::Output => ("(", ")"),
+        ServiceType::None => ("<", ">"),
+    };
+    format!(
+        "{}{}{} {} ({})",
+        open,
+        service.name,
+        close,
+        service.address,
+        health_class(service.health_status)
+    )
+}
+
+fn render_ascii(services: &[Service], edges: &[ServiceEdge]) -> String {
+    let by_id: HashMap<i32, &Service> = services.iter().map(|s| (s.service_id, s)).collect();
+    let mut outputs: BTreeMap<i32, Vec<&Service>> = BTreeMap::new();
+    for edge in edges {
+        if let Some(&output) = by_id.get(&edge.output_service_id) {
+            outputs
+                .entry(edge.input_service_id)
+                .or_default()
+                .push(output);
+        }
+    }
+
+    let mut out = String::new();
+    for service in services {
+        writeln!(out, "{}", ascii_node(service)).unwrap();
+        let service_outputs = outputs
+            .get(&service.service_id)
+            .cloned()
+            .unwrap_or_default();
+        for (i, output) in service_outputs.iter().enumerate() {
+            let branch = if i + 1 == service_outputs.len() {
+                "`--"
+            } else {
+                "|--"
+            };
+            writeln!(out, "  {}> {}", branch, ascii_node(output)).unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> (Vec<Service>, Vec<ServiceEdge>) {
+        let services = vec![
+            Service {
+                service_id: 1,
+                name: "camera".to_string(),
+                address: "localhost:1".to_string(),
+                service_type: ServiceType::Input,
+                health_status: HealthStatus::Healthy,
+                ..Default::default()
+            },
+            Service {
+                service_id: 2,
+                name: "detector".to_string(),
+                address: "localhost:2".to_string(),
+                service_type: ServiceType::Processor,
+                health_status: HealthStatus::Critical,
+                ..Default::default()
+            },
+            Service {
+                service_id: 3,
+                name: "arm".to_string(),
+                address: "localhost:3".to_string(),
+                service_type: ServiceType::Output,
+                health_status: HealthStatus::Disconnected,
+                ..Default::default()
+            },
+        ];
+        let edges = vec![
+            ServiceEdge {
+                input_service_id: 1,
+                output_service_id: 2,
+                ..Default::default()
+            },
+            ServiceEdge {
+                input_service_id: 2,
+                output_service_id: 3,
+                ..Default::default()
+            },
+        ];
+        (services, edges)
+    }
+
+    #[test]
+    fn test_render_dot() {
+        let (services, edges) = graph();
+        let dot = render(&services, &edges, GraphFormat::Dot);
+        assert!(dot.starts_with("digraph services {"));
+        assert!(dot
+            .contains("s2 [label=\"detector\\nlocalhost:2\", shape=box, fillcolor=\"#f44336\"];"));
+        assert!(dot.contains("s1 -> s2;"));
+        assert!(dot.contains("s2 -> s3;"));
+    }
+
+    #[test]
+    fn test_render_mermaid() {
+        let (services, edges) = graph();
+        let mermaid = render(&services, &edges, GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("flowchart LR"));
+        assert!(mermaid.contains("s1>\"camera<br/>localhost:1\"]"));
+        assert!(mermaid.contains("s3[(\"arm<br/>localhost:3\")]"));
+        assert!(mermaid.contains("class s3 disconnected"));
+        assert!(mermaid.contains("s1 --> s2"));
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let (services, edges) = graph();
+        let ascii = render(&services, &edges, GraphFormat::Ascii);
+        assert_eq!(
+            ascii,
+            ">camera> localhost:1 (healthy)\n\
+             \x20 `--> [detector] localhost:2 (critical)\n\
+             [detector] localhost:2 (critical)\n\
+             \x20 `--> (arm) localhost:3 (disconnected)\n\
+             (arm) localhost:3 (disconnected)\n"
+        );
+    }
+}
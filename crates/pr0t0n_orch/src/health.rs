@@ -0,0 +1,140 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use actix::Addr;
+use actix_web::{web::Data, HttpResponse};
+use diesel::RunQueryDsl;
+use pr0t0n_orch_db::{get_conn, models::AssetGroupSummary, PgPool};
+
+use crate::{
+    metrics::{Exposition, METRICS},
+    websocket::{GetStats, Server},
+    Error,
+};
+
+/// How long readiness checks wait for the database and the websocket server.
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Liveness probe. Responds as long as the process can serve requests.
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body("ok")
+}
+
+/// Readiness probe. Checks that a database connection can be used and that the websocket server
+/// answers, responding 503 with the failed checks otherwise.
+pub async fn readyz(pool: Data<PgPool>, server: Data<Addr<Server>>) -> HttpResponse {
+    let mut checks = BTreeMap::new();
+
+    let database = pool
+        .get_timeout(READY_TIMEOUT)
+        .map_err(|err| err.to_string())
+        .and_then(|conn| {
+            diesel::sql_query("SELECT 1")
+                .execute(&conn)
+                .map_err(|err| err.to_string())
+        });
+    checks.insert("database", database.map(|_| "ok".to_string()));
+
+    let server = server.send(GetStats).timeout(READY_TIMEOUT).await;
+    checks.insert(
+        "server",
+        server
+            .map(|_| "ok".to_string())
+            .map_err(|err| err.to_string()),
+    );
+
+    let ready = checks.values().all(Result::is_ok);
+    let checks: BTreeMap<&str, String> = checks
+        .into_iter()
+        .map(|(name, result)| (name, result.unwrap_or_else(|err| err)))
+        .collect();
+    if ready {
+        HttpResponse::Ok().json(checks)
+    } else {
+        HttpResponse::ServiceUnavailable().json(checks)
+    }
+}
+
+/// Serves metrics in the Prometheus text format.
+pub async fn metrics(
+    pool: Data<PgPool>,
+    server: Data<Addr<Server>>,
+) -> Result<HttpResponse, Error> {
+    let mut exposition = Exposition::default();
+
+    let stats = server
+        .send(GetStats)
+        .timeout(READY_TIMEOUT)
+        .await
+        .map_err(|err| Error::InternalServerError(err.to_string()))?;
+    exposition.header(
+        "pr0t0n_websocket_sessions",
+        "Connected service sessions",
+        "gauge",
+    );
+    for (asset_group_id, count) in &stats.sessions {
+        let asset_group_id = asset_group_id.to_string();
+        exposition.sample(
+            "pr0t0n_websocket_sessions",
+            &[("asset_group_id", &asset_group_id)],
+            count,
+        );
+    }
+    exposition.header(
+        "pr0t0n_websocket_watchers",
+        "Connected watcher sessions",
+        "gauge",
+    );
+    for (asset_group_id, count) in &stats.watchers {
+        let asset_group_id = asset_group_id.to_string();
+        exposition.sample(
+            "pr0t0n_websocket_watchers",
+            &[("asset_group_id", &asset_group_id)],
+            count,
+        );
+    }
+
+    // Report the pool before taking a connection from it.
+    let state = pool.state();
+    exposition.gauge(
+        "pr0t0n_db_pool_connections",
+        "Open database connections",
+        state.connections,
+    );
+    exposition.gauge(
+        "pr0t0n_db_pool_idle_connections",
+        "Idle database connections",
+        state.idle_connections,
+    );
+    exposition.gauge(
+        "pr0t0n_db_pool_max_connections",
+        "Maximum database connections",
+        pool.max_size(),
+    );
+
+    let conn = get_conn(&pool)?;
+    exposition.header(
+        "pr0t0n_services",
+        "Services by asset group and health status",
+        "gauge",
+    );
+    for summary in AssetGroupSummary::get_all(&conn)? {
+        let asset_group_id = summary.asset_group.asset_group_id.to_string();
+        let mut counts: Vec<_> = summary.health_counts.into_iter().collect();
+        counts.sort_by_key(|(health_status, _)| health_status.severity());
+        for (health_status, count) in counts {
+            exposition.sample(
+                "pr0t0n_services",
+                &[
+                    ("asset_group_id", &asset_group_id),
+                    ("health_status", &format!("{:?}", health_status)),
+                ],
+                count,
+            );
+        }
+    }
+
+    METRICS.render(&mut exposition);
+    Ok(HttpResponse::Ok()
+        .content_type(Exposition::CONTENT_TYPE)
+        .body(exposition.into_string()))
+}
@@ -1,6 +1,18 @@
 //! Pr0t0n Orchestrator.
+pub mod alerts;
+pub mod asset_groups;
+pub mod configs;
 pub mod errors;
 pub use errors::Error;
+pub mod graph;
+pub mod health;
+pub mod maintenance;
+pub mod metrics;
+pub mod notify;
+pub mod openapi;
+pub mod registration;
+pub mod services;
+pub mod settings;
 pub mod sync;
 pub mod websocket;
 
@@ -9,18 +21,152 @@ extern crate log;
 
 pub mod testing;
 
-use actix_web::{web, Responder};
+use actix_web::{web, Resource, Responder};
 
+use settings::Features;
+
+/// Register every route.
 pub fn routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::resource("/").route(web::get().to(index)));
-    cfg.service(web::resource("/ws/").route(web::get().to(websocket::ws_index)));
-    cfg.service(web::resource("/sync/upload/").route(web::post().to(sync::upload)));
-    cfg.service(web::resource("/sync/download/").route(web::get().to(sync::download)));
+    routes_with(cfg, &Features::default())
+}
+
+/// Register the routes, leaving out disabled features.
+pub fn routes_with(cfg: &mut web::ServiceConfig, features: &Features) {
+    // Report malformed bodies, queries and paths in the same format as other errors.
+    cfg.app_data(
+        web::JsonConfig::default()
+            .error_handler(|err, _| Error::BadRequest(err.to_string()).into()),
+    );
+    cfg.app_data(
+        web::QueryConfig::default()
+            .error_handler(|err, _| Error::BadRequest(err.to_string()).into()),
+    );
+    cfg.app_data(
+        web::PathConfig::default()
+            .error_handler(|err, _| Error::BadRequest(err.to_string()).into()),
+    );
+    for (_path, resource) in resources(features) {
+        cfg.service(resource);
+    }
+}
+
+/// Paths and resources of every route, leaving out disabled features. `routes_with` registers
+/// these, and the OpenAPI tests check the paths against the document.
+pub fn resources(features: &Features) -> Vec<(&'static str, Resource)> {
+    let mut resources = Vec::new();
+    let mut add = |path: &'static str, route: fn(Resource) -> Resource| {
+        resources.push((path, route(web::resource(path))));
+    };
+    add("/", |resource| resource.route(web::get().to(index)));
+    add("/healthz", |resource| {
+        resource.route(web::get().to(health::healthz))
+    });
+    add("/readyz", |resource| {
+        resource.route(web::get().to(health::readyz))
+    });
+    if features.metrics {
+        add("/metrics", |resource| {
+            resource.route(web::get().to(health::metrics))
+        });
+    }
+    if features.openapi {
+        add("/openapi.json", |resource| {
+            resource.route(web::get().to(openapi::openapi))
+        });
+    }
+    add("/ws/", |resource| {
+        resource.route(web::get().to(websocket::ws_index))
+    });
+    if features.watch {
+        add("/ws/watch/", |resource| {
+            resource.route(web::get().to(websocket::ws_watch))
+        });
+    }
+    add("/sync/upload/", |resource| {
+        resource.route(web::post().to(sync::upload))
+    });
+    add("/asset-groups/", |resource| {
+        resource
+            .route(web::get().to(asset_groups::list))
+            .route(web::post().to(asset_groups::create))
+    });
+    add("/asset-groups/{asset_group_id}", |resource| {
+        resource
+            .route(web::get().to(asset_groups::show))
+            .route(web::patch().to(asset_groups::update))
+            .route(web::delete().to(asset_groups::delete))
+    });
+    add("/asset-groups/{asset_group_id}/system", |resource| {
+        resource
+            .route(web::get().to(sync::system))
+            .route(web::patch().to(sync::patch))
+    });
+    add("/asset-groups/{asset_group_id}/graph", |resource| {
+        resource.route(web::get().to(graph::graph))
+    });
+    add("/asset-groups/{asset_group_id}/services", |resource| {
+        resource.route(web::get().to(services::list))
+    });
+    add(
+        "/asset-groups/{asset_group_id}/services/{service_id}",
+        |resource| resource.route(web::get().to(services::show)),
+    );
+    add(
+        "/asset-groups/{asset_group_id}/reconciliation",
+        |resource| resource.route(web::get().to(services::reconciliation)),
+    );
+    add("/asset-groups/{asset_group_id}/alerts", |resource| {
+        resource.route(web::get().to(alerts::list))
+    });
+    add(
+        "/asset-groups/{asset_group_id}/alerts/{alert_state_id}/acknowledge",
+        |resource| resource.route(web::post().to(alerts::acknowledge)),
+    );
+    add(
+        "/asset-groups/{asset_group_id}/maintenance-windows",
+        |resource| {
+            resource
+                .route(web::get().to(maintenance::list))
+                .route(web::post().to(maintenance::create))
+        },
+    );
+    add(
+        "/asset-groups/{asset_group_id}/maintenance-windows/{window_id}/expire",
+        |resource| resource.route(web::post().to(maintenance::expire)),
+    );
+    add(
+        "/asset-groups/{asset_group_id}/pending-services",
+        |resource| resource.route(web::get().to(registration::list)),
+    );
+    add(
+        "/asset-groups/{asset_group_id}/pending-services/{pending_id}",
+        |resource| resource.route(web::delete().to(registration::reject)),
+    );
+    add(
+        "/asset-groups/{asset_group_id}/pending-services/{pending_id}/approve",
+        |resource| resource.route(web::post().to(registration::approve)),
+    );
+    add("/asset-groups/{asset_group_id}/configs/", |resource| {
+        resource
+            .route(web::get().to(configs::list))
+            .route(web::post().to(configs::create))
+    });
+    add(
+        "/asset-groups/{asset_group_id}/configs/{name}",
+        |resource| {
+            resource
+                .route(web::get().to(configs::show))
+                .route(web::put().to(configs::update))
+                .route(web::delete().to(configs::delete))
+        },
+    );
+    add(
+        "/asset-groups/{asset_group_id}/configs/{name}/used-by",
+        |resource| resource.route(web::get().to(configs::used_by)),
+    );
+    resources
 }
 
-pub async fn index(// mut system: web::Json<SystemRepr>,
-    // pool: Data<PgPool>,
-) -> Result<impl Responder, Error> {
-    println!("Log index!");
+pub async fn index() -> Result<impl Responder, Error> {
     Ok("Index")
 }
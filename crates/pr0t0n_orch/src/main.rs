@@ -1,27 +1,104 @@
-//! Simple echo websocket server.
+//! Pr0t0n Orchestrator server.
 use actix::Actor;
 use actix_web::{middleware, App, HttpServer};
-use pr0t0n_orch::{routes, websocket};
+use futures::future;
+use log::{error, info};
+use structopt::StructOpt;
+
+use pr0t0n_orch::{
+    notify::Dispatcher,
+    routes_with,
+    settings::{Opt, Settings},
+    websocket::{self, Reconcile, Shutdown},
+};
 use pr0t0n_orch_db::{new_pool, PgPool};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "info");
-    env_logger::init();
+    // Let `.env` provide `DATABASE_URL` and the other variables read by the options.
+    dotenv::dotenv().ok();
+    let settings = match Settings::load(Opt::from_args()) {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    env_logger::Builder::new()
+        .parse_filters(&settings.server.log_level)
+        .init();
 
-    let pool: PgPool = new_pool();
-    let server = websocket::Server::new(pool.clone()).start();
+    let pool: PgPool = new_pool(&settings.database);
+    let mut server = websocket::Server::new(pool.clone())
+        .with_alert_settings(settings.alerts.clone())
+        .with_register_undeclared(settings.websocket.register_undeclared);
+    if settings.has_notifications() {
+        if settings.smtp.is_enabled() {
+            info!(
+                "Emailing alerts through {}:{}",
+                settings.smtp.host, settings.smtp.port
+            );
+        }
+        for name in settings.channels.keys() {
+            info!("Sending alerts through channel {}", name);
+        }
+        let notifier = Dispatcher::from_settings(&settings, pool.clone()).start();
+        server = server.with_notifier(notifier);
+    }
+    let server = server.start();
+    server.do_send(Reconcile {
+        grace_period: settings.websocket.reconcile_grace_period(),
+    });
 
-    HttpServer::new(move || {
+    let websocket_settings = settings.websocket.clone();
+    let features = settings.features.clone();
+    let app_server = server.clone();
+    let mut http_server = HttpServer::new(move || {
         App::new()
             .data(pool.clone())
-            .data(server.clone())
+            .data(app_server.clone())
+            .data(websocket_settings.clone())
             .wrap(middleware::Logger::default())
-            .configure(routes)
+            .configure(|cfg| routes_with(cfg, &features))
     })
-    // start http server on 127.0.0.1:8080
-    .workers(4)
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    .workers(settings.server.workers)
+    .keep_alive(settings.server.keep_alive)
+    .shutdown_timeout(settings.server.shutdown_timeout)
+    // Signals are handled below, so sessions are closed while the workers still run.
+    .disable_signals();
+    for addr in &settings.server.bind {
+        http_server = http_server.bind(addr)?;
+    }
+    let running = http_server.run();
+
+    let stopping = running.clone();
+    actix_rt::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down.");
+        match server.send(Shutdown).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => error!("Error disconnecting services: {:?}", err),
+            Err(err) => error!("Error closing sessions: {:?}", err),
+        }
+        stopping.stop(true).await;
+    });
+    running.await
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM on Unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use actix_rt::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to handle SIGTERM");
+        future::select(
+            Box::pin(actix_rt::signal::ctrl_c()),
+            Box::pin(terminate.recv()),
+        )
+        .await;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = actix_rt::signal::ctrl_c().await;
+    }
 }
@@ -0,0 +1,72 @@
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use chrono::Utc;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{DbFind, MaintenanceWindow, MaintenanceWindowRepr},
+    PgPool,
+};
+use serde::Deserialize;
+
+use crate::Error;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListQuery {
+    /// Include windows that have ended or were expired.
+    #[serde(default)]
+    pub all: bool,
+}
+
+/// Lists the maintenance windows of an asset group that have not ended, soonest first.
+pub async fn list(
+    path: web::Path<i32>,
+    query: web::Query<ListQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let conn = get_conn(&pool)?;
+    let windows = MaintenanceWindow::get_group(&conn, path.into_inner(), query.all, Utc::now())?;
+    Ok(HttpResponse::Ok().json(windows))
+}
+
+/// Creates a maintenance window, silencing alerts on what it covers while it is active.
+pub async fn create(
+    path: web::Path<i32>,
+    window: web::Json<MaintenanceWindowRepr>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    if let Err((field, message)) = window.validate() {
+        return Err(Error::InvalidField {
+            field: field.to_string(),
+            message,
+        });
+    }
+    let conn = get_conn(&pool)?;
+    let inserted = window.insert(&conn, path.into_inner())?;
+    Ok(HttpResponse::Created().json(inserted))
+}
+
+/// Ends a maintenance window now, along with any later occurrences.
+pub async fn expire(
+    path: web::Path<(i32, i32)>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let (asset_group_id, maintenance_window_id) = path.into_inner();
+    let conn = get_conn(&pool)?;
+    let window = MaintenanceWindow::find(&conn, maintenance_window_id)?;
+    if window.asset_group_id != asset_group_id {
+        return Err(Error::NotFound(format!(
+            "Maintenance window {} not found",
+            maintenance_window_id
+        )));
+    }
+    let now = Utc::now();
+    if window.occurrence(now).is_none() {
+        return Err(Error::Conflict(format!(
+            "Maintenance window {} has already ended",
+            maintenance_window_id
+        )));
+    }
+    Ok(HttpResponse::Ok().json(window.expire(&conn, now)?))
+}
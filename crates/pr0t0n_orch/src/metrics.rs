@@ -0,0 +1,240 @@
+//! Process-wide counters and histograms, rendered in the Prometheus text format by `/metrics`.
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Metrics of this process.
+pub static METRICS: Metrics = Metrics::new();
+
+/// A monotonically increasing count.
+#[derive(Debug)]
+pub struct Counter(AtomicU64);
+impl Counter {
+    pub const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Upper bounds of the histogram buckets, in seconds.
+pub const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// A histogram of durations with the [`BUCKETS`] bounds.
+#[derive(Debug)]
+pub struct Histogram {
+    /// Observations per bucket, not cumulative. The last one is `+Inf`.
+    buckets: [Counter; BUCKETS.len() + 1],
+    count: Counter,
+    sum_micros: AtomicU64,
+}
+impl Histogram {
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: Counter = Counter::new();
+        Self {
+            buckets: [ZERO; BUCKETS.len() + 1],
+            count: Counter::new(),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[bucket].inc();
+        self.count.inc();
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.get()
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    /// Time taken to sync uploaded and patched systems, including failed syncs.
+    pub sync_duration: Histogram,
+    pub websocket_messages_received: Counter,
+    pub websocket_messages_sent: Counter,
+    pub service_heartbeat_timeouts: Counter,
+    pub watcher_heartbeat_timeouts: Counter,
+}
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            sync_duration: Histogram::new(),
+            websocket_messages_received: Counter::new(),
+            websocket_messages_sent: Counter::new(),
+            service_heartbeat_timeouts: Counter::new(),
+            watcher_heartbeat_timeouts: Counter::new(),
+        }
+    }
+
+    /// Render these metrics.
+    pub fn render(&self, exposition: &mut Exposition) {
+        exposition.histogram(
+            "pr0t0n_sync_duration_seconds",
+            "Time taken to sync systems",
+            &self.sync_duration,
+        );
+        exposition.header(
+            "pr0t0n_websocket_messages_total",
+            "Websocket messages received from and sent to sessions",
+            "counter",
+        );
+        let received = self.websocket_messages_received.get();
+        let sent = self.websocket_messages_sent.get();
+        exposition.sample(
+            "pr0t0n_websocket_messages_total",
+            &[("direction", "received")],
+            received,
+        );
+        exposition.sample(
+            "pr0t0n_websocket_messages_total",
+            &[("direction", "sent")],
+            sent,
+        );
+        exposition.header(
+            "pr0t0n_heartbeat_timeouts_total",
+            "Sessions disconnected for missing heartbeats",
+            "counter",
+        );
+        exposition.sample(
+            "pr0t0n_heartbeat_timeouts_total",
+            &[("session", "service")],
+            self.service_heartbeat_timeouts.get(),
+        );
+        exposition.sample(
+            "pr0t0n_heartbeat_timeouts_total",
+            &[("session", "watcher")],
+            self.watcher_heartbeat_timeouts.get(),
+        );
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A document in the Prometheus text exposition format.
+#[derive(Debug, Default)]
+pub struct Exposition(String);
+impl Exposition {
+    /// Media type of the format.
+    pub const CONTENT_TYPE: &'static str = "text/plain; version=0.0.4; charset=utf-8";
+
+    /// Start a metric with its help text and type.
+    pub fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.0, "# HELP {} {}", name, help);
+        let _ = writeln!(self.0, "# TYPE {} {}", name, kind);
+    }
+
+    /// Add a sample of the current metric.
+    pub fn sample<V: std::fmt::Display>(&mut self, name: &str, labels: &[(&str, &str)], value: V) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                .collect();
+            let _ = write!(self.0, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.0, " {}", value);
+    }
+
+    /// Add a metric with a single unlabelled sample.
+    pub fn gauge<V: std::fmt::Display>(&mut self, name: &str, help: &str, value: V) {
+        self.header(name, help, "gauge");
+        self.sample(name, &[], value);
+    }
+
+    pub fn histogram(&mut self, name: &str, help: &str, histogram: &Histogram) {
+        self.header(name, help, "histogram");
+        let bucket = format!("{}_bucket", name);
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(&histogram.buckets) {
+            cumulative += count.get();
+            self.sample(&bucket, &[("le", &bound.to_string())], cumulative);
+        }
+        cumulative += histogram.buckets[BUCKETS.len()].get();
+        self.sample(&bucket, &[("le", "+Inf")], cumulative);
+        let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        self.sample(&format!("{}_sum", name), &[], sum);
+        self.sample(&format!("{}_count", name), &[], histogram.count());
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::new();
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_millis(30));
+        histogram.observe(Duration::from_secs(60));
+        let mut exposition = Exposition::default();
+        exposition.histogram("test_seconds", "Test", &histogram);
+        let text = exposition.into_string();
+        assert!(text.contains("# TYPE test_seconds histogram\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"0.025\"} 1\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"0.05\"} 2\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"10\"} 2\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("test_seconds_sum 60.033\n"));
+        assert!(text.contains("test_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut exposition = Exposition::default();
+        exposition.sample("test", &[("name", "a \"quoted\"\\name")], 1);
+        assert_eq!(
+            exposition.into_string(),
+            "test{name=\"a \\\"quoted\\\"\\\\name\"} 1\n"
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Email notifications over SMTP.
+use std::time::Duration;
+
+use lettre::{
+    smtp::{authentication::Credentials, error::Error as SmtpError},
+    ClientSecurity, ClientTlsParameters, SmtpClient, Transport,
+};
+use lettre_email::{Email, EmailBuilder};
+use native_tls::TlsConnector;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    alerts::Alert,
+    notify::Notifier,
+    settings::{SmtpSecurity, SmtpSettings},
+    Error,
+};
+
+/// Fill in a subject or body template. Placeholders are `{service}`, `{address}`,
+/// `{asset_group}`, `{asset_group_id}`, `{event}` (the `on` of the rule), `{status}` (`Firing` or
+/// `Resolved`) and `{message}`; other braces are left as they are.
+pub fn render(template: &str, alert: &Alert, asset_group: &str) -> String {
+    let value = |name: &str| match name {
+        "service" => Some(alert.service_name.clone()),
+        "address" => Some(alert.address.clone()),
+        "asset_group" => Some(asset_group.to_string()),
+        "asset_group_id" => Some(alert.asset_group_id.to_string()),
+        "event" => Some(alert.trigger.to_string()),
+        "status" => Some(format!("{:?}", alert.status)),
+        "message" => Some(alert.message.clone()),
+        _ => None,
+    };
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let placeholder = rest
+            .find('}')
+            .and_then(|end| Some((value(&rest[1..end])?, end)));
+        match placeholder {
+            Some((value, end)) => {
+                rendered.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// An email as delivered, and as kept in dead letters.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EmailPayload {
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Sends alerts by email to the recipients of their rules.
+pub struct EmailNotifier {
+    settings: SmtpSettings,
+}
+impl EmailNotifier {
+    pub fn new(settings: SmtpSettings) -> Self {
+        Self { settings }
+    }
+
+    fn client(&self) -> Result<SmtpClient, Error> {
+        let address = (self.settings.host.as_str(), self.settings.port);
+        let tls = || -> Result<ClientTlsParameters, Error> {
+            let connector = TlsConnector::new().map_err(SmtpError::Tls)?;
+            Ok(ClientTlsParameters::new(
+                self.settings.host.clone(),
+                connector,
+            ))
+        };
+        let security = match self.settings.security {
+            SmtpSecurity::None => ClientSecurity::None,
+            SmtpSecurity::StartTls => ClientSecurity::Required(tls()?),
+            SmtpSecurity::Tls => ClientSecurity::Wrapper(tls()?),
+        };
+        let mut client = SmtpClient::new(address, security)?.timeout(Some(self.settings.timeout()));
+        if let (Some(username), Some(password)) = (&self.settings.username, &self.settings.password)
+        {
+            client = client.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(client)
+    }
+
+    fn build(payload: EmailPayload) -> Result<Email, Error> {
+        let mut builder = EmailBuilder::new()
+            .from(payload.from)
+            .subject(payload.subject)
+            .text(payload.body);
+        for recipient in payload.to {
+            builder = builder.to(recipient);
+        }
+        Ok(builder.build()?)
+    }
+}
+impl Notifier for EmailNotifier {
+    fn payload(&self, alert: &Alert, asset_group: &str) -> Result<Value, Error> {
+        Ok(serde_json::to_value(EmailPayload {
+            from: alert.sender.clone(),
+            to: alert.recipients.clone(),
+            subject: render(&self.settings.subject, alert, asset_group),
+            body: render(&self.settings.body, alert, asset_group),
+        })?)
+    }
+
+    fn deliver(&self, payload: &Value) -> Result<(), Error> {
+        let email = Self::build(serde_json::from_value(payload.clone())?)?;
+        let mut transport = self.client()?.transport();
+        transport.send(email.into())?;
+        Ok(())
+    }
+
+    fn is_transient(&self, err: &Error) -> bool {
+        matches!(
+            err,
+            Error::Smtp(SmtpError::Transient(_))
+                | Error::Smtp(SmtpError::Io(_))
+                | Error::Smtp(SmtpError::Resolution)
+        )
+    }
+
+    fn recipients(&self, alert: &Alert) -> Vec<String> {
+        alert.recipients.clone()
+    }
+
+    fn retries(&self) -> u32 {
+        self.settings.retries
+    }
+
+    fn retry_backoff(&self, retry: u32) -> Duration {
+        self.settings.retry_backoff(retry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pr0t0n_orch_db::models::AlertStatus;
+
+    #[test]
+    fn test_render() {
+        let alert = Alert {
+            alert_rule_id: 1,
+            asset_group_id: 2,
+            trigger: "disconnect",
+            service_name: "camera".to_string(),
+            address: "localhost:5678".to_string(),
+            dedup_key: "localhost:5678/disconnect".to_string(),
+            status: AlertStatus::Firing,
+            sender: "pr0t0nalerts@gmail.com".to_string(),
+            recipients: vec!["joshikatsu@gmail.com".to_string()],
+            channels: vec![],
+            message: "camera (localhost:5678) disconnected".to_string(),
+        };
+        let settings = SmtpSettings::default();
+        assert_eq!(
+            render(&settings.subject, &alert, "lobby"),
+            "[pr0t0n] disconnect alert on camera"
+        );
+        assert_eq!(
+            render(&settings.body, &alert, "lobby"),
+            "camera (localhost:5678) disconnected\n\n\
+             Service: camera (localhost:5678)\n\
+             Asset group: lobby (2)\n"
+        );
+        // Values are not rendered again, and unknown placeholders are kept.
+        let alert = Alert {
+            message: "{service} is {unknown}".to_string(),
+            ..alert
+        };
+        assert_eq!(
+            render("{message} {} {asset_group", &alert, "lobby"),
+            "{service} is {unknown} {} {asset_group"
+        );
+        assert_eq!(render("{status}", &alert.resolved(), "lobby"), "Resolved");
+    }
+}
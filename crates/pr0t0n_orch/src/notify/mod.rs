@@ -0,0 +1,341 @@
+//! Notifications of fired alerts through the channels named by their rules.
+use std::{collections::BTreeMap, sync::Arc, thread, time::Duration};
+
+use actix::{
+    Actor, Addr, Context, Handler, Message, MessageResult, ResponseFuture, SyncArbiter, SyncContext,
+};
+use diesel::PgConnection;
+use futures::future;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{AssetGroup, DbFind, DbInsert, Event, EventKind, NewDeadLetter},
+    PgPool,
+};
+use serde_json::Value;
+
+use crate::{
+    alerts::Alert,
+    settings::{ChannelSettings, Settings},
+    Error,
+};
+
+pub mod email;
+pub mod webhook;
+pub use email::EmailNotifier;
+pub use webhook::WebhookNotifier;
+
+/// Name of the channel alerts are emailed to their recipients through.
+pub const EMAIL: &str = "email";
+
+/// A way of delivering alerts. Delivering blocks, so a [`Dispatcher`] runs each channel's
+/// notifier on a thread of its own.
+pub trait Notifier: Send + Sync {
+    /// What is delivered for an alert. Dead letters keep this, so it can be delivered again.
+    fn payload(&self, alert: &Alert, asset_group: &str) -> Result<Value, Error>;
+
+    /// Deliver a payload once.
+    fn deliver(&self, payload: &Value) -> Result<(), Error>;
+
+    /// Whether a failed delivery may succeed when tried again.
+    fn is_transient(&self, err: &Error) -> bool;
+
+    /// Who an alert is delivered to, as recorded in the event log.
+    fn recipients(&self, alert: &Alert) -> Vec<String>;
+
+    /// Attempts after the first failed one.
+    fn retries(&self) -> u32;
+
+    /// Time to wait before a retry, where the first retry is 1.
+    fn retry_backoff(&self, retry: u32) -> Duration;
+}
+
+/// Asks the dispatcher to send an alert through its channels.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct Notify(pub Alert);
+
+/// Names of the channels an alert is sent through: email when its rule has recipients, then
+/// the channels of the rule.
+fn channels(alert: &Alert) -> Vec<&str> {
+    let mut channels = vec![];
+    if !alert.recipients.is_empty() {
+        channels.push(EMAIL);
+    }
+    for channel in &alert.channels {
+        if !channels.contains(&channel.as_str()) {
+            channels.push(channel);
+        }
+    }
+    channels
+}
+
+/// Asks a channel to deliver an alert.
+struct Deliver {
+    alert: Alert,
+    asset_group: String,
+}
+impl Message for Deliver {
+    type Result = Delivery;
+}
+
+/// How delivering an alert through a channel went. The error is kept as text, as it is sent
+/// back from the channel's thread.
+struct Delivery {
+    /// `None` when there is no payload, in which case the alert itself is kept.
+    payload: Option<Value>,
+    attempts: u32,
+    result: Result<Vec<String>, String>,
+}
+
+/// Delivers the alerts of one channel. Each channel has a thread of its own, so retrying a
+/// channel that is down only holds up the alerts sent through it.
+struct Channel {
+    name: String,
+    notifier: Arc<dyn Notifier>,
+}
+impl Channel {
+    /// Deliver a payload, retrying transient failures. Returns the number of attempts made.
+    fn deliver(&self, payload: &Value) -> (u32, Result<(), Error>) {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let err = match self.notifier.deliver(payload) {
+                Ok(()) => return (attempts, Ok(())),
+                Err(err) => err,
+            };
+            // Rejected payloads and unusable settings fail the same way every time.
+            if !self.notifier.is_transient(&err) || attempts > self.notifier.retries() {
+                return (attempts, Err(err));
+            }
+            let backoff = self.notifier.retry_backoff(attempts);
+            warn!(
+                "Failed to send alert through {}, retrying in {:?}: {:?}",
+                self.name, backoff, err
+            );
+            thread::sleep(backoff);
+        }
+    }
+}
+impl Actor for Channel {
+    type Context = SyncContext<Self>;
+}
+impl Handler<Deliver> for Channel {
+    type Result = MessageResult<Deliver>;
+
+    fn handle(&mut self, msg: Deliver, _: &mut SyncContext<Self>) -> Self::Result {
+        let (payload, attempts, result) = match self.notifier.payload(&msg.alert, &msg.asset_group)
+        {
+            Ok(payload) => {
+                let (attempts, result) = self.deliver(&payload);
+                let result = result.map(|()| self.notifier.recipients(&msg.alert));
+                (Some(payload), attempts, result)
+            }
+            Err(err) => (None, 0, Err(err)),
+        };
+        let result = result.map_err(|err| {
+            error!(
+                "Failed to send alert {} through {}: {:?}",
+                msg.alert.alert_rule_id, self.name, err
+            );
+            err.to_string()
+        });
+        MessageResult(Delivery {
+            payload,
+            attempts,
+            result,
+        })
+    }
+}
+
+/// Sends alerts through named channels, retrying failed deliveries with backoff. Outcomes are
+/// recorded in the event log, and deliveries that fail for good are kept as dead letters.
+pub struct Dispatcher {
+    channels: BTreeMap<String, Addr<Channel>>,
+    pool: PgPool,
+}
+impl Dispatcher {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            channels: BTreeMap::new(),
+            pool,
+        }
+    }
+
+    /// Send alerts naming `name` through `notifier`, on a thread of its own.
+    pub fn with_channel(mut self, name: &str, notifier: impl Notifier + 'static) -> Self {
+        let notifier: Arc<dyn Notifier> = Arc::new(notifier);
+        let channel_name = name.to_string();
+        let channel = SyncArbiter::start(1, move || Channel {
+            name: channel_name.clone(),
+            notifier: notifier.clone(),
+        });
+        self.channels.insert(name.to_string(), channel);
+        self
+    }
+
+    /// Email through the SMTP server of the settings, if any, and send through their channels.
+    pub fn from_settings(settings: &Settings, pool: PgPool) -> Self {
+        let mut dispatcher = Self::new(pool);
+        if settings.smtp.is_enabled() {
+            dispatcher = dispatcher.with_channel(EMAIL, EmailNotifier::new(settings.smtp.clone()));
+        }
+        for (name, channel) in &settings.channels {
+            dispatcher = match channel {
+                ChannelSettings::Webhook(webhook) => {
+                    dispatcher.with_channel(name, WebhookNotifier::new(webhook.clone()))
+                }
+            };
+        }
+        dispatcher
+    }
+
+    /// Name of the asset group an alert is about, as notifiers show it.
+    fn asset_group_name(&self, alert: &Alert) -> Result<String, Error> {
+        let conn = get_conn(&self.pool)?;
+        Ok(AssetGroup::find(&conn, alert.asset_group_id)?.name)
+    }
+}
+
+/// Record how an alert went through one channel, returning the event recording the outcome.
+fn record(
+    conn: &PgConnection,
+    name: &str,
+    alert: &Alert,
+    delivery: Delivery,
+) -> Result<EventKind, Error> {
+    let error = match delivery.result {
+        Ok(recipients) => {
+            return Ok(EventKind::NotificationSent {
+                address: alert.address.clone(),
+                alert_rule_id: alert.alert_rule_id,
+                channel: name.to_string(),
+                recipients,
+                attempts: delivery.attempts,
+            })
+        }
+        Err(error) => error,
+    };
+    let payload = match delivery.payload {
+        Some(payload) => payload,
+        None => serde_json::to_value(alert)?,
+    };
+    NewDeadLetter {
+        asset_group_id: alert.asset_group_id,
+        alert_rule_id: alert.alert_rule_id,
+        address: &alert.address,
+        channel: name,
+        payload: &payload,
+        error: &error,
+        attempts: delivery.attempts as i32,
+    }
+    .insert(conn)?;
+    Ok(EventKind::NotificationFailed {
+        address: alert.address.clone(),
+        alert_rule_id: alert.alert_rule_id,
+        channel: name.to_string(),
+        error,
+        attempts: delivery.attempts,
+    })
+}
+
+/// Record the outcomes of an alert in the order of its channels.
+fn record_all(
+    pool: &PgPool,
+    alert: &Alert,
+    deliveries: Vec<(String, Delivery)>,
+) -> Result<(), Error> {
+    let conn = get_conn(pool)?;
+    for (name, delivery) in deliveries {
+        let kind = record(&conn, &name, alert, delivery)?;
+        Event::new(alert.asset_group_id, kind).log(&conn)?;
+    }
+    Ok(())
+}
+
+impl Actor for Dispatcher {
+    type Context = Context<Self>;
+}
+impl Handler<Notify> for Dispatcher {
+    type Result = ResponseFuture<()>;
+
+    /// Deliver through every channel at once, and finish when all of them have.
+    fn handle(&mut self, msg: Notify, _: &mut Context<Self>) -> Self::Result {
+        let alert = msg.0;
+        let asset_group = match self.asset_group_name(&alert) {
+            Ok(asset_group) => asset_group,
+            Err(err) => {
+                error!("Error notifying alert {:?}: {:?}", alert, err);
+                return Box::pin(future::ready(()));
+            }
+        };
+        let alert_rule_id = alert.alert_rule_id;
+        let deliveries: Vec<_> = channels(&alert)
+            .into_iter()
+            .map(|name| {
+                let name = name.to_string();
+                let sent = self.channels.get(&name).map(|channel| {
+                    channel.send(Deliver {
+                        alert: alert.clone(),
+                        asset_group: asset_group.clone(),
+                    })
+                });
+                async move {
+                    let result = match sent {
+                        Some(sent) => sent.await.map_err(|err| format!("{:?}", err)),
+                        None => {
+                            let err = Error::UnknownChannel(name.clone());
+                            error!(
+                                "Failed to send alert {} through {}: {:?}",
+                                alert_rule_id, name, err
+                            );
+                            Err(err.to_string())
+                        }
+                    };
+                    let delivery = result.unwrap_or_else(|error| Delivery {
+                        payload: None,
+                        attempts: 0,
+                        result: Err(error),
+                    });
+                    (name, delivery)
+                }
+            })
+            .collect();
+        let pool = self.pool.clone();
+        Box::pin(async move {
+            let deliveries = future::join_all(deliveries).await;
+            if let Err(err) = record_all(&pool, &alert, deliveries) {
+                error!("Error notifying alert {:?}: {:?}", alert, err);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pr0t0n_orch_db::models::AlertStatus;
+
+    #[test]
+    fn test_channels() {
+        let alert = Alert {
+            alert_rule_id: 1,
+            asset_group_id: 2,
+            trigger: "disconnect",
+            service_name: "camera".to_string(),
+            address: "localhost:5678".to_string(),
+            dedup_key: "localhost:5678/disconnect".to_string(),
+            status: AlertStatus::Firing,
+            sender: "pr0t0nalerts@gmail.com".to_string(),
+            recipients: vec!["joshikatsu@gmail.com".to_string()],
+            channels: vec!["oncall".to_string(), "email".to_string()],
+            message: "camera (localhost:5678) disconnected".to_string(),
+        };
+        assert_eq!(channels(&alert), vec!["email", "oncall"]);
+        let alert = Alert {
+            recipients: vec![],
+            channels: vec!["oncall".to_string()],
+            ..alert
+        };
+        assert_eq!(channels(&alert), vec!["oncall"]);
+    }
+}
@@ -0,0 +1,1147 @@
+//! OpenAPI 3 description of the HTTP API, served at `/openapi.json`.
+//!
+//! Schemas of the Rust types in the API are derived from them with `schemars`, which follows
+//! their serde attributes. The paths are written here by hand, as actix-web does not describe its
+//! routes, and the tests check the schemas against serialized values of the types.
+use actix_web::HttpResponse;
+use pr0t0n_orch_db::{
+    models::{
+        Acknowledgement, AlertRuleRepr, AlertState, AlertStatus, AssetGroup, AssetGroupChanges,
+        AssetGroupRepr, AssetGroupSummary, BalancingStrategy, ConfigRepr, ConfigUsageRepr,
+        ErrorCode, ErrorDetail, ErrorResponse, EscalationTier, HealthStatus, MaintenanceWindow,
+        MaintenanceWindowRepr, PendingService, PoolRepr, ReconciledService, Reconciliation,
+        ReconciliationReport, Recurrence, RegistrationPolicy, ServiceApproval, ServiceDetail,
+        ServiceLink, ServicePage, ServiceRepr, ServiceSort, ServiceSummary, ServiceType,
+        SystemRepr, SystemRevision,
+    },
+    PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+use schemars::{gen::SchemaSettings, JsonSchema};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    graph::GraphFormat,
+    sync::{SystemFormat, JSON_PATCH_CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE},
+};
+
+/// Version of the OpenAPI specification the document follows.
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// Reference to the schema of `T`.
+pub fn schema_ref<T: JsonSchema>() -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", T::schema_name()) })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn integer(format: &str) -> Value {
+    json!({ "type": "integer", "format": format })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+/// A map from strings to `values`.
+fn map(values: Value) -> Value {
+    json!({ "type": "object", "additionalProperties": values })
+}
+
+/// Bring a derived schema in line with the rest of the document. Objects are closed unless they
+/// are one of several alternatives, as the flattened variants of a tagged enum are, since each
+/// alternative only lists its own properties.
+fn normalize(schema: &mut Value, closed: bool) {
+    // OpenAPI 3.0 has no boolean schemas.
+    if *schema == json!(true) {
+        *schema = json!({});
+    }
+    let object = match schema.as_object_mut() {
+        Some(object) => object,
+        None => return,
+    };
+    // Doc comments are written for Rust, not for clients.
+    object.remove("description");
+    // Documented variants are derived as alternatives of their own, which reads worse than an enum.
+    let variants = object
+        .get("oneOf")
+        .and_then(Value::as_array)
+        .and_then(|alternatives| {
+            alternatives
+                .iter()
+                .map(|alternative| match alternative["enum"].as_array() {
+                    Some(names) if alternative["type"] == "string" => Some(names.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+        });
+    if let Some(names) = variants {
+        object.remove("oneOf");
+        object.insert("type".to_string(), json!("string"));
+        object.insert("enum".to_string(), json!(names.concat()));
+    }
+    // Siblings of `$ref` are ignored, so wrap references.
+    if object.contains_key("$ref") && object.len() > 1 {
+        let reference = object.remove("$ref").unwrap();
+        object.insert("allOf".to_string(), json!([{ "$ref": reference }]));
+    }
+    if let Some(properties) = object.get_mut("properties").and_then(Value::as_object_mut) {
+        properties
+            .values_mut()
+            .for_each(|property| normalize(property, true));
+    }
+    for keyword in &["items", "additionalProperties"] {
+        if let Some(schema) = object.get_mut(*keyword) {
+            normalize(schema, true);
+        }
+    }
+    for keyword in &["allOf", "anyOf", "oneOf"] {
+        if let Some(Value::Array(schemas)) = object.get_mut(*keyword) {
+            let closed = *keyword == "allOf";
+            schemas
+                .iter_mut()
+                .for_each(|schema| normalize(schema, closed));
+        }
+    }
+    let alternatives = object.contains_key("anyOf") || object.contains_key("oneOf");
+    if closed && !alternatives && object.contains_key("properties") {
+        object.entry("additionalProperties").or_insert(json!(false));
+    }
+}
+
+/// Schemas of the types of the API, derived from their serde attributes.
+fn components() -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    macro_rules! add {
+        ($($ty:ty),* $(,)?) => {
+            $(generator.subschema_for::<$ty>();)*
+        };
+    }
+    add!(
+        Acknowledgement,
+        AlertRuleRepr,
+        AlertState,
+        AlertStatus,
+        AssetGroup,
+        AssetGroupChanges,
+        AssetGroupRepr,
+        AssetGroupSummary,
+        BalancingStrategy,
+        ConfigRepr,
+        ConfigUsageRepr,
+        ErrorCode,
+        ErrorDetail,
+        ErrorResponse,
+        EscalationTier,
+        GraphFormat,
+        HealthStatus,
+        MaintenanceWindow,
+        MaintenanceWindowRepr,
+        PendingService,
+        PoolRepr,
+        Reconciliation,
+        ReconciledService,
+        ReconciliationReport,
+        Recurrence,
+        RegistrationPolicy,
+        ServiceApproval,
+        ServiceDetail,
+        ServiceLink,
+        ServicePage,
+        ServiceRepr,
+        ServiceSort,
+        ServiceSummary,
+        ServiceType,
+        SystemFormat,
+        SystemRepr,
+        SystemRevision,
+    );
+    let schemas: Map<String, Value> = generator
+        .take_definitions()
+        .into_iter()
+        .map(|(name, schema)| {
+            let mut schema = serde_json::to_value(schema).unwrap();
+            normalize(&mut schema, true);
+            (name, schema)
+        })
+        .collect();
+    json!({ "schemas": schemas })
+}
+
+fn path_param(name: &str, schema: Value) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "schema": schema })
+}
+
+fn query_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({ "name": name, "in": "query", "description": description, "schema": schema })
+}
+
+fn header_param(name: &str, description: &str, required: bool) -> Value {
+    json!({
+        "name": name,
+        "in": "header",
+        "description": description,
+        "required": required,
+        "schema": string(),
+    })
+}
+
+fn asset_group_id() -> Value {
+    path_param("asset_group_id", integer("int32"))
+}
+
+fn config_name() -> Value {
+    path_param("name", string())
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({ "required": true, "content": { "application/json": { "schema": schema } } })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": { "application/json": { "schema": schema } } })
+}
+
+fn text_response(description: &str) -> Value {
+    json!({ "description": description, "content": { "text/plain": { "schema": string() } } })
+}
+
+fn no_content(description: &str) -> Value {
+    json!({ "description": description })
+}
+
+/// Responses of an operation: the given successful ones and an `ErrorResponse` for each of
+/// `errors`, plus 500.
+fn responses(success: &[(u16, Value)], errors: &[u16]) -> Value {
+    let mut responses = Map::new();
+    for (status, response) in success {
+        responses.insert(status.to_string(), response.clone());
+    }
+    for status in errors.iter().chain(&[500]) {
+        let description = actix_web::http::StatusCode::from_u16(*status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("Error");
+        responses.insert(
+            status.to_string(),
+            json_response(description, schema_ref::<ErrorResponse>()),
+        );
+    }
+    Value::Object(responses)
+}
+
+fn operation(
+    operation_id: &str,
+    summary: &str,
+    parameters: Vec<Value>,
+    request_body: Option<Value>,
+    responses: Value,
+) -> Value {
+    let mut operation = json!({
+        "operationId": operation_id,
+        "summary": summary,
+        "parameters": parameters,
+        "responses": responses,
+    });
+    if let Some(request_body) = request_body {
+        operation["requestBody"] = request_body;
+    }
+    operation
+}
+
+fn websocket(operation_id: &str, summary: &str, parameters: Vec<Value>, errors: &[u16]) -> Value {
+    operation(
+        operation_id,
+        summary,
+        parameters,
+        None,
+        responses(
+            &[(101, no_content("Switching to the WebSocket protocol"))],
+            errors,
+        ),
+    )
+}
+
+/// Every route registered by [`crate::routes`] with its operations.
+fn paths() -> Value {
+    let if_match = || {
+        header_param(
+            "If-Match",
+            "ETag of the revision the change is based on. The change is refused with 409 \
+             unless the asset group is still at that revision.",
+            false,
+        )
+    };
+    let system_response = || {
+        json!({
+            "description": "The asset group's system",
+            "headers": { "ETag": { "schema": string() } },
+            "content": {
+                "application/json": { "schema": schema_ref::<SystemRepr>() },
+                "application/yaml": { "schema": schema_ref::<SystemRepr>() },
+            },
+        })
+    };
+    let revision_response = || {
+        json!({
+            "description": "The new revision of the asset group",
+            "headers": { "ETag": { "schema": string() } },
+            "content": { "application/json": { "schema": schema_ref::<SystemRevision>() } },
+        })
+    };
+
+    json!({
+        "/": {
+            "get": operation(
+                "index",
+                "Check that the server is up",
+                vec![],
+                None,
+                responses(&[(200, text_response("The server is up"))], &[]),
+            ),
+        },
+        "/healthz": {
+            "get": operation(
+                "getHealth",
+                "Liveness probe",
+                vec![],
+                None,
+                responses(&[(200, text_response("The process is up"))], &[]),
+            ),
+        },
+        "/readyz": {
+            "get": operation(
+                "getReadiness",
+                "Readiness probe, checking the database and the websocket server",
+                vec![],
+                None,
+                json!({
+                    "200": json_response("All checks passed", map(string())),
+                    "503": json_response(
+                        "Some checks failed; failed checks give their error",
+                        map(string()),
+                    ),
+                }),
+            ),
+        },
+        "/metrics": {
+            "get": operation(
+                "getMetrics",
+                "Metrics in the Prometheus text format",
+                vec![],
+                None,
+                responses(&[(200, text_response("The metrics"))], &[]),
+            ),
+        },
+        "/openapi.json": {
+            "get": operation(
+                "getOpenApi",
+                "Get this OpenAPI document",
+                vec![],
+                None,
+                responses(
+                    &[(200, json!({
+                        "description": "The OpenAPI document",
+                        "content": { "application/json": { "schema": { "type": "object" } } },
+                    }))],
+                    &[],
+                ),
+            ),
+        },
+        "/ws/": {
+            "get": websocket(
+                "connectService",
+                "Connect a service, reporting its health while connected. Asset groups with the \
+                 Strict registration policy refuse addresses that are not their services",
+                vec![
+                    header_param(PR0T0N_ASSET_GROUP_ID_HEADER, "Asset group of the service", true),
+                    header_param(PR0T0N_CLIENT_ADDRESS_HEADER, "Address of the service", true),
+                ],
+                &[400, 403],
+            ),
+        },
+        "/ws/watch/": {
+            "get": websocket(
+                "watchAssetGroup",
+                "Stream health changes of an asset group's services",
+                vec![header_param(
+                    PR0T0N_ASSET_GROUP_ID_HEADER,
+                    "Asset group to watch",
+                    true,
+                )],
+                &[400],
+            ),
+        },
+        "/sync/upload/": {
+            "post": operation(
+                "uploadSystem",
+                "Make an asset group match a system",
+                vec![if_match()],
+                Some(json_body(schema_ref::<SystemRepr>())),
+                responses(&[(200, revision_response())], &[400, 404, 409, 422]),
+            ),
+        },
+        "/asset-groups/": {
+            "get": operation(
+                "listAssetGroups",
+                "List asset groups with service counts and aggregate health",
+                vec![],
+                None,
+                responses(
+                    &[(
+                        200,
+                        json_response("Asset groups", array(schema_ref::<AssetGroupSummary>())),
+                    )],
+                    &[],
+                ),
+            ),
+            "post": operation(
+                "createAssetGroup",
+                "Create an asset group",
+                vec![],
+                Some(json_body(schema_ref::<AssetGroupRepr>())),
+                responses(
+                    &[(201, json_response("The created asset group", schema_ref::<AssetGroup>()))],
+                    &[400, 409, 422],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}": {
+            "get": operation(
+                "showAssetGroup",
+                "Show an asset group with service counts and aggregate health",
+                vec![asset_group_id()],
+                None,
+                responses(
+                    &[(200, json_response("The asset group", schema_ref::<AssetGroupSummary>()))],
+                    &[400, 404],
+                ),
+            ),
+            "patch": operation(
+                "updateAssetGroup",
+                "Update the given fields of an asset group",
+                vec![asset_group_id()],
+                Some(json_body(schema_ref::<AssetGroupChanges>())),
+                responses(
+                    &[(200, json_response("The updated asset group", schema_ref::<AssetGroup>()))],
+                    &[400, 404, 409, 422],
+                ),
+            ),
+            "delete": operation(
+                "deleteAssetGroup",
+                "Delete an asset group",
+                vec![
+                    asset_group_id(),
+                    query_param(
+                        "cascade",
+                        "Also delete everything the asset group owns, from its services to its event logs",
+                        json!({ "type": "boolean", "default": false }),
+                    ),
+                ],
+                None,
+                responses(&[(204, no_content("The asset group was deleted"))], &[400, 404, 409]),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/system": {
+            "get": operation(
+                "getSystem",
+                "Download the system of an asset group",
+                vec![
+                    asset_group_id(),
+                    query_param("format", "Format of the body", schema_ref::<SystemFormat>()),
+                    header_param(
+                        "If-None-Match",
+                        "ETag of a cached copy; responds 304 if it is current",
+                        false,
+                    ),
+                ],
+                None,
+                responses(
+                    &[
+                        (200, system_response()),
+                        (304, no_content("The cached copy is current")),
+                    ],
+                    &[400, 404],
+                ),
+            ),
+            "patch": operation(
+                "patchSystem",
+                "Apply a JSON Patch or JSON Merge Patch to the system of an asset group",
+                vec![asset_group_id(), if_match()],
+                Some(json!({
+                    "required": true,
+                    "content": {
+                        JSON_PATCH_CONTENT_TYPE: {
+                            "schema": array(json!({ "type": "object" })),
+                        },
+                        MERGE_PATCH_CONTENT_TYPE: { "schema": { "type": "object" } },
+                    },
+                })),
+                responses(&[(200, revision_response())], &[400, 404, 409, 415, 422]),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/graph": {
+            "get": operation(
+                "getGraph",
+                "Render the service graph of an asset group",
+                vec![
+                    asset_group_id(),
+                    query_param("format", "Output format", schema_ref::<GraphFormat>()),
+                ],
+                None,
+                responses(
+                    &[(200, json!({
+                        "description": "The rendered graph",
+                        "content": {
+                            "text/vnd.graphviz": { "schema": string() },
+                            "text/plain": { "schema": string() },
+                        },
+                    }))],
+                    &[400, 404],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/services": {
+            "get": operation(
+                "listServices",
+                "List a page of the services of an asset group",
+                vec![
+                    asset_group_id(),
+                    query_param("service_type", "Only this type", schema_ref::<ServiceType>()),
+                    query_param(
+                        "health_status",
+                        "Only this health status",
+                        schema_ref::<HealthStatus>(),
+                    ),
+                    query_param("name", "Only names containing this", string()),
+                    query_param("label", "Only services with this `key=value` label", string()),
+                    query_param("sort", "Sort order", schema_ref::<ServiceSort>()),
+                    query_param("cursor", "`next_cursor` of the previous page", string()),
+                    query_param("limit", "Page size", integer("int64")),
+                ],
+                None,
+                responses(
+                    &[(200, json_response("A page of services", schema_ref::<ServicePage>()))],
+                    &[400],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/services/{service_id}": {
+            "get": operation(
+                "showService",
+                "Show a service with its input and output services",
+                vec![asset_group_id(), path_param("service_id", integer("int32"))],
+                None,
+                responses(
+                    &[(200, json_response("The service", schema_ref::<ServiceDetail>()))],
+                    &[400, 404],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/reconciliation": {
+            "get": operation(
+                "reconcileServices",
+                "Compare the services declared in an asset group with the ones connected",
+                vec![asset_group_id()],
+                None,
+                responses(
+                    &[(
+                        200,
+                        json_response(
+                            "Services by how they reconcile",
+                            schema_ref::<ReconciliationReport>(),
+                        ),
+                    )],
+                    &[400],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/alerts": {
+            "get": operation(
+                "listAlerts",
+                "List the alerts of an asset group that ever fired or were pending, most recent \
+                 first",
+                vec![asset_group_id()],
+                None,
+                responses(
+                    &[(200, json_response("Alerts", array(schema_ref::<AlertState>())))],
+                    &[400],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/alerts/{alert_state_id}/acknowledge": {
+            "post": operation(
+                "acknowledgeAlert",
+                "Acknowledge a firing alert, stopping its escalation",
+                vec![asset_group_id(), path_param("alert_state_id", integer("int32"))],
+                Some(json_body(schema_ref::<Acknowledgement>())),
+                responses(
+                    &[(200, json_response("The acknowledged alert", schema_ref::<AlertState>()))],
+                    &[400, 404, 409],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/maintenance-windows": {
+            "get": operation(
+                "listMaintenanceWindows",
+                "List the maintenance windows of an asset group that have not ended, soonest \
+                 first",
+                vec![
+                    asset_group_id(),
+                    query_param(
+                        "all",
+                        "Include windows that have ended or were expired",
+                        boolean(),
+                    ),
+                ],
+                None,
+                responses(
+                    &[(
+                        200,
+                        json_response(
+                            "Maintenance windows",
+                            array(schema_ref::<MaintenanceWindow>()),
+                        ),
+                    )],
+                    &[400],
+                ),
+            ),
+            "post": operation(
+                "createMaintenanceWindow",
+                "Create a maintenance window, silencing alerts on what it covers while it is \
+                 active",
+                vec![asset_group_id()],
+                Some(json_body(schema_ref::<MaintenanceWindowRepr>())),
+                responses(
+                    &[(
+                        201,
+                        json_response(
+                            "The created maintenance window",
+                            schema_ref::<MaintenanceWindow>(),
+                        ),
+                    )],
+                    &[400, 422],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/maintenance-windows/{window_id}/expire": {
+            "post": operation(
+                "expireMaintenanceWindow",
+                "End a maintenance window now, along with any later occurrences",
+                vec![
+                    asset_group_id(),
+                    path_param("window_id", integer("int32")),
+                ],
+                None,
+                responses(
+                    &[(
+                        200,
+                        json_response(
+                            "The expired maintenance window",
+                            schema_ref::<MaintenanceWindow>(),
+                        ),
+                    )],
+                    &[400, 404, 409],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/pending-services": {
+            "get": operation(
+                "listPendingServices",
+                "List the addresses waiting to be approved as services of an asset group, in the \
+                 order they connected",
+                vec![asset_group_id()],
+                None,
+                responses(
+                    &[(
+                        200,
+                        json_response("Pending services", array(schema_ref::<PendingService>())),
+                    )],
+                    &[400],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/pending-services/{pending_id}": {
+            "delete": operation(
+                "rejectPendingService",
+                "Forget a pending address and close its session",
+                vec![asset_group_id(), path_param("pending_id", integer("int32"))],
+                None,
+                responses(&[(204, no_content("The address was rejected"))], &[400, 404]),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/pending-services/{pending_id}/approve": {
+            "post": operation(
+                "approvePendingService",
+                "Register a pending address as a service with the given name, type and config",
+                vec![asset_group_id(), path_param("pending_id", integer("int32"))],
+                Some(json_body(schema_ref::<ServiceApproval>())),
+                responses(
+                    &[(
+                        201,
+                        json_response("The registered service", schema_ref::<ServiceDetail>()),
+                    )],
+                    &[400, 404, 409, 422],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/configs/": {
+            "get": operation(
+                "listConfigs",
+                "List the configs of an asset group with the services using each",
+                vec![asset_group_id()],
+                None,
+                responses(
+                    &[(200, json_response("Configs", array(schema_ref::<ConfigUsageRepr>())))],
+                    &[400],
+                ),
+            ),
+            "post": operation(
+                "createConfig",
+                "Create a config",
+                vec![asset_group_id()],
+                Some(json_body(schema_ref::<ConfigRepr>())),
+                responses(
+                    &[(201, json_response("The created config", schema_ref::<ConfigRepr>()))],
+                    &[400, 409, 422],
+                ),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/configs/{name}": {
+            "get": operation(
+                "showConfig",
+                "Show a config",
+                vec![asset_group_id(), config_name()],
+                None,
+                responses(
+                    &[(200, json_response("The config", schema_ref::<ConfigRepr>()))],
+                    &[400, 404],
+                ),
+            ),
+            "put": operation(
+                "updateConfig",
+                "Replace a config, renaming it if the body has a new name",
+                vec![asset_group_id(), config_name()],
+                Some(json_body(schema_ref::<ConfigRepr>())),
+                responses(
+                    &[(200, json_response("The updated config", schema_ref::<ConfigRepr>()))],
+                    &[400, 404, 409, 422],
+                ),
+            ),
+            "delete": operation(
+                "deleteConfig",
+                "Delete a config",
+                vec![
+                    asset_group_id(),
+                    config_name(),
+                    query_param(
+                        "force",
+                        "Delete even if services use the config, leaving them without one",
+                        json!({ "type": "boolean", "default": false }),
+                    ),
+                ],
+                None,
+                responses(&[(204, no_content("The config was deleted"))], &[400, 404, 409]),
+            ),
+        },
+        "/asset-groups/{asset_group_id}/configs/{name}/used-by": {
+            "get": operation(
+                "getConfigUsers",
+                "List the services using a config",
+                vec![asset_group_id(), config_name()],
+                None,
+                responses(
+                    &[(200, json_response("Services", array(schema_ref::<ServiceLink>())))],
+                    &[400, 404],
+                ),
+            ),
+        },
+    })
+}
+
+/// The OpenAPI document of the HTTP API.
+pub fn document() -> Value {
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "Pr0t0n Orchestrator",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths(),
+        "components": components(),
+    })
+}
+
+/// Serves the OpenAPI document.
+pub async fn openapi() -> HttpResponse {
+    HttpResponse::Ok().json(document())
+}
+
+/// Check that `value` matches `schema`, resolving references in `document`. Objects must not
+/// have properties missing from their schema unless it allows `additionalProperties`.
+pub fn check(document: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+    check_at(document, schema, value, "")
+}
+
+fn check_at(document: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let pointer = reference.trim_start_matches('#');
+        let resolved = document
+            .pointer(pointer)
+            .ok_or_else(|| format!("{}: unknown schema {}", at, reference))?;
+        return check_at(document, resolved, value, at);
+    }
+    if value.is_null() && schema["nullable"] == json!(true) {
+        return Ok(());
+    }
+    if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+        for schema in all_of {
+            check_at(document, schema, value, at)?;
+        }
+        return Ok(());
+    }
+    for keyword in &["anyOf", "oneOf"] {
+        if let Some(alternatives) = schema.get(*keyword).and_then(Value::as_array) {
+            let matches = alternatives
+                .iter()
+                .filter(|schema| check_at(document, schema, value, at).is_ok())
+                .count();
+            if matches == 0 || (*keyword == "oneOf" && matches > 1) {
+                return Err(format!(
+                    "{}: {} matches {} of the {} alternatives",
+                    at, value, matches, keyword
+                ));
+            }
+        }
+    }
+    if let Some(names) = schema.get("enum").and_then(Value::as_array) {
+        if !names.contains(value) {
+            return Err(format!("{}: {} is not one of {:?}", at, value, names));
+        }
+    }
+    let matches = match schema.get("type").and_then(Value::as_str) {
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        Some("array") => value.is_array(),
+        Some("object") => value.is_object(),
+        _ => true,
+    };
+    if !matches {
+        return Err(format!(
+            "{}: expected {}, got {}",
+            at, schema["type"], value
+        ));
+    }
+
+    if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+        for (i, value) in values.iter().enumerate() {
+            check_at(document, items, value, &format!("{}/{}", at, i))?;
+        }
+    }
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    return Err(format!("{}: missing property {}", at, name));
+                }
+            }
+        }
+        for (name, value) in object {
+            let at = format!("{}/{}", at, name);
+            match (
+                properties.and_then(|p| p.get(name)),
+                &schema["additionalProperties"],
+            ) {
+                (Some(property), _) => check_at(document, property, value, &at)?,
+                (None, Value::Bool(false)) => {
+                    return Err(format!("{}: property is not in the schema", at))
+                }
+                (None, Value::Object(_)) => {
+                    check_at(document, &schema["additionalProperties"], value, &at)?
+                }
+                (None, _) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use pr0t0n_orch_db::models::AlertTrigger;
+    use serde::Serialize;
+
+    use super::*;
+
+    fn check_type<T: JsonSchema + Serialize>(value: &T) {
+        let document = document();
+        let value = serde_json::to_value(value).unwrap();
+        if let Err(err) = check(&document, &schema_ref::<T>(), &value) {
+            panic!("{} does not match its schema: {}", T::schema_name(), err);
+        }
+    }
+
+    fn service() -> ServiceRepr {
+        ServiceRepr {
+            address: "localhost:1234".to_string(),
+            service_type: ServiceType::Input,
+            name: "camera".to_string(),
+            output_addresses: vec!["localhost:2345".to_string()],
+            config_name: Some("camera_config".to_string()),
+            labels: vec![("zone".to_string(), "north".to_string())]
+                .into_iter()
+                .collect::<BTreeMap<_, _>>(),
+            alerts: vec![
+                AlertRuleRepr {
+                    trigger: AlertTrigger::Health {
+                        to: vec![HealthStatus::Critical],
+                    },
+                    sender: "pr0t0nalerts@gmail.com".to_string(),
+                    recipients: vec!["joshikatsu@gmail.com".to_string()],
+                    channels: vec![],
+                    key: None,
+                    for_seconds: 0,
+                    escalate: vec![],
+                },
+                AlertRuleRepr {
+                    trigger: AlertTrigger::Metric {
+                        metric: "cpu".to_string(),
+                        above: Some(0.9),
+                        below: None,
+                    },
+                    sender: String::new(),
+                    recipients: vec![],
+                    channels: vec!["oncall".to_string()],
+                    key: Some("camera-cpu".to_string()),
+                    for_seconds: 60,
+                    escalate: vec![EscalationTier {
+                        after: 300,
+                        recipients: vec![],
+                        channels: vec!["manager".to_string()],
+                    }],
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn config() -> ConfigRepr {
+        ConfigRepr {
+            name: "camera_config".to_string(),
+            json_config: json!({ "fps": 30 }),
+            ..Default::default()
+        }
+    }
+
+    fn link() -> ServiceLink {
+        ServiceLink {
+            service_id: 2,
+            name: "detector".to_string(),
+            address: "localhost:2345".to_string(),
+            health_status: HealthStatus::Warning,
+        }
+    }
+
+    #[test]
+    fn test_schemas() {
+        let asset_group = AssetGroup {
+            asset_group_id: 1,
+            name: "group".to_string(),
+            description: "A group".to_string(),
+            revision: 3,
+            registration_policy: RegistrationPolicy::Pending,
+        };
+        check_type(&asset_group);
+        check_type(&AssetGroupRepr::default());
+        check_type(&AssetGroupChanges::default());
+        check_type(&AssetGroupChanges {
+            name: Some("group".to_string()),
+            description: Some("A group".to_string()),
+            registration_policy: Some(RegistrationPolicy::Strict),
+        });
+        let mut health_counts = HashMap::new();
+        health_counts.insert(HealthStatus::Healthy, 2);
+        check_type(&AssetGroupSummary {
+            asset_group,
+            service_count: 2,
+            health_status: Some(HealthStatus::Healthy),
+            health_counts,
+        });
+
+        check_type(&service());
+        check_type(&ServiceRepr::default());
+        check_type(&ServiceRepr {
+            service_type: ServiceType::Processor,
+            standby_addresses: vec!["localhost:3456".to_string()],
+            failback: true,
+            ..service()
+        });
+        check_type(&ServiceRepr {
+            service_type: ServiceType::Processor,
+            pool: Some(PoolRepr {
+                replica_addresses: vec!["localhost:3456".to_string()],
+                strategy: BalancingStrategy::LeastLoaded,
+                load_metric: Some("queue".to_string()),
+            }),
+            ..service()
+        });
+        check_type(&ServicePage {
+            services: vec![ServiceSummary {
+                service_id: 1,
+                service: service(),
+            }],
+            next_cursor: Some("1:1".to_string()),
+        });
+        check_type(&ServiceDetail {
+            service_id: 1,
+            service: service(),
+            inputs: vec![],
+            outputs: vec![link()],
+        });
+
+        check_type(&ConfigUsageRepr {
+            config: config(),
+            used_by: vec!["localhost:1234".to_string()],
+        });
+        check_type(&SystemRepr {
+            asset_group_id: 1,
+            revision: Some(3),
+            services: vec![service()],
+            configs: vec![config()],
+        });
+        check_type(&SystemRevision {
+            asset_group_id: 1,
+            revision: 3,
+        });
+        let now = chrono::Utc::now();
+        let alert_state = AlertState {
+            alert_state_id: 1,
+            asset_group_id: 1,
+            dedup_key: "camera-cpu".to_string(),
+            alert_rule_id: 2,
+            address: "localhost:1234".to_string(),
+            status: AlertStatus::Firing,
+            message: "camera (localhost:1234) reported cpu 0.95 above 0.9".to_string(),
+            since: now,
+            fired_at: Some(now),
+            resolved_at: None,
+            acknowledged_at: Some(now),
+            acknowledged_by: Some("joshi".to_string()),
+            escalations: 1,
+            flapping: false,
+            transitions: vec![now],
+        };
+        check_type(&alert_state);
+        check_type(&AlertState {
+            acknowledged_by: None,
+            ..alert_state
+        });
+        check_type(&Acknowledgement::default());
+        let window = MaintenanceWindowRepr {
+            address: None,
+            label: Some("site=lab".to_string()),
+            reason: "Servicing".to_string(),
+            starts_at: now,
+            ends_at: now + chrono::Duration::hours(2),
+            recurrence: Recurrence::Weekly,
+            until: Some(now + chrono::Duration::weeks(4)),
+        };
+        check_type(&window);
+        check_type(&MaintenanceWindowRepr {
+            label: None,
+            reason: String::new(),
+            recurrence: Recurrence::Once,
+            until: None,
+            ..window.clone()
+        });
+        check_type(&MaintenanceWindow {
+            maintenance_window_id: 1,
+            asset_group_id: 1,
+            address: Some("localhost:1234".to_string()),
+            label: None,
+            reason: window.reason,
+            starts_at: window.starts_at,
+            ends_at: window.ends_at,
+            recurrence: Recurrence::Daily,
+            until: None,
+            expired_at: Some(now),
+            created_at: now,
+        });
+        check_type(&PendingService {
+            pending_service_id: 1,
+            asset_group_id: 1,
+            address: "localhost:1234".to_string(),
+            first_seen_at: now,
+            last_seen_at: now,
+        });
+        check_type(&ServiceApproval::default());
+        check_type(&ServiceApproval {
+            name: "camera".to_string(),
+            service_type: ServiceType::Input,
+            config: Some("default".to_string()),
+        });
+        let report = ReconciliationReport::new(1, vec![], &["localhost:1234".to_string()]);
+        check_type(&report);
+        check_type(&ReconciledService {
+            address: "localhost:1235".to_string(),
+            state: Reconciliation::Disconnected,
+            service_id: Some(2),
+            health_status: Some(HealthStatus::Disconnected),
+            last_connected_at: Some(now),
+        });
+
+        check_type(&ErrorResponse::from(
+            ErrorDetail::new(ErrorCode::ValidationFailed, "Invalid").with_field("name"),
+        ));
+    }
+
+    #[test]
+    fn test_check() {
+        let document = document();
+        let schema = schema_ref::<SystemRevision>();
+        assert!(check(
+            &document,
+            &schema,
+            &json!({ "asset_group_id": 1, "revision": 2 })
+        )
+        .is_ok());
+        assert!(check(&document, &schema, &json!({ "asset_group_id": 1 })).is_err());
+        assert!(check(
+            &document,
+            &schema,
+            &json!({ "asset_group_id": 1, "revision": 2, "extra": true })
+        )
+        .is_err());
+        assert!(check(&document, &schema_ref::<HealthStatus>(), &json!("Sleepy")).is_err());
+    }
+
+    #[test]
+    fn test_references() {
+        // Every reference in the document must resolve.
+        fn visit(document: &Value, value: &Value) {
+            match value {
+                Value::Object(object) => {
+                    if let Some(Value::String(reference)) = object.get("$ref") {
+                        let pointer = reference.trim_start_matches('#');
+                        assert!(document.pointer(pointer).is_some(), "{}", reference);
+                    }
+                    object.values().for_each(|value| visit(document, value));
+                }
+                Value::Array(values) => values.iter().for_each(|value| visit(document, value)),
+                _ => {}
+            }
+        }
+        let document = document();
+        visit(&document, &document);
+    }
+}
@@ -0,0 +1,101 @@
+//! Approval of addresses held by asset groups with the `Pending` registration policy.
+use actix::Addr;
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use diesel::PgConnection;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{Config, DbDelete, DbFind, PendingService, Service, ServiceApproval, ServiceDetail},
+    PgPool,
+};
+
+use crate::{
+    websocket::{ApproveSession, RejectSession, Server},
+    Error,
+};
+
+fn find(
+    conn: &PgConnection,
+    asset_group_id: i32,
+    pending_service_id: i32,
+) -> Result<PendingService, Error> {
+    match PendingService::find(conn, pending_service_id) {
+        Ok(pending) if pending.asset_group_id == asset_group_id => Ok(pending),
+        Ok(_) | Err(diesel::result::Error::NotFound) => Err(Error::NotFound(format!(
+            "Pending service {} not found",
+            pending_service_id
+        ))),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Lists the addresses waiting to be approved in an asset group, in the order they connected.
+pub async fn list(path: web::Path<i32>, pool: Data<PgPool>) -> Result<HttpResponse, Error> {
+    let conn = get_conn(&pool)?;
+    let pending = PendingService::get_group(&conn, path.into_inner())?;
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+/// Registers a pending address as a service with the given name, type and config.
+pub async fn approve(
+    path: web::Path<(i32, i32)>,
+    approval: web::Json<ServiceApproval>,
+    pool: Data<PgPool>,
+    server: Data<Addr<Server>>,
+) -> Result<HttpResponse, Error> {
+    let (asset_group_id, pending_service_id) = path.into_inner();
+    if approval.name.trim().is_empty() {
+        return Err(Error::InvalidField {
+            field: "name".to_string(),
+            message: "Service name must not be empty".to_string(),
+        });
+    }
+    let conn = get_conn(&pool)?;
+    let pending = find(&conn, asset_group_id, pending_service_id)?;
+    let config_id = match &approval.config {
+        Some(name) => match Config::find_by_name(&conn, asset_group_id, name)? {
+            Some(config) => Some(config.config_id),
+            None => {
+                return Err(Error::InvalidField {
+                    field: "config".to_string(),
+                    message: format!("Config {} not found", name),
+                })
+            }
+        },
+        None => None,
+    };
+    if Service::try_find_by_addr(&conn, &pending.address)?.is_some() {
+        return Err(Error::Conflict(format!(
+            "A service with address {} already exists",
+            pending.address
+        )));
+    }
+    let service = pending.approve(&conn, &approval, config_id)?;
+    server
+        .send(ApproveSession {
+            client_addr: service.address.clone(),
+        })
+        .await
+        .map_err(|err| Error::InternalServerError(err.to_string()))??;
+    let detail = ServiceDetail::get(&conn, asset_group_id, service.service_id)?
+        .ok_or_else(|| Error::NotFound(format!("Service {} not found", service.service_id)))?;
+    Ok(HttpResponse::Created().json(detail))
+}
+
+/// Forgets a pending address, closing its session. It is held again if it reconnects.
+pub async fn reject(
+    path: web::Path<(i32, i32)>,
+    pool: Data<PgPool>,
+    server: Data<Addr<Server>>,
+) -> Result<HttpResponse, Error> {
+    let (asset_group_id, pending_service_id) = path.into_inner();
+    let conn = get_conn(&pool)?;
+    let pending = find(&conn, asset_group_id, pending_service_id)?;
+    PendingService::delete(&conn, pending_service_id)?;
+    server.do_send(RejectSession {
+        client_addr: pending.address,
+    });
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,51 @@
+use actix::Addr;
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{ReconciliationReport, ServiceDetail, ServicePage, ServiceQuery},
+    PgPool,
+};
+
+use crate::{
+    websocket::{GetConnected, Server},
+    Error,
+};
+
+/// Lists a page of the services of an asset group, filtered and sorted by the query.
+pub async fn list(
+    path: web::Path<i32>,
+    query: web::Query<ServiceQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let conn = get_conn(&pool)?;
+    let page = ServicePage::get(&conn, path.into_inner(), &query)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// Shows a service with its input and output services.
+pub async fn show(path: web::Path<(i32, i32)>, pool: Data<PgPool>) -> Result<HttpResponse, Error> {
+    let (asset_group_id, service_id) = path.into_inner();
+    let conn = get_conn(&pool)?;
+    let service = ServiceDetail::get(&conn, asset_group_id, service_id)?
+        .ok_or_else(|| Error::NotFound(format!("Service {} not found", service_id)))?;
+    Ok(HttpResponse::Ok().json(service))
+}
+
+/// Compares the services declared in an asset group with the ones connected to the server.
+pub async fn reconciliation(
+    path: web::Path<i32>,
+    pool: Data<PgPool>,
+    server: Data<Addr<Server>>,
+) -> Result<HttpResponse, Error> {
+    let asset_group_id = path.into_inner();
+    let connected = server
+        .send(GetConnected { asset_group_id })
+        .await
+        .map_err(|err| Error::InternalServerError(err.to_string()))?;
+    let conn = get_conn(&pool)?;
+    let report = ReconciliationReport::get(&conn, asset_group_id, &connected)?;
+    Ok(HttpResponse::Ok().json(report))
+}
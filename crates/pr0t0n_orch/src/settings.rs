@@ -0,0 +1,601 @@
+//! Server settings, layered from defaults, a TOML file, environment variables and flags.
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use pr0t0n_orch_db::PoolSettings;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use crate::Error;
+
+/// Settings of the orchestrator server.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: PoolSettings,
+    pub websocket: WebsocketSettings,
+    pub smtp: SmtpSettings,
+    /// Notification channels by name, which alert rules refer to in `channels`.
+    pub channels: BTreeMap<String, ChannelSettings>,
+    pub alerts: AlertSettings,
+    pub features: Features,
+}
+
+/// Settings of the HTTP server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    /// Addresses to listen on.
+    pub bind: Vec<String>,
+    pub workers: usize,
+    /// Seconds to keep idle connections open.
+    pub keep_alive: usize,
+    /// Seconds workers get to finish their requests when stopping.
+    pub shutdown_timeout: u64,
+    /// `env_logger` filter, such as `info` or `pr0t0n_orch=debug,info`.
+    pub log_level: String,
+}
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            bind: vec!["127.0.0.1:8080".to_string()],
+            workers: 4,
+            keep_alive: 5,
+            shutdown_timeout: 30,
+            log_level: "info".to_string(),
+        }
+    }
+}
+
+/// Heartbeats and reconnection of websocket sessions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebsocketSettings {
+    /// Seconds between pings sent to each session.
+    pub heartbeat_interval: u64,
+    /// Seconds without a pong before a session is disconnected.
+    pub client_timeout: u64,
+    /// Seconds services connected before a restart get to reconnect before they are marked
+    /// disconnected.
+    pub reconcile_grace_period: u64,
+    /// Register services that connect without being declared in the system of their asset
+    /// group. When off, their sessions are accepted but leave the services untouched.
+    pub register_undeclared: bool,
+}
+impl Default for WebsocketSettings {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: 5,
+            client_timeout: 20,
+            reconcile_grace_period: 60,
+            register_undeclared: true,
+        }
+    }
+}
+impl WebsocketSettings {
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_interval)
+    }
+
+    pub fn client_timeout(&self) -> Duration {
+        Duration::from_secs(self.client_timeout)
+    }
+
+    pub fn reconcile_grace_period(&self) -> Duration {
+        Duration::from_secs(self.reconcile_grace_period)
+    }
+}
+
+/// How the connection to the SMTP server is secured.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain text, for local relays and tests only.
+    None,
+    /// Upgrade a plain connection with `STARTTLS`, usually on port 587.
+    StartTls,
+    /// TLS from the start, usually on port 465.
+    Tls,
+}
+
+/// Email notifications of alerts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpSettings {
+    /// SMTP server. Alerts are not emailed when this is empty.
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Seconds to wait for each SMTP command.
+    pub timeout: u64,
+    /// Attempts after the first failed one.
+    pub retries: u32,
+    /// Seconds before the first retry, doubled for each one after.
+    pub retry_backoff: u64,
+    /// Templates of the subject and body, see [`crate::notify::email::render`].
+    pub subject: String,
+    pub body: String,
+}
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 587,
+            security: SmtpSecurity::StartTls,
+            username: None,
+            password: None,
+            timeout: 10,
+            retries: 3,
+            retry_backoff: 2,
+            subject: "[pr0t0n] {event} alert on {service}".to_string(),
+            body: "{message}\n\nService: {service} ({address})\n\
+                   Asset group: {asset_group} ({asset_group_id})\n"
+                .to_string(),
+        }
+    }
+}
+impl SmtpSettings {
+    pub fn is_enabled(&self) -> bool {
+        !self.host.is_empty()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    /// Time to wait before a retry, where the first retry is 1.
+    pub fn retry_backoff(&self, retry: u32) -> Duration {
+        backoff(self.retry_backoff, retry)
+    }
+}
+
+/// `seconds` doubled for each retry after the first.
+fn backoff(seconds: u64, retry: u32) -> Duration {
+    let doublings = retry.saturating_sub(1).min(16);
+    Duration::from_secs(seconds.saturating_mul(1 << doublings))
+}
+
+/// A named notification channel.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ChannelSettings {
+    Webhook(WebhookSettings),
+}
+
+/// What a webhook is sent.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The alert as JSON, see [`crate::notify::webhook::WebhookPayload`].
+    Json,
+    /// A `{"text": ..}` message, as taken by the incoming webhooks of Slack, Mattermost and
+    /// Rocket.Chat.
+    Chat,
+}
+
+/// Alerts POSTed to an HTTP endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookSettings {
+    pub url: String,
+    pub format: WebhookFormat,
+    /// Key bodies are signed with in the `X-Pr0t0n-Signature` header. Bodies are not signed
+    /// without one.
+    pub secret: Option<String>,
+    /// Template of chat messages, see [`crate::notify::email::render`].
+    pub text: String,
+    /// Seconds to wait for a response.
+    pub timeout: u64,
+    /// Attempts after the first failed one.
+    pub retries: u32,
+    /// Seconds before the first retry, doubled for each one after.
+    pub retry_backoff: u64,
+}
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            format: WebhookFormat::Json,
+            secret: None,
+            text: "[pr0t0n] {message}".to_string(),
+            timeout: 10,
+            retries: 3,
+            retry_backoff: 2,
+        }
+    }
+}
+impl WebhookSettings {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    /// Time to wait before a retry, where the first retry is 1.
+    pub fn retry_backoff(&self, retry: u32) -> Duration {
+        backoff(self.retry_backoff, retry)
+    }
+}
+
+/// Timing of alerts and the detection of flapping ones.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertSettings {
+    /// Seconds between checks for pending alerts to fire and firing ones to escalate.
+    pub check_interval: u64,
+    /// Seconds over which the times an alert fired and resolved are counted.
+    pub flap_window: u64,
+    /// Times an alert may fire or resolve within the window before its notifications are held
+    /// back, or 0 to never hold them.
+    pub flap_threshold: usize,
+}
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            check_interval: 5,
+            flap_window: 600,
+            flap_threshold: 5,
+        }
+    }
+}
+impl AlertSettings {
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_secs(self.check_interval)
+    }
+
+    pub fn flap_window(&self) -> Duration {
+        Duration::from_secs(self.flap_window)
+    }
+}
+
+/// Optional endpoints, all enabled by default.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// `/openapi.json`.
+    pub openapi: bool,
+    /// `/metrics`.
+    pub metrics: bool,
+    /// `/ws/watch/`.
+    pub watch: bool,
+}
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            openapi: true,
+            metrics: true,
+            watch: true,
+        }
+    }
+}
+
+/// Command-line options, each also read from an environment variable. These override the
+/// settings file.
+#[derive(StructOpt, Debug, Default)]
+#[structopt(name = "pr0t0n_orch", about = "Pr0t0n Orchestrator server.")]
+pub struct Opt {
+    /// Settings file in TOML.
+    #[structopt(long, short, env = "PR0T0N_CONFIG", parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Address to listen on, instead of those in the settings file. Can be repeated.
+    #[structopt(long, env = "PR0T0N_BIND", use_delimiter = true)]
+    pub bind: Vec<String>,
+
+    #[structopt(long, env = "PR0T0N_WORKERS")]
+    pub workers: Option<usize>,
+
+    #[structopt(long, env = "RUST_LOG")]
+    pub log_level: Option<String>,
+
+    /// Postgres connection URL.
+    #[structopt(long, env = "DATABASE_URL", hide_env_values = true)]
+    pub database_url: Option<String>,
+
+    #[structopt(long, env = "PR0T0N_DATABASE_MAX_SIZE")]
+    pub database_max_size: Option<u32>,
+
+    #[structopt(long, env = "PR0T0N_HEARTBEAT_INTERVAL")]
+    pub heartbeat_interval: Option<u64>,
+
+    #[structopt(long, env = "PR0T0N_CLIENT_TIMEOUT")]
+    pub client_timeout: Option<u64>,
+
+    /// SMTP server alerts are emailed through.
+    #[structopt(long, env = "PR0T0N_SMTP_HOST")]
+    pub smtp_host: Option<String>,
+
+    #[structopt(long, env = "PR0T0N_SMTP_PORT")]
+    pub smtp_port: Option<u16>,
+
+    #[structopt(long, env = "PR0T0N_SMTP_USERNAME")]
+    pub smtp_username: Option<String>,
+
+    #[structopt(long, env = "PR0T0N_SMTP_PASSWORD", hide_env_values = true)]
+    pub smtp_password: Option<String>,
+}
+
+impl Settings {
+    /// Load the settings file named by the options, if any, and apply the options over it.
+    pub fn load(opt: Opt) -> Result<Self, Error> {
+        let settings = match &opt.config {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path).map_err(|err| {
+                    Error::InvalidSettings(format!("Cannot read {}: {}", path.display(), err))
+                })?;
+                Self::parse(&contents)?
+            }
+            None => Self::default(),
+        };
+        settings.with_opt(opt).validate()
+    }
+
+    /// Whether alerts are sent anywhere.
+    pub fn has_notifications(&self) -> bool {
+        self.smtp.is_enabled() || !self.channels.is_empty()
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        toml::from_str(contents).map_err(|err| Error::InvalidSettings(err.to_string()))
+    }
+
+    /// Override these settings with the options that were given.
+    pub fn with_opt(mut self, opt: Opt) -> Self {
+        if !opt.bind.is_empty() {
+            self.server.bind = opt.bind;
+        }
+        if let Some(workers) = opt.workers {
+            self.server.workers = workers;
+        }
+        if let Some(log_level) = opt.log_level {
+            self.server.log_level = log_level;
+        }
+        if let Some(url) = opt.database_url {
+            self.database.url = url;
+        }
+        if let Some(max_size) = opt.database_max_size {
+            self.database.max_size = max_size;
+        }
+        if let Some(heartbeat_interval) = opt.heartbeat_interval {
+            self.websocket.heartbeat_interval = heartbeat_interval;
+        }
+        if let Some(client_timeout) = opt.client_timeout {
+            self.websocket.client_timeout = client_timeout;
+        }
+        if let Some(host) = opt.smtp_host {
+            self.smtp.host = host;
+        }
+        if let Some(port) = opt.smtp_port {
+            self.smtp.port = port;
+        }
+        if let Some(username) = opt.smtp_username {
+            self.smtp.username = Some(username);
+        }
+        if let Some(password) = opt.smtp_password {
+            self.smtp.password = Some(password);
+        }
+        self
+    }
+
+    fn validate(self) -> Result<Self, Error> {
+        let invalid = |message: &str| Err(Error::InvalidSettings(message.to_string()));
+        if self.server.bind.is_empty() {
+            return invalid("server.bind needs at least one address");
+        }
+        if self.server.workers == 0 {
+            return invalid("server.workers must be at least 1");
+        }
+        if self.database.url.is_empty() {
+            return invalid("database.url or DATABASE_URL must be set");
+        }
+        if self.database.max_size == 0 {
+            return invalid("database.max_size must be at least 1");
+        }
+        if self.websocket.heartbeat_interval == 0 {
+            return invalid("websocket.heartbeat_interval must be at least 1");
+        }
+        if self.websocket.client_timeout <= self.websocket.heartbeat_interval {
+            return invalid("websocket.client_timeout must be longer than heartbeat_interval");
+        }
+        if self.smtp.is_enabled() {
+            if self.smtp.port == 0 {
+                return invalid("smtp.port must be set");
+            }
+            if self.smtp.username.is_some() != self.smtp.password.is_some() {
+                return invalid("smtp.username and smtp.password must be set together");
+            }
+        }
+        if self.alerts.check_interval == 0 {
+            return invalid("alerts.check_interval must be at least 1");
+        }
+        if self.alerts.flap_threshold == 1 {
+            return invalid("alerts.flap_threshold must be 0 or at least 2");
+        }
+        for (name, channel) in &self.channels {
+            if name == crate::notify::EMAIL {
+                return invalid("channels.email is taken by the [smtp] settings");
+            }
+            match channel {
+                ChannelSettings::Webhook(webhook) => {
+                    if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+                        return Err(Error::InvalidSettings(format!(
+                            "channels.{}.url must be an http or https URL",
+                            name
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let settings = Settings::parse(
+            r#"
+            [server]
+            bind = ["0.0.0.0:80", "[::]:80"]
+            workers = 8
+
+            [database]
+            url = "postgres://localhost/pr0t0n"
+
+            [features]
+            openapi = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.server.bind, vec!["0.0.0.0:80", "[::]:80"]);
+        assert_eq!(settings.server.workers, 8);
+        assert_eq!(settings.server.log_level, "info");
+        assert_eq!(settings.database.max_size, 1);
+        assert_eq!(settings.websocket, WebsocketSettings::default());
+        assert!(!settings.features.openapi);
+        assert!(settings.features.metrics);
+
+        assert!(!settings.smtp.is_enabled());
+
+        assert!(Settings::parse("[server]\nworker = 8").is_err());
+        assert_eq!(Settings::parse("").unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn test_layers() {
+        let file = Settings::parse("[server]\nworkers = 8\nlog_level = \"debug\"").unwrap();
+        let settings = file
+            .with_opt(Opt {
+                workers: Some(2),
+                database_url: Some("postgres://localhost/pr0t0n".to_string()),
+                ..Default::default()
+            })
+            .validate()
+            .unwrap();
+        assert_eq!(settings.server.workers, 2);
+        assert_eq!(settings.server.log_level, "debug");
+        assert_eq!(settings.database.url, "postgres://localhost/pr0t0n");
+
+        // Without a database there is nothing to serve.
+        assert!(Settings::default().validate().is_err());
+        let opt = Opt::from_iter_safe(&[
+            "pr0t0n_orch",
+            "--bind",
+            "0.0.0.0:80",
+            "--bind",
+            "0.0.0.0:8080",
+            "--client-timeout",
+            "3",
+            "--database-url",
+            "postgres://localhost/pr0t0n",
+        ])
+        .unwrap();
+        assert_eq!(opt.bind, vec!["0.0.0.0:80", "0.0.0.0:8080"]);
+        assert!(Settings::default().with_opt(opt).validate().is_err());
+    }
+
+    #[test]
+    fn test_smtp() {
+        let settings = Settings::parse(
+            r#"
+            [database]
+            url = "postgres://localhost/pr0t0n"
+
+            [smtp]
+            host = "smtp.gmail.com"
+            security = "tls"
+            port = 465
+            username = "pr0t0nalerts@gmail.com"
+            retry_backoff = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.smtp.security, SmtpSecurity::Tls);
+        assert_eq!(settings.smtp.retry_backoff(1), Duration::from_secs(3));
+        assert_eq!(settings.smtp.retry_backoff(3), Duration::from_secs(12));
+        // A username without a password is a mistake.
+        assert!(settings.clone().validate().is_err());
+        let opt = Opt {
+            smtp_password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+        assert!(settings.with_opt(opt).validate().is_ok());
+    }
+
+    #[test]
+    fn test_channels() {
+        let settings = Settings::parse(
+            r#"
+            [database]
+            url = "postgres://localhost/pr0t0n"
+
+            [channels.oncall]
+            type = "webhook"
+            url = "https://oncall.example.com/hooks/pr0t0n"
+            secret = "hunter2"
+            retries = 5
+
+            [channels.ops-chat]
+            type = "webhook"
+            format = "chat"
+            url = "https://chat.example.com/hooks/abc"
+            "#,
+        )
+        .unwrap();
+        assert!(settings.has_notifications());
+        let ChannelSettings::Webhook(oncall) = &settings.channels["oncall"];
+        assert_eq!(oncall.format, WebhookFormat::Json);
+        assert_eq!(oncall.secret.as_deref(), Some("hunter2"));
+        assert_eq!(oncall.retries, 5);
+        assert_eq!(oncall.retry_backoff(2), Duration::from_secs(4));
+        assert!(settings.clone().validate().is_ok());
+
+        // Channels need a type, and unknown fields are mistakes.
+        assert!(Settings::parse("[channels.oncall]\nurl = \"http://localhost\"").is_err());
+        assert!(Settings::parse(
+            "[channels.oncall]\ntype = \"webhook\"\nurl = \"http://localhost\"\nsecert = \"x\""
+        )
+        .is_err());
+
+        let mut invalid = settings.clone();
+        let webhook = invalid.channels["oncall"].clone();
+        invalid.channels.insert("email".to_string(), webhook);
+        assert!(invalid.validate().is_err());
+        let mut invalid = settings;
+        invalid.channels.insert(
+            "local".to_string(),
+            ChannelSettings::Webhook(WebhookSettings {
+                url: "localhost:8080".to_string(),
+                ..Default::default()
+            }),
+        );
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_alerts() {
+        let settings = Settings::parse(
+            r#"
+            [database]
+            url = "postgres://localhost/pr0t0n"
+
+            [alerts]
+            flap_threshold = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.alerts.flap_threshold, 0);
+        assert_eq!(settings.alerts.flap_window(), Duration::from_secs(600));
+        assert!(settings.clone().validate().is_ok());
+
+        let mut invalid = settings.clone();
+        invalid.alerts.check_interval = 0;
+        assert!(invalid.validate().is_err());
+        let mut invalid = settings;
+        invalid.alerts.flap_threshold = 1;
+        assert!(invalid.validate().is_err());
+    }
+}
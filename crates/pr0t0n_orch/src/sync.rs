@@ -1,35 +1,393 @@
+use std::{collections::HashSet, time::Instant};
+
 use actix_web::{
+    http::header,
     web::{self, Data},
-    Responder,
+    HttpMessage, HttpRequest, HttpResponse,
 };
+use diesel::{Connection, PgConnection};
 use pr0t0n_orch_db::{
     get_conn,
-    models::{GetGroupRequest, SystemRepr},
+    models::{revision_etag, AssetGroup, SystemRepr, SystemRevision},
     PgPool,
 };
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configs,
+    errors::{ErrorCode, ErrorDetail, ErrorResponse},
+    metrics::METRICS,
+    Error,
+};
+
+/// Media type of RFC 6902 JSON Patch documents.
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+/// Media type of RFC 7396 JSON Merge Patch documents.
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+
+fn asset_group_not_found(asset_group_id: i32) -> Error {
+    Error::NotFound(format!("Asset group {} not found", asset_group_id))
+}
+
+/// Whether an `If-Match` header matches an ETag. Uses strong comparison, as the header requires.
+fn if_match(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Checks every system must pass before it is synced.
+fn validate(system: &SystemRepr) -> Result<(), Error> {
+    let mut addresses = HashSet::new();
+    for (i, service) in system.services.iter().enumerate() {
+        let field = format!("services/{}/address", i);
+        if service.address.trim().is_empty() {
+            return Err(Error::InvalidField {
+                field,
+                message: "Service addresses must not be empty".to_string(),
+            });
+        }
+        if !addresses.insert(&service.address) {
+            return Err(Error::InvalidField {
+                field,
+                message: format!(
+                    "Service address '{}' is used more than once",
+                    service.address
+                ),
+            });
+        }
+        for (j, rule) in service.alerts.iter().enumerate() {
+            if let Err((name, message)) = rule.validate() {
+                return Err(Error::InvalidField {
+                    field: format!("services/{}/alerts/{}/{}", i, j, name),
+                    message,
+                });
+            }
+        }
+    }
+    let mut names = HashSet::new();
+    for (i, config) in system.configs.iter().enumerate() {
+        let path = format!("configs/{}/", i);
+        configs::validate(config, &path)?;
+        if !names.insert(&config.name) {
+            return Err(Error::InvalidField {
+                field: format!("{}name", path),
+                message: format!("Config name '{}' is used more than once", config.name),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn get_if_match(request: &HttpRequest) -> Option<&str> {
+    request
+        .headers()
+        .get(header::IF_MATCH)
+        .and_then(|value| value.to_str().ok())
+}
 
-use crate::Error;
+/// Validate and sync the system built by `build` from the current revision, in one transaction
+/// holding the asset group's revision lock. Returns the new revision, or `Err` with the current
+/// revision if it does not match `expected`.
+fn sync_locked(
+    conn: &PgConnection,
+    asset_group_id: i32,
+    expected: Option<&str>,
+    build: impl FnOnce(i64) -> Result<SystemRepr, Error>,
+) -> Result<Result<i64, i64>, Error> {
+    let started = Instant::now();
+    // Lock the revision so concurrent changes are checked one at a time.
+    let result = conn.transaction::<_, Error, _>(|| {
+        let current = AssetGroup::lock_revision(conn, asset_group_id)?
+            .ok_or_else(|| asset_group_not_found(asset_group_id))?;
+        if let Some(expected) = expected {
+            if !if_match(expected, &revision_etag(asset_group_id, current)) {
+                return Ok(Err(current));
+            }
+        }
+        let mut system = build(current)?;
+        validate(&system)?;
+        system.sync_db(conn)?;
+        let revision = AssetGroup::get_revision(conn, asset_group_id)?
+            .ok_or_else(|| asset_group_not_found(asset_group_id))?;
+        Ok(Ok(revision))
+    });
+    METRICS.sync_duration.observe(started.elapsed());
+    result
+}
 
+fn sync_response(asset_group_id: i32, result: Result<i64, i64>) -> HttpResponse {
+    match result {
+        Ok(revision) => HttpResponse::Ok()
+            .header(header::ETAG, revision_etag(asset_group_id, revision))
+            .json(SystemRevision {
+                asset_group_id,
+                revision,
+            }),
+        Err(current) => {
+            let message = format!(
+                "Asset group {} was changed by someone else and is now at revision {}",
+                asset_group_id, current
+            );
+            HttpResponse::Conflict()
+                .header(header::ETAG, revision_etag(asset_group_id, current))
+                .json(ErrorResponse::from(ErrorDetail::new(
+                    ErrorCode::RevisionConflict,
+                    message,
+                )))
+        }
+    }
+}
+
+/// Makes an asset group match an uploaded system. When `If-Match` is given, the upload is refused
+/// with 409 unless the asset group is still at that revision.
 pub async fn upload(
-    mut system: web::Json<SystemRepr>,
+    system: web::Json<SystemRepr>,
+    request: HttpRequest,
     pool: Data<PgPool>,
-) -> Result<impl Responder, Error> {
-    println!("Upload!");
-    println!("System: {:#?}", system);
+) -> Result<HttpResponse, Error> {
+    let system = system.into_inner();
+    let asset_group_id = system.asset_group_id;
     let conn = get_conn(&pool)?;
-    let res = system.sync_db(&conn);
-    if let Err(e) = res {
-        println!("Error: {:#?}", e);
+    let result = sync_locked(&conn, asset_group_id, get_if_match(&request), |_| {
+        Ok(system)
+    })?;
+    Ok(sync_response(asset_group_id, result))
+}
+
+/// A patch to the JSON form of a `SystemRepr`.
+pub enum SystemPatch {
+    Json(json_patch::Patch),
+    Merge(serde_json::Value),
+}
+impl SystemPatch {
+    /// Parse a patch, choosing the kind from the request's content type.
+    pub fn parse(content_type: &str, body: &[u8]) -> Result<Self, Error> {
+        let invalid = |err: serde_json::Error| Error::BadRequest(format!("Invalid patch: {}", err));
+        match content_type {
+            JSON_PATCH_CONTENT_TYPE => {
+                Ok(Self::Json(serde_json::from_slice(body).map_err(invalid)?))
+            }
+            MERGE_PATCH_CONTENT_TYPE => {
+                Ok(Self::Merge(serde_json::from_slice(body).map_err(invalid)?))
+            }
+            _ => Err(Error::UnsupportedMediaType(format!(
+                "Patches must be {} or {}",
+                JSON_PATCH_CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE
+            ))),
+        }
+    }
+
+    /// Apply the patch to a system, which must stay in the same asset group.
+    pub fn apply(&self, system: &SystemRepr) -> Result<SystemRepr, Error> {
+        let mut value = serde_json::to_value(system)?;
+        match self {
+            Self::Json(patch) => json_patch::patch(&mut value, patch).map_err(|err| {
+                Error::UnprocessableEntity(format!("Failed to apply patch: {}", err))
+            })?,
+            Self::Merge(patch) => json_patch::merge(&mut value, patch),
+        }
+        let patched: SystemRepr = serde_json::from_value(value).map_err(|err| {
+            Error::UnprocessableEntity(format!("Patched system is invalid: {}", err))
+        })?;
+        if patched.asset_group_id != system.asset_group_id {
+            return Err(Error::UnprocessableEntity(
+                "Patches must not change asset_group_id".to_string(),
+            ));
+        }
+        Ok(patched)
     }
-    Ok("Done")
 }
 
-pub async fn download(
-    get_group_req: web::Json<GetGroupRequest>,
+/// Applies a JSON Patch or JSON Merge Patch to the current system of an asset group, then syncs
+/// the result the same way as an upload.
+pub async fn patch(
+    path: web::Path<i32>,
+    body: web::Bytes,
+    request: HttpRequest,
     pool: Data<PgPool>,
-) -> Result<impl Responder, Error> {
-    println!("Download!");
+) -> Result<HttpResponse, Error> {
+    let asset_group_id = path.into_inner();
+    let patch = SystemPatch::parse(request.content_type(), &body)?;
     let conn = get_conn(&pool)?;
-    let system_repr = SystemRepr::get_group(&conn, get_group_req.asset_group_id)?;
-    Ok(web::Json(system_repr))
+    let result = sync_locked(&conn, asset_group_id, get_if_match(&request), |revision| {
+        let mut current = SystemRepr::get_group(&conn, asset_group_id)?;
+        current.revision = Some(revision);
+        patch.apply(&current)
+    })?;
+    Ok(sync_response(asset_group_id, result))
+}
+
+/// Formats a system representation can be downloaded in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+impl SystemFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Yaml => "application/yaml; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SystemQuery {
+    #[serde(default)]
+    pub format: SystemFormat,
+}
+
+/// Whether an `If-None-Match` header matches an ETag. Uses weak comparison, as the header requires.
+fn if_none_match(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Downloads the system of an asset group, or responds 304 if the client's copy is current.
+pub async fn system(
+    path: web::Path<i32>,
+    query: web::Query<SystemQuery>,
+    request: HttpRequest,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, Error> {
+    let asset_group_id = path.into_inner();
+    let cached = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok());
+    let conn = get_conn(&pool)?;
+
+    // Read the revision and the system from one snapshot, so the ETag always matches the body.
+    let (etag, system_repr) = conn
+        .build_transaction()
+        .read_only()
+        .repeatable_read()
+        .run::<_, Error, _>(|| {
+            let revision = AssetGroup::get_revision(&conn, asset_group_id)?
+                .ok_or_else(|| asset_group_not_found(asset_group_id))?;
+            let etag = revision_etag(asset_group_id, revision);
+            if cached.is_some_and(|cached| if_none_match(cached, &etag)) {
+                return Ok((etag, None));
+            }
+            let mut system_repr = SystemRepr::get_group(&conn, asset_group_id)?;
+            system_repr.revision = Some(revision);
+            Ok((etag, Some(system_repr)))
+        })?;
+
+    let system_repr = match system_repr {
+        Some(system_repr) => system_repr,
+        None => {
+            return Ok(HttpResponse::NotModified()
+                .header(header::ETAG, etag)
+                .finish())
+        }
+    };
+    let body = match query.format {
+        SystemFormat::Json => serde_json::to_string(&system_repr)?,
+        SystemFormat::Yaml => serde_yaml::to_string(&system_repr)?,
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(query.format.content_type())
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pr0t0n_orch_db::models::{ConfigRepr, ServiceRepr};
+
+    fn system() -> SystemRepr {
+        SystemRepr {
+            asset_group_id: 3,
+            revision: Some(12),
+            services: vec![ServiceRepr {
+                address: "localhost:123".to_string(),
+                name: "camera".to_string(),
+                config_name: Some("camera".to_string()),
+                ..Default::default()
+            }],
+            configs: vec![ConfigRepr {
+                name: "camera".to_string(),
+                json_config: serde_json::json!({ "fps": 30, "exposure": 10 }),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let patch = SystemPatch::parse(
+            JSON_PATCH_CONTENT_TYPE,
+            br#"[{ "op": "replace", "path": "/configs/0/json_config/fps", "value": 15 }]"#,
+        )
+        .unwrap();
+        let patched = patch.apply(&system()).unwrap();
+        assert_eq!(
+            patched.configs[0].json_config,
+            serde_json::json!({ "fps": 15, "exposure": 10 })
+        );
+        assert_eq!(patched.services, system().services);
+
+        let failing = SystemPatch::parse(
+            JSON_PATCH_CONTENT_TYPE,
+            br#"[{ "op": "remove", "path": "/configs/5" }]"#,
+        )
+        .unwrap();
+        assert!(failing.apply(&system()).is_err());
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let body = serde_json::to_vec(&serde_json::json!({
+            "services": [{
+                "address": "localhost:234",
+                "service_type": "Output",
+                "health_status": "Healthy",
+                "name": "arm",
+                "output_addresses": [],
+                "config_name": null,
+            }]
+        }))
+        .unwrap();
+        let patch = SystemPatch::parse(MERGE_PATCH_CONTENT_TYPE, &body).unwrap();
+        let patched = patch.apply(&system()).unwrap();
+        assert_eq!(patched.services.len(), 1);
+        assert_eq!(patched.services[0].name, "arm");
+        assert_eq!(patched.configs, system().configs);
+
+        let moved =
+            SystemPatch::parse(MERGE_PATCH_CONTENT_TYPE, br#"{ "asset_group_id": 4 }"#).unwrap();
+        assert!(moved.apply(&system()).is_err());
+        assert!(SystemPatch::parse("application/json", b"{}").is_err());
+    }
+
+    #[test]
+    fn test_if_match() {
+        let etag = revision_etag(3, 12);
+        assert!(if_match("\"3-12\"", &etag));
+        assert!(if_match("*", &etag));
+        assert!(!if_match("W/\"3-12\"", &etag));
+        assert!(!if_match("\"3-11\"", &etag));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = revision_etag(3, 12);
+        assert_eq!(etag, "\"3-12\"");
+        assert!(if_none_match("\"3-12\"", &etag));
+        assert!(if_none_match("\"3-11\", W/\"3-12\"", &etag));
+        assert!(if_none_match("*", &etag));
+        assert!(!if_none_match("\"3-11\"", &etag));
+        assert!(!if_none_match("\"13-12\"", &etag));
+    }
 }
@@ -1,4 +1,11 @@
-use std::time::SystemTime;
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::SystemTime,
+};
 
 use actix::Actor;
 use actix_http::Request;
@@ -8,7 +15,9 @@ use actix_web_actors::ws;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json;
 
-use crate::{routes, websocket::Server};
+use pr0t0n_orch_db::{new_pool, PoolSettings};
+
+use crate::{routes, settings::WebsocketSettings, websocket::Server};
 
 #[derive(Deserialize, Serialize, Debug)]
 struct CookieValue {
@@ -23,8 +32,9 @@ pub async fn get_service(
 ) -> impl Service<Request = Request, Response = ServiceResponse<Body>, Error = Error> {
     test::init_service(
         App::new()
-            .data(pr0t0n_orch_db::new_pool())
-            .data(Server::new(pr0t0n_orch_db::new_pool()).start())
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(Server::new(new_pool(&PoolSettings::from_env())).start())
+            .data(WebsocketSettings::default())
             .configure(routes),
     )
     .await
@@ -33,8 +43,9 @@ pub async fn get_service(
 pub fn get_test_server() -> test::TestServer {
     test::start(|| {
         App::new()
-            .data(pr0t0n_orch_db::new_pool())
-            .data(Server::new(pr0t0n_orch_db::new_pool()).start())
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(Server::new(new_pool(&PoolSettings::from_env())).start())
+            .data(WebsocketSettings::default())
             .configure(routes)
     })
 }
@@ -105,3 +116,153 @@ pub fn get_websocket_frame_data(frame: ws::Frame) -> Option<String> {
     }
     None
 }
+
+/// A local SMTP server that keeps the messages it is sent, for testing email notifications.
+pub struct SmtpSink {
+    pub port: u16,
+    messages: Arc<Mutex<Vec<String>>>,
+}
+impl SmtpSink {
+    /// Start the sink on a free port. The first `unavailable` connections are turned away with a
+    /// transient error, like a busy server would.
+    pub fn start(unavailable: usize) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let received = messages.clone();
+        thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                if i < unavailable {
+                    let _ = stream.write_all(b"421 Service not available\r\n");
+                    continue;
+                }
+                if let Ok(Some(message)) = Self::session(&mut stream) {
+                    received.lock().unwrap().push(message);
+                }
+            }
+        });
+        Self { port, messages }
+    }
+
+    /// Speak just enough SMTP to receive one message.
+    fn session(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        stream.write_all(b"220 localhost ESMTP\r\n")?;
+        let mut message = None;
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            let command = line.trim_end().to_uppercase();
+            if command.starts_with("DATA") {
+                stream.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")?;
+                let mut data = String::new();
+                loop {
+                    let mut data_line = String::new();
+                    if reader.read_line(&mut data_line)? == 0 || data_line == ".\r\n" {
+                        break;
+                    }
+                    data.push_str(&data_line);
+                }
+                message = Some(data);
+                stream.write_all(b"250 OK\r\n")?;
+            } else if command.starts_with("QUIT") {
+                stream.write_all(b"221 Bye\r\n")?;
+                break;
+            } else {
+                stream.write_all(b"250 OK\r\n")?;
+            }
+            line.clear();
+        }
+        Ok(message)
+    }
+
+    /// Messages received so far, with their headers.
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+/// A request received by a [`WebhookSink`].
+#[derive(Debug, Clone)]
+pub struct WebhookRequest {
+    /// Headers, with lowercase names.
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+/// A local HTTP server that keeps the requests it is sent, for testing webhook notifications.
+pub struct WebhookSink {
+    pub port: u16,
+    requests: Arc<Mutex<Vec<WebhookRequest>>>,
+}
+impl WebhookSink {
+    /// Start the sink on a free port. The first `unavailable` requests get a 503, and those after
+    /// are accepted; all of them are kept.
+    pub fn start(unavailable: usize) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                if let Ok(request) = Self::read_request(&mut stream) {
+                    received.lock().unwrap().push(request);
+                }
+                let status = if i < unavailable {
+                    "503 Service Unavailable"
+                } else {
+                    "200 OK"
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+            }
+        });
+        Self { port, requests }
+    }
+
+    fn read_request(stream: &mut TcpStream) -> std::io::Result<WebhookRequest> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut headers = BTreeMap::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+        let length = headers
+            .get("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        Ok(WebhookRequest {
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+    }
+
+    /// Requests received so far.
+    pub fn requests(&self) -> Vec<WebhookRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// URL of the sink.
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}/hooks/alerts", self.port)
+    }
+}
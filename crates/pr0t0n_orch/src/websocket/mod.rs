@@ -1,13 +1,20 @@
 use actix::prelude::Addr;
 use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{AssetGroup, RegistrationPolicy, Service},
+    PgPool,
+};
 
-use crate::Error;
+use crate::{settings::WebsocketSettings, Error};
 
 mod server;
 pub use server::*;
 mod session;
 use session::WebSocketSession;
+mod watch;
+use watch::WatchSession;
 
 use pr0t0n_orch_db::{PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER};
 
@@ -15,6 +22,17 @@ fn get_header_str<'a>(req: &'a HttpRequest, key: &str) -> Option<&'a str> {
     req.headers().get(key)?.to_str().ok()
 }
 
+fn get_asset_group_header(req: &HttpRequest) -> Result<i32, Error> {
+    get_header_str(req, PR0T0N_ASSET_GROUP_ID_HEADER)
+        .and_then(|asset_group_id_str| asset_group_id_str.parse::<i32>().ok())
+        .ok_or_else(|| {
+            Error::BadRequest(format!(
+                "Missing required header {}.",
+                PR0T0N_ASSET_GROUP_ID_HEADER
+            ))
+        })
+}
+
 fn get_conn_headers<'a>(req: &'a HttpRequest) -> Result<(i32, &'a str), Error> {
     match (
         get_header_str(&req, PR0T0N_ASSET_GROUP_ID_HEADER),
@@ -43,13 +61,30 @@ pub async fn ws_index(
     request: HttpRequest,
     stream: web::Payload,
     server_addr: web::Data<Addr<Server>>,
+    settings: web::Data<WebsocketSettings>,
+    pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let (asset_group_id, client_addr) = get_conn_headers(&request)?;
+    // Asset groups with the strict policy only accept their own services.
+    let conn = get_conn(&pool).map_err(Error::from)?;
+    let policy = AssetGroup::get_registration_policy(&conn, asset_group_id).map_err(Error::from)?;
+    if policy == Some(RegistrationPolicy::Strict) {
+        let service = Service::try_find_by_addr(&conn, client_addr).map_err(Error::from)?;
+        let known = service.is_some_and(|service| service.asset_group_id == asset_group_id);
+        if !known {
+            warn!(
+                "Rejected {}, which is not a service of asset group {}",
+                client_addr, asset_group_id
+            );
+            return Err(Error::Forbidden.into());
+        }
+    }
     let res = ws::start(
         WebSocketSession::new(
             server_addr.get_ref().clone(),
             client_addr.to_string(),
             asset_group_id,
+            settings.get_ref().clone(),
         ),
         &request,
         stream,
@@ -57,3 +92,22 @@ pub async fn ws_index(
 
     Ok(res)
 }
+
+/// Streams the events of an asset group to a read-only watcher such as `porch watch`.
+pub async fn ws_watch(
+    request: HttpRequest,
+    stream: web::Payload,
+    server_addr: web::Data<Addr<Server>>,
+    settings: web::Data<WebsocketSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let asset_group_id = get_asset_group_header(&request)?;
+    ws::start(
+        WatchSession::new(
+            server_addr.get_ref().clone(),
+            asset_group_id,
+            settings.get_ref().clone(),
+        ),
+        &request,
+        stream,
+    )
+}
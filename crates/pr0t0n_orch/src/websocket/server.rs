@@ -1,36 +1,663 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 
-use actix::prelude::{Actor, Context, Handler, Message, Recipient};
-use pr0t0n_orch_db::{get_conn, models::Service, Error, PgPool};
+use actix::prelude::{
+    Actor, Addr, AsyncContext, Context, Handler, Message, MessageResult, Recipient,
+};
+use chrono::{DateTime, Utc};
+use diesel::PgConnection;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AlertRule, AlertState, AlertStatus, AssetGroup, Config, DbFind, DbInsert, Event, EventKind,
+        Failover, HealthStatus, MaintenanceWindow, NewAlertState, PendingService,
+        RegistrationPolicy, Routing, Service, ServicePool, ServiceStatus, ServiceType,
+    },
+    Error, PgPool,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::{
+    alerts::{self, Alert, Change},
+    notify::{Dispatcher, Notify},
+    settings::AlertSettings,
+};
+
 #[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub struct TextMessage(pub String);
 
+/// Asks a session to send a close frame with the given reason and stop.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct CloseSession(pub String);
+
 struct Session {
     addr: Recipient<TextMessage>,
+    close: Recipient<CloseSession>,
+    asset_group_id: i32,
+    /// Config the service last reported running.
+    config_name: Option<String>,
+    /// Metrics the service last reported.
+    metrics: BTreeMap<String, f64>,
+    /// Whether the session updates a service, as opposed to an undeclared one that is not
+    /// registered.
+    registered: bool,
 }
 impl Session {
-    fn new(addr: Recipient<TextMessage>) -> Self {
-        Session { addr }
+    fn new(
+        addr: Recipient<TextMessage>,
+        close: Recipient<CloseSession>,
+        asset_group_id: i32,
+        registered: bool,
+    ) -> Self {
+        Session {
+            addr,
+            close,
+            asset_group_id,
+            config_name: None,
+            metrics: BTreeMap::new(),
+            registered,
+        }
     }
 }
 
+struct Watcher {
+    addr: Recipient<TextMessage>,
+    close: Recipient<CloseSession>,
+}
+
 /// Server for managing websockets.
 pub struct Server {
     pool: PgPool,
     sessions: HashMap<String, Session>,
+    /// Watcher sessions by asset group, then by watcher ID.
+    watchers: HashMap<i32, HashMap<usize, Watcher>>,
+    next_watcher_id: usize,
+    notifier: Option<Addr<Dispatcher>>,
+    alert_settings: AlertSettings,
+    register_undeclared: bool,
 }
 impl Server {
     pub fn new(pool: PgPool) -> Self {
         Server {
             pool,
             sessions: HashMap::new(),
+            watchers: HashMap::new(),
+            next_watcher_id: 0,
+            notifier: None,
+            alert_settings: AlertSettings::default(),
+            register_undeclared: true,
         }
     }
 
+    /// Send fired alerts through the channels of `notifier`.
+    pub fn with_notifier(mut self, notifier: Addr<Dispatcher>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn with_alert_settings(mut self, alert_settings: AlertSettings) -> Self {
+        self.alert_settings = alert_settings;
+        self
+    }
+
+    /// Whether services that connect without being declared are registered. See
+    /// [`WebsocketSettings::register_undeclared`](crate::settings::WebsocketSettings).
+    pub fn with_register_undeclared(mut self, register_undeclared: bool) -> Self {
+        self.register_undeclared = register_undeclared;
+        self
+    }
+
+    /// Send an event to everyone watching its asset group.
+    fn publish(&self, event: Event) {
+        let watchers = match self.watchers.get(&event.asset_group_id) {
+            Some(watchers) => watchers,
+            None => return,
+        };
+        let data = match serde_json::to_string(&event) {
+            Ok(data) => data,
+            Err(err) => {
+                error!("Error serializing event {:?}: {:?}", event, err);
+                return;
+            }
+        };
+        for watcher in watchers.values() {
+            if let Err(err) = watcher.addr.do_send(TextMessage(data.clone())) {
+                error!("Error sending watcher message: {:?}", err);
+            }
+        }
+    }
+
+    /// Log an event and send it to watchers.
+    fn record(&self, conn: &PgConnection, event: Event) {
+        if let Err(err) = event.log(conn) {
+            error!("Error logging event {:?}: {:?}", event, err);
+        }
+        self.publish(event);
+    }
+
+    /// The maintenance window covering a service at `now`, if any.
+    fn maintenance(
+        &self,
+        conn: &PgConnection,
+        service: &Service,
+        now: DateTime<Utc>,
+    ) -> Option<MaintenanceWindow> {
+        match MaintenanceWindow::find_covering(conn, service, now) {
+            Ok(window) => window,
+            Err(err) => {
+                error!(
+                    "Error getting maintenance windows of {}: {:?}",
+                    service.address, err
+                );
+                None
+            }
+        }
+    }
+
+    fn notify(&self, alert: Alert) {
+        if let Some(notifier) = &self.notifier {
+            notifier.do_send(Notify(alert));
+        }
+    }
+
+    /// Note that an alert fired or resolved, notifying it unless it is flapping. Only the start
+    /// of flapping is notified, until it settles.
+    fn transition(
+        &self,
+        conn: &PgConnection,
+        state: &mut AlertState,
+        alert: Alert,
+        now: DateTime<Utc>,
+    ) {
+        let flapping = alerts::note_transition(state, now, &self.alert_settings);
+        if state.flapping {
+            return;
+        }
+        if flapping {
+            warn!("Alert {} is flapping: {}", state.dedup_key, state.message);
+            state.flapping = true;
+            self.record(
+                conn,
+                Event::new(
+                    state.asset_group_id,
+                    EventKind::AlertFlapping {
+                        address: state.address.clone(),
+                        alert_rule_id: state.alert_rule_id,
+                        flapping: true,
+                    },
+                ),
+            );
+            self.notify(alert.flapping());
+        } else {
+            self.notify(alert);
+        }
+    }
+
+    /// Fire a pending alert, recording it and starting its escalation afresh.
+    fn fire(&self, conn: &PgConnection, state: &mut AlertState, alert: Alert, now: DateTime<Utc>) {
+        warn!("Alert {}: {}", alert.alert_rule_id, alert.message);
+        state.status = AlertStatus::Firing;
+        state.fired_at = Some(now);
+        state.resolved_at = None;
+        state.acknowledged_at = None;
+        state.acknowledged_by = None;
+        state.escalations = 0;
+        self.record(
+            conn,
+            Event::new(
+                alert.asset_group_id,
+                EventKind::AlertFired {
+                    address: alert.address.clone(),
+                    alert_rule_id: alert.alert_rule_id,
+                    message: alert.message.clone(),
+                },
+            ),
+        );
+        self.transition(conn, state, alert, now);
+    }
+
+    /// Start an alert of a rule, firing it at once unless the rule has a `for`. Alerts already
+    /// pending or firing under the same key are left alone. During a maintenance window, the
+    /// alert stays pending until the window ends.
+    fn trigger(
+        &self,
+        conn: &PgConnection,
+        alert_rule: &AlertRule,
+        alert: Alert,
+        maintenance: Option<&MaintenanceWindow>,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut state = match AlertState::find_key(conn, alert.asset_group_id, &alert.dedup_key)? {
+            Some(state) if state.status != AlertStatus::Resolved => return Ok(()),
+            Some(mut state) => {
+                state.status = AlertStatus::Pending;
+                state.since = now;
+                state
+            }
+            None => NewAlertState {
+                asset_group_id: alert.asset_group_id,
+                dedup_key: &alert.dedup_key,
+                alert_rule_id: alert.alert_rule_id,
+                address: &alert.address,
+                status: AlertStatus::Pending,
+                message: &alert.message,
+                since: now,
+                fired_at: None,
+                transitions: vec![],
+            }
+            .insert(conn)?,
+        };
+        state.alert_rule_id = alert.alert_rule_id;
+        state.address = alert.address.clone();
+        state.message = alert.message.clone();
+        if let Some(window) = maintenance {
+            info!(
+                "Alert {} silenced by maintenance window {}: {}",
+                alert.alert_rule_id, window.maintenance_window_id, alert.message
+            );
+        } else if alert_rule.rule.for_seconds == 0 {
+            self.fire(conn, &mut state, alert, now);
+        } else {
+            info!(
+                "Alert {} pending for {}s: {}",
+                alert.alert_rule_id, alert_rule.rule.for_seconds, alert.message
+            );
+        }
+        state.save(conn)?;
+        Ok(())
+    }
+
+    /// Resolve the alert of a rule on a service, if it is pending or firing. Pending alerts
+    /// never fired, so resolve quietly, as do firing ones during a maintenance window.
+    fn clear(
+        &self,
+        conn: &PgConnection,
+        alert_rule: &AlertRule,
+        service: &Service,
+        maintenance: Option<&MaintenanceWindow>,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let dedup_key = alert_rule.rule.dedup_key(&service.address);
+        let mut state = match AlertState::find_key(conn, service.asset_group_id, &dedup_key)? {
+            Some(state) if state.status != AlertStatus::Resolved => state,
+            _ => return Ok(()),
+        };
+        let was_firing = state.status == AlertStatus::Firing;
+        state.status = AlertStatus::Resolved;
+        state.resolved_at = Some(now);
+        if was_firing {
+            info!("Resolved alert {}: {}", dedup_key, state.message);
+            self.record(
+                conn,
+                Event::new(
+                    state.asset_group_id,
+                    EventKind::AlertResolved {
+                        address: state.address.clone(),
+                        alert_rule_id: alert_rule.alert_rule_id,
+                        message: state.message.clone(),
+                    },
+                ),
+            );
+            if maintenance.is_none() {
+                let alert = Alert::new(alert_rule, service, state.message.clone()).resolved();
+                self.transition(conn, &mut state, alert, now);
+            }
+        }
+        state.save(conn)?;
+        Ok(())
+    }
+
+    /// Fire pending alerts whose `for` has passed, escalate firing alerts nobody acknowledged
+    /// and end flapping that settled. Alerts of services in maintenance are left until the
+    /// window ends.
+    fn check_alerts(&self) -> Result<(), Error> {
+        let conn = get_conn(&self.pool)?;
+        let now = Utc::now();
+        for mut state in AlertState::get_open(&conn)? {
+            let service = Service::try_find_by_addr(&conn, &state.address)?
+                .filter(|service| service.asset_group_id == state.asset_group_id);
+            let alert_rule = match &service {
+                Some(service) => AlertRule::get_for_service(&conn, service.service_id)?
+                    .into_iter()
+                    .find(|alert_rule| {
+                        alert_rule.rule.dedup_key(&service.address) == state.dedup_key
+                    }),
+                None => None,
+            };
+            let (service, alert_rule) = match (service, alert_rule) {
+                (Some(service), Some(alert_rule)) => (service, alert_rule),
+                _ => {
+                    // The rule was removed by a sync, so nothing can resolve the alert.
+                    if state.status != AlertStatus::Resolved {
+                        state.resolved_at = Some(now);
+                    }
+                    state.status = AlertStatus::Resolved;
+                    state.flapping = false;
+                    state.save(&conn)?;
+                    continue;
+                }
+            };
+            if self.maintenance(&conn, &service, now).is_some() {
+                continue;
+            }
+            let alert = Alert::new(&alert_rule, &service, state.message.clone());
+            if alerts::due_to_fire(&alert_rule.rule, &state, now) {
+                self.fire(&conn, &mut state, alert, now);
+            } else if let Some(tier) = alerts::due_tier(&alert_rule.rule, &state, now) {
+                state.escalations += 1;
+                warn!(
+                    "Escalating alert {} to tier {}: {}",
+                    state.dedup_key, state.escalations, state.message
+                );
+                self.record(
+                    &conn,
+                    Event::new(
+                        state.asset_group_id,
+                        EventKind::AlertEscalated {
+                            address: state.address.clone(),
+                            alert_rule_id: alert_rule.alert_rule_id,
+                            tier: state.escalations as u32,
+                        },
+                    ),
+                );
+                self.notify(alert.escalated(tier));
+            } else if alerts::settled(&state, now, &self.alert_settings) {
+                info!("Alert {} stopped flapping", state.dedup_key);
+                state.flapping = false;
+                self.record(
+                    &conn,
+                    Event::new(
+                        state.asset_group_id,
+                        EventKind::AlertFlapping {
+                            address: state.address.clone(),
+                            alert_rule_id: alert_rule.alert_rule_id,
+                            flapping: false,
+                        },
+                    ),
+                );
+                // Tell those told it was flapping where it ended up.
+                self.notify(match state.status {
+                    AlertStatus::Resolved => alert.resolved(),
+                    _ => alert,
+                });
+            } else {
+                continue;
+            }
+            state.save(&conn)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate the alert rules of a service against changes, firing and resolving their alerts.
+    fn fire_alerts(&self, conn: &PgConnection, service: &Service, changes: &[Change]) {
+        let rules = match AlertRule::get_for_service(conn, service.service_id) {
+            Ok(rules) => rules,
+            Err(err) => {
+                error!(
+                    "Error getting alert rules of {}: {:?}",
+                    service.address, err
+                );
+                return;
+            }
+        };
+        if rules.is_empty() {
+            return;
+        }
+        let now = Utc::now();
+        let maintenance = self.maintenance(conn, service, now);
+        for change in changes {
+            for alert in alerts::evaluate(&rules, service, change) {
+                let alert_rule = match rules
+                    .iter()
+                    .find(|alert_rule| alert_rule.alert_rule_id == alert.alert_rule_id)
+                {
+                    Some(alert_rule) => alert_rule,
+                    None => continue,
+                };
+                if let Err(err) = self.trigger(conn, alert_rule, alert, maintenance.as_ref(), now) {
+                    error!("Error firing alert on {}: {:?}", service.address, err);
+                }
+            }
+            for alert_rule in alerts::resolve(&rules, change) {
+                if let Err(err) = self.clear(conn, alert_rule, service, maintenance.as_ref(), now) {
+                    error!("Error resolving alert on {}: {:?}", service.address, err);
+                }
+            }
+        }
+    }
+
+    /// Log and publish that services were set to `health_status` by reconciliation.
+    fn record_reconciled(&self, conn: &PgConnection, services: Vec<Service>, to: HealthStatus) {
+        let mut addresses: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+        for service in services {
+            addresses
+                .entry(service.asset_group_id)
+                .or_default()
+                .push(service.address);
+        }
+        for (asset_group_id, addresses) in addresses {
+            info!(
+                "Marked {} {:?} in asset group {}",
+                addresses.join(", "),
+                to,
+                asset_group_id
+            );
+            self.record(
+                conn,
+                Event::new(
+                    asset_group_id,
+                    EventKind::Reconciled {
+                        addresses,
+                        health_status: to,
+                    },
+                ),
+            );
+        }
+    }
+
+    /// Mark services still `Unknown` since [`Reconcile`] as disconnected.
+    fn expire_unknown(&self) -> Result<(), Error> {
+        let conn = get_conn(&self.pool)?;
+        let connected: Vec<&str> = self.sessions.keys().map(String::as_str).collect();
+        let services = Service::reconcile_health(
+            &conn,
+            &[HealthStatus::Unknown],
+            HealthStatus::Disconnected,
+            &connected,
+        )?;
+        for service in &services {
+            let changes = [
+                Change::Disconnected,
+                Change::Health {
+                    from: HealthStatus::Unknown,
+                    to: HealthStatus::Disconnected,
+                },
+            ];
+            self.fire_alerts(&conn, service, &changes);
+        }
+        self.record_reconciled(&conn, services, HealthStatus::Disconnected);
+        Ok(())
+    }
+
+    /// Whether a service has a registered session.
+    fn is_connected(&self, address: &str) -> bool {
+        self.sessions
+            .get(address)
+            .is_some_and(|session| session.registered)
+    }
+
+    /// Send the current routing to the connected sessions of some services and their neighbours.
+    fn send_routing(&self, conn: &PgConnection, services: &[&Service]) -> Result<(), Error> {
+        let mut rerouted: BTreeMap<i32, Service> = BTreeMap::new();
+        for service in services {
+            for neighbour in service
+                .get_inputs(conn)?
+                .into_iter()
+                .chain(service.get_outputs(conn)?)
+            {
+                rerouted.insert(neighbour.service_id, neighbour);
+            }
+            rerouted.insert(service.service_id, (*service).clone());
+        }
+        for service in rerouted.values() {
+            if self.sessions.contains_key(&service.address) {
+                let mut routing = Routing::get(conn, service)?;
+                routing.apply_sessions(|address| self.metrics(address));
+                self.send_to_client(&service.address, TextMessage(routing.to_message()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a service the routing it has now, as its session registers. Routing is otherwise only
+    /// sent when it changes, so a service that reconnects during a failover would not learn of it.
+    fn send_current_routing(&self, conn: &PgConnection, address: &str) -> Result<(), Error> {
+        let mut routing = Routing::get(conn, &Service::find_by_addr(conn, address)?)?;
+        routing.apply_sessions(|address| self.metrics(address));
+        self.send_to_client(address, TextMessage(routing.to_message()));
+        Ok(())
+    }
+
+    /// Metrics the service at an address last reported, if it has a registered session.
+    fn metrics(&self, address: &str) -> Option<&BTreeMap<String, f64>> {
+        self.sessions
+            .get(address)
+            .filter(|session| session.registered)
+            .map(|session| &session.metrics)
+    }
+
+    /// Send the routing of the pools a replica belongs to again, when the replica joined or left
+    /// their healthy replicas or reported one of the `metrics` they balance on.
+    fn update_pools(
+        &self,
+        conn: &PgConnection,
+        replica: &Service,
+        membership: bool,
+        metrics: &[String],
+    ) -> Result<(), Error> {
+        if replica.service_type != ServiceType::Processor {
+            return Ok(());
+        }
+        for (pool, pool_service) in ServicePool::find_by_replica(conn, replica.service_id)? {
+            let loaded = pool
+                .load_metric()
+                .is_some_and(|metric| metrics.iter().any(|name| name == metric));
+            if membership || loaded {
+                self.send_routing(conn, &[&pool_service, replica])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the routing of a disconnected processor to its first connected standby. When the
+    /// disconnected service is itself standing in for a processor, the routing moves on to the
+    /// next connected standby, or back to the processor if there is none.
+    fn fail_over(&self, conn: &PgConnection, service: &Service) -> Result<(), Error> {
+        // Only processors have standbys or stand in for others, so spare the rest the queries.
+        if service.service_type != ServiceType::Processor {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let (primary, active) = match Failover::find_active_by_standby(conn, service.service_id)? {
+            Some(failover) => (Service::find(conn, failover.service_id)?, Some(failover)),
+            None if Failover::find_active(conn, service.service_id)?.is_some() => return Ok(()),
+            None => (service.clone(), None),
+        };
+        let mut next = None;
+        for standby in primary.get_standbys(conn)? {
+            if standby.service_id != service.service_id
+                && self.is_connected(&standby.address)
+                && Failover::find_active_by_standby(conn, standby.service_id)?.is_none()
+            {
+                next = Some(standby);
+                break;
+            }
+        }
+
+        match (next, active) {
+            (Some(standby), active) => {
+                match active {
+                    Some(failover) => failover.switch(conn, &primary, &standby, now)?,
+                    None => Failover::start(conn, &primary, &standby, now)?,
+                };
+                info!("Failed {} over to {}", primary.address, standby.address);
+                self.send_routing(conn, &[&standby])?;
+                self.record(
+                    conn,
+                    Event::new(
+                        primary.asset_group_id,
+                        EventKind::FailedOver {
+                            address: primary.address,
+                            standby: standby.address,
+                        },
+                    ),
+                );
+            }
+            (None, Some(failover)) => {
+                failover.end(conn, now)?;
+                self.send_routing(conn, &[&primary])?;
+                let kind = if self.is_connected(&primary.address) {
+                    EventKind::FailedBack {
+                        address: primary.address,
+                        standby: service.address.clone(),
+                    }
+                } else {
+                    EventKind::FailoverFailed {
+                        address: primary.address,
+                    }
+                };
+                self.record(conn, Event::new(primary.asset_group_id, kind));
+            }
+            (None, None) => {
+                if primary.get_standbys(conn)?.is_empty() {
+                    return Ok(());
+                }
+                warn!("No standby of {} is connected", primary.address);
+                self.record(
+                    conn,
+                    Event::new(
+                        primary.asset_group_id,
+                        EventKind::FailoverFailed {
+                            address: primary.address,
+                        },
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the routing of a reconnected processor back from its standby, if it fails back.
+    fn fail_back(&self, conn: &PgConnection, service: &Service) -> Result<(), Error> {
+        if !service.failback {
+            return Ok(());
+        }
+        let failover = match Failover::find_active(conn, service.service_id)? {
+            Some(failover) => failover,
+            None => return Ok(()),
+        };
+        failover.end(conn, Utc::now())?;
+        let standby = Service::find(conn, failover.standby_service_id)?;
+        info!("Failed {} back from {}", service.address, standby.address);
+        self.send_routing(conn, &[service, &standby])?;
+        self.record(
+            conn,
+            Event::new(
+                service.asset_group_id,
+                EventKind::FailedBack {
+                    address: service.address.clone(),
+                    standby: standby.address,
+                },
+            ),
+        );
+        Ok(())
+    }
+
     fn send_to_client(&self, addr: &str, data: TextMessage) {
         info!("Sending to client: '{}'", data.0);
         if let Some(session) = self.sessions.get(addr) {
@@ -47,12 +674,21 @@ impl Server {
 }
 impl Actor for Server {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(self.alert_settings.check_interval(), |act, _| {
+            if let Err(err) = act.check_alerts() {
+                error!("Error checking alerts: {:?}", err);
+            }
+        });
+    }
 }
 
 #[derive(Message, Debug)]
 #[rtype(result = "Result<(), Error>")]
 pub struct ConnectMessage {
     pub addr: Recipient<TextMessage>,
+    pub close: Recipient<CloseSession>,
     pub asset_group_id: i32,
     pub client_addr: String,
 }
@@ -61,12 +697,79 @@ impl Handler<ConnectMessage> for Server {
 
     fn handle(&mut self, msg: ConnectMessage, _: &mut Context<Self>) -> Result<(), Error> {
         info!("Receieved {:?}", msg);
-        self.sessions
-            .insert(msg.client_addr.clone(), Session::new(msg.addr));
-
         let conn = get_conn(&self.pool)?;
+        let previous = Service::try_find_by_addr(&conn, &msg.client_addr)?
+            .filter(|service| service.asset_group_id == msg.asset_group_id);
+        let policy = match &previous {
+            Some(_) => RegistrationPolicy::Auto,
+            None => {
+                AssetGroup::get_registration_policy(&conn, msg.asset_group_id)?.unwrap_or_default()
+            }
+        };
+        let declared = previous.as_ref().is_some_and(|service| service.declared);
+        let registered =
+            policy == RegistrationPolicy::Auto && (declared || self.register_undeclared);
+        self.sessions.insert(
+            msg.client_addr.clone(),
+            Session::new(msg.addr, msg.close, msg.asset_group_id, registered),
+        );
+        if policy == RegistrationPolicy::Pending {
+            info!(
+                "Holding {} until it is approved in asset group {}",
+                msg.client_addr, msg.asset_group_id
+            );
+            PendingService::upsert(&conn, msg.asset_group_id, &msg.client_addr, Utc::now())?;
+            self.send_to_client(&msg.client_addr, TextMessage("Pending".to_string()));
+            self.publish(Event::new(
+                msg.asset_group_id,
+                EventKind::Connected {
+                    address: msg.client_addr,
+                },
+            ));
+            return Ok(());
+        }
+        if !registered {
+            warn!(
+                "Service {} is not declared in asset group {}, so it is not registered",
+                msg.client_addr, msg.asset_group_id
+            );
+            self.send_to_client(&msg.client_addr, TextMessage("Unregistered".to_string()));
+            self.publish(Event::new(
+                msg.asset_group_id,
+                EventKind::Connected {
+                    address: msg.client_addr,
+                },
+            ));
+            return Ok(());
+        }
+
         Service::upsert_healthy_address(&conn, msg.asset_group_id, &msg.client_addr)?;
         self.send_to_client(&msg.client_addr, TextMessage("Registered".to_string()));
+        if let Some(mut service) = previous {
+            let from = service.health_status;
+            service.health_status = HealthStatus::Healthy;
+            let changes = [
+                Change::Connected,
+                Change::Health {
+                    from,
+                    to: HealthStatus::Healthy,
+                },
+            ];
+            self.fire_alerts(&conn, &service, &changes);
+            if let Err(err) = self.fail_back(&conn, &service) {
+                error!("Error failing back {}: {:?}", service.address, err);
+            }
+            if let Err(err) = self.update_pools(&conn, &service, true, &[]) {
+                error!("Error updating pools of {}: {:?}", service.address, err);
+            }
+        }
+        self.send_current_routing(&conn, &msg.client_addr)?;
+        self.publish(Event::new(
+            msg.asset_group_id,
+            EventKind::Connected {
+                address: msg.client_addr,
+            },
+        ));
         Ok(())
     }
 }
@@ -80,15 +783,383 @@ impl Handler<DisconnectMessage> for Server {
     type Result = Result<(), Error>;
 
     fn handle(&mut self, msg: DisconnectMessage, _: &mut Context<Self>) -> Result<(), Error> {
-        self.sessions.remove(&msg.client_addr);
+        let session = self.sessions.remove(&msg.client_addr);
+        if let Some(session) = session.as_ref().filter(|session| !session.registered) {
+            self.publish(Event::new(
+                session.asset_group_id,
+                EventKind::Disconnected {
+                    address: msg.client_addr,
+                    maintenance: None,
+                },
+            ));
+            return Ok(());
+        }
 
         let conn = get_conn(&self.pool)?;
+        let previous = Service::try_find_by_addr(&conn, &msg.client_addr)?;
         Service::disconnect_address(&conn, &msg.client_addr)?;
         info!("Service {} was disconnected.", &msg.client_addr);
+
+        // Sessions can report a disconnect twice, so only alert and publish the first one.
+        if let Some(session) = session {
+            let mut maintenance = None;
+            if let Some(mut service) = previous {
+                maintenance = self
+                    .maintenance(&conn, &service, Utc::now())
+                    .map(|window| window.maintenance_window_id);
+                let from = service.health_status;
+                service.health_status = HealthStatus::Disconnected;
+                let changes = [
+                    Change::Disconnected,
+                    Change::Health {
+                        from,
+                        to: HealthStatus::Disconnected,
+                    },
+                ];
+                self.fire_alerts(&conn, &service, &changes);
+                if let Err(err) = self.fail_over(&conn, &service) {
+                    error!("Error failing over {}: {:?}", service.address, err);
+                }
+                if let Err(err) = self.update_pools(&conn, &service, true, &[]) {
+                    error!("Error updating pools of {}: {:?}", service.address, err);
+                }
+            }
+            self.publish(Event::new(
+                session.asset_group_id,
+                EventKind::Disconnected {
+                    address: msg.client_addr,
+                    maintenance,
+                },
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A status report from the service of a session.
+#[derive(Message, Debug)]
+#[rtype(result = "Result<(), Error>")]
+pub struct StatusMessage {
+    pub client_addr: String,
+    pub status: ServiceStatus,
+}
+impl Handler<StatusMessage> for Server {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: StatusMessage, _: &mut Context<Self>) -> Result<(), Error> {
+        let session = match self.sessions.get_mut(&msg.client_addr) {
+            Some(session) if session.registered => session,
+            Some(_) => {
+                info!("Ignoring status from undeclared {}", msg.client_addr);
+                return Ok(());
+            }
+            None => {
+                warn!("Status from {} without a session", msg.client_addr);
+                return Ok(());
+            }
+        };
+        let conn = get_conn(&self.pool)?;
+        let mut service = Service::find_by_addr(&conn, &msg.client_addr)?;
+        let assigned = match service.config_id {
+            Some(config_id) => Config::get_names(&conn, &[config_id])?.remove(&config_id),
+            None => None,
+        };
+
+        let mut changes = Vec::new();
+        let mut health_changed = false;
+        if let Some(to) = msg.status.health_status {
+            if to != service.health_status {
+                changes.push(Change::Health {
+                    from: service.health_status,
+                    to,
+                });
+                service.health_status = to;
+                health_changed = true;
+            }
+        }
+        if let Some(running) = msg.status.config_name {
+            // Only evaluate when the service starts running another config.
+            if session.config_name.as_ref() != Some(&running) {
+                changes.push(Change::Config {
+                    assigned,
+                    running: running.clone(),
+                });
+            }
+            session.config_name = Some(running);
+        }
+        let mut metrics_changed = Vec::new();
+        for (name, value) in msg.status.metrics {
+            let previous = session.metrics.insert(name.clone(), value);
+            if previous != Some(value) {
+                metrics_changed.push(name.clone());
+            }
+            changes.push(Change::Metric {
+                name,
+                previous,
+                value,
+            });
+        }
+
+        if health_changed {
+            Service::set_health(&conn, &service.address, service.health_status)?;
+            self.publish(Event::new(
+                service.asset_group_id,
+                EventKind::HealthChanged {
+                    address: service.address.clone(),
+                    health_status: service.health_status,
+                    maintenance: self
+                        .maintenance(&conn, &service, Utc::now())
+                        .map(|window| window.maintenance_window_id),
+                },
+            ));
+        }
+        self.fire_alerts(&conn, &service, &changes);
+        if let Err(err) = self.update_pools(&conn, &service, health_changed, &metrics_changed) {
+            error!("Error updating pools of {}: {:?}", service.address, err);
+        }
+        Ok(())
+    }
+}
+
+/// Subscribes a watcher to events for an asset group. Returns the watcher ID.
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct WatchMessage {
+    pub addr: Recipient<TextMessage>,
+    pub close: Recipient<CloseSession>,
+    pub asset_group_id: i32,
+}
+impl Handler<WatchMessage> for Server {
+    type Result = usize;
+
+    fn handle(&mut self, msg: WatchMessage, _: &mut Context<Self>) -> usize {
+        let watcher_id = self.next_watcher_id;
+        self.next_watcher_id += 1;
+        self.watchers.entry(msg.asset_group_id).or_default().insert(
+            watcher_id,
+            Watcher {
+                addr: msg.addr,
+                close: msg.close,
+            },
+        );
+        info!(
+            "Watcher {} subscribed to asset group {}",
+            watcher_id, msg.asset_group_id
+        );
+        watcher_id
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UnwatchMessage {
+    pub asset_group_id: i32,
+    pub watcher_id: usize,
+}
+impl Handler<UnwatchMessage> for Server {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnwatchMessage, _: &mut Context<Self>) {
+        if let Some(watchers) = self.watchers.get_mut(&msg.asset_group_id) {
+            watchers.remove(&msg.watcher_id);
+            if watchers.is_empty() {
+                self.watchers.remove(&msg.asset_group_id);
+            }
+        }
+    }
+}
+
+/// Reconciles the health of services with the sessions after the orchestrator starts, as services
+/// connected to a previous run were left in whatever state they had.
+///
+/// Services without a session are marked `Unknown` at once, then `Disconnected` if they have not
+/// reconnected after the grace period. Both changes are logged as events.
+#[derive(Message)]
+#[rtype(result = "Result<(), Error>")]
+pub struct Reconcile {
+    pub grace_period: Duration,
+}
+impl Handler<Reconcile> for Server {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: Reconcile, ctx: &mut Context<Self>) -> Result<(), Error> {
+        let conn = get_conn(&self.pool)?;
+        let connected: Vec<&str> = self.sessions.keys().map(String::as_str).collect();
+        let services = Service::reconcile_health(
+            &conn,
+            &[
+                HealthStatus::Healthy,
+                HealthStatus::Warning,
+                HealthStatus::Critical,
+            ],
+            HealthStatus::Unknown,
+            &connected,
+        )?;
+        self.record_reconciled(&conn, services, HealthStatus::Unknown);
+
+        ctx.run_later(msg.grace_period, |act, _| {
+            if let Err(err) = act.expire_unknown() {
+                error!("Error reconciling unknown services: {:?}", err);
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Closes every session and watcher before the orchestrator stops, marking the services of the
+/// sessions disconnected.
+#[derive(Message)]
+#[rtype(result = "Result<(), Error>")]
+pub struct Shutdown;
+impl Handler<Shutdown> for Server {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, _: Shutdown, _: &mut Context<Self>) -> Result<(), Error> {
+        let reason = CloseSession("Orchestrator shutting down".to_string());
+        let sessions: Vec<(String, Session)> = self.sessions.drain().collect();
+        for (_, session) in &sessions {
+            let _ = session.close.do_send(reason.clone());
+        }
+        for watcher in self.watchers.values().flat_map(HashMap::values) {
+            let _ = watcher.close.do_send(reason.clone());
+        }
+
+        let addresses: Vec<&str> = sessions
+            .iter()
+            .filter(|(_, session)| session.registered)
+            .map(|(addr, _)| addr.as_str())
+            .collect();
+        let conn = get_conn(&self.pool)?;
+        let now = Utc::now();
+        let maintenance: HashMap<String, i32> = Service::find_by_addrs(&conn, &addresses)?
+            .into_iter()
+            .filter_map(|service| {
+                let window = self.maintenance(&conn, &service, now)?;
+                Some((service.address, window.maintenance_window_id))
+            })
+            .collect();
+        Service::disconnect_addresses(&conn, &addresses)?;
+        info!("Disconnected {} services for shutdown.", addresses.len());
+        for (address, session) in sessions {
+            let maintenance = maintenance.get(&address).copied();
+            self.publish(Event::new(
+                session.asset_group_id,
+                EventKind::Disconnected {
+                    address,
+                    maintenance,
+                },
+            ));
+        }
+        self.watchers.clear();
         Ok(())
     }
 }
 
+/// Sends an event logged outside the server to the watchers of its asset group.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Publish(pub Event);
+impl Handler<Publish> for Server {
+    type Result = ();
+
+    fn handle(&mut self, msg: Publish, _: &mut Context<Self>) {
+        self.publish(msg.0);
+    }
+}
+
+/// Numbers of connected sessions and watchers by asset group.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ServerStats {
+    pub sessions: BTreeMap<i32, usize>,
+    pub watchers: BTreeMap<i32, usize>,
+}
+
+/// Asks the server for its [`ServerStats`]. Also used to check that it is responsive.
+#[derive(Message)]
+#[rtype(result = "ServerStats")]
+pub struct GetStats;
+impl Handler<GetStats> for Server {
+    type Result = MessageResult<GetStats>;
+
+    fn handle(&mut self, _: GetStats, _: &mut Context<Self>) -> Self::Result {
+        let mut stats = ServerStats::default();
+        for session in self.sessions.values() {
+            *stats.sessions.entry(session.asset_group_id).or_default() += 1;
+        }
+        for (&asset_group_id, watchers) in &self.watchers {
+            stats.watchers.insert(asset_group_id, watchers.len());
+        }
+        MessageResult(stats)
+    }
+}
+
+/// Asks the server for the addresses with a session in an asset group, sorted.
+#[derive(Message)]
+#[rtype(result = "Vec<String>")]
+pub struct GetConnected {
+    pub asset_group_id: i32,
+}
+impl Handler<GetConnected> for Server {
+    type Result = MessageResult<GetConnected>;
+
+    fn handle(&mut self, msg: GetConnected, _: &mut Context<Self>) -> Self::Result {
+        let mut addresses: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.asset_group_id == msg.asset_group_id)
+            .map(|(address, _)| address.clone())
+            .collect();
+        addresses.sort();
+        MessageResult(addresses)
+    }
+}
+
+/// Registers the session of a pending service that was approved, if it is connected.
+#[derive(Message)]
+#[rtype(result = "Result<(), Error>")]
+pub struct ApproveSession {
+    pub client_addr: String,
+}
+impl Handler<ApproveSession> for Server {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: ApproveSession, _: &mut Context<Self>) -> Result<(), Error> {
+        let session = match self.sessions.get_mut(&msg.client_addr) {
+            Some(session) if !session.registered => session,
+            _ => return Ok(()),
+        };
+        session.registered = true;
+        let asset_group_id = session.asset_group_id;
+        let conn = get_conn(&self.pool)?;
+        Service::upsert_healthy_address(&conn, asset_group_id, &msg.client_addr)?;
+        self.send_to_client(&msg.client_addr, TextMessage("Registered".to_string()));
+        self.send_current_routing(&conn, &msg.client_addr)
+    }
+}
+
+/// Closes the session of a pending service that was rejected, if it is connected.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RejectSession {
+    pub client_addr: String,
+}
+impl Handler<RejectSession> for Server {
+    type Result = ();
+
+    fn handle(&mut self, msg: RejectSession, _: &mut Context<Self>) {
+        if let Some(session) = self
+            .sessions
+            .get(&msg.client_addr)
+            .filter(|session| !session.registered)
+        {
+            session
+                .close
+                .do_send(CloseSession("Rejected".to_string()))
+                .ok();
+        }
+    }
+}
+
 /// Message sent back to clients for config updates.
 #[derive(Message, Deserialize, Serialize, Debug)]
 #[rtype(result = "()")]
@@ -1,4 +1,4 @@
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use actix::{
     fut,
@@ -7,34 +7,44 @@ use actix::{
 };
 use actix_web_actors::ws;
 
-use crate::websocket::{ConnectMessage, DisconnectMessage};
-
-use super::{Server, TextMessage};
+use crate::{
+    metrics::METRICS,
+    settings::WebsocketSettings,
+    websocket::{ConnectMessage, DisconnectMessage, StatusMessage},
+};
+use pr0t0n_orch_db::models::ServiceStatus;
 
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(20);
+use super::{CloseSession, Server, TextMessage};
 
 pub struct WebSocketSession {
     server_addr: Addr<Server>,
     hb: Instant,
     client_addr: String,
     asset_group_id: i32,
+    settings: WebsocketSettings,
 }
 
 impl WebSocketSession {
-    pub fn new(server_addr: Addr<Server>, client_addr: String, asset_group_id: i32) -> Self {
+    pub fn new(
+        server_addr: Addr<Server>,
+        client_addr: String,
+        asset_group_id: i32,
+        settings: WebsocketSettings,
+    ) -> Self {
         Self {
             server_addr,
             hb: Instant::now(),
             client_addr,
             asset_group_id,
+            settings,
         }
     }
 
     fn send_heartbeat(&self, ctx: &mut <Self as Actor>::Context) {
-        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
-            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+        ctx.run_interval(self.settings.heartbeat_interval(), |act, ctx| {
+            if Instant::now().duration_since(act.hb) > act.settings.client_timeout() {
                 println!("Websocket Client heartbeat failed, disconnecting!");
+                METRICS.service_heartbeat_timeouts.inc();
                 act.server_addr.do_send(DisconnectMessage {
                     client_addr: act.client_addr.clone(),
                 });
@@ -57,7 +67,8 @@ impl Actor for WebSocketSession {
 
         self.server_addr
             .send(ConnectMessage {
-                addr: session_addr.recipient(),
+                addr: session_addr.clone().recipient(),
+                close: session_addr.recipient(),
                 client_addr: self.client_addr.clone(),
                 asset_group_id: self.asset_group_id,
             })
@@ -84,13 +95,29 @@ impl Handler<TextMessage> for WebSocketSession {
     type Result = ();
 
     fn handle(&mut self, msg: TextMessage, ctx: &mut Self::Context) {
+        METRICS.websocket_messages_sent.inc();
         ctx.text(msg.0);
     }
 }
 
+impl Handler<CloseSession> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: CloseSession, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Away,
+            description: Some(msg.0),
+        }));
+        ctx.stop();
+    }
+}
+
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocketSession {
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         debug!("Message: {:?}", msg);
+        if msg.is_ok() {
+            METRICS.websocket_messages_received.inc();
+        }
         match msg {
             Ok(ws::Message::Ping(msg)) => {
                 self.hb = Instant::now();
@@ -101,6 +128,12 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocketSession
             }
             Ok(ws::Message::Text(text)) => {
                 info!("Received '{}' from {}", text, self.client_addr);
+                if let Ok(status) = serde_json::from_str::<ServiceStatus>(&text) {
+                    self.server_addr.do_send(StatusMessage {
+                        client_addr: self.client_addr.clone(),
+                        status,
+                    });
+                }
                 ctx.text("Received message.");
             }
             Ok(ws::Message::Binary(bin)) => ctx.binary(bin),
@@ -0,0 +1,133 @@
+use std::time::Instant;
+
+use actix::{
+    fut,
+    prelude::{Actor, Addr, StreamHandler},
+    ActorContext, ActorFuture, AsyncContext, ContextFutureSpawner, Handler, Running, WrapFuture,
+};
+use actix_web_actors::ws;
+
+use crate::{metrics::METRICS, settings::WebsocketSettings};
+
+use super::{CloseSession, Server, TextMessage, UnwatchMessage, WatchMessage};
+
+/// Read-only session streaming the events of an asset group to a watcher.
+pub struct WatchSession {
+    server_addr: Addr<Server>,
+    hb: Instant,
+    asset_group_id: i32,
+    watcher_id: Option<usize>,
+    settings: WebsocketSettings,
+}
+
+impl WatchSession {
+    pub fn new(
+        server_addr: Addr<Server>,
+        asset_group_id: i32,
+        settings: WebsocketSettings,
+    ) -> Self {
+        Self {
+            server_addr,
+            hb: Instant::now(),
+            asset_group_id,
+            watcher_id: None,
+            settings,
+        }
+    }
+
+    fn send_heartbeat(&self, ctx: &mut <Self as Actor>::Context) {
+        ctx.run_interval(self.settings.heartbeat_interval(), |act, ctx| {
+            if Instant::now().duration_since(act.hb) > act.settings.client_timeout() {
+                info!("Watcher heartbeat failed, disconnecting!");
+                METRICS.watcher_heartbeat_timeouts.inc();
+                ctx.stop();
+                return; // Don't send another ping if timed out.
+            }
+            ctx.ping(b"");
+        });
+    }
+}
+
+impl Actor for WatchSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Started watcher for asset group {}", self.asset_group_id);
+        self.send_heartbeat(ctx);
+
+        self.server_addr
+            .send(WatchMessage {
+                addr: ctx.address().recipient(),
+                close: ctx.address().recipient(),
+                asset_group_id: self.asset_group_id,
+            })
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(watcher_id) => act.watcher_id = Some(watcher_id),
+                    _ => ctx.stop(),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    fn stopping(&mut self, _ctx: &mut Self::Context) -> Running {
+        if let Some(watcher_id) = self.watcher_id {
+            self.server_addr.do_send(UnwatchMessage {
+                asset_group_id: self.asset_group_id,
+                watcher_id,
+            });
+        }
+        Running::Stop
+    }
+}
+
+impl Handler<TextMessage> for WatchSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: TextMessage, ctx: &mut Self::Context) {
+        METRICS.websocket_messages_sent.inc();
+        ctx.text(msg.0);
+    }
+}
+
+impl Handler<CloseSession> for WatchSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: CloseSession, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Away,
+            description: Some(msg.0),
+        }));
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WatchSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        if msg.is_ok() {
+            METRICS.websocket_messages_received.inc();
+        }
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.hb = Instant::now();
+            }
+            // Watchers are read-only.
+            Ok(ws::Message::Text(_)) | Ok(ws::Message::Binary(_)) => {}
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(err) => {
+                error!("Error handling watcher msg: {:?}", err);
+                ctx.stop()
+            }
+            _ => ctx.stop(),
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::time::{Duration, Instant};
+
+use actix::{clock::delay_for, Actor};
+use actix_web::{client::Client, http::StatusCode, test, App};
+use futures::{SinkExt, StreamExt};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        Acknowledgement, AlertRuleRepr, AlertState, AlertStatus, AlertTrigger, AssetGroup,
+        DbDelete, DbInsert, EscalationTier, Event, EventKind, NewAssetGroup, ServiceRepr,
+        ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+use serde_json::Value;
+
+use pr0t0n_orch::{
+    notify::{Dispatcher, WebhookNotifier},
+    routes,
+    settings::{AlertSettings, WebhookSettings, WebsocketSettings},
+    testing::{get_websocket_frame_data, WebhookSink},
+    websocket::Server,
+    Error,
+};
+
+fn rule(channel: &str) -> AlertRuleRepr {
+    AlertRuleRepr {
+        trigger: AlertTrigger::Disconnect,
+        sender: String::new(),
+        recipients: vec![],
+        channels: vec![channel.to_string()],
+        key: None,
+        for_seconds: 0,
+        escalate: vec![],
+    }
+}
+
+fn service(address: &str, alert: AlertRuleRepr) -> ServiceRepr {
+    ServiceRepr {
+        address: address.to_string(),
+        service_type: ServiceType::Input,
+        name: "camera".to_string(),
+        alerts: vec![alert],
+        ..Default::default()
+    }
+}
+
+/// Payloads a sink received about the service at `address`.
+fn payloads(sink: &WebhookSink, address: &str) -> Vec<Value> {
+    sink.requests()
+        .into_iter()
+        .map(|request| serde_json::from_str::<Value>(&request.body).unwrap())
+        .filter(|payload| payload["address"] == address)
+        .collect()
+}
+
+/// Wait until `done`, failing after a while.
+async fn wait_for(what: &str, done: impl Fn() -> bool) {
+    let start = Instant::now();
+    while !done() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "Timed out waiting for {}",
+            what
+        );
+        delay_for(Duration::from_millis(100)).await;
+    }
+}
+
+#[actix_rt::test]
+async fn test_alert_states() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let oncall = WebhookSink::start(0);
+    let manager = WebhookSink::start(0);
+    let webhook = |sink: &WebhookSink| WebhookSettings {
+        url: sink.url(),
+        retries: 0,
+        ..Default::default()
+    };
+    let notifier = Dispatcher::new(new_pool(&PoolSettings::from_env()))
+        .with_channel("oncall", WebhookNotifier::new(webhook(&oncall)))
+        .with_channel("manager", WebhookNotifier::new(webhook(&manager)))
+        .start();
+    let server = Server::new(new_pool(&PoolSettings::from_env()))
+        .with_notifier(notifier)
+        .with_alert_settings(AlertSettings {
+            check_interval: 1,
+            flap_window: 3,
+            flap_threshold: 4,
+        })
+        .start();
+    let test_server = test::start(move || {
+        App::new()
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(server.clone())
+            .data(WebsocketSettings::default())
+            .configure(routes)
+    });
+
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    let (slow, flappy, lobby_1, lobby_2) = (
+        "localhost:9541",
+        "localhost:9542",
+        "localhost:9543",
+        "localhost:9544",
+    );
+    let lobby = AlertRuleRepr {
+        key: Some("lobby".to_string()),
+        ..rule("oncall")
+    };
+    let system_repr = SystemRepr {
+        asset_group_id,
+        revision: None,
+        services: vec![
+            service(
+                slow,
+                AlertRuleRepr {
+                    for_seconds: 2,
+                    escalate: vec![EscalationTier {
+                        after: 1,
+                        recipients: vec![],
+                        channels: vec!["manager".to_string()],
+                    }],
+                    ..rule("oncall")
+                },
+            ),
+            service(flappy, rule("oncall")),
+            service(lobby_1, lobby.clone()),
+            service(lobby_2, lobby),
+        ],
+        configs: vec![],
+    };
+    let client = Client::default();
+    let response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&system_repr)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let connect = |address: &'static str| {
+        let request = client
+            .ws(test_server.url("/ws/"))
+            .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+            .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, address);
+        async move {
+            let (_response, sock) = request.connect().await.unwrap();
+            let mut sock = sock.fuse();
+            let registered = get_websocket_frame_data(sock.next().await.unwrap().unwrap());
+            assert_eq!(registered, Some("Registered".to_string()));
+            sock
+        }
+    };
+    let state = |key: &str| {
+        AlertState::find_key(&conn, asset_group_id, key)
+            .unwrap()
+            .unwrap()
+    };
+    let slow_key = format!("{}/{{\"on\":\"disconnect\"}}", slow);
+
+    // Reconnecting within the `for` of the rule resolves the alert without a notification.
+    let mut sock = connect(slow).await;
+    sock.close().await.unwrap();
+    delay_for(Duration::from_millis(300)).await;
+    assert_eq!(state(&slow_key).status, AlertStatus::Pending);
+    let mut sock = connect(slow).await;
+    delay_for(Duration::from_millis(300)).await;
+    assert_eq!(state(&slow_key).status, AlertStatus::Resolved);
+
+    // Staying disconnected fires it once the `for` passes, then escalates it.
+    sock.close().await.unwrap();
+    wait_for("the alert to fire", || payloads(&oncall, slow).len() == 1).await;
+    assert_eq!(payloads(&oncall, slow)[0]["status"], "Firing");
+    wait_for("the alert to escalate", || {
+        payloads(&manager, slow).len() == 1
+    })
+    .await;
+    let firing = state(&slow_key);
+    assert_eq!(firing.status, AlertStatus::Firing);
+    assert_eq!(firing.escalations, 1);
+
+    let mut response = client
+        .get(test_server.url(&format!("/asset-groups/{}/alerts", asset_group_id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let listed: Vec<AlertState> = response.json().await.unwrap();
+    assert_eq!(listed, vec![firing.clone()]);
+
+    let acknowledge = |alert_state_id: i32| {
+        client.post(test_server.url(&format!(
+            "/asset-groups/{}/alerts/{}/acknowledge",
+            asset_group_id, alert_state_id
+        )))
+    };
+    let by = Acknowledgement {
+        by: Some("joshi".to_string()),
+    };
+    let mut response = acknowledge(firing.alert_state_id)
+        .send_json(&by)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let acknowledged: AlertState = response.json().await.unwrap();
+    assert_eq!(acknowledged.acknowledged_by.as_deref(), Some("joshi"));
+    let response = acknowledge(firing.alert_state_id + 1000)
+        .send_json(&by)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Reconnecting resolves it, which is notified too, and resolved alerts cannot be
+    // acknowledged.
+    let sock = connect(slow).await;
+    wait_for("the alert to resolve", || {
+        payloads(&oncall, slow).len() == 2
+    })
+    .await;
+    let resolved = &payloads(&oncall, slow)[1];
+    assert_eq!(resolved["status"], "Resolved");
+    assert_eq!(resolved["key"], slow_key.as_str());
+    assert_eq!(state(&slow_key).status, AlertStatus::Resolved);
+    let response = acknowledge(firing.alert_state_id)
+        .send_json(&by)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    drop(sock);
+
+    // Alerts sharing a key are notified once while either fires.
+    let mut sock_1 = connect(lobby_1).await;
+    let mut sock_2 = connect(lobby_2).await;
+    sock_1.close().await.unwrap();
+    sock_2.close().await.unwrap();
+    delay_for(Duration::from_millis(300)).await;
+    let lobby_payloads: Vec<Value> = oncall
+        .requests()
+        .into_iter()
+        .map(|request| serde_json::from_str::<Value>(&request.body).unwrap())
+        .filter(|payload| payload["key"] == "lobby")
+        .collect();
+    assert_eq!(lobby_payloads.len(), 1);
+    assert_eq!(state("lobby").status, AlertStatus::Firing);
+
+    // A service that keeps reconnecting is notified until it flaps, then held back until it
+    // settles.
+    let flappy_key = format!("{}/{{\"on\":\"disconnect\"}}", flappy);
+    let mut sock = connect(flappy).await;
+    for _ in 0..3 {
+        sock.close().await.unwrap();
+        delay_for(Duration::from_millis(100)).await;
+        sock = connect(flappy).await;
+    }
+    delay_for(Duration::from_millis(300)).await;
+    let notified: Vec<String> = payloads(&oncall, flappy)
+        .iter()
+        .map(|payload| payload["message"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        notified,
+        vec![
+            "camera (localhost:9542) disconnected",
+            "Resolved: camera (localhost:9542) disconnected",
+            "camera (localhost:9542) disconnected",
+            "Flapping, held back until it settles: Resolved: camera (localhost:9542) \
+             disconnected",
+        ]
+    );
+    assert!(state(&flappy_key).flapping);
+    wait_for("the alert to settle", || !state(&flappy_key).flapping).await;
+    wait_for("the settled state to be notified", || {
+        payloads(&oncall, flappy).len() == 5
+    })
+    .await;
+    assert_eq!(payloads(&oncall, flappy)[4]["status"], "Resolved");
+    drop(sock);
+
+    let flapping: Vec<bool> = Event::get_logged(&conn, asset_group_id)?
+        .into_iter()
+        .filter_map(|event| match event.kind {
+            EventKind::AlertFlapping { flapping, .. } => Some(flapping),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(flapping, vec![true, false]);
+
+    test_server.stop().await;
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
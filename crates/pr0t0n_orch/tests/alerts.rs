@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use actix::{clock::delay_for, Actor};
+use actix_web::{client::Client, http::StatusCode, test, App};
+use actix_web_actors::ws;
+use futures::{SinkExt, StreamExt};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AlertRuleRepr, AlertTrigger, AssetGroup, DbDelete, DbInsert, ErrorResponse, Event,
+        EventKind, HealthStatus, NewAssetGroup, Service, ServiceRepr, ServiceStatus, ServiceType,
+        SystemRepr,
+    },
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+
+use pr0t0n_orch::{
+    routes, settings::WebsocketSettings, testing::get_websocket_frame_data, websocket::Server,
+    Error,
+};
+
+fn rule(trigger: AlertTrigger) -> AlertRuleRepr {
+    AlertRuleRepr {
+        trigger,
+        sender: "pr0t0nalerts@gmail.com".to_string(),
+        recipients: vec!["joshikatsu@gmail.com".to_string()],
+        channels: vec![],
+        key: None,
+        for_seconds: 0,
+        escalate: vec![],
+    }
+}
+
+#[actix_rt::test]
+async fn test_alerts() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let server = Server::new(new_pool(&PoolSettings::from_env())).start();
+    let test_server = test::start(move || {
+        App::new()
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(server.clone())
+            .data(WebsocketSettings::default())
+            .configure(routes)
+    });
+
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    let address = "localhost:9445";
+    let mut system_repr = SystemRepr {
+        asset_group_id,
+        revision: None,
+        services: vec![ServiceRepr {
+            address: address.to_string(),
+            service_type: ServiceType::Input,
+            name: "camera".to_string(),
+            alerts: vec![
+                rule(AlertTrigger::Disconnect),
+                rule(AlertTrigger::Health {
+                    to: vec![HealthStatus::Critical],
+                }),
+                // Sent only through a channel, which this server does not have.
+                AlertRuleRepr {
+                    sender: String::new(),
+                    recipients: vec![],
+                    channels: vec!["oncall".to_string()],
+                    ..rule(AlertTrigger::ConfigDrift)
+                },
+                rule(AlertTrigger::Metric {
+                    metric: "cpu".to_string(),
+                    above: Some(0.9),
+                    below: None,
+                }),
+            ],
+            ..Default::default()
+        }],
+        configs: vec![],
+    };
+
+    // Rules without recipients are rejected with the path of the field.
+    let client = Client::default();
+    let mut invalid = system_repr.clone();
+    invalid.services[0].alerts[1].recipients.clear();
+    let mut response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&invalid)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(
+        error.details[0].field.as_deref(),
+        Some("services/0/alerts/1/recipients")
+    );
+
+    // Rules are synced with the system.
+    let response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&system_repr)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut response = client
+        .get(test_server.url(&format!("/asset-groups/{}/system", asset_group_id)))
+        .send()
+        .await
+        .unwrap();
+    let mut downloaded: SystemRepr = response.json().await.unwrap();
+    downloaded.revision = None;
+    system_repr.services[0].health_status = downloaded.services[0].health_status;
+    assert_eq!(downloaded, system_repr);
+
+    // The service connects and reports its status.
+    let (_response, sock) = client
+        .ws(test_server.url("/ws/"))
+        .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+        .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, address)
+        .connect()
+        .await
+        .unwrap();
+    let mut sock = sock.fuse();
+    let registered = get_websocket_frame_data(sock.next().await.unwrap().unwrap());
+    assert_eq!(registered, Some("Registered".to_string()));
+
+    let mut status = ServiceStatus {
+        health_status: Some(HealthStatus::Critical),
+        config_name: Some("old_config".to_string()),
+        ..Default::default()
+    };
+    status.metrics.insert("cpu".to_string(), 0.95);
+    let text = serde_json::to_string(&status)?;
+    sock.send(ws::Message::Text(text.clone())).await.unwrap();
+    // The same report again changes nothing, so fires nothing.
+    sock.send(ws::Message::Text(text)).await.unwrap();
+    delay_for(Duration::from_millis(300)).await;
+    assert_eq!(
+        Service::find_by_addr(&conn, address)?.health_status,
+        HealthStatus::Critical
+    );
+
+    // Disconnecting fires the disconnect rule, but not the health rule.
+    sock.close().await.unwrap();
+    delay_for(Duration::from_millis(300)).await;
+
+    let fired: Vec<String> = Event::get_logged(&conn, asset_group_id)?
+        .into_iter()
+        .filter_map(|event| match event.kind {
+            EventKind::AlertFired { message, .. } => Some(message),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        fired,
+        vec![
+            "camera (localhost:9445) went from Healthy to Critical",
+            "camera (localhost:9445) is running config 'old_config' instead of none",
+            "camera (localhost:9445) reported cpu 0.95 above 0.9",
+            "camera (localhost:9445) disconnected",
+        ]
+    );
+
+    test_server.stop().await;
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use actix_web::{http::StatusCode, test};
+use pr0t0n_orch::{testing::get_service, Error};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, AssetGroupChanges, AssetGroupRepr, AssetGroupSummary, DbFind, HealthStatus,
+        RegistrationPolicy, ServiceRepr, ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings,
+};
+
+#[actix_rt::test]
+async fn test_asset_groups() -> Result<(), Error> {
+    let mut app = get_service().await;
+
+    // Setup database connection and server.
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+
+    // Create.
+    let asset_group: AssetGroup = {
+        let request = test::TestRequest::post()
+            .uri("/asset-groups/")
+            .set_json(&AssetGroupRepr {
+                name: "temp_asset_group".to_string(),
+                description: "A test asset group".to_string(),
+                registration_policy: RegistrationPolicy::Pending,
+            })
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = test::read_body(response).await;
+        serde_json::from_slice(&body)?
+    };
+    let asset_group_id = asset_group.asset_group_id;
+    assert_eq!(asset_group.registration_policy, RegistrationPolicy::Pending);
+    let asset_group_uri = format!("/asset-groups/{}", asset_group_id);
+
+    // Names must not be empty.
+    {
+        let request = test::TestRequest::post()
+            .uri("/asset-groups/")
+            .set_json(&AssetGroupRepr::default())
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    // Patch only the description.
+    {
+        let request = test::TestRequest::patch()
+            .uri(&asset_group_uri)
+            .set_json(&AssetGroupChanges {
+                description: Some("A patched asset group".to_string()),
+                ..Default::default()
+            })
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let patched = AssetGroup::find(&conn, asset_group_id)?;
+        assert_eq!(patched.name, "temp_asset_group");
+        assert_eq!(patched.description, "A patched asset group");
+    }
+
+    SystemRepr {
+        asset_group_id,
+        revision: None,
+        services: vec![
+            ServiceRepr {
+                address: "localhost:345".to_string(),
+                service_type: ServiceType::Input,
+                name: "camera".to_string(),
+                output_addresses: vec!["localhost:456".to_string()],
+                ..Default::default()
+            },
+            ServiceRepr {
+                address: "localhost:456".to_string(),
+                service_type: ServiceType::Processor,
+                health_status: Some(HealthStatus::Warning),
+                name: "detector".to_string(),
+                ..Default::default()
+            },
+        ],
+        configs: vec![],
+    }
+    .sync_db(&conn)?;
+
+    // Show and list include service counts and the worst health status.
+    {
+        let request = test::TestRequest::get().uri(&asset_group_uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let summary: AssetGroupSummary = serde_json::from_slice(&body)?;
+        assert_eq!(summary.service_count, 2);
+        assert_eq!(summary.health_status, Some(HealthStatus::Warning));
+
+        let request = test::TestRequest::get().uri("/asset-groups/").to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let summaries: Vec<AssetGroupSummary> = serde_json::from_slice(&body)?;
+        assert!(summaries.contains(&summary));
+    }
+
+    // Deleting a group with services needs an explicit cascade.
+    {
+        let request = test::TestRequest::delete()
+            .uri(&asset_group_uri)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert!(AssetGroup::find(&conn, asset_group_id).is_ok());
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("{}?cascade=true", asset_group_uri))
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let request = test::TestRequest::get().uri(&asset_group_uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    Ok(())
+}
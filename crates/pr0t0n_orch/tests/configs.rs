@@ -0,0 +1,157 @@
+use actix_web::{http::StatusCode, test};
+use pr0t0n_orch::{testing::get_service, Error};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, Config, ConfigRepr, ConfigUsageRepr, DbDelete, DbFind, DbInsert, ErrorCode,
+        ErrorResponse, NewAssetGroup, NewService, Service, ServiceLink, ServiceType,
+    },
+    new_pool, PoolSettings,
+};
+
+#[actix_rt::test]
+async fn test_configs() -> Result<(), Error> {
+    let mut app = get_service().await;
+
+    // Setup database connection and server.
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+
+    // Create a new adgroup for testing.
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id: i32 = asset_group.asset_group_id;
+    let configs_uri = format!("/asset-groups/{}/configs/", asset_group_id);
+    let config_uri = format!("{}RestConfig", configs_uri);
+
+    let mut config = ConfigRepr {
+        name: "RestConfig".to_string(),
+        description: "A config created over REST".to_string(),
+        json_config: serde_json::json!({ "key": "value" }),
+        ..Default::default()
+    };
+
+    // Create.
+    {
+        let request = test::TestRequest::post()
+            .uri(&configs_uri)
+            .set_json(&config)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    // Configs must be JSON objects.
+    {
+        let invalid = ConfigRepr {
+            name: "InvalidConfig".to_string(),
+            json_config: serde_json::json!([1, 2]),
+            ..Default::default()
+        };
+        let request = test::TestRequest::post()
+            .uri(&configs_uri)
+            .set_json(&invalid)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = test::read_body(response).await;
+        let error: ErrorResponse = serde_json::from_slice(&body)?;
+        assert_eq!(error.code(), Some(ErrorCode::ValidationFailed));
+        assert_eq!(error.details[0].field.as_deref(), Some("json_config"));
+    }
+
+    // Names are unique.
+    {
+        let request = test::TestRequest::post()
+            .uri(&configs_uri)
+            .set_json(&config)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = test::read_body(response).await;
+        let error: ErrorResponse = serde_json::from_slice(&body)?;
+        assert_eq!(error.code(), Some(ErrorCode::UniqueViolation));
+        assert_eq!(error.details[0].field.as_deref(), Some("name"));
+    }
+
+    // Update and show.
+    config.json_config = serde_json::json!({ "key": "new value" });
+    {
+        let request = test::TestRequest::put()
+            .uri(&config_uri)
+            .set_json(&config)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = test::TestRequest::get().uri(&config_uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let shown: ConfigRepr = serde_json::from_slice(&body)?;
+        assert_eq!(shown, config);
+    }
+
+    // List.
+    {
+        let request = test::TestRequest::get().uri(&configs_uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let listed: Vec<ConfigUsageRepr> = serde_json::from_slice(&body)?;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].config, config);
+        assert!(listed[0].used_by.is_empty());
+    }
+
+    // Used by.
+    let config_id = Config::find_by_name(&conn, asset_group_id, "RestConfig")?
+        .unwrap()
+        .config_id;
+    let service = NewService {
+        asset_group_id,
+        name: "config_user",
+        address: "localhost:345",
+        service_type: ServiceType::Input,
+        config_id: Some(config_id),
+        ..Default::default()
+    }
+    .insert(&conn)?;
+    {
+        let request = test::TestRequest::get()
+            .uri(&format!("{}/used-by", config_uri))
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let used_by: Vec<ServiceLink> = serde_json::from_slice(&body)?;
+        assert_eq!(used_by.len(), 1);
+        assert_eq!(used_by[0].address, "localhost:345");
+    }
+
+    // Deleting a config in use must be forced.
+    {
+        let request = test::TestRequest::delete().uri(&config_uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = test::read_body(response).await;
+        assert!(String::from_utf8_lossy(&body).contains("localhost:345"));
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("{}?force=true", config_uri))
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(Service::find(&conn, service.service_id)?.config_id, None);
+
+        let request = test::TestRequest::get().uri(&config_uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    AssetGroup::delete(&conn, asset_group.asset_group_id)?;
+    Ok(())
+}
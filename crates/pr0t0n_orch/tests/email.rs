@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use actix::{clock::delay_for, Actor};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AlertStatus, AssetGroup, DbDelete, DbInsert, DeadLetter, Event, EventKind, NewAssetGroup,
+    },
+    new_pool, PoolSettings,
+};
+
+use pr0t0n_orch::{
+    alerts::Alert,
+    notify::{Dispatcher, EmailNotifier, Notify, EMAIL},
+    settings::{SmtpSecurity, SmtpSettings},
+    testing::SmtpSink,
+    Error,
+};
+
+#[actix_rt::test]
+async fn test_email() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+
+    // The server is busy at first, so the email is only sent on the second attempt.
+    let sink = SmtpSink::start(1);
+    let settings = SmtpSettings {
+        host: "127.0.0.1".to_string(),
+        port: sink.port,
+        security: SmtpSecurity::None,
+        retries: 2,
+        retry_backoff: 0,
+        subject: "{event} on {service} in {asset_group}".to_string(),
+        ..Default::default()
+    };
+    let notifier = Dispatcher::new(new_pool(&PoolSettings::from_env()))
+        .with_channel(EMAIL, EmailNotifier::new(settings.clone()))
+        .start();
+
+    let alert = Alert {
+        alert_rule_id: 1,
+        asset_group_id,
+        trigger: "disconnect",
+        service_name: "camera".to_string(),
+        address: "localhost:5678".to_string(),
+        dedup_key: "localhost:5678/disconnect".to_string(),
+        status: AlertStatus::Firing,
+        sender: "pr0t0nalerts@gmail.com".to_string(),
+        recipients: vec!["joshikatsu@gmail.com".to_string()],
+        channels: vec![],
+        message: "camera (localhost:5678) disconnected".to_string(),
+    };
+    notifier.send(Notify(alert.clone())).await.unwrap();
+
+    let messages = sink.messages();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("Subject: disconnect on camera in temp_asset_group\r\n"));
+    assert!(messages[0].contains("To: <joshikatsu@gmail.com>\r\n"));
+    assert!(messages[0].contains("camera (localhost:5678) disconnected"));
+
+    // Senders that are not addresses fail without retrying.
+    notifier
+        .send(Notify(Alert {
+            sender: "not an address".to_string(),
+            ..alert
+        }))
+        .await
+        .unwrap();
+    delay_for(Duration::from_millis(100)).await;
+
+    let logged: Vec<EventKind> = Event::get_logged(&conn, asset_group_id)?
+        .into_iter()
+        .map(|event| event.kind)
+        .collect();
+    assert_eq!(logged.len(), 2);
+    assert_eq!(
+        logged[0],
+        EventKind::NotificationSent {
+            address: "localhost:5678".to_string(),
+            alert_rule_id: 1,
+            channel: "email".to_string(),
+            recipients: vec!["joshikatsu@gmail.com".to_string()],
+            attempts: 2,
+        }
+    );
+    match &logged[1] {
+        EventKind::NotificationFailed { attempts, .. } => assert!(*attempts <= 1),
+        kind => panic!("Expected a failed notification, got {:?}", kind),
+    }
+    // The email that could not be sent is kept.
+    let dead_letters = DeadLetter::get_group(&conn, asset_group_id)?;
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].channel, "email");
+    assert_eq!(dead_letters[0].payload["from"], "not an address");
+
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
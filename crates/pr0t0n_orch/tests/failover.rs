@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use actix::{clock::delay_for, Actor};
+use actix_web::{client::Client, http::StatusCode, test, App};
+use futures::{SinkExt, StreamExt};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, DbDelete, DbInsert, Event, EventKind, NewAssetGroup, Routing, Service,
+        ServiceRepr, ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+
+use pr0t0n_orch::{
+    routes, settings::WebsocketSettings, testing::get_websocket_frame_data, websocket::Server,
+    Error,
+};
+
+fn routing(inputs: &[&str], outputs: &[&str]) -> String {
+    Routing {
+        inputs: inputs.iter().map(|input| input.to_string()).collect(),
+        outputs: outputs.iter().map(|output| output.to_string()).collect(),
+        ..Default::default()
+    }
+    .to_message()
+}
+
+#[actix_rt::test]
+async fn test_failover() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let server = Server::new(new_pool(&PoolSettings::from_env())).start();
+    let test_server = test::start(move || {
+        App::new()
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(server.clone())
+            .data(WebsocketSettings::default())
+            .configure(routes)
+    });
+
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    let (camera, detector, backup, arm) = (
+        "localhost:9631",
+        "localhost:9632",
+        "localhost:9633",
+        "localhost:9634",
+    );
+    let service = |address: &str, service_type: ServiceType, outputs: &[&str]| ServiceRepr {
+        address: address.to_string(),
+        service_type,
+        name: address.to_string(),
+        output_addresses: outputs.iter().map(|output| output.to_string()).collect(),
+        ..Default::default()
+    };
+    let system = SystemRepr {
+        asset_group_id,
+        revision: None,
+        services: vec![
+            service(camera, ServiceType::Input, &[detector]),
+            ServiceRepr {
+                standby_addresses: vec![backup.to_string()],
+                failback: true,
+                ..service(detector, ServiceType::Processor, &[arm])
+            },
+            service(backup, ServiceType::Processor, &[]),
+            service(arm, ServiceType::Output, &[]),
+        ],
+        configs: vec![],
+    };
+    let client = Client::default();
+
+    // Standbys only stand in for processors, and take no part in the routing themselves.
+    let mut invalid = system.clone();
+    invalid.services[3].output_addresses = vec![backup.to_string()];
+    let response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&invalid)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&system)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let connect = |address: &'static str| {
+        client
+            .ws(test_server.url("/ws/"))
+            .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+            .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, address)
+            .connect()
+    };
+    let mut socks = Vec::new();
+    for (address, expected) in &[
+        (camera, routing(&[], &[detector])),
+        (detector, routing(&[camera], &[arm])),
+        (backup, routing(&[], &[])),
+        (arm, routing(&[detector], &[])),
+    ] {
+        let (_response, sock) = connect(address).await.unwrap();
+        let mut sock = sock.fuse();
+        let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+        assert_eq!(data, "Registered");
+        let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+        assert_eq!(&data, expected);
+        socks.push(sock);
+    }
+    let mut arm_sock = socks.pop().unwrap();
+    let mut backup_sock = socks.pop().unwrap();
+    let mut detector_sock = socks.pop().unwrap();
+    let mut camera_sock = socks.pop().unwrap();
+
+    // The standby takes over the routing of the disconnected processor.
+    detector_sock.close().await.unwrap();
+    drop(detector_sock);
+    let data = get_websocket_frame_data(backup_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, routing(&[camera], &[arm]));
+    let data = get_websocket_frame_data(camera_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, routing(&[], &[backup]));
+    let data = get_websocket_frame_data(arm_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, routing(&[backup], &[]));
+    let outputs = Service::find_by_addr(&conn, camera)?.get_outputs(&conn)?;
+    assert_eq!(outputs[0].address, backup);
+
+    // The declared system is unchanged by the failover.
+    let mut response = client
+        .get(test_server.url(&format!("/asset-groups/{}/system", asset_group_id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let downloaded: SystemRepr = response.json().await.unwrap();
+    assert_eq!(downloaded.services[0].output_addresses, vec![detector]);
+    assert_eq!(downloaded.services[1].output_addresses, vec![arm]);
+    assert!(downloaded.services[2].output_addresses.is_empty());
+
+    // Inputs that reconnect during the failover are sent to the standby.
+    camera_sock.close().await.unwrap();
+    drop(camera_sock);
+    let (_response, sock) = connect(camera).await.unwrap();
+    let mut camera_sock = sock.fuse();
+    let data = get_websocket_frame_data(camera_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, "Registered");
+    let data = get_websocket_frame_data(camera_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, routing(&[], &[backup]));
+
+    // The processor takes its routing back when it reconnects.
+    let (_response, sock) = connect(detector).await.unwrap();
+    let mut detector_sock = sock.fuse();
+    let data = get_websocket_frame_data(detector_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, "Registered");
+    let data = get_websocket_frame_data(detector_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, routing(&[camera], &[arm]));
+    let data = get_websocket_frame_data(backup_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, routing(&[], &[]));
+    let outputs = Service::find_by_addr(&conn, camera)?.get_outputs(&conn)?;
+    assert_eq!(outputs[0].address, detector);
+
+    let failovers: Vec<EventKind> = Event::get_logged(&conn, asset_group_id)?
+        .into_iter()
+        .map(|event| event.kind)
+        .filter(|kind| {
+            matches!(
+                kind,
+                EventKind::FailedOver { .. } | EventKind::FailedBack { .. }
+            )
+        })
+        .collect();
+    assert_eq!(
+        failovers,
+        vec![
+            EventKind::FailedOver {
+                address: detector.to_string(),
+                standby: backup.to_string(),
+            },
+            EventKind::FailedBack {
+                address: detector.to_string(),
+                standby: backup.to_string(),
+            },
+        ]
+    );
+
+    drop((camera_sock, detector_sock, backup_sock, arm_sock));
+    delay_for(Duration::from_millis(200)).await;
+    test_server.stop().await;
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use actix_web::{http::StatusCode, test};
+use pr0t0n_orch::{testing::get_service, Error};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{AssetGroup, DbDelete, DbInsert, NewAssetGroup, ServiceRepr, ServiceType, SystemRepr},
+    new_pool, PoolSettings,
+};
+
+#[actix_rt::test]
+async fn test_graph() -> Result<(), Error> {
+    let mut app = get_service().await;
+
+    // Setup database connection and server.
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+
+    // Create a new adgroup for testing.
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id: i32 = asset_group.asset_group_id;
+
+    SystemRepr {
+        asset_group_id,
+        revision: None,
+        services: vec![
+            ServiceRepr {
+                address: "localhost:345".to_string(),
+                service_type: ServiceType::Input,
+                name: "camera".to_string(),
+                output_addresses: vec!["localhost:456".to_string()],
+                ..Default::default()
+            },
+            ServiceRepr {
+                address: "localhost:456".to_string(),
+                service_type: ServiceType::Processor,
+                name: "detector".to_string(),
+                ..Default::default()
+            },
+        ],
+        configs: vec![],
+    }
+    .sync_db(&conn)?;
+
+    for (format, expected) in &[
+        ("dot", "digraph services {"),
+        ("mermaid", "flowchart LR"),
+        ("ascii", ">camera> localhost:345 (healthy)"),
+    ] {
+        let request = test::TestRequest::get()
+            .uri(&format!(
+                "/asset-groups/{}/graph?format={}",
+                asset_group_id, format
+            ))
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let rendered = String::from_utf8(body.to_vec()).unwrap();
+        assert!(rendered.contains(expected), "{}", rendered);
+    }
+
+    AssetGroup::delete(&conn, asset_group.asset_group_id)?;
+
+    // A deleted asset group has no graph, rather than an empty one.
+    let request = test::TestRequest::get()
+        .uri(&format!("/asset-groups/{}/graph", asset_group_id))
+        .to_request();
+    let response = test::call_service(&mut app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    Ok(())
+}
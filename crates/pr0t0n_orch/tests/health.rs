@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use actix_web::{http::StatusCode, test};
+use pr0t0n_orch::{testing::get_service, Error};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, DbDelete, DbInsert, HealthStatus, NewAssetGroup, NewService, ServiceType,
+        SystemRepr,
+    },
+    new_pool, PoolSettings,
+};
+
+#[actix_rt::test]
+async fn test_health() -> Result<(), Error> {
+    let mut app = get_service().await;
+
+    // Setup database connection and server.
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+
+    {
+        let request = test::TestRequest::get().uri("/healthz").to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    {
+        let request = test::TestRequest::get().uri("/readyz").to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let checks: BTreeMap<String, String> = serde_json::from_slice(&body)?;
+        assert_eq!(checks.get("database").map(String::as_str), Some("ok"));
+        assert_eq!(checks.get("server").map(String::as_str), Some("ok"));
+    }
+
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    NewService {
+        asset_group_id,
+        name: "metrics_service",
+        address: "localhost:8345",
+        service_type: ServiceType::Input,
+        health_status: HealthStatus::Warning,
+        ..Default::default()
+    }
+    .insert(&conn)?;
+
+    // Sync once so the histogram has an observation.
+    {
+        let system = SystemRepr::get_group(&conn, asset_group_id)?;
+        let request = test::TestRequest::post()
+            .uri("/sync/upload/")
+            .set_json(&system)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    {
+        let request = test::TestRequest::get().uri("/metrics").to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let text = String::from_utf8_lossy(&body);
+        let services = format!(
+            "pr0t0n_services{{asset_group_id=\"{}\",health_status=\"Warning\"}} 1\n",
+            asset_group_id
+        );
+        assert!(text.contains(&services), "{}", text);
+        for name in &[
+            "pr0t0n_websocket_sessions",
+            "pr0t0n_websocket_watchers",
+            "pr0t0n_db_pool_connections",
+            "pr0t0n_websocket_messages_total",
+            "pr0t0n_heartbeat_timeouts_total",
+        ] {
+            assert!(text.contains(&format!("# TYPE {} ", name)), "{}", name);
+        }
+        assert!(!text.contains("pr0t0n_sync_duration_seconds_count 0\n"));
+    }
+
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use std::time::{Duration, Instant};
+
+use actix::{clock::delay_for, Actor};
+use actix_web::{client::Client, http::StatusCode, test, App};
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AlertRuleRepr, AlertState, AlertStatus, AlertTrigger, AssetGroup, DbDelete, DbInsert,
+        ErrorResponse, Event, EventKind, MaintenanceWindow, MaintenanceWindowRepr, NewAssetGroup,
+        Recurrence, ServiceRepr, ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+use serde_json::Value;
+
+use pr0t0n_orch::{
+    notify::{Dispatcher, WebhookNotifier},
+    routes,
+    settings::{AlertSettings, WebhookSettings, WebsocketSettings},
+    testing::{get_websocket_frame_data, WebhookSink},
+    websocket::Server,
+    Error,
+};
+
+fn service(address: &str, labels: &[(&str, &str)]) -> ServiceRepr {
+    ServiceRepr {
+        address: address.to_string(),
+        service_type: ServiceType::Input,
+        name: "arm".to_string(),
+        labels: labels
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+        alerts: vec![AlertRuleRepr {
+            trigger: AlertTrigger::Disconnect,
+            sender: String::new(),
+            recipients: vec![],
+            channels: vec!["oncall".to_string()],
+            key: None,
+            for_seconds: 0,
+            escalate: vec![],
+        }],
+        ..Default::default()
+    }
+}
+
+#[actix_rt::test]
+async fn test_maintenance_windows() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let oncall = WebhookSink::start(0);
+    let notifier = Dispatcher::new(new_pool(&PoolSettings::from_env()))
+        .with_channel(
+            "oncall",
+            WebhookNotifier::new(WebhookSettings {
+                url: oncall.url(),
+                retries: 0,
+                ..Default::default()
+            }),
+        )
+        .start();
+    let server = Server::new(new_pool(&PoolSettings::from_env()))
+        .with_notifier(notifier)
+        .with_alert_settings(AlertSettings {
+            check_interval: 1,
+            ..Default::default()
+        })
+        .start();
+    let test_server = test::start(move || {
+        App::new()
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(server.clone())
+            .data(WebsocketSettings::default())
+            .configure(routes)
+    });
+
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    let (serviced, working) = ("localhost:9561", "localhost:9562");
+    let client = Client::default();
+    let response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&SystemRepr {
+            asset_group_id,
+            revision: None,
+            services: vec![
+                service(serviced, &[("site", "lab")]),
+                service(working, &[("site", "floor")]),
+            ],
+            configs: vec![],
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let windows_url = test_server.url(&format!(
+        "/asset-groups/{}/maintenance-windows",
+        asset_group_id
+    ));
+    let now = Utc::now();
+    let window = MaintenanceWindowRepr {
+        address: None,
+        label: Some("site=lab".to_string()),
+        reason: "Servicing the arm".to_string(),
+        starts_at: now - chrono::Duration::minutes(1),
+        ends_at: now + chrono::Duration::hours(1),
+        recurrence: Recurrence::Once,
+        until: None,
+    };
+    let mut response = client.post(&windows_url).send_json(&window).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: MaintenanceWindow = response.json().await.unwrap();
+    assert_eq!(created.label.as_deref(), Some("site=lab"));
+
+    let mut response = client
+        .post(&windows_url)
+        .send_json(&MaintenanceWindowRepr {
+            address: Some(serviced.to_string()),
+            ..window.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error.details[0].field.as_deref(), Some("label"));
+
+    let (_response, watcher) = client
+        .ws(test_server.url("/ws/watch/"))
+        .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+        .connect()
+        .await
+        .unwrap();
+    let mut watcher = watcher.fuse();
+    delay_for(Duration::from_millis(200)).await;
+
+    // Both services go down, but only the one outside the window is alerted on.
+    for address in &[serviced, working] {
+        let (_response, sock) = client
+            .ws(test_server.url("/ws/"))
+            .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+            .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, *address)
+            .connect()
+            .await
+            .unwrap();
+        let mut sock = sock.fuse();
+        sock.next().await;
+        sock.close().await.unwrap();
+    }
+    delay_for(Duration::from_millis(500)).await;
+    let notified = || -> Vec<String> {
+        oncall
+            .requests()
+            .into_iter()
+            .map(|request| serde_json::from_str::<Value>(&request.body).unwrap())
+            .map(|payload| payload["address"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(notified(), vec![working.to_string()]);
+    let serviced_key = format!("{}/{{\"on\":\"disconnect\"}}", serviced);
+    let state = AlertState::find_key(&conn, asset_group_id, &serviced_key)?.unwrap();
+    assert_eq!(state.status, AlertStatus::Pending);
+
+    // Disconnects are annotated with the window covering the service.
+    let mut disconnects = Vec::new();
+    while disconnects.len() < 2 {
+        let data = get_websocket_frame_data(watcher.next().await.unwrap().unwrap()).unwrap();
+        let event: Event = serde_json::from_str(&data)?;
+        if let EventKind::Disconnected {
+            address,
+            maintenance,
+        } = event.kind
+        {
+            disconnects.push((address, maintenance));
+        }
+    }
+    assert_eq!(
+        disconnects,
+        vec![
+            (serviced.to_string(), Some(created.maintenance_window_id)),
+            (working.to_string(), None),
+        ]
+    );
+
+    let mut response = client.get(&windows_url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let listed: Vec<MaintenanceWindow> = response.json().await.unwrap();
+    assert_eq!(listed, vec![created.clone()]);
+
+    // Once the window is expired, the service still being down fires its alert.
+    let expire = |maintenance_window_id: i32| {
+        client.post(test_server.url(&format!(
+            "/asset-groups/{}/maintenance-windows/{}/expire",
+            asset_group_id, maintenance_window_id
+        )))
+    };
+    let mut response = expire(created.maintenance_window_id).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let expired: MaintenanceWindow = response.json().await.unwrap();
+    assert!(expired.expired_at.is_some());
+    let response = expire(created.maintenance_window_id).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = expire(created.maintenance_window_id + 1000)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let start = Instant::now();
+    while notified().len() < 2 {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "Timed out waiting for the alert to fire"
+        );
+        delay_for(Duration::from_millis(100)).await;
+    }
+    assert_eq!(notified()[1], serviced);
+
+    let mut response = client.get(&windows_url).send().await.unwrap();
+    let listed: Vec<MaintenanceWindow> = response.json().await.unwrap();
+    assert!(listed.is_empty());
+    let mut response = client
+        .get(format!("{}?all=true", windows_url))
+        .send()
+        .await
+        .unwrap();
+    let listed: Vec<MaintenanceWindow> = response.json().await.unwrap();
+    assert_eq!(listed, vec![expired]);
+
+    drop(watcher);
+    test_server.stop().await;
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use actix_web::{
+    http::{Method, StatusCode},
+    test,
+};
+use pr0t0n_orch::{openapi, resources, settings::Features, testing::get_service, Error};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, ConfigRepr, DbDelete, DbInsert, ErrorResponse, NewAssetGroup, Service,
+        ServiceRepr, ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings,
+};
+use serde_json::Value;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// Paths registered in `routes()`.
+fn registered_paths() -> Vec<String> {
+    resources(&Features::default())
+        .into_iter()
+        .map(|(path, _resource)| path.to_string())
+        .collect()
+}
+
+/// Fill in every parameter of a path with a value that does not exist.
+fn missing(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                "-1"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[actix_rt::test]
+async fn test_openapi() -> Result<(), Error> {
+    let mut app = get_service().await;
+
+    let request = test::TestRequest::get().uri("/openapi.json").to_request();
+    let response = test::call_service(&mut app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = test::read_body(response).await;
+    let document: Value = serde_json::from_slice(&body)?;
+    assert_eq!(document["openapi"], openapi::OPENAPI_VERSION);
+    let paths = document["paths"].as_object().unwrap();
+
+    // Every route is documented, and every documented path is a route.
+    let registered = registered_paths();
+    for path in &registered {
+        assert!(paths.contains_key(path), "{} is not documented", path);
+    }
+    for path in paths.keys() {
+        assert!(
+            registered.contains(path),
+            "{} is documented but not routed",
+            path
+        );
+    }
+
+    // Documented methods are routed, the others are not.
+    for (path, operations) in paths {
+        for method in &METHODS {
+            let request = test::TestRequest::default()
+                .method(Method::from_bytes(method.to_uppercase().as_bytes()).unwrap())
+                .uri(&missing(path))
+                .to_request();
+            let response = test::call_service(&mut app, request).await;
+            let status = response.status();
+            let body = test::read_body(response).await;
+            if operations.get(*method).is_some() {
+                assert_ne!(
+                    status,
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "{} {}",
+                    method,
+                    path
+                );
+                // Unrouted requests get an empty 404, handlers explain theirs.
+                if status == StatusCode::NOT_FOUND {
+                    let error: Result<ErrorResponse, _> = serde_json::from_slice(&body);
+                    assert!(error.is_ok(), "{} {}", method, path);
+                }
+            } else {
+                assert_eq!(
+                    status,
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "{} {}",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    // Responses match their documented schemas.
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    SystemRepr {
+        asset_group_id,
+        revision: None,
+        services: vec![
+            ServiceRepr {
+                address: "localhost:7345".to_string(),
+                service_type: ServiceType::Input,
+                name: "camera".to_string(),
+                output_addresses: vec!["localhost:7456".to_string()],
+                config_name: Some("OpenApiConfig".to_string()),
+                labels: vec![("site".to_string(), "north".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            ServiceRepr {
+                address: "localhost:7456".to_string(),
+                service_type: ServiceType::Processor,
+                name: "detector".to_string(),
+                ..Default::default()
+            },
+        ],
+        configs: vec![ConfigRepr {
+            name: "OpenApiConfig".to_string(),
+            json_config: serde_json::json!({ "key": "value" }),
+            ..Default::default()
+        }],
+    }
+    .sync_db(&conn)?;
+    let service_id = Service::find_by_addr(&conn, "localhost:7345")?.service_id;
+
+    let fill = |path: &str| {
+        path.replace("{asset_group_id}", &asset_group_id.to_string())
+            .replace("{service_id}", &service_id.to_string())
+            .replace("{name}", "OpenApiConfig")
+    };
+    for (path, operations) in paths {
+        let schema = &operations["get"]["responses"]["200"]["content"]["application/json"];
+        if schema.is_null() {
+            continue;
+        }
+        let request = test::TestRequest::get().uri(&fill(path)).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK, "GET {}", path);
+        let body = test::read_body(response).await;
+        let value: Value = serde_json::from_slice(&body)?;
+        if let Err(err) = openapi::check(&document, &schema["schema"], &value) {
+            panic!("GET {} does not match its schema: {}", path, err);
+        }
+    }
+
+    // So do errors.
+    {
+        let path = "/asset-groups/{asset_group_id}";
+        let request = test::TestRequest::get().uri(&missing(path)).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = test::read_body(response).await;
+        let value: Value = serde_json::from_slice(&body)?;
+        let schema = &paths[path]["get"]["responses"]["404"]["content"]["application/json"];
+        openapi::check(&document, &schema["schema"], &value).unwrap();
+    }
+
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use actix::{clock::delay_for, Actor};
+use actix_web::{client::Client, http::StatusCode, test, App};
+use actix_web_actors::ws;
+use futures::{SinkExt, StreamExt};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, BalancingStrategy, DbDelete, DbInsert, NewAssetGroup, PoolRepr, Routing,
+        ServiceRepr, ServiceStatus, ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+use serde_json::Value;
+
+use pr0t0n_orch::{
+    routes, settings::WebsocketSettings, testing::get_websocket_frame_data, websocket::Server,
+    Error,
+};
+
+fn parse_routing(data: &str) -> Routing {
+    let mut message: Value = serde_json::from_str(data).unwrap();
+    serde_json::from_value(message["routing"].take()).unwrap()
+}
+
+#[actix_rt::test]
+async fn test_pools() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let server = Server::new(new_pool(&PoolSettings::from_env())).start();
+    let test_server = test::start(move || {
+        App::new()
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(server.clone())
+            .data(WebsocketSettings::default())
+            .configure(routes)
+    });
+
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    let (camera, detector, arm) = ("localhost:9661", "localhost:9662", "localhost:9665");
+    let (first, second) = ("localhost:9663", "localhost:9664");
+    let service = |address: &str, service_type: ServiceType, outputs: &[&str]| ServiceRepr {
+        address: address.to_string(),
+        service_type,
+        name: address.to_string(),
+        output_addresses: outputs.iter().map(|output| output.to_string()).collect(),
+        ..Default::default()
+    };
+    let client = Client::default();
+    let response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&SystemRepr {
+            asset_group_id,
+            revision: None,
+            services: vec![
+                service(camera, ServiceType::Input, &[detector]),
+                ServiceRepr {
+                    pool: Some(PoolRepr {
+                        replica_addresses: vec![first.to_string(), second.to_string()],
+                        strategy: BalancingStrategy::LeastLoaded,
+                        load_metric: Some("queue".to_string()),
+                    }),
+                    ..service(detector, ServiceType::Processor, &[arm])
+                },
+                service(first, ServiceType::Processor, &[]),
+                service(second, ServiceType::Processor, &[]),
+                service(arm, ServiceType::Output, &[]),
+            ],
+            configs: vec![],
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let connect = |address: &'static str| {
+        client
+            .ws(test_server.url("/ws/"))
+            .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+            .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, address)
+            .connect()
+    };
+    let mut socks = Vec::new();
+    for address in &[camera, arm, first, second] {
+        let (_response, sock) = connect(address).await.unwrap();
+        let mut sock = sock.fuse();
+        let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+        assert_eq!(data, "Registered");
+        let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+        let routing = parse_routing(&data);
+        assert!(routing.pools.values().all(|pool| pool.replicas.is_empty()));
+        socks.push(sock);
+    }
+    let mut second_sock = socks.pop().unwrap();
+    let mut first_sock = socks.pop().unwrap();
+    let mut arm_sock = socks.pop().unwrap();
+    let mut camera_sock = socks.pop().unwrap();
+
+    // Replicas are routed like the pool, and the pool's inputs and outputs learn of each one
+    // as it connects.
+    let data = get_websocket_frame_data(first_sock.next().await.unwrap().unwrap()).unwrap();
+    let routing = parse_routing(&data);
+    assert_eq!(routing.inputs, vec![camera]);
+    assert_eq!(routing.outputs, vec![arm]);
+    for sock in [&mut camera_sock, &mut arm_sock].iter_mut() {
+        let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+        assert_eq!(parse_routing(&data).pools[detector].replicas, vec![first]);
+        let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+        let routing = parse_routing(&data);
+        assert_eq!(
+            routing.pools[detector].strategy,
+            BalancingStrategy::LeastLoaded
+        );
+        assert_eq!(routing.pools[detector].replicas, vec![first, second]);
+    }
+    let data = get_websocket_frame_data(second_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(parse_routing(&data).outputs, vec![arm]);
+
+    // Reported loads reorder the replicas, least loaded first.
+    let report = |queue: f64| {
+        let mut status = ServiceStatus::default();
+        status.metrics.insert("queue".to_string(), queue);
+        ws::Message::Text(serde_json::to_string(&status).unwrap())
+    };
+    first_sock.send(report(5.0)).await.unwrap();
+    let data = get_websocket_frame_data(camera_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(
+        parse_routing(&data).pools[detector].replicas,
+        vec![first, second]
+    );
+    second_sock.send(report(1.0)).await.unwrap();
+    let data = get_websocket_frame_data(camera_sock.next().await.unwrap().unwrap()).unwrap();
+    let routing = parse_routing(&data);
+    assert_eq!(routing.pools[detector].replicas, vec![second, first]);
+    assert_eq!(routing.pools[detector].loads[first], 5.0);
+
+    // Inputs that reconnect are sent the replicas as they are now.
+    camera_sock.close().await.unwrap();
+    drop(camera_sock);
+    let (_response, sock) = connect(camera).await.unwrap();
+    let mut camera_sock = sock.fuse();
+    let data = get_websocket_frame_data(camera_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, "Registered");
+    let data = get_websocket_frame_data(camera_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(parse_routing(&data), routing);
+
+    // Replicas leave the pool when they disconnect.
+    first_sock.close().await.unwrap();
+    drop(first_sock);
+    let data = get_websocket_frame_data(camera_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(parse_routing(&data).pools[detector].replicas, vec![second]);
+
+    drop((camera_sock, arm_sock, second_sock));
+    delay_for(Duration::from_millis(200)).await;
+    test_server.stop().await;
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
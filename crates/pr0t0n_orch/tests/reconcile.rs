@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use actix::{clock::delay_for, Actor};
+use actix_web::{client::Client, test, App};
+use actix_web_actors::ws;
+use futures::StreamExt;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, DbDelete, DbInsert, Event, EventKind, HealthStatus, NewAssetGroup, NewService,
+        Routing, Service, ServiceType,
+    },
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+
+use pr0t0n_orch::{
+    routes,
+    settings::WebsocketSettings,
+    testing::get_websocket_frame_data,
+    websocket::{Reconcile, Server, Shutdown},
+    Error,
+};
+
+#[actix_rt::test]
+async fn test_reconcile() -> Result<(), Error> {
+    // Setup database connection and a server we can send messages to.
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let server = Server::new(new_pool(&PoolSettings::from_env())).start();
+    let app_server = server.clone();
+    let test_server = test::start(move || {
+        App::new()
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(app_server.clone())
+            .data(WebsocketSettings::default())
+            .configure(routes)
+    });
+
+    // Services left over from a previous run of the orchestrator.
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    let (connected, stale, disconnected) = ("localhost:9345", "localhost:9346", "localhost:9347");
+    for (address, health_status) in &[
+        (connected, HealthStatus::Healthy),
+        (stale, HealthStatus::Warning),
+        (disconnected, HealthStatus::Disconnected),
+    ] {
+        NewService {
+            asset_group_id,
+            name: address,
+            address,
+            service_type: ServiceType::Input,
+            health_status: *health_status,
+            ..Default::default()
+        }
+        .insert(&conn)?;
+    }
+    let health = |address| Service::find_by_addr(&conn, address).map(|s| s.health_status);
+
+    // One of them reconnects before reconciling.
+    let (_response, sock) = Client::default()
+        .ws(test_server.url("/ws/"))
+        .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+        .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, connected)
+        .connect()
+        .await
+        .unwrap();
+    let mut sock = sock.fuse();
+    let registered = get_websocket_frame_data(sock.next().await.unwrap().unwrap());
+    assert_eq!(registered, Some("Registered".to_string()));
+    let routing = get_websocket_frame_data(sock.next().await.unwrap().unwrap());
+    assert_eq!(routing, Some(Routing::default().to_message()));
+
+    server
+        .send(Reconcile {
+            grace_period: Duration::from_millis(300),
+        })
+        .await
+        .unwrap()?;
+    assert_eq!(health(connected)?, HealthStatus::Healthy);
+    assert_eq!(health(stale)?, HealthStatus::Unknown);
+    assert_eq!(health(disconnected)?, HealthStatus::Disconnected);
+
+    // The others are given up on after the grace period.
+    delay_for(Duration::from_millis(600)).await;
+    assert_eq!(health(stale)?, HealthStatus::Disconnected);
+    let logged: Vec<EventKind> = Event::get_logged(&conn, asset_group_id)?
+        .into_iter()
+        .map(|event| event.kind)
+        .collect();
+    assert_eq!(
+        logged,
+        vec![
+            EventKind::Reconciled {
+                addresses: vec![stale.to_string()],
+                health_status: HealthStatus::Unknown,
+            },
+            EventKind::Reconciled {
+                addresses: vec![stale.to_string()],
+                health_status: HealthStatus::Disconnected,
+            },
+        ]
+    );
+
+    // Shutting down closes the session properly and marks it disconnected.
+    server.send(Shutdown).await.unwrap()?;
+    match sock.next().await {
+        Some(Ok(ws::Frame::Close(Some(reason)))) => assert_eq!(reason.code, ws::CloseCode::Away),
+        frame => panic!("Expected a close frame, got {:?}", frame),
+    }
+    assert_eq!(health(connected)?, HealthStatus::Disconnected);
+
+    // Clean up.
+    test_server.stop().await;
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
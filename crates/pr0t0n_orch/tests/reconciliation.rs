@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use actix::{clock::delay_for, Actor};
+use actix_web::{client::Client, http::StatusCode, test, App};
+use futures::{SinkExt, StreamExt};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, DbDelete, DbInsert, NewAssetGroup, Reconciliation, ReconciliationReport,
+        Service, ServiceRepr, ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+
+use pr0t0n_orch::{
+    routes, settings::WebsocketSettings, testing::get_websocket_frame_data, websocket::Server,
+    Error,
+};
+
+fn service(address: &str) -> ServiceRepr {
+    ServiceRepr {
+        address: address.to_string(),
+        service_type: ServiceType::Input,
+        name: "camera".to_string(),
+        ..Default::default()
+    }
+}
+
+#[actix_rt::test]
+async fn test_reconciliation() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let server = Server::new(new_pool(&PoolSettings::from_env()))
+        .with_register_undeclared(false)
+        .start();
+    let test_server = test::start(move || {
+        App::new()
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(server.clone())
+            .data(WebsocketSettings::default())
+            .configure(routes)
+    });
+
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    let (connected, never_seen, undeclared) =
+        ("localhost:9581", "localhost:9582", "localhost:9583");
+    let client = Client::default();
+    let response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&SystemRepr {
+            asset_group_id,
+            revision: None,
+            services: vec![service(connected), service(never_seen)],
+            configs: vec![],
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut socks = Vec::new();
+    for (address, reply) in &[(connected, "Registered"), (undeclared, "Unregistered")] {
+        let (_response, sock) = client
+            .ws(test_server.url("/ws/"))
+            .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+            .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, *address)
+            .connect()
+            .await
+            .unwrap();
+        let mut sock = sock.fuse();
+        // Undeclared services are still answered, just not registered.
+        let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+        assert_eq!(data, *reply);
+        socks.push(sock);
+    }
+
+    let mut response = client
+        .get(test_server.url(&format!("/asset-groups/{}/reconciliation", asset_group_id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: ReconciliationReport = response.json().await.unwrap();
+    let states: Vec<(&str, Reconciliation)> = report
+        .services
+        .iter()
+        .map(|service| (service.address.as_str(), service.state))
+        .collect();
+    assert_eq!(
+        states,
+        vec![
+            (connected, Reconciliation::Connected),
+            (never_seen, Reconciliation::NeverSeen),
+            (undeclared, Reconciliation::Undeclared),
+        ]
+    );
+    assert_eq!(report.services[2].service_id, None);
+    assert!(Service::try_find_by_addr(&conn, undeclared)?.is_none());
+
+    // Once disconnected, the declared service was seen and the undeclared one is gone.
+    for mut sock in socks {
+        sock.close().await.unwrap();
+    }
+    delay_for(Duration::from_millis(300)).await;
+    let report = ReconciliationReport::get(&conn, asset_group_id, &[])?;
+    let states: Vec<(&str, Reconciliation)> = report
+        .services
+        .iter()
+        .map(|service| (service.address.as_str(), service.state))
+        .collect();
+    assert_eq!(
+        states,
+        vec![
+            (connected, Reconciliation::Disconnected),
+            (never_seen, Reconciliation::NeverSeen),
+        ]
+    );
+    assert!(Service::try_find_by_addr(&conn, undeclared)?.is_none());
+
+    test_server.stop().await;
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use actix::{clock::delay_for, Actor};
+use actix_web::{client::Client, http::StatusCode, test, App};
+use actix_web_actors::ws::Frame;
+use futures::StreamExt;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, AssetGroupChanges, ConfigRepr, DbDelete, DbInsert, ErrorResponse, HealthStatus,
+        NewAssetGroup, PendingService, RegistrationPolicy, Service, ServiceApproval, ServiceDetail,
+        ServiceRepr, ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+
+use pr0t0n_orch::{
+    routes, settings::WebsocketSettings, testing::get_websocket_frame_data, websocket::Server,
+    Error,
+};
+
+#[actix_rt::test]
+async fn test_registration_policy() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let server = Server::new(new_pool(&PoolSettings::from_env())).start();
+    let test_server = test::start(move || {
+        App::new()
+            .data(new_pool(&PoolSettings::from_env()))
+            .data(server.clone())
+            .data(WebsocketSettings::default())
+            .configure(routes)
+    });
+
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+    let (declared, laptop, stray) = ("localhost:9601", "localhost:9602", "localhost:9603");
+    let client = Client::default();
+    let response = client
+        .post(test_server.url("/sync/upload/"))
+        .send_json(&SystemRepr {
+            asset_group_id,
+            revision: None,
+            services: vec![ServiceRepr {
+                address: declared.to_string(),
+                service_type: ServiceType::Input,
+                name: "camera".to_string(),
+                ..Default::default()
+            }],
+            configs: vec![ConfigRepr {
+                name: "registration_test_config".to_string(),
+                description: "A config for approved services".to_string(),
+                json_config: serde_json::json!({ "fps": 30 }),
+                ..Default::default()
+            }],
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let set_policy = |policy: RegistrationPolicy| {
+        client
+            .patch(test_server.url(&format!("/asset-groups/{}", asset_group_id)))
+            .send_json(&AssetGroupChanges {
+                registration_policy: Some(policy),
+                ..Default::default()
+            })
+    };
+    let connect = |address: &'static str| {
+        client
+            .ws(test_server.url("/ws/"))
+            .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+            .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, address)
+            .connect()
+    };
+
+    // Strict asset groups only accept their own services.
+    let response = set_policy(RegistrationPolicy::Strict).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(connect(laptop).await.is_err());
+    assert!(Service::try_find_by_addr(&conn, laptop)?.is_none());
+    let (_response, sock) = connect(declared).await.unwrap();
+    let mut sock = sock.fuse();
+    let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, "Registered");
+    drop(sock);
+
+    // Pending asset groups hold unknown addresses until they are approved.
+    set_policy(RegistrationPolicy::Pending).await.unwrap();
+    let mut socks = Vec::new();
+    for address in &[laptop, stray] {
+        let (_response, sock) = connect(address).await.unwrap();
+        let mut sock = sock.fuse();
+        let data = get_websocket_frame_data(sock.next().await.unwrap().unwrap()).unwrap();
+        assert_eq!(data, "Pending");
+        socks.push(sock);
+    }
+    assert!(Service::try_find_by_addr(&conn, laptop)?.is_none());
+    let pending_url = test_server.url(&format!(
+        "/asset-groups/{}/pending-services",
+        asset_group_id
+    ));
+    let mut response = client.get(&pending_url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let pending: Vec<PendingService> = response.json().await.unwrap();
+    let addresses: Vec<&str> = pending.iter().map(|p| p.address.as_str()).collect();
+    assert_eq!(addresses, vec![laptop, stray]);
+
+    let approve = |pending_service_id: i32, approval: ServiceApproval| {
+        client
+            .post(format!("{}/{}/approve", pending_url, pending_service_id))
+            .send_json(&approval)
+    };
+    let approval = ServiceApproval {
+        name: "laptop".to_string(),
+        service_type: ServiceType::Processor,
+        config: Some("missing".to_string()),
+    };
+    let mut response = approve(pending[0].pending_service_id, approval.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error.details[0].field.as_deref(), Some("config"));
+
+    let approval = ServiceApproval {
+        config: Some("registration_test_config".to_string()),
+        ..approval
+    };
+    let mut response = approve(pending[0].pending_service_id, approval.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let detail: ServiceDetail = response.json().await.unwrap();
+    assert_eq!(detail.service.name, "laptop");
+    assert_eq!(detail.service.service_type, ServiceType::Processor);
+    assert_eq!(
+        detail.service.config_name.as_deref(),
+        Some("registration_test_config")
+    );
+    let response = approve(pending[0].pending_service_id, approval)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The approved session is registered without reconnecting.
+    let mut laptop_sock = socks.remove(0);
+    let data = get_websocket_frame_data(laptop_sock.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(data, "Registered");
+    let service = Service::find_by_addr(&conn, laptop)?;
+    assert_eq!(service.health_status, HealthStatus::Healthy);
+    assert!(service.declared);
+
+    // Rejected addresses are forgotten and their sessions closed.
+    let response = client
+        .delete(format!("{}/{}", pending_url, pending[1].pending_service_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let mut stray_sock = socks.remove(0);
+    match stray_sock.next().await {
+        Some(Ok(Frame::Close(reason))) => {
+            assert_eq!(reason.unwrap().description.as_deref(), Some("Rejected"))
+        }
+        frame => panic!("Expected the session to close, got {:?}", frame),
+    }
+    assert!(PendingService::get_group(&conn, asset_group_id)?.is_empty());
+    assert!(Service::try_find_by_addr(&conn, stray)?.is_none());
+
+    drop(laptop_sock);
+    delay_for(Duration::from_millis(200)).await;
+    test_server.stop().await;
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
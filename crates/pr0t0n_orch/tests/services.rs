@@ -0,0 +1,128 @@
+use actix_web::{http::StatusCode, test};
+use pr0t0n_orch::{testing::get_service, Error};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AssetGroup, DbDelete, DbInsert, NewAssetGroup, ServiceDetail, ServicePage, ServiceRepr,
+        ServiceType, SystemRepr,
+    },
+    new_pool, PoolSettings,
+};
+
+#[actix_rt::test]
+async fn test_services() -> Result<(), Error> {
+    let mut app = get_service().await;
+
+    // Setup database connection and server.
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+
+    // Create a new adgroup for testing.
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id: i32 = asset_group.asset_group_id;
+    let services_uri = format!("/asset-groups/{}/services", asset_group_id);
+
+    SystemRepr {
+        asset_group_id,
+        revision: None,
+        services: vec![
+            ServiceRepr {
+                address: "localhost:345".to_string(),
+                service_type: ServiceType::Input,
+                name: "camera".to_string(),
+                output_addresses: vec!["localhost:456".to_string(), "localhost:567".to_string()],
+                labels: vec![("site".to_string(), "north".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            ServiceRepr {
+                address: "localhost:456".to_string(),
+                service_type: ServiceType::Processor,
+                name: "detector".to_string(),
+                ..Default::default()
+            },
+            ServiceRepr {
+                address: "localhost:567".to_string(),
+                service_type: ServiceType::Processor,
+                name: "classifier".to_string(),
+                ..Default::default()
+            },
+        ],
+        configs: vec![],
+    }
+    .sync_db(&conn)?;
+
+    // Filter and page through processors by name.
+    let first: ServicePage = {
+        let uri = format!("{}?service_type=Processor&sort=name&limit=1", services_uri);
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        serde_json::from_slice(&body)?
+    };
+    assert_eq!(first.services.len(), 1);
+    assert_eq!(first.services[0].service.name, "classifier");
+    {
+        let uri = format!(
+            "{}?service_type=Processor&sort=name&limit=1&cursor={}",
+            services_uri,
+            first.next_cursor.as_deref().unwrap()
+        );
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let second: ServicePage = serde_json::from_slice(&body)?;
+        assert_eq!(second.services.len(), 1);
+        assert_eq!(second.services[0].service.name, "detector");
+        assert_eq!(second.next_cursor, None);
+    }
+
+    // Label filter.
+    let camera_id = {
+        let uri = format!("{}?label=site%3Dnorth", services_uri);
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let page: ServicePage = serde_json::from_slice(&body)?;
+        assert_eq!(page.services.len(), 1);
+        assert_eq!(page.services[0].service.name, "camera");
+        page.services[0].service_id
+    };
+
+    // Invalid queries are rejected.
+    for query in &["limit=0", "label=north", "cursor=abc"] {
+        let uri = format!("{}?{}", services_uri, query);
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+
+    // Show a service with its neighbours.
+    {
+        let uri = format!("{}/{}", services_uri, camera_id);
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let detail: ServiceDetail = serde_json::from_slice(&body)?;
+        assert!(detail.inputs.is_empty());
+        let outputs: Vec<&str> = detail.outputs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(outputs, vec!["detector", "classifier"]);
+
+        let uri = format!("{}/{}", services_uri, camera_id + 1000);
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    AssetGroup::delete(&conn, asset_group.asset_group_id)?;
+    Ok(())
+}
@@ -1,13 +1,16 @@
-use actix_web::{http::StatusCode, test};
+use actix_web::{
+    http::{header, StatusCode},
+    test,
+};
 use assert_json_diff::assert_json_eq;
 use pr0t0n_orch::{testing::get_service, Error};
 use pr0t0n_orch_db::{
     get_conn,
     models::{
-        AssetGroup, ConfigRepr, DbDelete, DbInsert, GetGroupRequest, NewAssetGroup, ServiceRepr,
-        ServiceType, SystemRepr,
+        revision_etag, AssetGroup, ConfigRepr, DbDelete, DbInsert, ErrorCode, ErrorResponse,
+        HealthStatus, NewAssetGroup, Service, ServiceRepr, ServiceType, SystemRepr, SystemRevision,
     },
-    new_pool,
+    new_pool, PoolSettings,
 };
 
 #[actix_rt::test]
@@ -15,7 +18,7 @@ async fn test_response() -> Result<(), Error> {
     let mut app = get_service().await;
 
     // Setup database connection and server.
-    let pool = new_pool();
+    let pool = new_pool(&PoolSettings::from_env());
     let conn = get_conn(&pool)?;
 
     // Create a new adgroup for testing.
@@ -28,6 +31,7 @@ async fn test_response() -> Result<(), Error> {
 
     let system_repr: SystemRepr = SystemRepr {
         asset_group_id,
+        revision: None,
         services: vec![
             ServiceRepr {
                 address: "localhost:123".to_string(),
@@ -65,19 +69,197 @@ async fn test_response() -> Result<(), Error> {
     }
 
     // Test download
+    let system_uri = format!("/asset-groups/{}/system", asset_group_id);
+    let etag = {
+        let request = test::TestRequest::get().uri(&system_uri).to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+        let body = test::read_body(response).await;
+        let mut response_system_repr: SystemRepr = serde_json::from_slice(&body)?;
+        let revision = response_system_repr.revision.take().unwrap();
+        assert_eq!(etag, revision_etag(asset_group_id, revision).as_str());
+        assert_json_eq!(system_repr, response_system_repr);
+        etag
+    };
+
+    // Unchanged systems are not sent again, even as their services connect and disconnect.
+    {
+        Service::upsert_healthy_address(&conn, asset_group_id, "localhost:123")?;
+        Service::disconnect_address(&conn, "localhost:234")?;
+        let request = test::TestRequest::get()
+            .uri(&system_uri)
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(header::ETAG), Some(&etag));
+    }
+
+    // Health reports do not make uploads against the same revision conflict.
+    {
+        Service::set_health(&conn, "localhost:123", HealthStatus::Critical)?;
+        let request = test::TestRequest::post()
+            .uri("/sync/upload/")
+            .header(header::IF_MATCH, etag.clone())
+            .set_json(&system_repr)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // Uploading the same system again leaves its ETag alone.
+    {
+        let request = test::TestRequest::post()
+            .uri("/sync/upload/")
+            .set_json(&system_repr)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let uploaded: SystemRevision = serde_json::from_slice(&body)?;
+        assert_eq!(
+            revision_etag(asset_group_id, uploaded.revision).as_str(),
+            etag
+        );
+
+        let request = test::TestRequest::get()
+            .uri(&system_uri)
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    // Test download as YAML
     {
-        let get_group_request = GetGroupRequest { asset_group_id };
         let request = test::TestRequest::get()
-            .uri("/sync/download/")
-            .set_json(&get_group_request)
+            .uri(&format!("{}?format=yaml", system_uri))
             .to_request();
         let response = test::call_service(&mut app, request).await;
         assert_eq!(response.status(), StatusCode::OK);
         let body = test::read_body(response).await;
-        let response_system_repr: SystemRepr = serde_json::from_slice(&body)?;
+        let mut response_system_repr: SystemRepr = serde_yaml::from_slice(&body)?;
+        response_system_repr.revision = None;
         assert_json_eq!(system_repr, response_system_repr);
     }
 
+    // Changes give a new ETag.
+    {
+        let mut changed = system_repr.clone();
+        changed.services[1].name = "renamed".to_string();
+        let request = test::TestRequest::post()
+            .uri("/sync/upload/")
+            .set_json(&changed)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = test::TestRequest::get()
+            .uri(&system_uri)
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers().get(header::ETAG), Some(&etag));
+    }
+
+    // Uploads based on an old revision conflict.
+    {
+        let mut stale = system_repr.clone();
+        stale.services[1].name = "stale".to_string();
+        let request = test::TestRequest::post()
+            .uri("/sync/upload/")
+            .header(header::IF_MATCH, etag.clone())
+            .set_json(&stale)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let current = response.headers().get(header::ETAG).unwrap().clone();
+        assert_ne!(current, etag);
+        let body = test::read_body(response).await;
+        let error: ErrorResponse = serde_json::from_slice(&body)?;
+        assert_eq!(error.code(), Some(ErrorCode::RevisionConflict));
+
+        // Retrying against the current revision succeeds and moves the revision on.
+        let request = test::TestRequest::post()
+            .uri("/sync/upload/")
+            .header(header::IF_MATCH, current.clone())
+            .set_json(&stale)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        let uploaded: SystemRevision = serde_json::from_slice(&body)?;
+        assert_ne!(
+            revision_etag(asset_group_id, uploaded.revision).as_str(),
+            current
+        );
+        let system = SystemRepr::get_group(&conn, asset_group_id)?;
+        assert_eq!(system.services[1].name, "stale");
+    }
+
+    // Patch a single config key.
+    {
+        let request = test::TestRequest::patch()
+            .uri(&system_uri)
+            .header(header::CONTENT_TYPE, "application/json-patch+json")
+            .set_payload(
+                r#"[{ "op": "replace", "path": "/configs/0/json_config/key", "value": "new" }]"#,
+            )
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let system = SystemRepr::get_group(&conn, asset_group_id)?;
+        assert_eq!(
+            system.configs[0].json_config,
+            serde_json::json!({ "key": "new" })
+        );
+        assert_eq!(system.services[1].name, "stale");
+
+        // Patches that make the system invalid are rejected without changing anything.
+        let request = test::TestRequest::patch()
+            .uri(&system_uri)
+            .header(header::CONTENT_TYPE, "application/merge-patch+json")
+            .set_payload(r#"{ "configs": [{ "name": "", "description": "", "json_config": {} }] }"#)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = test::read_body(response).await;
+        let error: ErrorResponse = serde_json::from_slice(&body)?;
+        assert_eq!(error.code(), Some(ErrorCode::ValidationFailed));
+        assert_eq!(error.details[0].field.as_deref(), Some("configs/0/name"));
+        assert_eq!(SystemRepr::get_group(&conn, asset_group_id)?, system);
+    }
+
+    // Systems the database cannot sync are rejected without changing anything.
+    {
+        let system = SystemRepr::get_group(&conn, asset_group_id)?;
+        let mut broken = system.clone();
+        broken.services[0]
+            .output_addresses
+            .push("localhost:9999".to_string());
+        let request = test::TestRequest::post()
+            .uri("/sync/upload/")
+            .set_json(&broken)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = test::read_body(response).await;
+        let error: ErrorResponse = serde_json::from_slice(&body)?;
+        assert_eq!(error.code(), Some(ErrorCode::SyncFailed));
+        assert_eq!(SystemRepr::get_group(&conn, asset_group_id)?, system);
+    }
+
+    // Unknown asset groups.
+    {
+        let request = test::TestRequest::get()
+            .uri("/asset-groups/-1/system")
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     AssetGroup::delete(&conn, asset_group.asset_group_id)?;
     println!("Deleted asset group");
     Ok(())
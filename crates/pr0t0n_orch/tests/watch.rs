@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use actix::clock::delay_for;
+use actix_web::client::Client;
+use futures::{SinkExt, StreamExt};
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{AssetGroup, DbDelete, DbInsert, Event, EventKind, NewAssetGroup},
+    new_pool, PoolSettings, PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER,
+};
+
+use pr0t0n_orch::{
+    testing::{get_test_server, get_websocket_frame_data},
+    Error,
+};
+
+#[actix_rt::test]
+async fn test_watch() -> Result<(), Error> {
+    // Setup database connection and server.
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let server = get_test_server();
+
+    // Create a new adgroup for testing.
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id_string: String = asset_group.asset_group_id.to_string();
+
+    // Attach a watcher.
+    let (_response, mut watcher) = Client::default()
+        .ws(server.url("/ws/watch/"))
+        .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id_string.clone())
+        .connect()
+        .await
+        .unwrap();
+    delay_for(Duration::from_secs_f32(0.2)).await; // Let the watcher subscribe
+
+    // Attach and detach a client.
+    let addr = "localhost:1236";
+    let (_response, sock) = Client::default()
+        .ws(server.url("/ws/"))
+        .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id_string.clone())
+        .set_header(PR0T0N_CLIENT_ADDRESS_HEADER, addr)
+        .connect()
+        .await
+        .unwrap();
+    let mut stream = sock.take(1);
+    stream.next().await;
+    stream.close().await.unwrap();
+
+    // The watcher sees both transitions.
+    for expected in [
+        EventKind::Connected {
+            address: addr.to_string(),
+        },
+        EventKind::Disconnected {
+            address: addr.to_string(),
+            maintenance: None,
+        },
+    ] {
+        let msg = watcher.next().await;
+        let data = get_websocket_frame_data(msg.unwrap().unwrap()).unwrap();
+        let event: Event = serde_json::from_str(&data)?;
+        assert_eq!(event.asset_group_id, asset_group.asset_group_id);
+        assert_eq!(event.kind, expected);
+    }
+
+    // Clean up.
+    watcher.close().await.unwrap();
+    server.stop().await;
+    AssetGroup::delete(&conn, asset_group.asset_group_id)?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::time::{Duration, Instant};
+
+use actix::Actor;
+use pr0t0n_orch_db::{
+    get_conn,
+    models::{
+        AlertStatus, AssetGroup, DbDelete, DbInsert, DeadLetter, Event, EventKind, NewAssetGroup,
+    },
+    new_pool, PoolSettings,
+};
+use serde_json::json;
+
+use pr0t0n_orch::{
+    alerts::Alert,
+    notify::{
+        webhook::{sign, WebhookPayload, SIGNATURE_HEADER},
+        Dispatcher, Notify, WebhookNotifier,
+    },
+    settings::{WebhookFormat, WebhookSettings},
+    testing::WebhookSink,
+    Error,
+};
+
+fn webhook(sink: &WebhookSink) -> WebhookSettings {
+    WebhookSettings {
+        url: sink.url(),
+        retries: 1,
+        retry_backoff: 0,
+        ..Default::default()
+    }
+}
+
+#[actix_rt::test]
+async fn test_webhooks() -> Result<(), Error> {
+    let pool = new_pool(&PoolSettings::from_env());
+    let conn = get_conn(&pool)?;
+    let asset_group = NewAssetGroup {
+        name: "temp_asset_group",
+        description: "A test asset group",
+    }
+    .insert(&conn)?;
+    let asset_group_id = asset_group.asset_group_id;
+
+    // The on-call tool is busy at first, and the other is down for good.
+    let oncall = WebhookSink::start(1);
+    let chat = WebhookSink::start(0);
+    let down = WebhookSink::start(usize::MAX);
+    let oncall_settings = WebhookSettings {
+        secret: Some("hunter2".to_string()),
+        ..webhook(&oncall)
+    };
+    let chat_settings = WebhookSettings {
+        format: WebhookFormat::Chat,
+        ..webhook(&chat)
+    };
+    let down_settings = webhook(&down);
+    let notifier = Dispatcher::new(new_pool(&PoolSettings::from_env()))
+        .with_channel("oncall", WebhookNotifier::new(oncall_settings))
+        .with_channel("chat", WebhookNotifier::new(chat_settings))
+        .with_channel("down", WebhookNotifier::new(down_settings))
+        .start();
+
+    let alert = Alert {
+        alert_rule_id: 1,
+        asset_group_id,
+        trigger: "disconnect",
+        service_name: "camera".to_string(),
+        address: "localhost:5678".to_string(),
+        dedup_key: "localhost:5678/disconnect".to_string(),
+        status: AlertStatus::Firing,
+        sender: String::new(),
+        recipients: vec![],
+        channels: vec![
+            "oncall".to_string(),
+            "chat".to_string(),
+            "down".to_string(),
+            "missing".to_string(),
+        ],
+        message: "camera (localhost:5678) disconnected".to_string(),
+    };
+    notifier.send(Notify(alert.clone())).await.unwrap();
+
+    // The same signed payload is sent again after the first attempt fails.
+    let requests = oncall.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].body, requests[1].body);
+    let request = &requests[1];
+    assert_eq!(request.headers["content-type"], "application/json");
+    assert_eq!(
+        request.headers[&SIGNATURE_HEADER.to_lowercase()],
+        sign("hunter2", request.body.as_bytes())
+    );
+    let payload: WebhookPayload = serde_json::from_str(&request.body)?;
+    assert_eq!(
+        payload,
+        WebhookPayload {
+            alert_rule_id: 1,
+            asset_group_id,
+            asset_group: "temp_asset_group".to_string(),
+            service: "camera".to_string(),
+            address: "localhost:5678".to_string(),
+            event: "disconnect".to_string(),
+            key: "localhost:5678/disconnect".to_string(),
+            status: AlertStatus::Firing,
+            message: "camera (localhost:5678) disconnected".to_string(),
+        }
+    );
+
+    // Chat webhooks get a message, and no signature without a secret.
+    let requests = chat.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(!requests[0].headers.contains_key("x-pr0t0n-signature"));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&requests[0].body)?,
+        json!({ "text": "[pr0t0n] camera (localhost:5678) disconnected" })
+    );
+    assert_eq!(down.requests().len(), 2);
+
+    let logged: Vec<(String, u32, bool)> = Event::get_logged(&conn, asset_group_id)?
+        .into_iter()
+        .map(|event| match event.kind {
+            EventKind::NotificationSent {
+                channel, attempts, ..
+            } => (channel, attempts, true),
+            EventKind::NotificationFailed {
+                channel, attempts, ..
+            } => (channel, attempts, false),
+            kind => panic!("Expected a notification, got {:?}", kind),
+        })
+        .collect();
+    assert_eq!(
+        logged,
+        vec![
+            ("oncall".to_string(), 2, true),
+            ("chat".to_string(), 1, true),
+            ("down".to_string(), 2, false),
+            ("missing".to_string(), 0, false),
+        ]
+    );
+
+    // What could not be delivered is kept, as it would be sent again.
+    let dead_letters = DeadLetter::get_group(&conn, asset_group_id)?;
+    assert_eq!(dead_letters.len(), 2);
+    assert_eq!(dead_letters[0].channel, "down");
+    assert_eq!(dead_letters[0].attempts, 2);
+    assert_eq!(dead_letters[0].error, "WebhookStatus(503)");
+    assert_eq!(dead_letters[0].payload["service"], "camera");
+    assert_eq!(dead_letters[1].channel, "missing");
+    assert_eq!(dead_letters[1].payload["channels"][3], "missing");
+
+    // A channel waiting to retry does not hold up alerts sent through the others.
+    let slow = WebhookSink::start(usize::MAX);
+    let fast = WebhookSink::start(0);
+    let notifier = Dispatcher::new(new_pool(&PoolSettings::from_env()))
+        .with_channel(
+            "slow",
+            WebhookNotifier::new(WebhookSettings {
+                retry_backoff: 2,
+                ..webhook(&slow)
+            }),
+        )
+        .with_channel("fast", WebhookNotifier::new(webhook(&fast)))
+        .start();
+    let slow_sent = notifier.send(Notify(Alert {
+        channels: vec!["slow".to_string()],
+        ..alert.clone()
+    }));
+    let started = Instant::now();
+    notifier
+        .send(Notify(Alert {
+            channels: vec!["fast".to_string()],
+            ..alert
+        }))
+        .await
+        .unwrap();
+    assert_eq!(fast.requests().len(), 1);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    slow_sent.await.unwrap();
+    assert_eq!(slow.requests().len(), 2);
+
+    AssetGroup::delete(&conn, asset_group_id)?;
+    Ok(())
+}
@@ -7,7 +7,7 @@ use futures::{SinkExt, StreamExt};
 use pr0t0n_orch_db::{
     get_conn,
     models::{AssetGroup, DbDelete, DbInsert, HealthStatus, NewAssetGroup, Service},
-    new_pool,
+    new_pool, PoolSettings,
 };
 
 use pr0t0n_orch::{
@@ -19,7 +19,7 @@ use pr0t0n_orch_db::{PR0T0N_ASSET_GROUP_ID_HEADER, PR0T0N_CLIENT_ADDRESS_HEADER}
 #[actix_rt::test]
 async fn test_ws() -> Result<(), Error> {
     // Setup database connection and server.
-    let pool = new_pool();
+    let pool = new_pool(&PoolSettings::from_env());
     let conn = get_conn(&pool)?;
     let server = get_test_server();
 
@@ -0,0 +1,410 @@
+use actix::{io::SinkWrite, Actor, StreamHandler};
+use awc::{http::header, Client, ClientResponse};
+use bytes::Bytes;
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver},
+    Stream, StreamExt,
+};
+use pr0t0n_orch_client::WatchClient;
+use pr0t0n_orch_db::{
+    models::{
+        revision_etag, AssetGroup, AssetGroupChanges, AssetGroupSummary, ConfigRepr,
+        ConfigUsageRepr, MaintenanceWindow, MaintenanceWindowRepr, PendingService,
+        ReconciliationReport, ServiceApproval, ServiceDetail, ServiceLink, ServicePage,
+        ServiceSummary, SystemRepr, SystemRevision, MAX_PAGE_SIZE,
+    },
+    PR0T0N_ASSET_GROUP_ID_HEADER,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Error;
+
+/// Largest response body the CLI will read.
+const MAX_BODY_SIZE: usize = 64 * 1024 * 1024;
+
+/// Thin client for the orchestrator HTTP API.
+pub struct ApiClient {
+    server: String,
+    client: Client,
+}
+impl ApiClient {
+    pub fn new(server: &str, token: Option<&str>) -> Self {
+        let mut builder = Client::builder();
+        if let Some(token) = token {
+            builder = builder.bearer_auth(token);
+        }
+        Self {
+            server: server.trim_end_matches('/').to_string(),
+            client: builder.finish(),
+        }
+    }
+
+    fn url(&self, route: &str) -> String {
+        format!("{}{}", self.server, route)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, route: &str) -> Result<T, Error> {
+        let response = self.client.get(self.url(route)).send().await?;
+        read_json(response).await
+    }
+
+    async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        route: &str,
+        body: &B,
+    ) -> Result<T, Error> {
+        let response = self.client.post(self.url(route)).send_json(body).await?;
+        read_json(response).await
+    }
+
+    async fn put_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        route: &str,
+        body: &B,
+    ) -> Result<T, Error> {
+        let response = self.client.put(self.url(route)).send_json(body).await?;
+        read_json(response).await
+    }
+
+    async fn patch_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        route: &str,
+        body: &B,
+    ) -> Result<T, Error> {
+        let response = self.client.patch(self.url(route)).send_json(body).await?;
+        read_json(response).await
+    }
+
+    async fn delete(&self, route: &str) -> Result<(), Error> {
+        let response = self.client.delete(self.url(route)).send().await?;
+        read_body(response).await?;
+        Ok(())
+    }
+
+    /// List the configs of an asset group with the services using each.
+    pub async fn list_configs(&self, asset_group_id: i32) -> Result<Vec<ConfigUsageRepr>, Error> {
+        self.get_json(&format!("/asset-groups/{}/configs/", asset_group_id))
+            .await
+    }
+
+    pub async fn get_config(&self, asset_group_id: i32, name: &str) -> Result<ConfigRepr, Error> {
+        self.get_json(&config_route(asset_group_id, name)).await
+    }
+
+    pub async fn create_config(
+        &self,
+        asset_group_id: i32,
+        config: &ConfigRepr,
+    ) -> Result<ConfigRepr, Error> {
+        self.post_json(
+            &format!("/asset-groups/{}/configs/", asset_group_id),
+            config,
+        )
+        .await
+    }
+
+    /// Replace the config called `name`, which may be renamed by `config`.
+    pub async fn update_config(
+        &self,
+        asset_group_id: i32,
+        name: &str,
+        config: &ConfigRepr,
+    ) -> Result<ConfigRepr, Error> {
+        self.put_json(&config_route(asset_group_id, name), config)
+            .await
+    }
+
+    /// Delete a config. Unless `force` is set, the server refuses if services use it.
+    pub async fn delete_config(
+        &self,
+        asset_group_id: i32,
+        name: &str,
+        force: bool,
+    ) -> Result<(), Error> {
+        let mut route = config_route(asset_group_id, name);
+        if force {
+            route.push_str("?force=true");
+        }
+        self.delete(&route).await
+    }
+
+    /// List the services using a config.
+    pub async fn config_used_by(
+        &self,
+        asset_group_id: i32,
+        name: &str,
+    ) -> Result<Vec<ServiceLink>, Error> {
+        self.get_json(&format!("{}/used-by", config_route(asset_group_id, name)))
+            .await
+    }
+
+    /// List the maintenance windows of an asset group, including ended ones if `all` is set.
+    pub async fn list_maintenance_windows(
+        &self,
+        asset_group_id: i32,
+        all: bool,
+    ) -> Result<Vec<MaintenanceWindow>, Error> {
+        let mut route = format!("/asset-groups/{}/maintenance-windows", asset_group_id);
+        if all {
+            route.push_str("?all=true");
+        }
+        self.get_json(&route).await
+    }
+
+    pub async fn create_maintenance_window(
+        &self,
+        asset_group_id: i32,
+        window: &MaintenanceWindowRepr,
+    ) -> Result<MaintenanceWindow, Error> {
+        self.post_json(
+            &format!("/asset-groups/{}/maintenance-windows", asset_group_id),
+            window,
+        )
+        .await
+    }
+
+    /// End a maintenance window now.
+    pub async fn expire_maintenance_window(
+        &self,
+        asset_group_id: i32,
+        maintenance_window_id: i32,
+    ) -> Result<MaintenanceWindow, Error> {
+        self.post_json(
+            &format!(
+                "/asset-groups/{}/maintenance-windows/{}/expire",
+                asset_group_id, maintenance_window_id
+            ),
+            &(),
+        )
+        .await
+    }
+
+    pub async fn get_asset_group(&self, asset_group_id: i32) -> Result<AssetGroupSummary, Error> {
+        self.get_json(&format!("/asset-groups/{}", asset_group_id))
+            .await
+    }
+
+    /// Update the fields of an asset group that are set in `changes`.
+    pub async fn update_asset_group(
+        &self,
+        asset_group_id: i32,
+        changes: &AssetGroupChanges,
+    ) -> Result<AssetGroup, Error> {
+        self.patch_json(&format!("/asset-groups/{}", asset_group_id), changes)
+            .await
+    }
+
+    /// List the addresses waiting to be approved as services of an asset group.
+    pub async fn list_pending_services(
+        &self,
+        asset_group_id: i32,
+    ) -> Result<Vec<PendingService>, Error> {
+        self.get_json(&format!(
+            "/asset-groups/{}/pending-services",
+            asset_group_id
+        ))
+        .await
+    }
+
+    pub async fn approve_pending_service(
+        &self,
+        asset_group_id: i32,
+        pending_service_id: i32,
+        approval: &ServiceApproval,
+    ) -> Result<ServiceDetail, Error> {
+        self.post_json(
+            &format!(
+                "/asset-groups/{}/pending-services/{}/approve",
+                asset_group_id, pending_service_id
+            ),
+            approval,
+        )
+        .await
+    }
+
+    pub async fn reject_pending_service(
+        &self,
+        asset_group_id: i32,
+        pending_service_id: i32,
+    ) -> Result<(), Error> {
+        self.delete(&format!(
+            "/asset-groups/{}/pending-services/{}",
+            asset_group_id, pending_service_id
+        ))
+        .await
+    }
+
+    /// Compare the services declared in an asset group with the ones connected.
+    pub async fn get_reconciliation(
+        &self,
+        asset_group_id: i32,
+    ) -> Result<ReconciliationReport, Error> {
+        self.get_json(&format!("/asset-groups/{}/reconciliation", asset_group_id))
+            .await
+    }
+
+    /// List every service of an asset group with its health, one page after another.
+    pub async fn list_services(&self, asset_group_id: i32) -> Result<Vec<ServiceSummary>, Error> {
+        let url = self.url(&format!("/asset-groups/{}/services", asset_group_id));
+        let limit = MAX_PAGE_SIZE.to_string();
+        let mut services = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut query = vec![("limit", limit.as_str())];
+            if let Some(cursor) = &cursor {
+                query.push(("cursor", cursor));
+            }
+            let response = self
+                .client
+                .get(&url)
+                .query(&query)
+                .map_err(|err| Error::InvalidArgument(err.to_string()))?
+                .send()
+                .await?;
+            let page: ServicePage = read_json(response).await?;
+            services.extend(page.services);
+            cursor = match page.next_cursor {
+                Some(next_cursor) => Some(next_cursor),
+                None => return Ok(services),
+            };
+        }
+    }
+
+    /// Download the full system representation for an asset group.
+    pub async fn download_system(&self, asset_group_id: i32) -> Result<SystemRepr, Error> {
+        self.get_json(&format!("/asset-groups/{}/system", asset_group_id))
+            .await
+    }
+
+    /// Patch the system of an asset group with a JSON Patch, or a JSON Merge Patch if `merge` is
+    /// set. With a revision, the server refuses with 409 unless the asset group is still at it.
+    pub async fn patch_system(
+        &self,
+        asset_group_id: i32,
+        patch: &serde_json::Value,
+        merge: bool,
+        revision: Option<i64>,
+    ) -> Result<SystemRevision, Error> {
+        let content_type = if merge {
+            "application/merge-patch+json"
+        } else {
+            "application/json-patch+json"
+        };
+        let mut request = self
+            .client
+            .patch(self.url(&format!("/asset-groups/{}/system", asset_group_id)))
+            .content_type(content_type);
+        if let Some(revision) = revision {
+            request = request.header(header::IF_MATCH, revision_etag(asset_group_id, revision));
+        }
+        let response = request.send_body(serde_json::to_vec(patch)?).await?;
+        read_json(response).await
+    }
+
+    /// Render the service graph of an asset group.
+    pub async fn get_graph(&self, asset_group_id: i32, format: &str) -> Result<String, Error> {
+        let response = self
+            .client
+            .get(self.url(&format!("/asset-groups/{}/graph", asset_group_id)))
+            .query(&[("format", format)])
+            .map_err(|err| Error::InvalidArgument(err.to_string()))?
+            .send()
+            .await?;
+        let body = read_body(response).await?;
+        Ok(String::from_utf8_lossy(&body).to_string())
+    }
+
+    /// Open a watcher session, returning a stream of JSON events for the asset group.
+    pub async fn watch(&self, asset_group_id: i32) -> Result<UnboundedReceiver<String>, Error> {
+        let (_response, framed) = self
+            .client
+            .ws(self.url("/ws/watch/"))
+            .set_header(PR0T0N_ASSET_GROUP_ID_HEADER, asset_group_id.to_string())
+            .connect()
+            .await
+            .map_err(|err| Error::RequestError(err.to_string()))?;
+
+        let (sink, stream) = framed.split();
+        let (tx, rx) = mpsc::unbounded();
+        WatchClient::create(|ctx| {
+            WatchClient::add_stream(stream, ctx);
+            WatchClient {
+                sink: SinkWrite::new(sink, ctx),
+                tx,
+            }
+        });
+        Ok(rx)
+    }
+
+    /// Upload a system representation, making the asset group match it. If the system has a
+    /// revision, the server refuses with 409 unless the asset group is still at that revision.
+    pub async fn upload_system(&self, system_repr: &SystemRepr) -> Result<SystemRevision, Error> {
+        let mut request = self.client.post(self.url("/sync/upload/"));
+        if let Some(revision) = system_repr.revision {
+            request = request.header(
+                header::IF_MATCH,
+                revision_etag(system_repr.asset_group_id, revision),
+            );
+        }
+        let response = request.send_json(system_repr).await?;
+        read_json(response).await
+    }
+}
+
+fn config_route(asset_group_id: i32, name: &str) -> String {
+    format!(
+        "/asset-groups/{}/configs/{}",
+        asset_group_id,
+        encode_path_segment(name)
+    )
+}
+
+/// Percent-encode everything but unreserved characters so names are safe in a URL path.
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Read the body of a response, failing on non-success status codes.
+async fn read_body<S>(mut response: ClientResponse<S>) -> Result<Bytes, Error>
+where
+    S: Stream<Item = Result<Bytes, awc::error::PayloadError>> + Unpin,
+{
+    let body = response.body().limit(MAX_BODY_SIZE).await?;
+    if !response.status().is_success() {
+        return Err(Error::ResponseError {
+            status: response.status().as_u16(),
+            body: String::from_utf8_lossy(&body).to_string(),
+        });
+    }
+    Ok(body)
+}
+
+/// Read and deserialize the JSON body of a response.
+async fn read_json<S, T>(response: ClientResponse<S>) -> Result<T, Error>
+where
+    S: Stream<Item = Result<Bytes, awc::error::PayloadError>> + Unpin,
+    T: DeserializeOwned,
+{
+    let body = read_body(response).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_path_segment() {
+        assert_eq!(encode_path_segment("Config_1.v2"), "Config_1.v2");
+        assert_eq!(encode_path_segment("my config/é"), "my%20config%2F%C3%A9");
+    }
+}
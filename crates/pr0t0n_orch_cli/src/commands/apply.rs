@@ -0,0 +1,194 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use pr0t0n_orch_db::models::{ErrorCode, ServiceRepr, SystemRepr};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{commands::Context, format::Format, prompt::confirm, Error};
+
+/// Makes an asset group match a system file.
+#[derive(StructOpt, Debug)]
+pub struct ApplyOpt {
+    /// System file to apply, as produced by `porch export`.
+    #[structopt(long, parse(from_os_str))]
+    pub path: PathBuf,
+
+    /// Input format, `yaml` or `json`. Guessed from `--path` if omitted.
+    #[structopt(long)]
+    pub format: Option<Format>,
+
+    /// Apply to this asset group instead of the one named in the file.
+    #[structopt(long)]
+    pub group: Option<i32>,
+}
+impl ApplyOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let format = self
+            .format
+            .or_else(|| Format::from_path(&self.path))
+            .unwrap_or_default();
+
+        let contents = std::fs::read_to_string(&self.path)?;
+        let mut system_repr: SystemRepr = format.deserialize(&contents)?;
+        if let Some(asset_group_id) = self.group {
+            if asset_group_id != system_repr.asset_group_id {
+                // The revision in the file belongs to a different asset group.
+                system_repr.asset_group_id = asset_group_id;
+                system_repr.revision = None;
+            }
+        }
+
+        loop {
+            match ctx.api.upload_system(&system_repr).await {
+                Ok(uploaded) => {
+                    eprintln!(
+                        "Applied {} services and {} configs to asset group {} (now at revision {})",
+                        system_repr.services.len(),
+                        system_repr.configs.len(),
+                        uploaded.asset_group_id,
+                        uploaded.revision
+                    );
+                    return Ok(());
+                }
+                Err(err) if err.code() == Some(ErrorCode::RevisionConflict) => {
+                    eprintln!("error: {}", err);
+                    let current = ctx.api.download_system(system_repr.asset_group_id).await?;
+                    let changes = describe_changes(&current, &system_repr)?;
+                    if changes.is_empty() {
+                        eprintln!("The asset group already matches {}.", self.path.display());
+                        return Ok(());
+                    }
+                    eprintln!(
+                        "Applying {} to the current system would make these changes:",
+                        self.path.display()
+                    );
+                    for change in &changes {
+                        eprintln!("  {}", change);
+                    }
+                    if !confirm("Apply anyway?")? {
+                        return Err(err);
+                    }
+                    system_repr.revision = current.revision;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Describe how applying `mine` would change `current`: `+` for additions, `-` for removals and
+/// `~` for changes, one line per service or config.
+fn describe_changes(current: &SystemRepr, mine: &SystemRepr) -> Result<Vec<String>, Error> {
+    // Health is only read for new services, so it does not count as a change.
+    let declared = |services: &[ServiceRepr]| -> Vec<ServiceRepr> {
+        services
+            .iter()
+            .map(|service| ServiceRepr {
+                health_status: None,
+                ..service.clone()
+            })
+            .collect()
+    };
+    let mut changes = Vec::new();
+    diff_by_key(
+        &mut changes,
+        "service",
+        &declared(&current.services),
+        &declared(&mine.services),
+        |service| &service.address,
+    )?;
+    diff_by_key(
+        &mut changes,
+        "config",
+        &current.configs,
+        &mine.configs,
+        |config| &config.name,
+    )?;
+    Ok(changes)
+}
+
+/// Compare items by their serialized form, so fields that are not serialized, like `config_id`,
+/// are ignored.
+fn diff_by_key<T: Serialize>(
+    changes: &mut Vec<String>,
+    kind: &str,
+    current: &[T],
+    mine: &[T],
+    key: impl Fn(&T) -> &str,
+) -> Result<(), Error> {
+    let to_map = |items: &[T]| -> Result<BTreeMap<String, serde_json::Value>, Error> {
+        items
+            .iter()
+            .map(|item| Ok((key(item).to_string(), serde_json::to_value(item)?)))
+            .collect()
+    };
+    let current = to_map(current)?;
+    let mine = to_map(mine)?;
+    for (key, value) in &mine {
+        match current.get(key) {
+            None => changes.push(format!("+ {} {}", kind, key)),
+            Some(existing) if existing != value => changes.push(format!("~ {} {}", kind, key)),
+            Some(_) => {}
+        }
+    }
+    for key in current.keys() {
+        if !mine.contains_key(key) {
+            changes.push(format!("- {} {}", kind, key));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pr0t0n_orch_db::models::{ConfigRepr, HealthStatus};
+
+    fn service(address: &str, name: &str) -> ServiceRepr {
+        ServiceRepr {
+            address: address.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_describe_changes() {
+        let current = SystemRepr {
+            asset_group_id: 1,
+            revision: Some(4),
+            services: vec![
+                service("a:1", "a"),
+                ServiceRepr {
+                    health_status: Some(HealthStatus::Disconnected),
+                    ..service("b:1", "b")
+                },
+            ],
+            configs: vec![ConfigRepr {
+                name: "shared".to_string(),
+                json_config_str: "{}".to_string(),
+                ..Default::default()
+            }],
+        };
+        let mine = SystemRepr {
+            asset_group_id: 1,
+            revision: Some(3),
+            services: vec![service("a:1", "renamed"), service("c:1", "c")],
+            configs: vec![ConfigRepr {
+                name: "shared".to_string(),
+                ..Default::default()
+            }],
+        };
+        assert_eq!(
+            describe_changes(&current, &mine).unwrap(),
+            vec!["~ service a:1", "+ service c:1", "- service b:1"]
+        );
+        assert!(describe_changes(&current, &current).unwrap().is_empty());
+
+        // Reported health is not a change.
+        let mut healthy = current.clone();
+        healthy.services[0].health_status = Some(HealthStatus::Healthy);
+        healthy.services[1].health_status = None;
+        assert!(describe_changes(&current, &healthy).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use pr0t0n_orch_db::models::{ConfigRepr, ErrorCode};
+use structopt::StructOpt;
+
+use crate::{api::ApiClient, commands::Context, prompt::confirm, Error};
+
+/// Manages named configs.
+#[derive(StructOpt, Debug)]
+pub enum ConfigOpt {
+    /// Creates a config from a JSON file with `name`, `description` and `json_config`.
+    Create {
+        /// Asset group owning the config. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        #[structopt(long, parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Prints a config as JSON.
+    Show {
+        /// Asset group owning the config. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        name: String,
+    },
+    /// Opens a config in `$EDITOR` and saves it once it is valid.
+    Edit {
+        /// Asset group owning the config. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        name: String,
+    },
+    /// Deletes a config, asking first if services use it.
+    Delete {
+        /// Asset group owning the config. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        /// Delete without asking, even if services use the config.
+        #[structopt(long)]
+        force: bool,
+
+        name: String,
+    },
+    /// Lists the services using a config.
+    UsedBy {
+        /// Asset group owning the config. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        name: String,
+    },
+    /// Lists configs and the services using each.
+    List {
+        /// Asset group owning the configs. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+    },
+}
+impl ConfigOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let api = &ctx.api;
+        match self {
+            Self::Create { group, path } => {
+                let config = parse_config(&std::fs::read_to_string(path)?)?;
+                let created = api.create_config(ctx.group(*group)?, &config).await?;
+                eprintln!("Created config '{}'", created.name);
+            }
+            Self::Show { group, name } => {
+                let config = api.get_config(ctx.group(*group)?, name).await?;
+                println!("{}", serde_json::to_string_pretty(&config)?);
+            }
+            Self::Edit { group, name } => edit(api, ctx.group(*group)?, name).await?,
+            Self::Delete { group, force, name } => {
+                let group = ctx.group(*group)?;
+                match api.delete_config(group, name, *force).await {
+                    Err(err) if err.code() == Some(ErrorCode::Conflict) => {
+                        eprintln!("{}", err);
+                        if !confirm("Delete it anyway?")? {
+                            return Ok(());
+                        }
+                        api.delete_config(group, name, true).await?;
+                    }
+                    result => result?,
+                }
+                eprintln!("Deleted config '{}'", name);
+            }
+            Self::UsedBy { group, name } => {
+                for service in api.config_used_by(ctx.group(*group)?, name).await? {
+                    println!(
+                        "{}\t{}\t{:?}",
+                        service.address, service.name, service.health_status
+                    );
+                }
+            }
+            Self::List { group } => {
+                let configs = api.list_configs(ctx.group(*group)?).await?;
+                let name_width = configs
+                    .iter()
+                    .map(|usage| usage.config.name.len())
+                    .chain(Some("NAME".len()))
+                    .max()
+                    .unwrap_or_default();
+                println!("{:w$}  USED BY", "NAME", w = name_width);
+                for usage in configs {
+                    let used_by = if usage.used_by.is_empty() {
+                        "-".to_string()
+                    } else {
+                        usage.used_by.join(", ")
+                    };
+                    println!("{:w$}  {}", usage.config.name, used_by, w = name_width);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse and validate a config the same way the server will.
+fn parse_config(contents: &str) -> Result<ConfigRepr, Error> {
+    let config: ConfigRepr = serde_json::from_str(contents)?;
+    if config.name.trim().is_empty() {
+        return Err(Error::InvalidArgument(
+            "Config name must not be empty".to_string(),
+        ));
+    }
+    if !config.json_config.is_object() {
+        return Err(Error::InvalidArgument(
+            "`json_config` must be a JSON object".to_string(),
+        ));
+    }
+    Ok(config)
+}
+
+/// Run `$VISUAL` or `$EDITOR` on a file and wait for it to exit.
+fn open_editor(path: &Path) -> Result<(), Error> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| Error::InvalidArgument("$EDITOR is empty".to_string()))?;
+    let status = Command::new(program).args(words).arg(path).status()?;
+    if !status.success() {
+        return Err(Error::InvalidArgument(format!(
+            "Editor exited with {}",
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// A file in the temp directory that only this user can read, removed when dropped.
+struct TempFile {
+    path: PathBuf,
+}
+impl TempFile {
+    /// Create a new file holding `contents`. Anything already at the path is left alone rather
+    /// than followed or replaced, and another name is tried.
+    fn create(contents: &str) -> Result<Self, Error> {
+        let mut attempts = 0;
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.subsec_nanos())
+                .unwrap_or_default();
+            let path = std::env::temp_dir().join(format!(
+                "porch-config-{}-{}.json",
+                std::process::id(),
+                nanos
+            ));
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            match options.open(&path) {
+                Ok(mut file) => {
+                    let temp = Self { path };
+                    file.write_all(contents.as_bytes())?;
+                    return Ok(temp);
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists && attempts < 10 => {
+                    attempts += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+async fn edit(api: &ApiClient, group: i32, name: &str) -> Result<(), Error> {
+    let original = api.get_config(group, name).await?;
+    let file = TempFile::create(&serde_json::to_string_pretty(&original)?)?;
+    let path = &file.path;
+
+    loop {
+        open_editor(path)?;
+        let saved = match parse_config(&std::fs::read_to_string(path)?) {
+            Ok(edited) if edited == original => {
+                eprintln!("No changes.");
+                return Ok(());
+            }
+            Ok(edited) => api.update_config(group, name, &edited).await,
+            Err(err) => Err(err),
+        };
+        match saved {
+            Ok(updated) => {
+                eprintln!("Updated config '{}'", updated.name);
+                return Ok(());
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                if !confirm("Re-open the editor?")? {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config =
+            parse_config(r#"{ "name": "a", "description": "", "json_config": { "k": 1 } }"#)
+                .unwrap();
+        assert_eq!(config.name, "a");
+        assert!(parse_config(r#"{ "name": "", "description": "", "json_config": {} }"#).is_err());
+        assert!(parse_config(r#"{ "name": "a", "description": "", "json_config": 1 }"#).is_err());
+        assert!(parse_config("{").is_err());
+    }
+
+    #[test]
+    fn test_temp_file() {
+        let file = TempFile::create("{}").unwrap();
+        let other = TempFile::create("{}").unwrap();
+        assert_ne!(file.path, other.path);
+        assert_eq!(std::fs::read_to_string(&file.path).unwrap(), "{}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&file.path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let path = file.path.clone();
+        drop(file);
+        assert!(!path.exists());
+    }
+}
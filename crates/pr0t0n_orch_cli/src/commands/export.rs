@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use crate::{commands::Context, format::Format, Error};
+
+/// Exports an asset group in the format consumed by `porch apply`.
+#[derive(StructOpt, Debug)]
+pub struct ExportOpt {
+    /// Asset group to export. Defaults to the profile's asset group.
+    #[structopt(long)]
+    pub group: Option<i32>,
+
+    /// Output format, `yaml` or `json`. Guessed from `--output` if omitted.
+    #[structopt(long)]
+    pub format: Option<Format>,
+
+    /// Write to this file instead of stdout.
+    #[structopt(long, short, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+}
+impl ExportOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let group = ctx.group(self.group)?;
+        let format = self
+            .format
+            .or_else(|| self.output.as_deref().and_then(Format::from_path))
+            .unwrap_or_default();
+
+        let system_repr = ctx.api.download_system(group).await?;
+        let serialized = format.serialize(&system_repr)?;
+        match &self.output {
+            Some(path) => {
+                std::fs::write(path, serialized)?;
+                eprintln!(
+                    "Exported {} services and {} configs to {}",
+                    system_repr.services.len(),
+                    system_repr.configs.len(),
+                    path.display()
+                );
+            }
+            None => print!("{}", serialized),
+        }
+        Ok(())
+    }
+}
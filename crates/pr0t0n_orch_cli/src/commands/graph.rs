@@ -0,0 +1,22 @@
+use structopt::StructOpt;
+
+use crate::{commands::Context, Error};
+
+/// Renders the service topology of an asset group.
+#[derive(StructOpt, Debug)]
+pub struct GraphOpt {
+    /// Asset group to render. Defaults to the profile's asset group.
+    #[structopt(long)]
+    pub group: Option<i32>,
+
+    /// Output format.
+    #[structopt(long, default_value = "ascii", possible_values = &["dot", "mermaid", "ascii"])]
+    pub format: String,
+}
+impl GraphOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let group = ctx.group(self.group)?;
+        print!("{}", ctx.api.get_graph(group, &self.format).await?);
+        Ok(())
+    }
+}
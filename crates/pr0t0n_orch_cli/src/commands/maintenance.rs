@@ -0,0 +1,214 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use pr0t0n_orch_db::models::{MaintenanceWindow, MaintenanceWindowRepr, Recurrence};
+use structopt::StructOpt;
+
+use crate::{commands::Context, Error};
+
+/// Manages maintenance windows, during which alerts are silenced.
+#[derive(StructOpt, Debug)]
+pub enum MaintenanceOpt {
+    /// Creates a maintenance window for an asset group, a service or the services with a label.
+    Create {
+        /// Asset group to create the window in. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        /// Only cover the service with this address.
+        #[structopt(long, conflicts_with = "label")]
+        address: Option<String>,
+
+        /// Only cover the services with this `key=value` label.
+        #[structopt(long)]
+        label: Option<String>,
+
+        /// When the window starts, in RFC 3339, e.g. `2021-12-20T09:00:00+01:00`. Defaults to
+        /// now.
+        #[structopt(long)]
+        start: Option<DateTime<Utc>>,
+
+        /// When the window ends, in RFC 3339.
+        #[structopt(long, required_unless = "duration", conflicts_with = "duration")]
+        end: Option<DateTime<Utc>>,
+
+        /// How long the window lasts, e.g. `90m`, `2h` or `1d`.
+        #[structopt(long, parse(try_from_str = parse_duration))]
+        duration: Option<Duration>,
+
+        /// Repeat the window `daily` or `weekly`.
+        #[structopt(long, parse(try_from_str = parse_recurrence), default_value = "once")]
+        recurrence: Recurrence,
+
+        /// Stop repeating the window at this time, in RFC 3339.
+        #[structopt(long)]
+        until: Option<DateTime<Utc>>,
+
+        /// Why the window is needed, e.g. `Servicing the arm`.
+        #[structopt(long, default_value = "")]
+        reason: String,
+    },
+    /// Lists the maintenance windows that have not ended.
+    List {
+        /// Asset group of the windows. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        /// Include windows that have ended or were expired.
+        #[structopt(long)]
+        all: bool,
+    },
+    /// Ends a maintenance window now, along with any later occurrences.
+    Expire {
+        /// Asset group of the window. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        id: i32,
+    },
+}
+impl MaintenanceOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let api = &ctx.api;
+        match self {
+            Self::Create {
+                group,
+                address,
+                label,
+                start,
+                end,
+                duration,
+                recurrence,
+                until,
+                reason,
+            } => {
+                let starts_at = start.unwrap_or_else(Utc::now);
+                let ends_at = match (end, duration) {
+                    (Some(end), _) => *end,
+                    (None, Some(duration)) => starts_at + *duration,
+                    (None, None) => {
+                        return Err(Error::InvalidArgument(
+                            "Pass --end or --duration".to_string(),
+                        ))
+                    }
+                };
+                let window = MaintenanceWindowRepr {
+                    address: address.clone(),
+                    label: label.clone(),
+                    reason: reason.clone(),
+                    starts_at,
+                    ends_at,
+                    recurrence: *recurrence,
+                    until: *until,
+                };
+                let created = api
+                    .create_maintenance_window(ctx.group(*group)?, &window)
+                    .await?;
+                eprintln!(
+                    "Created maintenance window {}",
+                    created.maintenance_window_id
+                );
+            }
+            Self::List { group, all } => {
+                let windows = api
+                    .list_maintenance_windows(ctx.group(*group)?, *all)
+                    .await?;
+                let now = Utc::now();
+                println!("ID\tSCOPE\tWHEN\tRECURRENCE\tREASON");
+                for window in windows {
+                    println!(
+                        "{}\t{}\t{}\t{:?}\t{}",
+                        window.maintenance_window_id,
+                        scope(&window),
+                        when(&window, now),
+                        window.recurrence,
+                        window.reason
+                    );
+                }
+            }
+            Self::Expire { group, id } => {
+                api.expire_maintenance_window(ctx.group(*group)?, *id)
+                    .await?;
+                eprintln!("Expired maintenance window {}", id);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn scope(window: &MaintenanceWindow) -> String {
+    match (&window.address, &window.label) {
+        (Some(address), _) => address.clone(),
+        (None, Some(label)) => label.clone(),
+        (None, None) => "*".to_string(),
+    }
+}
+
+/// The current or next occurrence of a window in local time, or that it ended.
+fn when(window: &MaintenanceWindow, now: DateTime<Utc>) -> String {
+    let format = |at: DateTime<Utc>| at.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+    match window.occurrence(now) {
+        Some((starts_at, ends_at)) if starts_at <= now => {
+            format!("active until {}", format(ends_at))
+        }
+        Some((starts_at, ends_at)) => format!("{} - {}", format(starts_at), format(ends_at)),
+        None => "ended".to_string(),
+    }
+}
+
+fn parse_recurrence(s: &str) -> Result<Recurrence, Error> {
+    match s.to_lowercase().as_str() {
+        "once" => Ok(Recurrence::Once),
+        "daily" => Ok(Recurrence::Daily),
+        "weekly" => Ok(Recurrence::Weekly),
+        _ => Err(Error::InvalidArgument(format!(
+            "Unknown recurrence '{}', expected 'once', 'daily' or 'weekly'",
+            s
+        ))),
+    }
+}
+
+/// Parse a duration like `90m`: a whole number followed by `s`, `m`, `h`, `d` or `w`.
+fn parse_duration(s: &str) -> Result<Duration, Error> {
+    let invalid = || {
+        Error::InvalidArgument(format!(
+            "Invalid duration '{}', expected e.g. '90m', '2h' or '1d'",
+            s
+        ))
+    };
+    let unit = s.chars().last().ok_or_else(invalid)?;
+    let count: i64 = s[..s.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    if count <= 0 {
+        return Err(invalid());
+    }
+    match unit {
+        's' => Ok(Duration::seconds(count)),
+        'm' => Ok(Duration::minutes(count)),
+        'h' => Ok(Duration::hours(count)),
+        'd' => Ok(Duration::days(count)),
+        'w' => Ok(Duration::weeks(count)),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::hours(2));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::weeks(1));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("0h").is_err());
+        assert!(parse_duration("2y").is_err());
+    }
+
+    #[test]
+    fn test_parse_recurrence() {
+        assert_eq!(parse_recurrence("Weekly").unwrap(), Recurrence::Weekly);
+        assert!(parse_recurrence("monthly").is_err());
+    }
+}
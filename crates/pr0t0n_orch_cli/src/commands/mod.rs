@@ -0,0 +1,35 @@
+use crate::{api::ApiClient, profile::Profile, Error};
+
+pub mod apply;
+pub mod config;
+pub mod export;
+pub mod graph;
+pub mod maintenance;
+pub mod patch;
+pub mod profile;
+pub mod reconcile;
+pub mod registration;
+pub mod watch;
+
+/// What commands need to talk to the orchestrator, resolved from flags and the active profile.
+pub struct Context {
+    pub api: ApiClient,
+    pub profile: Profile,
+}
+impl Context {
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            api: ApiClient::new(profile.server(), profile.token.as_deref()),
+            profile,
+        }
+    }
+
+    /// The asset group given by `--group`, falling back to the profile's.
+    pub fn group(&self, group: Option<i32>) -> Result<i32, Error> {
+        group.or(self.profile.asset_group_id).ok_or_else(|| {
+            Error::InvalidArgument(
+                "No asset group given. Pass --group or set one in the profile.".to_string(),
+            )
+        })
+    }
+}
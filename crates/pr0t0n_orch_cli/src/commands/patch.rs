@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use crate::{commands::Context, Error};
+
+/// Applies a JSON Patch or JSON Merge Patch to the system of an asset group.
+#[derive(StructOpt, Debug)]
+pub struct PatchOpt {
+    /// Asset group to patch. Defaults to the profile's asset group.
+    #[structopt(long)]
+    pub group: Option<i32>,
+
+    /// Patch document. Paths refer to the shape written by `porch export --format json`.
+    #[structopt(long, parse(from_os_str))]
+    pub path: PathBuf,
+
+    /// Treat the document as a JSON Merge Patch (RFC 7396) instead of a JSON Patch (RFC 6902).
+    #[structopt(long)]
+    pub merge: bool,
+
+    /// Only patch if the asset group is still at this revision.
+    #[structopt(long)]
+    pub revision: Option<i64>,
+}
+impl PatchOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let group = ctx.group(self.group)?;
+        let document: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&self.path)?)?;
+        let patched = ctx
+            .api
+            .patch_system(group, &document, self.merge, self.revision)
+            .await?;
+        eprintln!(
+            "Patched asset group {} (now at revision {})",
+            patched.asset_group_id, patched.revision
+        );
+        Ok(())
+    }
+}
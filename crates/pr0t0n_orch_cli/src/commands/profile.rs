@@ -0,0 +1,83 @@
+use structopt::StructOpt;
+
+use crate::{profile::Profiles, Error};
+
+/// Manages connection profiles in `~/.config/porch/profiles.toml`.
+#[derive(StructOpt, Debug)]
+pub enum ProfileOpt {
+    /// Makes a profile the default for future commands.
+    Use { name: String },
+    /// Lists profiles, marking the current one with `*`.
+    List,
+    /// Creates a profile or updates the given fields of an existing one.
+    Set {
+        name: String,
+
+        /// URL of the orchestrator server.
+        #[structopt(long)]
+        server: Option<String>,
+
+        /// Default asset group for commands.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        /// Bearer token sent with every request.
+        #[structopt(long)]
+        token: Option<String>,
+    },
+}
+impl ProfileOpt {
+    pub fn run(&self) -> Result<(), Error> {
+        let mut profiles = Profiles::load()?;
+        match self {
+            Self::Use { name } => {
+                if !profiles.profiles.contains_key(name) {
+                    return Err(Error::InvalidArgument(format!(
+                        "Unknown profile '{}'",
+                        name
+                    )));
+                }
+                profiles.current = Some(name.clone());
+                profiles.save()?;
+                eprintln!("Using profile '{}'", name);
+            }
+            Self::List => {
+                for (name, profile) in &profiles.profiles {
+                    let marker = if profiles.current.as_ref() == Some(name) {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    let group = profile
+                        .asset_group_id
+                        .map(|id| format!(" (asset group {})", id))
+                        .unwrap_or_default();
+                    println!("{} {}  {}{}", marker, name, profile.server(), group);
+                }
+            }
+            Self::Set {
+                name,
+                server,
+                group,
+                token,
+            } => {
+                let profile = profiles.profiles.entry(name.clone()).or_default();
+                if server.is_some() {
+                    profile.server = server.clone();
+                }
+                if group.is_some() {
+                    profile.asset_group_id = *group;
+                }
+                if token.is_some() {
+                    profile.token = token.clone();
+                }
+                if profiles.current.is_none() {
+                    profiles.current = Some(name.clone());
+                }
+                profiles.save()?;
+                eprintln!("Saved profile '{}'", name);
+            }
+        }
+        Ok(())
+    }
+}
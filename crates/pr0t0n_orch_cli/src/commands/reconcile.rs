@@ -0,0 +1,44 @@
+use pr0t0n_orch_db::models::ReconciledService;
+use structopt::StructOpt;
+
+use crate::{commands::Context, Error};
+
+/// Compares the services declared in an asset group with the ones connected to the server.
+#[derive(StructOpt, Debug)]
+pub struct ReconcileOpt {
+    /// Asset group to reconcile. Defaults to the profile's asset group.
+    #[structopt(long)]
+    pub group: Option<i32>,
+}
+impl ReconcileOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let group = ctx.group(self.group)?;
+        let report = ctx.api.get_reconciliation(group).await?;
+        println!("STATE\tADDRESS\tHEALTH\tLAST CONNECTED");
+        for service in &report.services {
+            println!("{}", row(service));
+        }
+        let counts: Vec<String> = report
+            .counts
+            .iter()
+            .map(|(state, count)| format!("{} {:?}", count, state))
+            .collect();
+        eprintln!("{}", counts.join(", "));
+        Ok(())
+    }
+}
+
+fn row(service: &ReconciledService) -> String {
+    let health = match service.health_status {
+        Some(health_status) => format!("{:?}", health_status),
+        None => "-".to_string(),
+    };
+    let last_connected = match service.last_connected_at {
+        Some(at) => at.to_rfc3339(),
+        None => "never".to_string(),
+    };
+    format!(
+        "{:?}\t{}\t{}\t{}",
+        service.state, service.address, health, last_connected
+    )
+}
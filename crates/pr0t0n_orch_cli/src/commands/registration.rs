@@ -0,0 +1,193 @@
+use chrono::{DateTime, Local, Utc};
+use pr0t0n_orch_db::models::{AssetGroupChanges, RegistrationPolicy, ServiceApproval, ServiceType};
+use structopt::StructOpt;
+
+use crate::{commands::Context, Error};
+
+/// Manages what happens when an address that is not a service of an asset group connects.
+#[derive(StructOpt, Debug)]
+pub enum RegistrationOpt {
+    /// Shows the registration policy of an asset group, or sets it to `strict`, `auto` or
+    /// `pending`.
+    Policy {
+        /// Asset group of the policy. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        #[structopt(parse(try_from_str = parse_policy))]
+        policy: Option<RegistrationPolicy>,
+    },
+    /// Lists the addresses waiting to be approved.
+    Pending {
+        /// Asset group of the addresses. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+    },
+    /// Registers a pending address as a service.
+    Approve {
+        /// Asset group of the address. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        id: i32,
+
+        /// Name of the service.
+        #[structopt(long)]
+        name: String,
+
+        /// Type of the service: `input`, `output`, `processor` or `none`.
+        #[structopt(long = "type", parse(try_from_str = parse_service_type))]
+        service_type: ServiceType,
+
+        /// Name of the config to assign to the service.
+        #[structopt(long)]
+        config: Option<String>,
+    },
+    /// Forgets a pending address and closes its session.
+    Reject {
+        /// Asset group of the address. Defaults to the profile's asset group.
+        #[structopt(long)]
+        group: Option<i32>,
+
+        id: i32,
+    },
+}
+impl RegistrationOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let api = &ctx.api;
+        match self {
+            Self::Policy {
+                group,
+                policy: None,
+            } => {
+                let summary = api.get_asset_group(ctx.group(*group)?).await?;
+                println!("{:?}", summary.asset_group.registration_policy);
+            }
+            Self::Policy {
+                group,
+                policy: Some(policy),
+            } => {
+                let changes = AssetGroupChanges {
+                    registration_policy: Some(*policy),
+                    ..Default::default()
+                };
+                let updated = api.update_asset_group(ctx.group(*group)?, &changes).await?;
+                eprintln!(
+                    "Set the registration policy of {} to {:?}",
+                    updated.name, updated.registration_policy
+                );
+            }
+            Self::Pending { group } => {
+                let pending = api.list_pending_services(ctx.group(*group)?).await?;
+                println!("ID\tADDRESS\tFIRST SEEN\tLAST SEEN");
+                let format = |at: DateTime<Utc>| {
+                    at.with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                };
+                for service in pending {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        service.pending_service_id,
+                        service.address,
+                        format(service.first_seen_at),
+                        format(service.last_seen_at)
+                    );
+                }
+            }
+            Self::Approve {
+                group,
+                id,
+                name,
+                service_type,
+                config,
+            } => {
+                let approval = ServiceApproval {
+                    name: name.clone(),
+                    service_type: *service_type,
+                    config: config.clone(),
+                };
+                let service = api
+                    .approve_pending_service(ctx.group(*group)?, *id, &approval)
+                    .await?;
+                eprintln!(
+                    "Registered {} as service {}",
+                    service.service.address, service.service_id
+                );
+            }
+            Self::Reject { group, id } => {
+                api.reject_pending_service(ctx.group(*group)?, *id).await?;
+                eprintln!("Rejected pending service {}", id);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_policy(s: &str) -> Result<RegistrationPolicy, Error> {
+    match s.to_lowercase().as_str() {
+        "strict" => Ok(RegistrationPolicy::Strict),
+        "auto" => Ok(RegistrationPolicy::Auto),
+        "pending" => Ok(RegistrationPolicy::Pending),
+        _ => Err(Error::InvalidArgument(format!(
+            "Unknown registration policy '{}', expected 'strict', 'auto' or 'pending'",
+            s
+        ))),
+    }
+}
+
+fn parse_service_type(s: &str) -> Result<ServiceType, Error> {
+    match s.to_lowercase().as_str() {
+        "none" => Ok(ServiceType::None),
+        "input" => Ok(ServiceType::Input),
+        "output" => Ok(ServiceType::Output),
+        "processor" => Ok(ServiceType::Processor),
+        _ => Err(Error::InvalidArgument(format!(
+            "Unknown service type '{}', expected 'input', 'output', 'processor' or 'none'",
+            s
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse_policy("Pending").unwrap(),
+            RegistrationPolicy::Pending
+        );
+        assert!(parse_policy("open").is_err());
+        assert_eq!(parse_service_type("input").unwrap(), ServiceType::Input);
+        assert!(parse_service_type("sensor").is_err());
+    }
+
+    #[test]
+    fn test_approve_args() {
+        let opt = RegistrationOpt::from_iter_safe(&[
+            "registration",
+            "approve",
+            "3",
+            "--name",
+            "camera",
+            "--type",
+            "input",
+        ])
+        .unwrap();
+        match opt {
+            RegistrationOpt::Approve {
+                id,
+                service_type,
+                config,
+                ..
+            } => {
+                assert_eq!(id, 3);
+                assert_eq!(service_type, ServiceType::Input);
+                assert_eq!(config, None);
+            }
+            opt => panic!("Parsed {:?}", opt),
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+};
+
+use chrono::Local;
+use futures::StreamExt;
+use pr0t0n_orch_db::models::{Event, EventKind, HealthStatus, ServiceSummary, ServiceType};
+use structopt::StructOpt;
+
+use crate::{commands::Context, Error};
+
+/// Number of recent events shown below the service table.
+const RECENT_EVENTS: usize = 10;
+
+/// Shows live service health for an asset group.
+#[derive(StructOpt, Debug)]
+pub struct WatchOpt {
+    /// Asset group to watch. Defaults to the profile's asset group.
+    #[structopt(long)]
+    pub group: Option<i32>,
+
+    /// Print a line per event instead of redrawing the service table.
+    #[structopt(long)]
+    pub events: bool,
+}
+impl WatchOpt {
+    pub async fn run(&self, ctx: &Context) -> Result<(), Error> {
+        let group = ctx.group(self.group)?;
+        let mut state = WatchState::new(group, &ctx.api.list_services(group).await?);
+        let mut events = ctx.api.watch(group).await?;
+        if !self.events {
+            redraw(&state);
+        }
+
+        while let Some(text) = events.next().await {
+            let event: Event = match serde_json::from_str(&text) {
+                Ok(event) => event,
+                Err(err) => {
+                    eprintln!("Ignoring unknown message '{}': {}", text, err);
+                    continue;
+                }
+            };
+            if self.events {
+                println!("{}", describe(&event));
+            }
+            state.apply(event);
+            if !self.events {
+                redraw(&state);
+            }
+        }
+        eprintln!("Server disconnected");
+        Ok(())
+    }
+}
+
+fn redraw(state: &WatchState) {
+    // Clear the screen and move the cursor home, like `top`.
+    print!("\x1b[2J\x1b[H{}", state.render());
+}
+
+fn describe(event: &Event) -> String {
+    format!(
+        "{} {}",
+        event.timestamp.with_timezone(&Local).format("%H:%M:%S"),
+        event.kind
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceRow {
+    pub name: String,
+    pub service_type: ServiceType,
+    pub health_status: HealthStatus,
+    pub connects: usize,
+    pub disconnects: usize,
+}
+
+/// Service health for an asset group, kept up to date from watcher events.
+#[derive(Debug, Default)]
+pub struct WatchState {
+    pub asset_group_id: i32,
+    pub services: BTreeMap<String, ServiceRow>,
+    pub events: VecDeque<Event>,
+}
+impl WatchState {
+    pub fn new(asset_group_id: i32, services: &[ServiceSummary]) -> Self {
+        let services = services
+            .iter()
+            .map(|ServiceSummary { service, .. }| {
+                let row = ServiceRow {
+                    name: service.name.clone(),
+                    service_type: service.service_type,
+                    health_status: service.health_status.unwrap_or(HealthStatus::Unknown),
+                    connects: 0,
+                    disconnects: 0,
+                };
+                (service.address.clone(), row)
+            })
+            .collect();
+        Self {
+            asset_group_id,
+            services,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn row(&mut self, address: &str) -> &mut ServiceRow {
+        // Addresses missing from the listing are held as pending or left unregistered, unless the
+        // server registers undeclared addresses, as inputs.
+        self.services
+            .entry(address.to_string())
+            .or_insert_with(|| ServiceRow {
+                name: address.to_string(),
+                service_type: ServiceType::Input,
+                health_status: HealthStatus::Disconnected,
+                connects: 0,
+                disconnects: 0,
+            })
+    }
+
+    pub fn apply(&mut self, event: Event) {
+        match &event.kind {
+            EventKind::Connected { address } => {
+                let row = self.row(address);
+                row.health_status = HealthStatus::Healthy;
+                row.connects += 1;
+            }
+            EventKind::Disconnected {
+                address,
+                maintenance,
+            } => {
+                let row = self.row(address);
+                row.health_status = HealthStatus::Disconnected;
+                // Planned downtime is not held against the service.
+                if maintenance.is_none() {
+                    row.disconnects += 1;
+                }
+            }
+            EventKind::Reconciled {
+                addresses,
+                health_status,
+            } => {
+                for address in addresses {
+                    self.row(address).health_status = *health_status;
+                }
+            }
+            EventKind::HealthChanged {
+                address,
+                health_status,
+                ..
+            } => {
+                self.row(address).health_status = *health_status;
+            }
+            // Alerts and failovers only show in the recent events.
+            EventKind::AlertFired { .. }
+            | EventKind::AlertResolved { .. }
+            | EventKind::AlertFlapping { .. }
+            | EventKind::AlertEscalated { .. }
+            | EventKind::AlertAcknowledged { .. }
+            | EventKind::NotificationSent { .. }
+            | EventKind::NotificationFailed { .. }
+            | EventKind::FailedOver { .. }
+            | EventKind::FailedBack { .. }
+            | EventKind::FailoverFailed { .. } => {}
+        }
+        self.events.push_back(event);
+        while self.events.len() > RECENT_EVENTS {
+            self.events.pop_front();
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for row in self.services.values() {
+            *counts
+                .entry(format!("{:?}", row.health_status).to_lowercase())
+                .or_default() += 1;
+        }
+        let summary: Vec<String> = counts
+            .iter()
+            .map(|(status, count)| format!("{} {}", count, status))
+            .collect();
+
+        let address_width = self
+            .services
+            .keys()
+            .map(|address| address.len())
+            .chain(Some("ADDRESS".len()))
+            .max()
+            .unwrap_or_default();
+        let name_width = self
+            .services
+            .values()
+            .map(|row| row.name.len())
+            .chain(Some("NAME".len()))
+            .max()
+            .unwrap_or_default();
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "Asset group {}: {} services ({})",
+            self.asset_group_id,
+            self.services.len(),
+            summary.join(", ")
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "{:aw$}  {:nw$}  {:10}  {:12}  {:>4}  {:>4}",
+            "ADDRESS",
+            "NAME",
+            "TYPE",
+            "HEALTH",
+            "UP",
+            "DOWN",
+            aw = address_width,
+            nw = name_width
+        )
+        .unwrap();
+        for (address, row) in &self.services {
+            writeln!(
+                out,
+                "{:aw$}  {:nw$}  {:10}  {:12}  {:>4}  {:>4}",
+                address,
+                row.name,
+                format!("{:?}", row.service_type),
+                format!("{:?}", row.health_status),
+                row.connects,
+                row.disconnects,
+                aw = address_width,
+                nw = name_width
+            )
+            .unwrap();
+        }
+        if !self.events.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "Recent events:").unwrap();
+            for event in &self.events {
+                writeln!(out, "  {}", describe(event)).unwrap();
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pr0t0n_orch_db::models::ServiceRepr;
+
+    #[test]
+    fn test_watch_state() {
+        let mut state = WatchState::new(
+            2,
+            &[ServiceSummary {
+                service_id: 1,
+                service: ServiceRepr {
+                    address: "localhost:123".to_string(),
+                    name: "camera".to_string(),
+                    service_type: ServiceType::Input,
+                    health_status: Some(HealthStatus::Healthy),
+                    ..Default::default()
+                },
+            }],
+        );
+
+        state.apply(Event::new(
+            2,
+            EventKind::Disconnected {
+                address: "localhost:123".to_string(),
+                maintenance: None,
+            },
+        ));
+        state.apply(Event::new(
+            2,
+            EventKind::Disconnected {
+                address: "localhost:123".to_string(),
+                maintenance: Some(4),
+            },
+        ));
+        state.apply(Event::new(
+            2,
+            EventKind::Connected {
+                address: "localhost:234".to_string(),
+            },
+        ));
+
+        let camera = &state.services["localhost:123"];
+        assert_eq!(camera.health_status, HealthStatus::Disconnected);
+        assert_eq!(camera.disconnects, 1);
+        let new_service = &state.services["localhost:234"];
+        assert_eq!(new_service.health_status, HealthStatus::Healthy);
+        assert_eq!(new_service.connects, 1);
+
+        let rendered = state.render();
+        assert!(rendered.starts_with("Asset group 2: 2 services (1 disconnected, 1 healthy)"));
+        assert!(!rendered.contains("localhost:234 disconnected"));
+        assert!(rendered.contains("localhost:123 disconnected"));
+        assert!(rendered.contains("localhost:123 disconnected during maintenance window 4"));
+
+        state.apply(Event::new(
+            2,
+            EventKind::Reconciled {
+                addresses: vec!["localhost:234".to_string()],
+                health_status: HealthStatus::Unknown,
+            },
+        ));
+        assert_eq!(
+            state.services["localhost:234"].health_status,
+            HealthStatus::Unknown
+        );
+        assert!(state.render().contains("(1 disconnected, 1 unknown)"));
+
+        state.apply(Event::new(
+            2,
+            EventKind::HealthChanged {
+                address: "localhost:234".to_string(),
+                health_status: HealthStatus::Critical,
+                maintenance: None,
+            },
+        ));
+        state.apply(Event::new(
+            2,
+            EventKind::AlertFired {
+                address: "localhost:234".to_string(),
+                alert_rule_id: 1,
+                message: "localhost:234 went from Unknown to Critical".to_string(),
+            },
+        ));
+        let rendered = state.render();
+        assert!(rendered.contains("(1 critical, 1 disconnected)"));
+        assert!(rendered.contains("Alert: localhost:234 went from Unknown to Critical"));
+    }
+
+    #[test]
+    fn test_recent_events_are_bounded() {
+        let mut state = WatchState::default();
+        for i in 0..(RECENT_EVENTS + 5) {
+            state.apply(Event::new(
+                0,
+                EventKind::Connected {
+                    address: format!("localhost:{}", i),
+                },
+            ));
+        }
+        assert_eq!(state.events.len(), RECENT_EVENTS);
+        assert_eq!(state.services.len(), RECENT_EVENTS + 5);
+    }
+}
@@ -0,0 +1,123 @@
+use pr0t0n_orch_db::models::{ErrorCode, ErrorResponse};
+
+/// Error enum.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    SerdeJsonError(serde_json::Error),
+    SerdeYamlError(serde_yaml::Error),
+    TomlError(String),
+    RequestError(String),
+    ResponseError { status: u16, body: String },
+    InvalidArgument(String),
+}
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::SerdeJsonError(err)
+    }
+}
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::SerdeYamlError(err)
+    }
+}
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::TomlError(err.to_string())
+    }
+}
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::TomlError(err.to_string())
+    }
+}
+impl From<awc::error::SendRequestError> for Error {
+    fn from(err: awc::error::SendRequestError) -> Self {
+        Self::RequestError(err.to_string())
+    }
+}
+impl From<awc::error::PayloadError> for Error {
+    fn from(err: awc::error::PayloadError) -> Self {
+        Self::RequestError(err.to_string())
+    }
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::SerdeJsonError(err) => write!(f, "Invalid JSON: {}", err),
+            Error::SerdeYamlError(err) => write!(f, "Invalid YAML: {}", err),
+            Error::TomlError(msg) => write!(f, "Invalid profile file: {}", msg),
+            Error::RequestError(msg) => write!(f, "Request failed: {}", msg),
+            Error::ResponseError { status, body } => match serde_json::from_str(body) {
+                Ok(ErrorResponse { details, .. }) if !details.is_empty() => {
+                    write!(f, "Server responded with {}:", status)?;
+                    for detail in details {
+                        match detail.field {
+                            Some(field) => write!(f, "\n  {}: {}", field, detail.message)?,
+                            None => write!(f, "\n  {}", detail.message)?,
+                        }
+                    }
+                    Ok(())
+                }
+                Ok(ErrorResponse { errors, .. }) if !errors.is_empty() => {
+                    write!(f, "Server responded with {}: {}", status, errors.join("; "))
+                }
+                _ => write!(f, "Server responded with {}: {}", status, body),
+            },
+            Error::InvalidArgument(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+impl Error {
+    /// The server's error response, if this is an error response in the API's format.
+    pub fn response(&self) -> Option<ErrorResponse> {
+        match self {
+            Error::ResponseError { body, .. } => serde_json::from_str(body).ok(),
+            _ => None,
+        }
+    }
+
+    /// Code of the server's first error, for reacting to specific errors.
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.response()?.code()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_error() {
+        let err = Error::ResponseError {
+            status: 422,
+            body: serde_json::json!({
+                "errors": ["Service addresses must not be empty"],
+                "details": [{
+                    "code": "validation_failed",
+                    "message": "Service addresses must not be empty",
+                    "field": "services/1/address",
+                }],
+            })
+            .to_string(),
+        };
+        assert_eq!(err.code(), Some(ErrorCode::ValidationFailed));
+        assert_eq!(
+            err.to_string(),
+            "Server responded with 422:\n  services/1/address: Service addresses must not be empty"
+        );
+
+        let err = Error::ResponseError {
+            status: 502,
+            body: "Bad Gateway".to_string(),
+        };
+        assert_eq!(err.code(), None);
+        assert_eq!(err.to_string(), "Server responded with 502: Bad Gateway");
+    }
+}
@@ -0,0 +1,113 @@
+use std::{path::Path, str::FromStr};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Error;
+
+/// File formats understood by `porch apply` and produced by `porch export`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Yaml,
+    Json,
+}
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" => Ok(Self::Json),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown format '{}', expected 'yaml' or 'json'",
+                s
+            ))),
+        }
+    }
+}
+impl Format {
+    /// Guess the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    /// Serialize a value into this format.
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String, Error> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::to_string(value)?,
+            Self::Json => serde_json::to_string_pretty(value)?,
+        })
+    }
+
+    /// Deserialize a value from this format.
+    pub fn deserialize<T: DeserializeOwned>(&self, s: &str) -> Result<T, Error> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::from_str(s)?,
+            Self::Json => serde_json::from_str(s)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pr0t0n_orch_db::models::{ConfigRepr, ServiceRepr, ServiceType, SystemRepr};
+
+    fn system_repr() -> SystemRepr {
+        SystemRepr {
+            asset_group_id: 7,
+            revision: None,
+            services: vec![
+                ServiceRepr {
+                    address: "localhost:123".to_string(),
+                    service_type: ServiceType::Input,
+                    name: "localhost:123".to_string(),
+                    output_addresses: vec!["localhost:234".to_string()],
+                    config_name: Some("TestConfig".to_string()),
+                    ..Default::default()
+                },
+                ServiceRepr {
+                    address: "localhost:234".to_string(),
+                    service_type: ServiceType::Processor,
+                    name: "localhost:234".to_string(),
+                    ..Default::default()
+                },
+            ],
+            configs: vec![ConfigRepr {
+                name: "TestConfig".to_string(),
+                description: "A test config".to_string(),
+                json_config: serde_json::from_str(r#"{ "key": "value", "rate": 1.5 }"#).unwrap(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let system_repr = system_repr();
+        for format in &[Format::Yaml, Format::Json] {
+            let serialized = format.serialize(&system_repr).unwrap();
+            let deserialized: SystemRepr = format.deserialize(&serialized).unwrap();
+            assert_eq!(system_repr, deserialized);
+        }
+    }
+
+    #[test]
+    fn test_example() {
+        let example = include_str!("../examples/data/config.yml");
+        let system_repr: SystemRepr = Format::Yaml.deserialize(example).unwrap();
+        assert_eq!(system_repr.services.len(), 2);
+        assert_eq!(system_repr.services[1].alerts.len(), 3);
+        for service in &system_repr.services {
+            assert!(service.alerts.iter().all(|rule| rule.validate().is_ok()));
+        }
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(Format::from_path(Path::new("a/b.yml")), Some(Format::Yaml));
+        assert_eq!(Format::from_path(Path::new("a/b.JSON")), Some(Format::Json));
+        assert_eq!(Format::from_path(Path::new("a/b.txt")), None);
+        assert_eq!(Format::from_path(Path::new("a/b")), None);
+    }
+}
@@ -1 +1,8 @@
-
+//! Pr0t0n Orchestrator command line.
+pub mod api;
+pub mod commands;
+pub mod errors;
+pub use errors::Error;
+pub mod format;
+pub mod profile;
+pub mod prompt;
@@ -1,24 +1,82 @@
-use actix::*;
+use structopt::StructOpt;
 
-use pr0t0n_orch_db::{
-    establish_connection,
-    models::{DbInsert, NewAssetGroup},
+use pr0t0n_orch_cli::{
+    commands::{
+        apply::ApplyOpt, config::ConfigOpt, export::ExportOpt, graph::GraphOpt,
+        maintenance::MaintenanceOpt, patch::PatchOpt, profile::ProfileOpt, reconcile::ReconcileOpt,
+        registration::RegistrationOpt, watch::WatchOpt, Context,
+    },
+    profile::Profiles,
+    Error,
 };
 
-fn main() {
-    ::std::env::set_var("RUST_LOG", "info");
-    env_logger::init();
+#[derive(StructOpt, Debug)]
+#[structopt(name = "porch", about = "Command line for Pr0t0n Orchestrator.")]
+struct Opt {
+    /// Profile to use instead of the current one.
+    #[structopt(long, env = "PORCH_PROFILE")]
+    profile: Option<String>,
+
+    /// URL of the orchestrator server, overriding the profile.
+    #[structopt(long)]
+    server: Option<String>,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
 
-    let sys = System::new("websocket-client");
+#[derive(StructOpt, Debug)]
+enum Command {
+    Profile(ProfileOpt),
+    #[structopt(flatten)]
+    Server(ServerCommand),
+}
+
+/// Commands that talk to a server.
+#[derive(StructOpt, Debug)]
+enum ServerCommand {
+    Apply(ApplyOpt),
+    Config(ConfigOpt),
+    Export(ExportOpt),
+    Graph(GraphOpt),
+    Maintenance(MaintenanceOpt),
+    Patch(PatchOpt),
+    Reconcile(ReconcileOpt),
+    Registration(RegistrationOpt),
+    Watch(WatchOpt),
+}
 
-    // Create a new adgroup for testing.
-    let conn = establish_connection();
-    let _asset_group = NewAssetGroup {
-        name: "temp_asset_group",
-        description: "A test asset group",
+async fn run(opt: Opt) -> Result<(), Error> {
+    let command = match opt.command {
+        // Profile management doesn't talk to a server.
+        Command::Profile(cmd) => return cmd.run(),
+        Command::Server(command) => command,
+    };
+
+    let mut profile = Profiles::load()?.resolve(opt.profile.as_deref())?;
+    if opt.server.is_some() {
+        profile.server = opt.server;
+    }
+    let ctx = Context::new(profile);
+    match command {
+        ServerCommand::Apply(cmd) => cmd.run(&ctx).await,
+        ServerCommand::Config(cmd) => cmd.run(&ctx).await,
+        ServerCommand::Export(cmd) => cmd.run(&ctx).await,
+        ServerCommand::Graph(cmd) => cmd.run(&ctx).await,
+        ServerCommand::Maintenance(cmd) => cmd.run(&ctx).await,
+        ServerCommand::Patch(cmd) => cmd.run(&ctx).await,
+        ServerCommand::Reconcile(cmd) => cmd.run(&ctx).await,
+        ServerCommand::Registration(cmd) => cmd.run(&ctx).await,
+        ServerCommand::Watch(cmd) => cmd.run(&ctx).await,
     }
-    .insert(&conn)
-    .unwrap();
+}
 
-    sys.run().unwrap();
+#[actix_web::main]
+async fn main() {
+    env_logger::init();
+
+    if let Err(err) = run(Opt::from_args()).await {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
 }
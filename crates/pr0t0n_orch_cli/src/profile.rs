@@ -0,0 +1,171 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Server used when neither `--server` nor the profile names one.
+pub const DEFAULT_SERVER: &str = "http://127.0.0.1:8080";
+
+/// Connection settings for one orchestrator.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// Asset group used by commands when `--group` is omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_group_id: Option<i32>,
+    /// Bearer token sent with every request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+impl Profile {
+    pub fn server(&self) -> &str {
+        self.server.as_deref().unwrap_or(DEFAULT_SERVER)
+    }
+}
+
+/// Contents of `~/.config/porch/profiles.toml`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Profiles {
+    /// Profile used when `--profile` is omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+impl Profiles {
+    /// Location of the profile file, honouring `$XDG_CONFIG_HOME`.
+    pub fn path() -> Result<PathBuf, Error> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => {
+                let home = std::env::var_os("HOME").ok_or_else(|| {
+                    Error::InvalidArgument("Cannot find the home directory".to_string())
+                })?;
+                PathBuf::from(home).join(".config")
+            }
+        };
+        Ok(config_dir.join("porch").join("profiles.toml"))
+    }
+
+    pub fn load() -> Result<Self, Error> {
+        Self::load_from(&Self::path()?)
+    }
+
+    /// Load profiles, treating a missing file as having none.
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(toml::from_str(&contents)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        self.save_to(&Self::path()?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // The file holds credentials, so keep it private from before anything is written. A file
+        // that already exists keeps its mode when opened, so that is set too.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(toml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Pick the named profile, else the current one, else an empty default profile.
+    pub fn resolve(&self, name: Option<&str>) -> Result<Profile, Error> {
+        match name.or(self.current.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::InvalidArgument(format!("Unknown profile '{}'", name))),
+            None => Ok(Profile::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles() -> Profiles {
+        let mut profiles = Profiles {
+            current: Some("lab".to_string()),
+            ..Default::default()
+        };
+        profiles.profiles.insert(
+            "lab".to_string(),
+            Profile {
+                server: Some("http://lab:8080".to_string()),
+                asset_group_id: Some(3),
+                token: None,
+            },
+        );
+        profiles.profiles.insert(
+            "site-1".to_string(),
+            Profile {
+                server: Some("https://site-1.example.com".to_string()),
+                asset_group_id: None,
+                token: Some("secret".to_string()),
+            },
+        );
+        profiles
+    }
+
+    #[test]
+    fn test_resolve() {
+        let profiles = profiles();
+        assert_eq!(profiles.resolve(None).unwrap().asset_group_id, Some(3));
+        assert_eq!(
+            profiles.resolve(Some("site-1")).unwrap().server(),
+            "https://site-1.example.com"
+        );
+        assert!(profiles.resolve(Some("missing")).is_err());
+        assert_eq!(
+            Profiles::default().resolve(None).unwrap().server(),
+            DEFAULT_SERVER
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("porch-test-{}", std::process::id()))
+            .join("profiles.toml");
+        assert_eq!(Profiles::load_from(&path).unwrap(), Profiles::default());
+
+        let profiles = profiles();
+        profiles.save_to(&path).unwrap();
+        assert_eq!(Profiles::load_from(&path).unwrap(), profiles);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
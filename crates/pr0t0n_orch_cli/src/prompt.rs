@@ -0,0 +1,15 @@
+use std::io::{self, Write};
+
+use crate::Error;
+
+/// Ask a yes/no question on the terminal. An empty answer means yes.
+pub fn confirm(question: &str) -> Result<bool, Error> {
+    print!("{} [Y/n] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_lowercase().as_str(),
+        "" | "y" | "yes"
+    ))
+}
@@ -8,7 +8,7 @@ use awc::{
     BoxedSocket,
 };
 use bytes::Bytes;
-use futures::stream::SplitSink;
+use futures::{channel::mpsc::UnboundedSender, stream::SplitSink};
 use std::time::Duration;
 
 pub struct ChatClient {
@@ -75,3 +75,47 @@ impl StreamHandler<Result<Frame, WsProtocolError>> for ChatClient {
 }
 
 impl actix::io::WriteHandler<WsProtocolError> for ChatClient {}
+
+/// Client for read-only sessions such as `/ws/watch/`. Forwards text frames to `tx` and closes
+/// it when the server disconnects.
+pub struct WatchClient {
+    pub sink: SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>,
+    pub tx: UnboundedSender<String>,
+}
+
+impl Actor for WatchClient {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        self.heartbeat(ctx)
+    }
+}
+
+impl WatchClient {
+    fn heartbeat(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(Duration::new(5, 0), |act, ctx| {
+            act.sink.write(Message::Ping(Bytes::from_static(b"")));
+            act.heartbeat(ctx);
+        });
+    }
+}
+
+/// Handle server websocket messages
+impl StreamHandler<Result<Frame, WsProtocolError>> for WatchClient {
+    fn handle(&mut self, msg: Result<Frame, WsProtocolError>, ctx: &mut Context<Self>) {
+        if let Ok(Frame::Text(txt)) = msg {
+            let text = String::from_utf8_lossy(&txt).to_string();
+            if self.tx.unbounded_send(text).is_err() {
+                // Nobody is listening anymore.
+                ctx.stop()
+            }
+        }
+    }
+
+    fn finished(&mut self, ctx: &mut Context<Self>) {
+        self.tx.close_channel();
+        ctx.stop()
+    }
+}
+
+impl actix::io::WriteHandler<WsProtocolError> for WatchClient {}
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS service_group_address_idx;
+DROP INDEX IF EXISTS service_group_name_idx;
+DROP INDEX IF EXISTS service_labels_idx;
+ALTER TABLE services DROP COLUMN IF EXISTS labels;
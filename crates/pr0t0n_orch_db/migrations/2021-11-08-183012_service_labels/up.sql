@@ -0,0 +1,7 @@
+-- Labels are stored as `key=value` strings so filters can use the GIN index.
+ALTER TABLE services
+ADD COLUMN labels TEXT [] NOT NULL DEFAULT '{}';
+CREATE INDEX service_labels_idx ON services USING GIN (labels);
+-- Keyset pagination sorts by these columns with the service ID as a tie-breaker.
+CREATE INDEX service_group_name_idx ON services (asset_group_id, name, service_id);
+CREATE INDEX service_group_address_idx ON services (asset_group_id, address, service_id);
@@ -0,0 +1,7 @@
+DROP TRIGGER IF EXISTS service_edges_revision ON service_edges;
+DROP TRIGGER IF EXISTS configs_update_revision ON configs;
+DROP TRIGGER IF EXISTS configs_revision ON configs;
+DROP TRIGGER IF EXISTS services_update_revision ON services;
+DROP TRIGGER IF EXISTS services_revision ON services;
+DROP FUNCTION IF EXISTS bump_asset_group_revision();
+ALTER TABLE asset_groups DROP COLUMN IF EXISTS revision;
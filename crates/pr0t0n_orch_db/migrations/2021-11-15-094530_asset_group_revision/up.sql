@@ -0,0 +1,44 @@
+-- Incremented on every change to the system of an asset group, so clients can cache it.
+ALTER TABLE asset_groups
+ADD COLUMN revision BIGINT NOT NULL DEFAULT 0;
+CREATE FUNCTION bump_asset_group_revision() RETURNS TRIGGER AS $$ BEGIN IF TG_OP IN ('UPDATE', 'DELETE') THEN
+UPDATE asset_groups
+SET revision = revision + 1
+WHERE asset_group_id = OLD.asset_group_id;
+END IF;
+IF TG_OP = 'INSERT'
+OR (
+  TG_OP = 'UPDATE'
+  AND NEW.asset_group_id <> OLD.asset_group_id
+) THEN
+UPDATE asset_groups
+SET revision = revision + 1
+WHERE asset_group_id = NEW.asset_group_id;
+END IF;
+RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER services_revision
+AFTER
+INSERT
+  OR DELETE ON services FOR EACH ROW EXECUTE PROCEDURE bump_asset_group_revision();
+-- Health is reported by the services themselves rather than declared, so it is left out.
+CREATE TRIGGER services_update_revision
+AFTER
+UPDATE ON services FOR EACH ROW
+  WHEN (
+    to_jsonb(OLD) - 'health_status' IS DISTINCT
+    FROM to_jsonb(NEW) - 'health_status'
+  ) EXECUTE PROCEDURE bump_asset_group_revision();
+CREATE TRIGGER configs_revision
+AFTER
+INSERT
+  OR DELETE ON configs FOR EACH ROW EXECUTE PROCEDURE bump_asset_group_revision();
+CREATE TRIGGER configs_update_revision
+AFTER
+UPDATE ON configs FOR EACH ROW
+  WHEN (OLD.* IS DISTINCT FROM NEW.*) EXECUTE PROCEDURE bump_asset_group_revision();
+CREATE TRIGGER service_edges_revision
+AFTER
+INSERT
+  OR DELETE ON service_edges FOR EACH ROW EXECUTE PROCEDURE bump_asset_group_revision();
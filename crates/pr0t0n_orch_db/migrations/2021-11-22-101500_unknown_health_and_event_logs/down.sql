@@ -0,0 +1,19 @@
+DROP TABLE IF EXISTS event_logs;
+CREATE TABLE event_logs (
+  config_id TIMESTAMP PRIMARY KEY,
+  asset_group_id SERIAL NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  entry JSONB NOT NULL DEFAULT '{}'
+);
+UPDATE services
+SET health_status = 'disconnected'
+WHERE health_status = 'unknown';
+ALTER TABLE services DROP CONSTRAINT services_health_status_check;
+ALTER TABLE services
+ADD CONSTRAINT services_health_status_check CHECK (
+    health_status IN (
+      'healthy',
+      'disconnected',
+      'warning',
+      'critical'
+    )
+  );
@@ -0,0 +1,22 @@
+-- Services whose state is not known yet, e.g. after the orchestrator restarted.
+ALTER TABLE services DROP CONSTRAINT services_health_status_check;
+ALTER TABLE services
+ADD CONSTRAINT services_health_status_check CHECK (
+    health_status IN (
+      'healthy',
+      'disconnected',
+      'warning',
+      'critical',
+      'unknown'
+    )
+  );
+-- The original table was keyed by its timestamp under the wrong name, so events logged in the
+-- same instant collided. It was never written to.
+DROP TABLE event_logs;
+CREATE TABLE event_logs (
+  event_log_id SERIAL PRIMARY KEY,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  logged_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  entry JSONB NOT NULL DEFAULT '{}'
+);
+CREATE INDEX event_log_group_idx ON event_logs (asset_group_id, logged_at);
@@ -0,0 +1,2 @@
+DROP TRIGGER IF EXISTS alert_rules_revision ON alert_rules;
+DROP TABLE IF EXISTS alert_rules;
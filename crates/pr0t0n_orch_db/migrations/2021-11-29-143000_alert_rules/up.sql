@@ -0,0 +1,17 @@
+-- Alerts declared on services, sent when their condition is met.
+CREATE TABLE alert_rules (
+  alert_rule_id SERIAL PRIMARY KEY,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  service_id INT NOT NULL REFERENCES services(service_id) ON DELETE CASCADE,
+  -- The `on` trigger and its parameters, e.g. `{"on": "metric", "metric": "cpu", "above": 0.9}`.
+  condition JSONB NOT NULL,
+  sender VARCHAR(255) NOT NULL,
+  recipients TEXT [] NOT NULL DEFAULT '{}'
+);
+CREATE INDEX alert_rule_service_idx ON alert_rules (service_id);
+CREATE INDEX alert_rule_group_idx ON alert_rules (asset_group_id);
+-- Rules are part of the system, so changing them changes its revision.
+CREATE TRIGGER alert_rules_revision
+AFTER
+INSERT
+  OR DELETE ON alert_rules FOR EACH ROW EXECUTE PROCEDURE bump_asset_group_revision();
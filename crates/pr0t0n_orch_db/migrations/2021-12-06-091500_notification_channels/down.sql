@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS dead_letters;
+ALTER TABLE alert_rules DROP COLUMN IF EXISTS channels;
@@ -0,0 +1,18 @@
+-- Named notification channels an alert is sent through, besides email to its recipients.
+ALTER TABLE alert_rules
+ADD COLUMN channels TEXT [] NOT NULL DEFAULT '{}';
+-- Notifications that could not be delivered after every retry, kept with the payload that was
+-- being sent so they can be looked into and replayed. Rules are replaced when the system is
+-- synced, so `alert_rule_id` may no longer exist.
+CREATE TABLE dead_letters (
+  dead_letter_id SERIAL PRIMARY KEY,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  alert_rule_id INT NOT NULL,
+  address VARCHAR(255) NOT NULL,
+  channel VARCHAR(255) NOT NULL,
+  payload JSONB NOT NULL,
+  error TEXT NOT NULL,
+  attempts INT NOT NULL,
+  failed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX dead_letter_group_idx ON dead_letters (asset_group_id, failed_at);
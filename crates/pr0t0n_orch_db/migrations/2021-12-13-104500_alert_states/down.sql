@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS alert_states;
+ALTER TABLE alert_rules DROP COLUMN IF EXISTS dedup_key,
+  DROP COLUMN IF EXISTS for_seconds,
+  DROP COLUMN IF EXISTS escalation;
@@ -0,0 +1,28 @@
+-- Rules can share the state of their alerts through a key, wait before firing and escalate.
+ALTER TABLE alert_rules
+ADD COLUMN dedup_key VARCHAR(255),
+  ADD COLUMN for_seconds INT NOT NULL DEFAULT 0,
+  ADD COLUMN escalation JSONB NOT NULL DEFAULT '[]';
+-- The state of each alert, by its key. Rules are replaced when the system is synced, so the
+-- state is kept by key rather than by rule, and `alert_rule_id` is the rule that last changed it.
+CREATE TABLE alert_states (
+  alert_state_id SERIAL PRIMARY KEY,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  dedup_key VARCHAR(1024) NOT NULL,
+  alert_rule_id INT NOT NULL,
+  address VARCHAR(255) NOT NULL,
+  status VARCHAR(16) NOT NULL CHECK (status IN ('pending', 'firing', 'resolved')),
+  message TEXT NOT NULL,
+  -- When the condition started to hold.
+  since TIMESTAMPTZ NOT NULL,
+  fired_at TIMESTAMPTZ,
+  resolved_at TIMESTAMPTZ,
+  acknowledged_at TIMESTAMPTZ,
+  acknowledged_by VARCHAR(255),
+  -- Escalation tiers notified since the alert fired.
+  escalations INT NOT NULL DEFAULT 0,
+  flapping BOOLEAN NOT NULL DEFAULT false,
+  -- Recent times the alert fired or resolved, to detect flapping.
+  transitions TIMESTAMPTZ [] NOT NULL DEFAULT '{}',
+  UNIQUE (asset_group_id, dedup_key)
+);
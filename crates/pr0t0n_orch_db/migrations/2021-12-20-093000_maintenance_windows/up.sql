@@ -0,0 +1,21 @@
+-- Windows during which alerts of an asset group, a service or the services with a label are
+-- silenced, e.g. while robots are powered down for servicing.
+CREATE TABLE maintenance_windows (
+  maintenance_window_id SERIAL PRIMARY KEY,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  -- Scope of the window. With neither, it covers the whole asset group.
+  address VARCHAR(255),
+  label VARCHAR(255),
+  reason TEXT NOT NULL DEFAULT '',
+  -- The first occurrence. Recurring windows repeat it every day or week until `until`.
+  starts_at TIMESTAMPTZ NOT NULL,
+  ends_at TIMESTAMPTZ NOT NULL,
+  recurrence VARCHAR(16) NOT NULL DEFAULT 'once' CHECK (recurrence IN ('once', 'daily', 'weekly')),
+  until TIMESTAMPTZ,
+  -- When the window was ended early.
+  expired_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  CHECK (ends_at > starts_at),
+  CHECK (address IS NULL OR label IS NULL)
+);
+CREATE INDEX maintenance_windows_asset_group_id ON maintenance_windows (asset_group_id);
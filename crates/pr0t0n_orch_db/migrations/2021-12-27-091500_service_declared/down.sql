@@ -0,0 +1,10 @@
+DROP TRIGGER IF EXISTS services_update_revision ON services;
+CREATE TRIGGER services_update_revision
+AFTER
+UPDATE ON services FOR EACH ROW
+  WHEN (
+    to_jsonb(OLD) - 'health_status' IS DISTINCT
+    FROM to_jsonb(NEW) - 'health_status'
+  ) EXECUTE PROCEDURE bump_asset_group_revision();
+ALTER TABLE services DROP COLUMN IF EXISTS declared,
+  DROP COLUMN IF EXISTS last_connected_at;
@@ -0,0 +1,19 @@
+-- Tell services declared in a system apart from ones registered by connecting, and services that
+-- never connected from ones that did.
+ALTER TABLE services
+ADD COLUMN declared BOOLEAN NOT NULL DEFAULT true,
+  ADD COLUMN last_connected_at TIMESTAMPTZ;
+-- Connecting is not a change to the system either.
+DROP TRIGGER IF EXISTS services_update_revision ON services;
+CREATE TRIGGER services_update_revision
+AFTER
+UPDATE ON services FOR EACH ROW
+  WHEN (
+    to_jsonb(OLD) - 'health_status' - 'last_connected_at' IS DISTINCT
+    FROM to_jsonb(NEW) - 'health_status' - 'last_connected_at'
+  ) EXECUTE PROCEDURE bump_asset_group_revision();
+-- Services that are not disconnected have connected at some point, so they are not reported as
+-- never seen. When is unknown, so now stands in for it.
+UPDATE services
+SET last_connected_at = now()
+WHERE health_status <> 'disconnected';
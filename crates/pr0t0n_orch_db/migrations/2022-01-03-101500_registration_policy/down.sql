@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS pending_services;
+ALTER TABLE asset_groups DROP COLUMN IF EXISTS registration_policy;
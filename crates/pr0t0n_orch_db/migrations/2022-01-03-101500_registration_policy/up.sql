@@ -0,0 +1,15 @@
+-- What happens when an address that is not a service of the asset group connects: rejected
+-- ('strict'), registered as a service ('auto') or held until an operator approves it ('pending').
+ALTER TABLE asset_groups
+ADD COLUMN registration_policy VARCHAR(16) NOT NULL DEFAULT 'auto' CHECK (
+    registration_policy IN ('strict', 'auto', 'pending')
+  );
+-- Addresses that connected to an asset group with the 'pending' policy, awaiting approval.
+CREATE TABLE pending_services (
+  pending_service_id SERIAL PRIMARY KEY,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  address VARCHAR(255) NOT NULL,
+  first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  UNIQUE (asset_group_id, address)
+);
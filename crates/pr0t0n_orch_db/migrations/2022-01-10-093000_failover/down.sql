@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS failovers;
+ALTER TABLE services DROP COLUMN IF EXISTS failback;
+DROP TABLE IF EXISTS service_standbys;
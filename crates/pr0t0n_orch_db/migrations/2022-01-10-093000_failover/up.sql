@@ -0,0 +1,28 @@
+-- Services that take over from a processor when it disconnects, in order of preference.
+CREATE TABLE service_standbys (
+  service_id INT NOT NULL REFERENCES services(service_id) ON DELETE CASCADE,
+  standby_service_id INT NOT NULL REFERENCES services(service_id) ON DELETE CASCADE,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  priority INT NOT NULL,
+  PRIMARY KEY (service_id, standby_service_id),
+  CHECK (service_id <> standby_service_id)
+);
+CREATE TRIGGER service_standbys_revision
+AFTER
+INSERT
+  OR DELETE ON service_standbys FOR EACH ROW EXECUTE PROCEDURE bump_asset_group_revision();
+-- Whether a service takes its edges back from its standby when it reconnects.
+ALTER TABLE services
+ADD COLUMN failback BOOLEAN NOT NULL DEFAULT false;
+-- Times the edges of a service were moved to one of its standbys. Active until `ended_at`.
+CREATE TABLE failovers (
+  failover_id SERIAL PRIMARY KEY,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  service_id INT NOT NULL REFERENCES services(service_id) ON DELETE CASCADE,
+  standby_service_id INT NOT NULL REFERENCES services(service_id) ON DELETE CASCADE,
+  started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  ended_at TIMESTAMPTZ
+);
+-- A service fails over to one standby at a time.
+CREATE UNIQUE INDEX failovers_active_service_id ON failovers (service_id)
+WHERE ended_at IS NULL;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS pool_replicas;
+DROP TABLE IF EXISTS service_pools;
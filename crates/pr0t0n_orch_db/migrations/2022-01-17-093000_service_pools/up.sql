@@ -0,0 +1,27 @@
+-- Logical processors whose work is spread over replica services. Upstream services pick a replica
+-- in turn ('roundrobin'), by the lowest reported `load_metric` ('leastloaded') or by hashing a key
+-- ('stickyhash').
+CREATE TABLE service_pools (
+  service_id INT PRIMARY KEY REFERENCES services(service_id) ON DELETE CASCADE,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  strategy VARCHAR(16) NOT NULL DEFAULT 'roundrobin' CHECK (
+    strategy IN ('roundrobin', 'leastloaded', 'stickyhash')
+  ),
+  load_metric VARCHAR(255)
+);
+CREATE TRIGGER service_pools_revision
+AFTER
+INSERT
+  OR DELETE ON service_pools FOR EACH ROW EXECUTE PROCEDURE bump_asset_group_revision();
+-- Services that do the work of a pool.
+CREATE TABLE pool_replicas (
+  service_id INT NOT NULL REFERENCES service_pools(service_id) ON DELETE CASCADE,
+  replica_service_id INT NOT NULL REFERENCES services(service_id) ON DELETE CASCADE,
+  asset_group_id INT NOT NULL REFERENCES asset_groups(asset_group_id) ON DELETE CASCADE,
+  PRIMARY KEY (service_id, replica_service_id),
+  CHECK (service_id <> replica_service_id)
+);
+CREATE TRIGGER pool_replicas_revision
+AFTER
+INSERT
+  OR DELETE ON pool_replicas FOR EACH ROW EXECUTE PROCEDURE bump_asset_group_revision();
@@ -7,6 +7,7 @@ pub enum Error {
     InvalidEnumValue(String),
     SerdeJsonError(serde_json::Error),
     DatabaseSyncError(String),
+    InvalidQuery(String),
 }
 impl From<diesel::result::Error> for Error {
     fn from(err: diesel::result::Error) -> Self {
@@ -8,7 +8,8 @@ use r2d2::{Pool, PooledConnection};
 
 use diesel::{pg::PgConnection, prelude::*, r2d2::ConnectionManager};
 use dotenv::dotenv;
-use std::env;
+use serde::{Deserialize, Serialize};
+use std::{env, time::Duration};
 
 pub mod errors;
 pub use errors::Error;
@@ -30,14 +31,44 @@ pub fn get_conn(pool: &PgPool) -> Result<PgPooledConnection, r2d2::Error> {
     })
 }
 
-pub fn new_pool() -> PgPool {
-    // TODO: pass the connection URL into this function rather than extracting
-    // it from the environment within this function
-    dotenv().ok();
-    let url = env::var("DATABASE_URL").expect("DATABASE_URL was not set");
-    let manager = ConnectionManager::<PgConnection>::new(url);
+/// Settings of the database connection pool.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolSettings {
+    /// Postgres connection URL.
+    pub url: String,
+    /// Maximum number of open connections.
+    pub max_size: u32,
+    /// Seconds to wait for a connection before giving up.
+    pub connection_timeout: u64,
+}
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            // Error with libpq requires this to be 1 for now:
+            // https://github.com/diesel-rs/diesel/discussions/2947.
+            max_size: 1,
+            connection_timeout: 30,
+        }
+    }
+}
+impl PoolSettings {
+    /// Default settings connecting to `DATABASE_URL`, read from the environment or `.env`.
+    pub fn from_env() -> Self {
+        dotenv().ok();
+        Self {
+            url: env::var("DATABASE_URL").expect("DATABASE_URL was not set"),
+            ..Default::default()
+        }
+    }
+}
+
+pub fn new_pool(settings: &PoolSettings) -> PgPool {
+    let manager = ConnectionManager::<PgConnection>::new(settings.url.as_str());
     r2d2::Pool::builder()
-        .max_size(1) // Error with libpq requires this to be 1 for now: https://github.com/diesel-rs/diesel/discussions/2947.
+        .max_size(settings.max_size)
+        .connection_timeout(Duration::from_secs(settings.connection_timeout))
         .build(manager)
         .expect("Failed to build connection pool")
 }
@@ -55,7 +86,7 @@ mod tests {
 
     #[test]
     fn it_works() {
-        let pool = new_pool();
+        let pool = new_pool(&PoolSettings::from_env());
         let _conn = pool.get().unwrap();
     }
 }
@@ -1,5 +1,5 @@
-use pr0t0n_orch_db::new_pool;
+use pr0t0n_orch_db::{new_pool, PoolSettings};
 fn main() {
-    let pool = new_pool();
+    let pool = new_pool(&PoolSettings::from_env());
     let _conn = pool.get().unwrap();
 }
@@ -0,0 +1,174 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, Queryable,
+    RunQueryDsl,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    models::{generic::*, AlertStatus},
+    schema::alert_states,
+};
+
+/// The state of an alert, shared by the rules with its key in an asset group.
+#[derive(Queryable, AsChangeset, Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+#[table_name = "alert_states"]
+#[primary_key(alert_state_id)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct AlertState {
+    pub alert_state_id: i32,
+    pub asset_group_id: i32,
+    pub dedup_key: String,
+    /// Rule that last changed the state.
+    pub alert_rule_id: i32,
+    /// Address of the service the alert is about.
+    pub address: String,
+    pub status: AlertStatus,
+    pub message: String,
+    /// When the condition started to hold.
+    pub since: DateTime<Utc>,
+    pub fired_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub acknowledged_by: Option<String>,
+    /// Number of escalation tiers notified since the alert fired.
+    pub escalations: i32,
+    /// Whether notifications are held back as the alert keeps firing and resolving.
+    pub flapping: bool,
+    /// Recent times the alert fired or resolved.
+    pub transitions: Vec<DateTime<Utc>>,
+}
+impl DbFind for AlertState {
+    type Table = alert_states::table;
+}
+impl DbDelete for AlertState {
+    type Table = alert_states::table;
+}
+impl AlertState {
+    /// State of the alert with a key in an asset group, if it ever fired or was pending.
+    pub fn find_key(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        dedup_key: &str,
+    ) -> Result<Option<Self>, Error> {
+        Ok(alert_states::table
+            .filter(alert_states::asset_group_id.eq(asset_group_id))
+            .filter(alert_states::dedup_key.eq(dedup_key))
+            .first(conn)
+            .optional()?)
+    }
+
+    /// Alert states of an asset group, most recently started first.
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        Ok(alert_states::table
+            .filter(alert_states::asset_group_id.eq(asset_group_id))
+            .order((
+                alert_states::since.desc(),
+                alert_states::alert_state_id.desc(),
+            ))
+            .load(conn)?)
+    }
+
+    /// Alerts that are pending, firing or flapping, in every asset group.
+    pub fn get_open(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+        Ok(alert_states::table
+            .filter(
+                alert_states::status
+                    .ne(AlertStatus::Resolved)
+                    .or(alert_states::flapping.eq(true)),
+            )
+            .order(alert_states::alert_state_id)
+            .load(conn)?)
+    }
+
+    /// Write every field of the state back.
+    pub fn save(&self, conn: &PgConnection) -> Result<Self, Error> {
+        Ok(
+            diesel::update(alert_states::table.find(self.alert_state_id))
+                .set(self)
+                .get_result(conn)?,
+        )
+    }
+}
+
+/// Body of a request acknowledging a firing alert.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug, Default)]
+pub struct Acknowledgement {
+    /// Who acknowledged it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by: Option<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "alert_states"]
+pub struct NewAlertState<'a> {
+    pub asset_group_id: i32,
+    pub dedup_key: &'a str,
+    pub alert_rule_id: i32,
+    pub address: &'a str,
+    pub status: AlertStatus,
+    pub message: &'a str,
+    pub since: DateTime<Utc>,
+    pub fired_at: Option<DateTime<Utc>>,
+    pub transitions: Vec<DateTime<Utc>>,
+}
+impl<'a> DbInsert for NewAlertState<'a> {
+    type Table = alert_states::table;
+    type Return = AlertState;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::testing::temp_asset_group_test;
+    use crate::{errors::Error, models::*};
+
+    #[test]
+    fn test_alert_states() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let now = Utc::now();
+            let state = NewAlertState {
+                asset_group_id,
+                dedup_key: "lobby-cameras",
+                alert_rule_id: 7,
+                address: "localhost:5678",
+                status: AlertStatus::Pending,
+                message: "camera (localhost:5678) disconnected",
+                since: now,
+                fired_at: None,
+                transitions: vec![],
+            }
+            .insert(conn)?;
+            assert_eq!(
+                AlertState::find_key(conn, asset_group_id, "lobby-cameras")?,
+                Some(state.clone())
+            );
+            assert_eq!(AlertState::find_key(conn, asset_group_id, "other")?, None);
+            assert!(AlertState::get_open(conn)?.contains(&state));
+
+            let mut firing = state.clone();
+            firing.status = AlertStatus::Firing;
+            firing.fired_at = Some(now);
+            firing.acknowledged_by = Some("joshi".to_string());
+            let firing = firing.save(conn)?;
+            assert_eq!(firing.acknowledged_by.as_deref(), Some("joshi"));
+
+            // Fields set back to nothing are cleared.
+            let mut resolved = firing.clone();
+            resolved.status = AlertStatus::Resolved;
+            resolved.acknowledged_by = None;
+            resolved.transitions = vec![now];
+            let resolved = resolved.save(conn)?;
+            assert_eq!(resolved.acknowledged_by, None);
+            assert_eq!(AlertState::get_group(conn, asset_group_id)?, vec![resolved]);
+            assert!(AlertState::get_open(conn)?
+                .iter()
+                .all(|open| open.asset_group_id != asset_group_id));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::BTreeMap;
+
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    errors::Error,
+    models::{HealthStatus, Service},
+    schema::{alert_rules, services},
+};
+
+/// What makes an alert rule fire.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+#[serde(tag = "on", rename_all = "snake_case")]
+pub enum AlertTrigger {
+    /// The service's session closes or times out.
+    Disconnect,
+    /// The service's health changes to one of `to`, or to anything if `to` is empty.
+    Health {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        to: Vec<HealthStatus>,
+    },
+    /// The service reports running a config other than the one assigned to it.
+    ConfigDrift,
+    /// A metric reported by the service goes above or below a threshold.
+    Metric {
+        metric: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        above: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        below: Option<f64>,
+    },
+}
+impl AlertTrigger {
+    /// Name of the trigger as written in `on`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Disconnect => "disconnect",
+            Self::Health { .. } => "health",
+            Self::ConfigDrift => "config_drift",
+            Self::Metric { .. } => "metric",
+        }
+    }
+}
+
+/// An alert declared on a service in a system file.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct AlertRuleRepr {
+    #[serde(flatten)]
+    pub trigger: AlertTrigger,
+    /// Address alerts are emailed from.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sender: String,
+    /// Addresses alerts are emailed to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+    /// Names of the notification channels alerts are also sent through, as configured on the
+    /// server.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<String>,
+    /// Alerts with the same key in an asset group are one alert, notified once while it fires.
+    /// Defaults to the address of the service and the trigger.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Seconds the condition must hold before the alert fires.
+    #[serde(rename = "for", default, skip_serializing_if = "is_zero")]
+    pub for_seconds: u32,
+    /// Who else to notify while the alert fires unacknowledged, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub escalate: Vec<EscalationTier>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// Who to notify once an alert has fired for `after` seconds without being acknowledged.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct EscalationTier {
+    pub after: u32,
+    /// Addresses emailed, from the sender of the rule.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<String>,
+}
+
+impl AlertRuleRepr {
+    /// Key of the alerts of this rule on the service at `address`.
+    pub fn dedup_key(&self, address: &str) -> String {
+        match &self.key {
+            Some(key) => key.clone(),
+            None => format!(
+                "{}/{}",
+                address,
+                serde_json::to_string(&self.trigger).unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Check the rule can ever be sent, returning the name of the offending field otherwise.
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.recipients.is_empty() && self.channels.is_empty() {
+            return Err((
+                "recipients",
+                "Alerts need at least one recipient or channel".to_string(),
+            ));
+        }
+        if !self.recipients.is_empty() && self.sender.trim().is_empty() {
+            return Err(("sender", "Emailed alerts need a sender".to_string()));
+        }
+        if self
+            .recipients
+            .iter()
+            .any(|recipient| recipient.trim().is_empty())
+        {
+            return Err((
+                "recipients",
+                "Alert recipients must not be empty".to_string(),
+            ));
+        }
+        if self
+            .channels
+            .iter()
+            .any(|channel| channel.trim().is_empty())
+        {
+            return Err(("channels", "Alert channels must not be empty".to_string()));
+        }
+        if self.key.as_ref().is_some_and(|key| key.trim().is_empty()) {
+            return Err(("key", "Alert keys must not be empty".to_string()));
+        }
+        let mut after = 0;
+        for tier in &self.escalate {
+            if tier.after <= after {
+                return Err((
+                    "escalate",
+                    "Escalation tiers need increasing `after` seconds".to_string(),
+                ));
+            }
+            after = tier.after;
+            if tier.recipients.is_empty() && tier.channels.is_empty() {
+                return Err((
+                    "escalate",
+                    "Escalation tiers need at least one recipient or channel".to_string(),
+                ));
+            }
+            if !tier.recipients.is_empty() && self.sender.trim().is_empty() {
+                return Err(("sender", "Emailed alerts need a sender".to_string()));
+            }
+        }
+        if let AlertTrigger::Metric {
+            metric,
+            above,
+            below,
+        } = &self.trigger
+        {
+            if metric.trim().is_empty() {
+                return Err(("metric", "Metric alerts need a metric name".to_string()));
+            }
+            if above.is_none() && below.is_none() {
+                return Err((
+                    "above",
+                    format!("Alert on metric '{}' needs `above` or `below`", metric),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+type AlertRuleRow = (
+    i32,
+    i32,
+    i32,
+    Value,
+    String,
+    Vec<String>,
+    Vec<String>,
+    Option<String>,
+    i32,
+    Value,
+);
+
+/// An alert rule of a service.
+#[derive(PartialEq, Clone, Debug)]
+pub struct AlertRule {
+    pub alert_rule_id: i32,
+    pub asset_group_id: i32,
+    pub service_id: i32,
+    pub rule: AlertRuleRepr,
+}
+impl AlertRule {
+    fn from_row(row: AlertRuleRow) -> Result<Self, Error> {
+        let (
+            alert_rule_id,
+            asset_group_id,
+            service_id,
+            condition,
+            sender,
+            recipients,
+            channels,
+            key,
+            for_seconds,
+            escalation,
+        ) = row;
+        Ok(Self {
+            alert_rule_id,
+            asset_group_id,
+            service_id,
+            rule: AlertRuleRepr {
+                trigger: serde_json::from_value(condition)?,
+                sender,
+                recipients,
+                channels,
+                key,
+                for_seconds: for_seconds as u32,
+                escalate: serde_json::from_value(escalation)?,
+            },
+        })
+    }
+
+    fn from_rows(rows: Vec<AlertRuleRow>) -> Result<Vec<Self>, Error> {
+        rows.into_iter().map(Self::from_row).collect()
+    }
+
+    /// Alert rules of every service in an asset group, in the order they were declared.
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        Self::from_rows(
+            alert_rules::table
+                .filter(alert_rules::asset_group_id.eq(asset_group_id))
+                .order(alert_rules::alert_rule_id)
+                .load(conn)?,
+        )
+    }
+
+    /// Alert rules of a service, in the order they were declared.
+    pub fn get_for_service(conn: &PgConnection, service_id: i32) -> Result<Vec<Self>, Error> {
+        Self::from_rows(
+            alert_rules::table
+                .filter(alert_rules::service_id.eq(service_id))
+                .order(alert_rules::alert_rule_id)
+                .load(conn)?,
+        )
+    }
+
+    /// Alert rules of the service at an address.
+    pub fn get_by_address(conn: &PgConnection, address: &str) -> Result<Vec<Self>, Error> {
+        Self::from_rows(
+            alert_rules::table
+                .inner_join(services::table)
+                .filter(services::address.eq(address))
+                .order(alert_rules::alert_rule_id)
+                .select(alert_rules::all_columns)
+                .load(conn)?,
+        )
+    }
+
+    /// Alert rules grouped by service ID.
+    pub fn get_group_map(
+        conn: &PgConnection,
+        asset_group_id: i32,
+    ) -> Result<BTreeMap<i32, Vec<AlertRuleRepr>>, Error> {
+        let mut map: BTreeMap<i32, Vec<AlertRuleRepr>> = BTreeMap::new();
+        for alert_rule in Self::get_group(conn, asset_group_id)? {
+            map.entry(alert_rule.service_id)
+                .or_default()
+                .push(alert_rule.rule);
+        }
+        Ok(map)
+    }
+
+    /// Replace the alert rules of a service, leaving them alone if they are unchanged so the
+    /// revision of the asset group is not bumped.
+    pub fn sync_service(
+        conn: &PgConnection,
+        service: &Service,
+        rules: &[AlertRuleRepr],
+    ) -> Result<(), Error> {
+        let existing: Vec<AlertRuleRepr> = Self::get_for_service(conn, service.service_id)?
+            .into_iter()
+            .map(|alert_rule| alert_rule.rule)
+            .collect();
+        if existing == rules {
+            return Ok(());
+        }
+
+        diesel::delete(alert_rules::table.filter(alert_rules::service_id.eq(service.service_id)))
+            .execute(conn)?;
+        let rows = rules
+            .iter()
+            .map(|rule| {
+                Ok((
+                    alert_rules::asset_group_id.eq(service.asset_group_id),
+                    alert_rules::service_id.eq(service.service_id),
+                    alert_rules::condition.eq(serde_json::to_value(&rule.trigger)?),
+                    alert_rules::sender.eq(&rule.sender),
+                    alert_rules::recipients.eq(&rule.recipients),
+                    alert_rules::channels.eq(&rule.channels),
+                    alert_rules::dedup_key.eq(&rule.key),
+                    alert_rules::for_seconds.eq(rule.for_seconds as i32),
+                    alert_rules::escalation.eq(serde_json::to_value(&rule.escalate)?),
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        diesel::insert_into(alert_rules::table)
+            .values(&rows)
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::temp_asset_group_test;
+    use crate::{errors::Error, models::*};
+
+    fn disconnect_rule() -> AlertRuleRepr {
+        AlertRuleRepr {
+            trigger: AlertTrigger::Disconnect,
+            sender: "pr0t0nalerts@gmail.com".to_string(),
+            recipients: vec!["joshikatsu@gmail.com".to_string()],
+            channels: vec![],
+            key: None,
+            for_seconds: 0,
+            escalate: vec![],
+        }
+    }
+
+    #[test]
+    fn test_alert_rule_yaml() {
+        let rules: Vec<AlertRuleRepr> = serde_yaml::from_str(
+            "
+            - on: disconnect
+              sender: pr0t0nalerts@gmail.com
+              recipients: [joshikatsu@gmail.com]
+            - on: metric
+              metric: cpu
+              above: 0.9
+              sender: pr0t0nalerts@gmail.com
+              recipients: [joshikatsu@gmail.com]
+            - on: health
+              to: [Critical]
+              channels: [oncall]
+            - on: disconnect
+              for: 60
+              key: lobby-cameras
+              channels: [oncall]
+              escalate:
+                - after: 300
+                  channels: [manager]
+            ",
+        )
+        .unwrap();
+        assert_eq!(rules[0], disconnect_rule());
+        assert_eq!(
+            rules[1].trigger,
+            AlertTrigger::Metric {
+                metric: "cpu".to_string(),
+                above: Some(0.9),
+                below: None,
+            }
+        );
+        assert!(rules[1].validate().is_ok());
+
+        let mut rule = rules[1].clone();
+        rule.trigger = AlertTrigger::Metric {
+            metric: "cpu".to_string(),
+            above: None,
+            below: None,
+        };
+        assert_eq!(rule.validate().unwrap_err().0, "above");
+        rule.recipients.clear();
+        assert_eq!(rule.validate().unwrap_err().0, "recipients");
+
+        // Rules sent only through channels need no email addresses.
+        assert_eq!(rules[2].channels, vec!["oncall"]);
+        assert!(rules[2].validate().is_ok());
+        let mut rule = rules[2].clone();
+        rule.recipients = vec!["joshikatsu@gmail.com".to_string()];
+        assert_eq!(rule.validate().unwrap_err().0, "sender");
+        rule.channels = vec![" ".to_string()];
+        rule.sender = "pr0t0nalerts@gmail.com".to_string();
+        assert_eq!(rule.validate().unwrap_err().0, "channels");
+
+        let rule = &rules[3];
+        assert_eq!(rule.for_seconds, 60);
+        assert_eq!(rule.escalate[0].after, 300);
+        assert!(rule.validate().is_ok());
+        assert_eq!(rule.dedup_key("localhost:5678"), "lobby-cameras");
+        assert_eq!(
+            rules[0].dedup_key("localhost:5678"),
+            r#"localhost:5678/{"on":"disconnect"}"#
+        );
+        let mut rule = rule.clone();
+        rule.escalate.push(EscalationTier {
+            after: 300,
+            recipients: vec![],
+            channels: vec!["director".to_string()],
+        });
+        assert_eq!(rule.validate().unwrap_err().0, "escalate");
+    }
+
+    #[test]
+    fn test_sync_alert_rules() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let service = NewService {
+                asset_group_id,
+                name: "camera",
+                address: "localhost:5678",
+                service_type: ServiceType::Input,
+                ..Default::default()
+            }
+            .insert(conn)?;
+
+            let rules = vec![
+                disconnect_rule(),
+                AlertRuleRepr {
+                    trigger: AlertTrigger::Health {
+                        to: vec![HealthStatus::Critical],
+                    },
+                    channels: vec!["oncall".to_string()],
+                    for_seconds: 30,
+                    escalate: vec![EscalationTier {
+                        after: 600,
+                        recipients: vec!["joshikatsu@gmail.com".to_string()],
+                        channels: vec![],
+                    }],
+                    ..disconnect_rule()
+                },
+            ];
+            AlertRule::sync_service(conn, &service, &rules)?;
+            let revision = AssetGroup::find(conn, asset_group_id)?.revision;
+
+            // Syncing the same rules again leaves the revision alone.
+            AlertRule::sync_service(conn, &service, &rules)?;
+            assert_eq!(AssetGroup::find(conn, asset_group_id)?.revision, revision);
+
+            let by_address = AlertRule::get_by_address(conn, "localhost:5678")?;
+            assert_eq!(by_address.len(), 2);
+            assert_eq!(by_address[1].rule, rules[1]);
+            let map = AlertRule::get_group_map(conn, asset_group_id)?;
+            assert_eq!(map[&service.service_id], rules);
+
+            AlertRule::sync_service(conn, &service, &rules[1..])?;
+            assert_eq!(
+                AlertRule::get_for_service(conn, service.service_id)?.len(),
+                1
+            );
+            assert!(AssetGroup::find(conn, asset_group_id)?.revision > revision);
+            Ok(())
+        })
+    }
+}
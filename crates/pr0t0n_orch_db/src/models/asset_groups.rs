@@ -1,12 +1,117 @@
+use std::collections::HashMap;
+
+use diesel::{
+    dsl::{count_star, sql},
+    query_dsl::GroupByDsl,
+    sql_types::BigInt,
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{models::generic::*, schema::asset_groups};
+use crate::{
+    models::{generic::*, HealthStatus, RegistrationPolicy},
+    schema::{
+        alert_rules, alert_states, asset_groups, configs, dead_letters, event_logs, failovers,
+        maintenance_windows, pending_services, pool_replicas, service_edges, service_pools,
+        service_standbys, services,
+    },
+    Error,
+};
 
-#[derive(Queryable, Debug)]
+#[derive(Queryable, Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 pub struct AssetGroup {
     pub asset_group_id: i32,
     pub name: String,
     pub description: String,
+    /// Incremented by the database on every change to the group's services, configs or edges.
+    pub revision: i64,
+    pub registration_policy: RegistrationPolicy,
+}
+impl AssetGroup {
+    /// Get the revision of an asset group, or `None` if it does not exist.
+    pub fn get_revision(conn: &PgConnection, asset_group_id: i32) -> Result<Option<i64>, Error> {
+        let revision = asset_groups::table
+            .find(asset_group_id)
+            .select(asset_groups::revision)
+            .first(conn)
+            .optional()?;
+        Ok(revision)
+    }
+
+    /// Get the revision of an asset group, locking its row until the end of the transaction.
+    pub fn lock_revision(conn: &PgConnection, asset_group_id: i32) -> Result<Option<i64>, Error> {
+        let revision = asset_groups::table
+            .find(asset_group_id)
+            .select(asset_groups::revision)
+            .for_update()
+            .first(conn)
+            .optional()?;
+        Ok(revision)
+    }
+
+    /// Get the registration policy of an asset group, or `None` if it does not exist.
+    pub fn get_registration_policy(
+        conn: &PgConnection,
+        asset_group_id: i32,
+    ) -> Result<Option<RegistrationPolicy>, Error> {
+        let policy = asset_groups::table
+            .find(asset_group_id)
+            .select(asset_groups::registration_policy)
+            .first(conn)
+            .optional()?;
+        Ok(policy)
+    }
+
+    /// Apply a partial update, returning the updated asset group.
+    pub fn patch(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        changes: &AssetGroupChanges,
+    ) -> Result<Self, Error> {
+        // Diesel refuses to build an `UPDATE` without any columns to set.
+        if changes.name.is_none()
+            && changes.description.is_none()
+            && changes.registration_policy.is_none()
+        {
+            return Ok(Self::find(conn, asset_group_id)?);
+        }
+        let updated = diesel::update(asset_groups::table.find(asset_group_id))
+            .set(changes)
+            .get_result(conn)?;
+        Ok(updated)
+    }
+
+    /// Count the rows that deleting this asset group would cascade to, in every table that
+    /// references it.
+    pub fn get_dependents(
+        conn: &PgConnection,
+        asset_group_id: i32,
+    ) -> Result<AssetGroupDependents, Error> {
+        macro_rules! count {
+            ($table:ident) => {
+                $table::table
+                    .filter($table::asset_group_id.eq(asset_group_id))
+                    .select(count_star())
+                    .first(conn)?
+            };
+        }
+        Ok(AssetGroupDependents {
+            services: count!(services),
+            configs: count!(configs),
+            service_edges: count!(service_edges),
+            service_standbys: count!(service_standbys),
+            service_pools: count!(service_pools),
+            pool_replicas: count!(pool_replicas),
+            alert_rules: count!(alert_rules),
+            alert_states: count!(alert_states),
+            failovers: count!(failovers),
+            maintenance_windows: count!(maintenance_windows),
+            pending_services: count!(pending_services),
+            dead_letters: count!(dead_letters),
+            event_logs: count!(event_logs),
+        })
+    }
 }
 impl DbUpdate for AssetGroup {
     type Table = asset_groups::table;
@@ -30,17 +135,300 @@ impl<'a> DbInsert for NewAssetGroup<'a> {
     type Return = AssetGroup;
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct GetGroupRequest {
-    pub asset_group_id: i32,
+/// Request body for creating an asset group.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, PartialEq)]
+pub struct AssetGroupRepr {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub registration_policy: RegistrationPolicy,
+}
+impl AssetGroupRepr {
+    pub fn insert(&self, conn: &PgConnection) -> Result<AssetGroup, Error> {
+        let new_asset_group = NewAssetGroup {
+            name: &self.name,
+            description: &self.description,
+        };
+        let asset_group = diesel::insert_into(asset_groups::table)
+            .values((
+                new_asset_group,
+                asset_groups::registration_policy.eq(self.registration_policy),
+            ))
+            .get_result(conn)?;
+        Ok(asset_group)
+    }
+}
+
+/// Request body for a partial update of an asset group. Omitted fields are left unchanged.
+#[derive(AsChangeset, Serialize, Deserialize, JsonSchema, Debug, Default, Clone, PartialEq)]
+#[table_name = "asset_groups"]
+pub struct AssetGroupChanges {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_policy: Option<RegistrationPolicy>,
+}
+
+/// Rows owned by an asset group, removed with it when it is deleted.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct AssetGroupDependents {
+    pub services: i64,
+    pub configs: i64,
+    pub service_edges: i64,
+    pub service_standbys: i64,
+    pub service_pools: i64,
+    pub pool_replicas: i64,
+    pub alert_rules: i64,
+    pub alert_states: i64,
+    pub failovers: i64,
+    pub maintenance_windows: i64,
+    pub pending_services: i64,
+    pub dead_letters: i64,
+    pub event_logs: i64,
+}
+impl AssetGroupDependents {
+    /// Number of rows of each kind there are any of, by the name of the kind.
+    pub fn counts(&self) -> Vec<(&'static str, i64)> {
+        vec![
+            ("services", self.services),
+            ("configs", self.configs),
+            ("service edges", self.service_edges),
+            ("service standbys", self.service_standbys),
+            ("service pools", self.service_pools),
+            ("pool replicas", self.pool_replicas),
+            ("alert rules", self.alert_rules),
+            ("alert states", self.alert_states),
+            ("failovers", self.failovers),
+            ("maintenance windows", self.maintenance_windows),
+            ("pending services", self.pending_services),
+            ("dead letters", self.dead_letters),
+            ("event logs", self.event_logs),
+        ]
+        .into_iter()
+        .filter(|&(_kind, count)| count > 0)
+        .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts().is_empty()
+    }
+}
+
+/// An asset group with a summary of the health of its services.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct AssetGroupSummary {
+    #[serde(flatten)]
+    pub asset_group: AssetGroup,
+    pub service_count: i64,
+    /// Worst health status of any service, or `None` for an empty group.
+    pub health_status: Option<HealthStatus>,
+    /// Number of services in each health status.
+    pub health_counts: HashMap<HealthStatus, i64>,
+}
+impl AssetGroupSummary {
+    fn new(asset_group: AssetGroup, health_counts: HashMap<HealthStatus, i64>) -> Self {
+        Self {
+            asset_group,
+            service_count: health_counts.values().sum(),
+            health_status: health_counts.keys().copied().max_by_key(|h| h.severity()),
+            health_counts,
+        }
+    }
+
+    /// Count services by health status, keyed by asset group.
+    fn get_health_counts(
+        conn: &PgConnection,
+        asset_group_id: Option<i32>,
+    ) -> Result<HashMap<i32, HashMap<HealthStatus, i64>>, Error> {
+        let mut query = services::table
+            .group_by((services::asset_group_id, services::health_status))
+            // Diesel 1.4 can't mix `count_star()` with grouped columns, so count in SQL.
+            .select((
+                services::asset_group_id,
+                services::health_status,
+                sql::<BigInt>("COUNT(*)"),
+            ))
+            .into_boxed();
+        if let Some(asset_group_id) = asset_group_id {
+            query = query.filter(services::asset_group_id.eq(asset_group_id));
+        }
+        let rows: Vec<(i32, HealthStatus, i64)> = query.load(conn)?;
+
+        let mut counts: HashMap<i32, HashMap<HealthStatus, i64>> = HashMap::new();
+        for (asset_group_id, health_status, count) in rows {
+            counts
+                .entry(asset_group_id)
+                .or_default()
+                .insert(health_status, count);
+        }
+        Ok(counts)
+    }
+
+    /// Summarise every asset group, ordered by ID.
+    pub fn get_all(conn: &PgConnection) -> Result<Vec<Self>, Error> {
+        let asset_groups: Vec<AssetGroup> = asset_groups::table
+            .order(asset_groups::asset_group_id)
+            .load(conn)?;
+        let mut counts = Self::get_health_counts(conn, None)?;
+        Ok(asset_groups
+            .into_iter()
+            .map(|asset_group| {
+                let health_counts = counts
+                    .remove(&asset_group.asset_group_id)
+                    .unwrap_or_default();
+                Self::new(asset_group, health_counts)
+            })
+            .collect())
+    }
+
+    pub fn get(conn: &PgConnection, asset_group_id: i32) -> Result<Self, Error> {
+        let asset_group = AssetGroup::find(conn, asset_group_id)?;
+        let health_counts = Self::get_health_counts(conn, Some(asset_group_id))?
+            .remove(&asset_group_id)
+            .unwrap_or_default();
+        Ok(Self::new(asset_group, health_counts))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::Utc;
+
+    use crate::models::*;
     use crate::testing::temp_asset_group_test;
 
     #[test]
     fn test_asset_group() {
         temp_asset_group_test(|_conn, _asset_group| Ok(())).unwrap();
     }
+
+    #[test]
+    fn test_revision() {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let revision = || AssetGroup::get_revision(conn, asset_group_id).map(Option::unwrap);
+            assert_eq!(revision()?, 0);
+
+            let mut service = NewService {
+                asset_group_id,
+                name: "test_revision",
+                address: "test_revision:1111",
+                service_type: ServiceType::Input,
+                ..Default::default()
+            }
+            .insert(conn)?;
+            assert_eq!(revision()?, 1);
+
+            // Updates only count when something declared changed.
+            service.update(conn)?;
+            assert_eq!(revision()?, 1);
+            service.health_status = HealthStatus::Warning;
+            service.update(conn)?;
+            Service::upsert_healthy_address(conn, asset_group_id, &service.address)?;
+            Service::disconnect_address(conn, &service.address)?;
+            assert_eq!(revision()?, 1);
+            service.name = "test_revision_renamed".to_string();
+            service.update(conn)?;
+            assert_eq!(revision()?, 2);
+
+            Service::delete(conn, service.service_id)?;
+            assert_eq!(revision()?, 3);
+            assert_eq!(AssetGroup::get_revision(conn, -1)?, None);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_registration_policy() {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            assert_eq!(asset_group.registration_policy, RegistrationPolicy::Auto);
+            let changes = AssetGroupChanges {
+                registration_policy: Some(RegistrationPolicy::Pending),
+                ..Default::default()
+            };
+            let patched = AssetGroup::patch(conn, asset_group_id, &changes)?;
+            assert_eq!(patched.registration_policy, RegistrationPolicy::Pending);
+            assert_eq!(
+                AssetGroup::get_registration_policy(conn, asset_group_id)?,
+                Some(RegistrationPolicy::Pending)
+            );
+            assert_eq!(AssetGroup::get_registration_policy(conn, -1)?, None);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_asset_group_summary() {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let summary = AssetGroupSummary::get(conn, asset_group_id)?;
+            assert_eq!(summary.service_count, 0);
+            assert_eq!(summary.health_status, None);
+            assert!(AssetGroup::get_dependents(conn, asset_group_id)?.is_empty());
+
+            // Rows that are not services count too, since they are deleted with the group.
+            let pending =
+                PendingService::upsert(conn, asset_group_id, "test_pending:3333", Utc::now())?;
+            let dependents = AssetGroup::get_dependents(conn, asset_group_id)?;
+            assert_eq!(dependents.counts(), vec![("pending services", 1)]);
+            PendingService::delete(conn, pending.pending_service_id)?;
+
+            let healthy = NewService {
+                asset_group_id,
+                name: "test_healthy",
+                address: "test_healthy:1111",
+                service_type: ServiceType::Input,
+                health_status: HealthStatus::Healthy,
+                ..Default::default()
+            }
+            .insert(conn)?;
+            let critical = NewService {
+                asset_group_id,
+                name: "test_critical",
+                address: "test_critical:2222",
+                service_type: ServiceType::Input,
+                health_status: HealthStatus::Critical,
+                ..Default::default()
+            }
+            .insert(conn)?;
+            vec![ServiceEdge {
+                asset_group_id,
+                input_service_id: healthy.service_id,
+                output_service_id: critical.service_id,
+            }]
+            .insert_all(conn)?;
+
+            let summary = AssetGroupSummary::get(conn, asset_group_id)?;
+            assert_eq!(summary.service_count, 2);
+            assert_eq!(summary.health_status, Some(HealthStatus::Critical));
+            assert_eq!(summary.health_counts.get(&HealthStatus::Healthy), Some(&1));
+            assert!(AssetGroupSummary::get_all(conn)?
+                .iter()
+                .any(|s| s.asset_group.asset_group_id == asset_group_id));
+
+            let dependents = AssetGroup::get_dependents(conn, asset_group_id)?;
+            assert_eq!(dependents.services, 2);
+            assert_eq!(dependents.configs, 0);
+            assert_eq!(dependents.service_edges, 1);
+
+            let patched = AssetGroup::patch(
+                conn,
+                asset_group_id,
+                &AssetGroupChanges {
+                    description: Some("Patched".to_string()),
+                    ..Default::default()
+                },
+            )?;
+            assert_eq!(patched.name, asset_group.name);
+            assert_eq!(patched.description, "Patched");
+            Ok(())
+        })
+        .unwrap();
+    }
 }
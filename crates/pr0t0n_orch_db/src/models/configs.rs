@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 use std::convert::TryFrom;
 
-use crate::models::{assets::*, generic::*};
-use crate::schema::configs;
+use crate::models::{assets::*, generic::*, services::Service};
+use crate::schema::{configs, services};
 use crate::Error;
-use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Queryable, AsChangeset, Debug)]
@@ -49,6 +50,65 @@ impl Config {
         Ok(map)
     }
 
+    pub fn find_by_name(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        name: &str,
+    ) -> Result<Option<Self>, Error> {
+        let result: Option<Self> = configs::table
+            .filter(configs::asset_group_id.eq(asset_group_id))
+            .filter(configs::name.eq(name))
+            .first(conn)
+            .optional()?;
+        Ok(result)
+    }
+
+    /// Find a config by name, locking its row until the end of the transaction. Services cannot
+    /// start using it until then.
+    pub fn lock_by_name(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        name: &str,
+    ) -> Result<Option<Self>, Error> {
+        let result: Option<Self> = configs::table
+            .filter(configs::asset_group_id.eq(asset_group_id))
+            .filter(configs::name.eq(name))
+            .for_update()
+            .first(conn)
+            .optional()?;
+        Ok(result)
+    }
+
+    /// Get the addresses of services using each config in an asset group.
+    pub fn get_users(
+        conn: &PgConnection,
+        asset_group_id: i32,
+    ) -> Result<HashMap<i32, Vec<String>>, Error> {
+        let mut results: Vec<(Option<i32>, String)> = services::table
+            .filter(services::asset_group_id.eq(asset_group_id))
+            .filter(services::config_id.is_not_null())
+            .select((services::config_id, services::address))
+            .order(services::address)
+            .get_results(conn)?;
+
+        let mut map: HashMap<i32, Vec<String>> = HashMap::new();
+        for (config_id, address) in results.drain(..) {
+            if let Some(config_id) = config_id {
+                map.entry(config_id).or_default().push(address);
+            }
+        }
+        Ok(map)
+    }
+
+    /// Get the services using this config, ordered by address.
+    pub fn get_services(&self, conn: &PgConnection) -> Result<Vec<Service>, Error> {
+        let results: Vec<Service> = services::table
+            .filter(services::config_id.eq(self.config_id))
+            .order(services::address)
+            .get_results(conn)?;
+        Ok(results)
+    }
+
     pub fn delete_all(conn: &PgConnection, config_ids: &[i32]) -> Result<usize, Error> {
         let num_deleted =
             diesel::delete(configs::dsl::configs.filter(configs::config_id.eq_any(config_ids)))
@@ -58,9 +118,10 @@ impl Config {
 
     /// Custom update for service.
     pub fn update(&self, conn: &PgConnection) -> Result<usize, Error> {
-        let result: usize = diesel::update(configs::table)
+        let result: usize = diesel::update(configs::table.find(self.config_id))
             .set((
                 configs::asset_group_id.eq(self.asset_group_id),
+                configs::name.eq(self.name.clone()),
                 configs::description.eq(self.description.clone()),
                 configs::json_config.eq(self.json_config.clone()),
             ))
@@ -109,7 +170,7 @@ impl DbInsertAll for Vec<NewConfig<'_>> {
 }
 
 /// Config representation for ergonomic config.
-#[derive(Serialize, Deserialize, PartialEq, Default, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Default, Clone, Debug)]
 pub struct ConfigRepr {
     pub name: String,
     pub description: String,
@@ -128,6 +189,12 @@ impl ConfigRepr {
             json_config: &self.json_config_str,
         }
     }
+
+    /// Insert this config into an asset group.
+    pub fn insert(&mut self, conn: &PgConnection, asset_group_id: i32) -> Result<Config, Error> {
+        let config = self.as_insertable(asset_group_id).insert(conn)?;
+        Ok(config)
+    }
 }
 impl TryFrom<Config> for ConfigRepr {
     type Error = Error;
@@ -140,12 +207,36 @@ impl TryFrom<Config> for ConfigRepr {
         })
     }
 }
+/// Config together with the addresses of the services using it.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Default, Clone, Debug)]
+pub struct ConfigUsageRepr {
+    #[serde(flatten)]
+    pub config: ConfigRepr,
+    pub used_by: Vec<String>,
+}
+impl ConfigUsageRepr {
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        let mut users = Config::get_users(conn, asset_group_id)?;
+        let mut configs = Config::get_group(conn, asset_group_id)?;
+        configs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut reprs: Vec<Self> = Vec::with_capacity(configs.len());
+        for config in configs {
+            reprs.push(Self {
+                used_by: users.remove(&config.config_id).unwrap_or_default(),
+                config: ConfigRepr::try_from(config)?,
+            });
+        }
+        Ok(reprs)
+    }
+}
+
 impl<'a> AssetRepr<'a> for ConfigRepr {
     type Asset = Config;
 
     fn try_merge_asset(&self, asset: &mut Self::Asset) -> Result<(), Error> {
         asset.name = self.name.clone();
-        asset.description = self.name.clone();
+        asset.description = self.description.clone();
         asset.json_config = serde_json::to_string(&self.json_config)?;
         Ok(())
     }
@@ -218,4 +309,54 @@ mod tests {
         })
         .unwrap();
     }
+
+    #[test]
+    fn test_config_usage() {
+        temp_asset_group_test(|conn: &PgConnection, asset_group: &AssetGroup| {
+            let asset_group_id = asset_group.asset_group_id;
+            let mut used = ConfigRepr {
+                name: "test_used_config".to_string(),
+                description: "Used config.".to_string(),
+                json_config: serde_json::json!({ "key": "value" }),
+                ..Default::default()
+            };
+            let used_config = used.insert(conn, asset_group_id)?;
+            ConfigRepr {
+                name: "test_unused_config".to_string(),
+                description: "Unused config.".to_string(),
+                json_config: serde_json::json!({}),
+                ..Default::default()
+            }
+            .insert(conn, asset_group_id)?;
+            NewService {
+                asset_group_id,
+                name: "test_config_user",
+                address: "test_config_user:2222",
+                service_type: ServiceType::Input,
+                config_id: Some(used_config.config_id),
+                ..Default::default()
+            }
+            .insert(conn)?;
+
+            let found = Config::find_by_name(conn, asset_group_id, "test_used_config")?;
+            assert_eq!(found.map(|c| c.config_id), Some(used_config.config_id));
+            assert!(Config::find_by_name(conn, asset_group_id, "missing")?.is_none());
+            let locked = Config::lock_by_name(conn, asset_group_id, "test_used_config")?;
+            assert_eq!(locked.map(|c| c.config_id), Some(used_config.config_id));
+            assert!(Config::lock_by_name(conn, asset_group_id, "missing")?.is_none());
+
+            let usage = ConfigUsageRepr::get_group(conn, asset_group_id)?;
+            assert_eq!(usage.len(), 2);
+            assert_eq!(usage[0].config.name, "test_unused_config");
+            assert!(usage[0].used_by.is_empty());
+            assert_eq!(usage[1].config.name, "test_used_config");
+            assert_eq!(usage[1].used_by, vec!["test_config_user:2222".to_string()]);
+
+            let users = used_config.get_services(conn)?;
+            assert_eq!(users.len(), 1);
+            assert_eq!(users[0].name, "test_config_user");
+            Ok(())
+        })
+        .unwrap();
+    }
 }
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{errors::Error, models::generic::*, schema::dead_letters};
+
+/// A notification that could not be delivered after every retry.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct DeadLetter {
+    pub dead_letter_id: i32,
+    pub asset_group_id: i32,
+    pub alert_rule_id: i32,
+    /// Address of the service the alert is about.
+    pub address: String,
+    /// Name of the channel it was sent through.
+    pub channel: String,
+    /// What was being sent, as the channel would send it again.
+    pub payload: Value,
+    pub error: String,
+    pub attempts: i32,
+    pub failed_at: DateTime<Utc>,
+}
+impl DbFind for DeadLetter {
+    type Table = dead_letters::table;
+}
+impl DbDelete for DeadLetter {
+    type Table = dead_letters::table;
+}
+impl DeadLetter {
+    /// Dead letters of an asset group, oldest first.
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        Ok(dead_letters::table
+            .filter(dead_letters::asset_group_id.eq(asset_group_id))
+            .order((dead_letters::failed_at, dead_letters::dead_letter_id))
+            .load(conn)?)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "dead_letters"]
+pub struct NewDeadLetter<'a> {
+    pub asset_group_id: i32,
+    pub alert_rule_id: i32,
+    pub address: &'a str,
+    pub channel: &'a str,
+    pub payload: &'a Value,
+    pub error: &'a str,
+    pub attempts: i32,
+}
+impl<'a> DbInsert for NewDeadLetter<'a> {
+    type Table = dead_letters::table;
+    type Return = DeadLetter;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::temp_asset_group_test;
+    use crate::{errors::Error, models::*};
+
+    #[test]
+    fn test_dead_letters() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let payload = serde_json::json!({"message": "camera (localhost:5678) disconnected"});
+            let dead_letter = NewDeadLetter {
+                asset_group_id,
+                alert_rule_id: 7,
+                address: "localhost:5678",
+                channel: "oncall",
+                payload: &payload,
+                error: "Webhook responded 503",
+                attempts: 4,
+            }
+            .insert(conn)?;
+            assert_eq!(dead_letter.payload, payload);
+
+            let dead_letters = DeadLetter::get_group(conn, asset_group_id)?;
+            assert_eq!(dead_letters, vec![dead_letter.clone()]);
+            DeadLetter::delete(conn, dead_letter.dead_letter_id)?;
+            assert!(DeadLetter::get_group(conn, asset_group_id)?.is_empty());
+            Ok(())
+        })
+    }
+}
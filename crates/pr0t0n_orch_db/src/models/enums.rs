@@ -1,11 +1,22 @@
 use diesel_enum::DbEnum;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::errors::Error;
 use diesel::sql_types::VarChar;
 
 #[derive(
-    Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, DbEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    DbEnum,
 )]
 #[sql_type = "VarChar"]
 #[error_fn = "Error::invalid_enum"]
@@ -24,7 +35,18 @@ impl Default for ServiceType {
 
 /// Health status for a service.
 #[derive(
-    Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, DbEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    DbEnum,
 )]
 #[sql_type = "VarChar"]
 #[error_fn = "Error::invalid_enum"]
@@ -34,9 +56,128 @@ pub enum HealthStatus {
     Disconnected,
     Warning,
     Critical,
+    /// Not reported since the orchestrator started, so it may or may not still be connected.
+    Unknown,
 }
 impl Default for HealthStatus {
     fn default() -> Self {
         Self::Healthy
     }
 }
+impl HealthStatus {
+    /// How bad this status is, for picking the worst of several.
+    pub fn severity(&self) -> u8 {
+        match self {
+            Self::Healthy => 0,
+            Self::Warning => 1,
+            Self::Unknown => 2,
+            Self::Disconnected => 3,
+            Self::Critical => 4,
+        }
+    }
+}
+
+/// Where an alert is in its life.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    DbEnum,
+)]
+#[sql_type = "VarChar"]
+#[error_fn = "Error::invalid_enum"]
+#[error_type = "Error"]
+pub enum AlertStatus {
+    /// The condition holds, but not for long enough to fire yet.
+    Pending,
+    Firing,
+    Resolved,
+}
+
+/// How often a maintenance window repeats.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    DbEnum,
+)]
+#[sql_type = "VarChar"]
+#[error_fn = "Error::invalid_enum"]
+#[error_type = "Error"]
+pub enum Recurrence {
+    #[default]
+    Once,
+    Daily,
+    Weekly,
+}
+
+/// What happens when an address that is not a service of an asset group connects to it.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    DbEnum,
+)]
+#[sql_type = "VarChar"]
+#[error_fn = "Error::invalid_enum"]
+#[error_type = "Error"]
+pub enum RegistrationPolicy {
+    /// Reject the connection.
+    Strict,
+    /// Register the address as a service.
+    #[default]
+    Auto,
+    /// Hold the address as a pending service until an operator approves it.
+    Pending,
+}
+
+/// How upstream services spread work over the replicas of a pool.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    DbEnum,
+)]
+#[sql_type = "VarChar"]
+#[error_fn = "Error::invalid_enum"]
+#[error_type = "Error"]
+pub enum BalancingStrategy {
+    /// Each replica in turn.
+    #[default]
+    RoundRobin,
+    /// The replica that last reported the lowest load.
+    LeastLoaded,
+    /// The replica picked by hashing a key of the work, so the same key keeps the same replica.
+    StickyHash,
+}
@@ -0,0 +1,280 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{errors::Error, models::HealthStatus, schema::event_logs};
+
+/// Something that happened to an asset group, streamed to watchers.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Event {
+    pub timestamp: DateTime<Utc>,
+    pub asset_group_id: i32,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+impl Event {
+    pub fn new(asset_group_id: i32, kind: EventKind) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            asset_group_id,
+            kind,
+        }
+    }
+
+    /// Store this event in the log of its asset group.
+    pub fn log(&self, conn: &PgConnection) -> Result<(), Error> {
+        diesel::insert_into(event_logs::table)
+            .values((
+                event_logs::asset_group_id.eq(self.asset_group_id),
+                event_logs::logged_at.eq(self.timestamp),
+                event_logs::entry.eq(serde_json::to_value(&self.kind)?),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Logged events of an asset group, oldest first.
+    pub fn get_logged(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        let rows: Vec<(DateTime<Utc>, Value)> = event_logs::table
+            .filter(event_logs::asset_group_id.eq(asset_group_id))
+            .order((event_logs::logged_at, event_logs::event_log_id))
+            .select((event_logs::logged_at, event_logs::entry))
+            .load(conn)?;
+        rows.into_iter()
+            .map(|(timestamp, entry)| {
+                Ok(Self {
+                    timestamp,
+                    asset_group_id,
+                    kind: serde_json::from_value(entry)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// A service opened a websocket session.
+    Connected { address: String },
+    /// A service's websocket session closed or timed out. `maintenance` is the maintenance
+    /// window covering the service then, if any, so it is not held against its uptime.
+    Disconnected {
+        address: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        maintenance: Option<i32>,
+    },
+    /// The orchestrator set the health of services it has no session for, after it started.
+    Reconciled {
+        addresses: Vec<String>,
+        health_status: HealthStatus,
+    },
+    /// A service reported a new health status, during the maintenance window `maintenance` if
+    /// one covers the service.
+    HealthChanged {
+        address: String,
+        health_status: HealthStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        maintenance: Option<i32>,
+    },
+    /// An alert rule of a service fired.
+    AlertFired {
+        address: String,
+        alert_rule_id: i32,
+        message: String,
+    },
+    /// A firing alert's condition no longer holds.
+    AlertResolved {
+        address: String,
+        alert_rule_id: i32,
+        message: String,
+    },
+    /// An alert started or stopped flapping. Its notifications are held back while it flaps.
+    AlertFlapping {
+        address: String,
+        alert_rule_id: i32,
+        flapping: bool,
+    },
+    /// A firing alert was not acknowledged in time, and escalation tier `tier` (from 1) was
+    /// notified.
+    AlertEscalated {
+        address: String,
+        alert_rule_id: i32,
+        tier: u32,
+    },
+    /// Someone acknowledged a firing alert, which stops its escalation.
+    AlertAcknowledged {
+        address: String,
+        alert_rule_id: i32,
+        by: Option<String>,
+    },
+    /// A notification of a fired alert was sent, after `attempts` tries.
+    NotificationSent {
+        address: String,
+        alert_rule_id: i32,
+        channel: String,
+        recipients: Vec<String>,
+        attempts: u32,
+    },
+    /// A notification of a fired alert could not be sent.
+    NotificationFailed {
+        address: String,
+        alert_rule_id: i32,
+        channel: String,
+        error: String,
+        attempts: u32,
+    },
+    /// A processor disconnected and its routing moved to the standby `standby`.
+    FailedOver { address: String, standby: String },
+    /// A processor reconnected and took its routing back from the standby `standby`.
+    FailedBack { address: String, standby: String },
+    /// A processor disconnected, but none of its standbys was connected to take over.
+    FailoverFailed { address: String },
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connected { address } => write!(f, "{} connected", address),
+            Self::Disconnected {
+                address,
+                maintenance,
+            } => {
+                write!(f, "{} disconnected", address)?;
+                write_maintenance(f, *maintenance)
+            }
+            Self::Reconciled {
+                addresses,
+                health_status,
+            } => write!(
+                f,
+                "{} marked {:?} after a restart",
+                addresses.join(", "),
+                health_status
+            ),
+            Self::HealthChanged {
+                address,
+                health_status,
+                maintenance,
+            } => {
+                write!(f, "{} is {:?}", address, health_status)?;
+                write_maintenance(f, *maintenance)
+            }
+            Self::AlertFired { message, .. } => write!(f, "Alert: {}", message),
+            Self::AlertResolved { message, .. } => write!(f, "Resolved: {}", message),
+            Self::AlertFlapping {
+                address, flapping, ..
+            } => {
+                if *flapping {
+                    write!(f, "Alert on {} is flapping", address)
+                } else {
+                    write!(f, "Alert on {} stopped flapping", address)
+                }
+            }
+            Self::AlertEscalated { address, tier, .. } => {
+                write!(f, "Alert on {} escalated to tier {}", address, tier)
+            }
+            Self::AlertAcknowledged { address, by, .. } => match by {
+                Some(by) => write!(f, "Alert on {} acknowledged by {}", address, by),
+                None => write!(f, "Alert on {} acknowledged", address),
+            },
+            Self::NotificationSent {
+                address,
+                channel,
+                recipients,
+                ..
+            } => write!(
+                f,
+                "Sent {} alert on {} to {}",
+                channel,
+                address,
+                recipients.join(", ")
+            ),
+            Self::NotificationFailed {
+                address,
+                channel,
+                error,
+                attempts,
+                ..
+            } => write!(
+                f,
+                "Failed to send {} alert on {} after {} attempts: {}",
+                channel, address, attempts, error
+            ),
+            Self::FailedOver { address, standby } => {
+                write!(f, "{} failed over to {}", address, standby)
+            }
+            Self::FailedBack { address, standby } => {
+                write!(f, "{} failed back from {}", address, standby)
+            }
+            Self::FailoverFailed { address } => {
+                write!(f, "{} has no connected standby to fail over to", address)
+            }
+        }
+    }
+}
+
+fn write_maintenance(f: &mut fmt::Formatter<'_>, maintenance: Option<i32>) -> fmt::Result {
+    match maintenance {
+        Some(maintenance_window_id) => {
+            write!(f, " during maintenance window {}", maintenance_window_id)
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_asset_group_test;
+
+    #[test]
+    fn test_event_json() {
+        let event = Event::new(
+            3,
+            EventKind::Connected {
+                address: "localhost:123".to_string(),
+            },
+        );
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["event"], "connected");
+        assert_eq!(value["address"], "localhost:123");
+        assert_eq!(value["asset_group_id"], 3);
+        assert_eq!(serde_json::from_value::<Event>(value).unwrap(), event);
+    }
+
+    #[test]
+    fn test_event_log() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let events = vec![
+                Event::new(
+                    asset_group_id,
+                    EventKind::Connected {
+                        address: "localhost:123".to_string(),
+                    },
+                ),
+                Event::new(
+                    asset_group_id,
+                    EventKind::Reconciled {
+                        addresses: vec!["localhost:123".to_string(), "localhost:456".to_string()],
+                        health_status: HealthStatus::Unknown,
+                    },
+                ),
+            ];
+            for event in &events {
+                event.log(conn)?;
+            }
+            let logged = Event::get_logged(conn, asset_group_id)?;
+            assert_eq!(logged.len(), 2);
+            assert_eq!(logged[1].kind, events[1].kind);
+            // Postgres keeps microseconds.
+            let drift = logged[0].timestamp - events[0].timestamp;
+            assert!(drift.num_milliseconds().abs() < 1);
+            Ok(())
+        })
+    }
+}
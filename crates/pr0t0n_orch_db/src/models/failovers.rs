@@ -0,0 +1,290 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, Queryable,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    models::{generic::*, Service},
+    schema::{failovers, service_edges, service_standbys},
+};
+
+/// A processor that takes over the routing of another while it is disconnected.
+#[derive(Insertable, Queryable, Debug, Default)]
+pub struct ServiceStandby {
+    pub service_id: i32,
+    pub standby_service_id: i32,
+    pub asset_group_id: i32,
+    /// Standbys with a lower priority are failed over to first.
+    pub priority: i32,
+}
+impl DbInsertAll for Vec<ServiceStandby> {
+    type Table = service_standbys::table;
+    type Return = ServiceStandby;
+}
+impl ServiceStandby {
+    /// Get all standbys for an asset_group_id, in priority order per service.
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        let results: Vec<Self> = service_standbys::table
+            .filter(service_standbys::asset_group_id.eq(asset_group_id))
+            .order((service_standbys::service_id, service_standbys::priority))
+            .get_results(conn)?;
+        Ok(results)
+    }
+}
+
+/// The routing of a disconnected processor, moved to one of its standbys. The failover is active
+/// until `ended_at` is set.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Failover {
+    pub failover_id: i32,
+    pub asset_group_id: i32,
+    pub service_id: i32,
+    pub standby_service_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+impl DbFind for Failover {
+    type Table = failovers::table;
+}
+impl Failover {
+    /// Failovers of an asset group, latest first.
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        let results: Vec<Self> = failovers::table
+            .filter(failovers::asset_group_id.eq(asset_group_id))
+            .order((failovers::started_at.desc(), failovers::failover_id.desc()))
+            .get_results(conn)?;
+        Ok(results)
+    }
+
+    /// Failovers of an asset group that have not ended.
+    pub fn get_active_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        let results: Vec<Self> = failovers::table
+            .filter(failovers::asset_group_id.eq(asset_group_id))
+            .filter(failovers::ended_at.is_null())
+            .order(failovers::failover_id)
+            .get_results(conn)?;
+        Ok(results)
+    }
+
+    /// The active failover away from a service, if any.
+    pub fn find_active(conn: &PgConnection, service_id: i32) -> Result<Option<Self>, Error> {
+        let result = failovers::table
+            .filter(failovers::service_id.eq(service_id))
+            .filter(failovers::ended_at.is_null())
+            .first(conn)
+            .optional()?;
+        Ok(result)
+    }
+
+    /// The active failover to a standby service, if any.
+    pub fn find_active_by_standby(
+        conn: &PgConnection,
+        standby_service_id: i32,
+    ) -> Result<Option<Self>, Error> {
+        let result = failovers::table
+            .filter(failovers::standby_service_id.eq(standby_service_id))
+            .filter(failovers::ended_at.is_null())
+            .first(conn)
+            .optional()?;
+        Ok(result)
+    }
+
+    /// Move the routing of a service to its standby at `now`.
+    pub fn start(
+        conn: &PgConnection,
+        service: &Service,
+        standby: &Service,
+        now: DateTime<Utc>,
+    ) -> Result<Self, Error> {
+        conn.transaction(|| {
+            let failover: Self = diesel::insert_into(failovers::table)
+                .values((
+                    failovers::asset_group_id.eq(service.asset_group_id),
+                    failovers::service_id.eq(service.service_id),
+                    failovers::standby_service_id.eq(standby.service_id),
+                    failovers::started_at.eq(now),
+                ))
+                .get_result(conn)?;
+            failover.apply_edges(conn)?;
+            Ok(failover)
+        })
+    }
+
+    /// End the failover at `now`, moving the routing back to the service it belongs to.
+    pub fn end(&self, conn: &PgConnection, now: DateTime<Utc>) -> Result<Self, Error> {
+        conn.transaction(|| {
+            self.restore_edges(conn)?;
+            let failover = diesel::update(failovers::table.find(self.failover_id))
+                .set(failovers::ended_at.eq(now))
+                .get_result(conn)?;
+            Ok(failover)
+        })
+    }
+
+    /// Move the routing on from the current standby to another at `now`, ending this failover
+    /// and returning the new one.
+    pub fn switch(
+        &self,
+        conn: &PgConnection,
+        service: &Service,
+        standby: &Service,
+        now: DateTime<Utc>,
+    ) -> Result<Self, Error> {
+        conn.transaction(|| {
+            self.end(conn, now)?;
+            Self::start(conn, service, standby, now)
+        })
+    }
+
+    /// Move the edges of the service to the standby.
+    pub fn apply_edges(&self, conn: &PgConnection) -> Result<(), Error> {
+        move_edges(conn, self.service_id, self.standby_service_id)
+    }
+
+    /// Move the edges of the standby back to the service.
+    pub fn restore_edges(&self, conn: &PgConnection) -> Result<(), Error> {
+        move_edges(conn, self.standby_service_id, self.service_id)
+    }
+}
+
+/// Replace a service with another in all of its edges. Edges are updated rather than deleted and
+/// inserted, so the asset group revision only changes when the declared system does.
+fn move_edges(conn: &PgConnection, from_service_id: i32, to_service_id: i32) -> Result<(), Error> {
+    diesel::update(
+        service_edges::table.filter(service_edges::input_service_id.eq(from_service_id)),
+    )
+    .set(service_edges::input_service_id.eq(to_service_id))
+    .execute(conn)?;
+    diesel::update(
+        service_edges::table.filter(service_edges::output_service_id.eq(from_service_id)),
+    )
+    .set(service_edges::output_service_id.eq(to_service_id))
+    .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::testing::temp_asset_group_test;
+    use crate::{errors::Error, models::*};
+
+    #[test]
+    fn test_failovers() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let service = |address: &'static str, service_type: ServiceType| {
+                NewService {
+                    asset_group_id,
+                    name: address,
+                    address,
+                    service_type,
+                    health_status: HealthStatus::Healthy,
+                    ..Default::default()
+                }
+                .insert(conn)
+            };
+            let camera = service("localhost:9611", ServiceType::Input)?;
+            let detector = service("localhost:9612", ServiceType::Processor)?;
+            let backup = service("localhost:9613", ServiceType::Processor)?;
+            let spare = service("localhost:9614", ServiceType::Processor)?;
+            let arm = service("localhost:9615", ServiceType::Output)?;
+            let edge = |input: &Service, output: &Service| ServiceEdge {
+                asset_group_id,
+                input_service_id: input.service_id,
+                output_service_id: output.service_id,
+            };
+            vec![edge(&camera, &detector), edge(&detector, &arm)].insert_all(conn)?;
+            vec![
+                ServiceStandby {
+                    service_id: detector.service_id,
+                    standby_service_id: spare.service_id,
+                    asset_group_id,
+                    priority: 1,
+                },
+                ServiceStandby {
+                    service_id: detector.service_id,
+                    standby_service_id: backup.service_id,
+                    asset_group_id,
+                    priority: 0,
+                },
+            ]
+            .insert_all(conn)?;
+            let standbys: Vec<String> = detector
+                .get_standbys(conn)?
+                .into_iter()
+                .map(|standby| standby.address)
+                .collect();
+            assert_eq!(standbys, vec!["localhost:9613", "localhost:9614"]);
+
+            let now = Utc.with_ymd_and_hms(2022, 1, 10, 9, 30, 0).unwrap();
+            let failover = Failover::start(conn, &detector, &backup, now)?;
+            assert_eq!(
+                Failover::find_active(conn, detector.service_id)?,
+                Some(failover.clone())
+            );
+            assert_eq!(
+                Routing::get(conn, &backup)?,
+                Routing {
+                    inputs: vec!["localhost:9611".to_string()],
+                    outputs: vec!["localhost:9615".to_string()],
+                    ..Default::default()
+                }
+            );
+            assert_eq!(Routing::get(conn, &detector)?, Routing::default());
+
+            // Declared routing still belongs to the processor.
+            let reprs = ServiceRepr::get_group(conn, asset_group_id)?;
+            let camera_repr = reprs.iter().find(|r| r.address == camera.address).unwrap();
+            assert_eq!(camera_repr.output_addresses, vec![detector.address.clone()]);
+            let detector_repr = reprs
+                .iter()
+                .find(|r| r.address == detector.address)
+                .unwrap();
+            assert_eq!(detector_repr.output_addresses, vec![arm.address.clone()]);
+            assert_eq!(detector_repr.standby_addresses, standbys);
+
+            // Syncing the declared system keeps the failover in place and the revision as it was.
+            let revision = AssetGroup::get_revision(conn, asset_group_id)?;
+            ServiceRepr::sync_db(conn, asset_group_id, &mut reprs.clone())?;
+            assert_eq!(Routing::get(conn, &backup)?.inputs, vec!["localhost:9611"]);
+            assert_eq!(ServiceRepr::get_group(conn, asset_group_id)?, reprs);
+            assert_eq!(AssetGroup::get_revision(conn, asset_group_id)?, revision);
+
+            // Reordering the standbys is a change.
+            let mut reordered = reprs.clone();
+            let detector_repr = reordered
+                .iter_mut()
+                .find(|r| r.address == detector.address)
+                .unwrap();
+            detector_repr.standby_addresses.reverse();
+            ServiceRepr::sync_db(conn, asset_group_id, &mut reordered)?;
+            assert_ne!(AssetGroup::get_revision(conn, asset_group_id)?, revision);
+            ServiceRepr::sync_db(conn, asset_group_id, &mut reprs.clone())?;
+            assert_eq!(ServiceRepr::get_group(conn, asset_group_id)?, reprs);
+
+            let failover = failover.switch(conn, &detector, &spare, now)?;
+            assert_eq!(
+                Failover::find_active_by_standby(conn, spare.service_id)?,
+                Some(failover.clone())
+            );
+            assert_eq!(Routing::get(conn, &backup)?, Routing::default());
+            assert_eq!(Routing::get(conn, &spare)?.outputs, vec!["localhost:9615"]);
+
+            let ended = failover.end(conn, now)?;
+            assert_eq!(ended.ended_at, Some(now));
+            assert!(Failover::find_active(conn, detector.service_id)?.is_none());
+            assert_eq!(
+                Routing::get(conn, &detector)?.inputs,
+                vec!["localhost:9611"]
+            );
+            assert_eq!(Routing::get(conn, &spare)?, Routing::default());
+            assert_eq!(Failover::get_group(conn, asset_group_id)?.len(), 2);
+            Ok(())
+        })
+    }
+}
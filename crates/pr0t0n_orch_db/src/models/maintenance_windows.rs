@@ -0,0 +1,381 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, Queryable, RunQueryDsl};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    models::{generic::*, Recurrence, Service},
+    schema::maintenance_windows,
+};
+
+impl Recurrence {
+    /// Time between the starts of two occurrences, if it repeats.
+    pub fn period(&self) -> Option<Duration> {
+        match self {
+            Self::Once => None,
+            Self::Daily => Some(Duration::days(1)),
+            Self::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+}
+
+/// A time during which alerts on part of an asset group are silenced, e.g. while its robots are
+/// serviced.
+#[derive(Queryable, Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct MaintenanceWindow {
+    pub maintenance_window_id: i32,
+    pub asset_group_id: i32,
+    /// Address of the only service the window covers.
+    pub address: Option<String>,
+    /// `key=value` label of the services the window covers.
+    pub label: Option<String>,
+    pub reason: String,
+    /// Start of the first occurrence.
+    pub starts_at: DateTime<Utc>,
+    /// End of the first occurrence.
+    pub ends_at: DateTime<Utc>,
+    pub recurrence: Recurrence,
+    /// No occurrence starts at or after this time.
+    pub until: Option<DateTime<Utc>>,
+    /// When the window was ended early.
+    pub expired_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+impl DbFind for MaintenanceWindow {
+    type Table = maintenance_windows::table;
+}
+impl DbDelete for MaintenanceWindow {
+    type Table = maintenance_windows::table;
+}
+impl MaintenanceWindow {
+    /// Windows of an asset group, soonest first. Unless `all` is set, only those that have not
+    /// ended by `now` are returned.
+    pub fn get_group(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        all: bool,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Self>, Error> {
+        let windows: Vec<Self> = maintenance_windows::table
+            .filter(maintenance_windows::asset_group_id.eq(asset_group_id))
+            .order((
+                maintenance_windows::starts_at,
+                maintenance_windows::maintenance_window_id,
+            ))
+            .load(conn)?;
+        Ok(windows
+            .into_iter()
+            .filter(|window| all || window.occurrence(now).is_some())
+            .collect())
+    }
+
+    /// A window covering a service at `now`, if any.
+    pub fn find_covering(
+        conn: &PgConnection,
+        service: &Service,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Self>, Error> {
+        let windows: Vec<Self> = maintenance_windows::table
+            .filter(maintenance_windows::asset_group_id.eq(service.asset_group_id))
+            .filter(maintenance_windows::starts_at.le(now))
+            .filter(maintenance_windows::expired_at.is_null())
+            .order(maintenance_windows::maintenance_window_id)
+            .load(conn)?;
+        Ok(windows
+            .into_iter()
+            .find(|window| window.covers(service) && window.active_at(now)))
+    }
+
+    /// The occurrence in progress at `now` or the next one, as its start and end. `None` once
+    /// the window has ended.
+    pub fn occurrence(&self, now: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        if self.expired_at.is_some_and(|expired_at| expired_at <= now) {
+            return None;
+        }
+        let length = self.ends_at - self.starts_at;
+        let period = match self.recurrence.period() {
+            Some(period) => period,
+            None if self.ends_at <= now => return None,
+            None => return Some((self.starts_at, self.ends_at)),
+        };
+        let mut starts_at = self.starts_at;
+        if now > self.starts_at {
+            let periods = (now - self.starts_at).num_seconds() / period.num_seconds();
+            starts_at = self.starts_at + period * periods as i32;
+            if starts_at + length <= now {
+                starts_at += period;
+            }
+        }
+        if self.until.is_some_and(|until| starts_at >= until) {
+            return None;
+        }
+        Some((starts_at, starts_at + length))
+    }
+
+    /// Whether an occurrence of the window is in progress at `now`.
+    pub fn active_at(&self, now: DateTime<Utc>) -> bool {
+        self.occurrence(now)
+            .is_some_and(|(starts_at, _)| starts_at <= now)
+    }
+
+    /// Whether the window's scope includes a service of its asset group.
+    pub fn covers(&self, service: &Service) -> bool {
+        match (&self.address, &self.label) {
+            (Some(address), _) => *address == service.address,
+            (None, Some(label)) => service.labels.contains(label),
+            (None, None) => true,
+        }
+    }
+
+    /// End the window at `now`, including any later occurrences.
+    pub fn expire(&self, conn: &PgConnection, now: DateTime<Utc>) -> Result<Self, Error> {
+        Ok(
+            diesel::update(maintenance_windows::table.find(self.maintenance_window_id))
+                .set(maintenance_windows::expired_at.eq(now))
+                .get_result(conn)?,
+        )
+    }
+}
+
+/// A maintenance window as created through the API.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct MaintenanceWindowRepr {
+    /// Address of the only service to cover.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// `key=value` label of the services to cover. Without an address or label, the window
+    /// covers the whole asset group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reason: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    #[serde(default)]
+    pub recurrence: Recurrence,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+impl MaintenanceWindowRepr {
+    /// Check the window makes sense, returning the name of the offending field and why if not.
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.address.is_some() && self.label.is_some() {
+            return Err((
+                "label",
+                "Maintenance windows cover either an address or a label, not both".to_string(),
+            ));
+        }
+        if self
+            .address
+            .as_ref()
+            .is_some_and(|address| address.trim().is_empty())
+        {
+            return Err(("address", "Addresses must not be empty".to_string()));
+        }
+        if self
+            .label
+            .as_ref()
+            .is_some_and(|label| !label.contains('='))
+        {
+            return Err(("label", "Labels must be given as 'key=value'".to_string()));
+        }
+        if self.ends_at <= self.starts_at {
+            return Err((
+                "ends_at",
+                "Maintenance windows must end after they start".to_string(),
+            ));
+        }
+        match self.recurrence.period() {
+            Some(period) if self.ends_at - self.starts_at > period => {
+                return Err((
+                    "ends_at",
+                    format!(
+                        "{:?} maintenance windows must not last longer than they recur",
+                        self.recurrence
+                    ),
+                ));
+            }
+            None if self.until.is_some() => {
+                return Err((
+                    "until",
+                    "Only recurring maintenance windows can have an `until`".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        if self.until.is_some_and(|until| until <= self.starts_at) {
+            return Err((
+                "until",
+                "Recurring maintenance windows must end after they start".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn insert(
+        &self,
+        conn: &PgConnection,
+        asset_group_id: i32,
+    ) -> Result<MaintenanceWindow, Error> {
+        Ok(diesel::insert_into(maintenance_windows::table)
+            .values((
+                maintenance_windows::asset_group_id.eq(asset_group_id),
+                maintenance_windows::address.eq(&self.address),
+                maintenance_windows::label.eq(&self.label),
+                maintenance_windows::reason.eq(&self.reason),
+                maintenance_windows::starts_at.eq(self.starts_at),
+                maintenance_windows::ends_at.eq(self.ends_at),
+                maintenance_windows::recurrence.eq(self.recurrence),
+                maintenance_windows::until.eq(self.until),
+            ))
+            .get_result(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::testing::temp_asset_group_test;
+    use crate::{errors::Error, models::*};
+
+    fn repr(starts_at: &str, ends_at: &str) -> MaintenanceWindowRepr {
+        MaintenanceWindowRepr {
+            address: None,
+            label: None,
+            reason: "Servicing".to_string(),
+            starts_at: starts_at.parse().unwrap(),
+            ends_at: ends_at.parse().unwrap(),
+            recurrence: Recurrence::Once,
+            until: None,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let window = repr("2021-12-20T09:00:00Z", "2021-12-20T11:00:00Z");
+        assert!(window.validate().is_ok());
+        let both = MaintenanceWindowRepr {
+            address: Some("localhost:123".to_string()),
+            label: Some("site=lab".to_string()),
+            ..window.clone()
+        };
+        assert_eq!(both.validate().unwrap_err().0, "label");
+        let label = MaintenanceWindowRepr {
+            label: Some("lab".to_string()),
+            ..window.clone()
+        };
+        assert_eq!(label.validate().unwrap_err().0, "label");
+        let backwards = repr("2021-12-20T11:00:00Z", "2021-12-20T09:00:00Z");
+        assert_eq!(backwards.validate().unwrap_err().0, "ends_at");
+        let long = MaintenanceWindowRepr {
+            recurrence: Recurrence::Daily,
+            ..repr("2021-12-20T09:00:00Z", "2021-12-21T11:00:00Z")
+        };
+        assert_eq!(long.validate().unwrap_err().0, "ends_at");
+        let until_once = MaintenanceWindowRepr {
+            until: Some(window.ends_at),
+            ..window.clone()
+        };
+        assert_eq!(until_once.validate().unwrap_err().0, "until");
+    }
+
+    #[test]
+    fn test_occurrence() {
+        let at = |hour, minute| Utc.with_ymd_and_hms(2021, 12, 20, hour, minute, 0).unwrap();
+        let window = MaintenanceWindow {
+            maintenance_window_id: 1,
+            asset_group_id: 1,
+            address: None,
+            label: None,
+            reason: String::new(),
+            starts_at: at(9, 0),
+            ends_at: at(11, 0),
+            recurrence: Recurrence::Once,
+            until: None,
+            expired_at: None,
+            created_at: at(8, 0),
+        };
+        assert_eq!(window.occurrence(at(8, 0)), Some((at(9, 0), at(11, 0))));
+        assert!(!window.active_at(at(8, 59)));
+        assert!(window.active_at(at(9, 0)));
+        assert!(!window.active_at(at(11, 0)));
+        assert_eq!(window.occurrence(at(11, 0)), None);
+
+        let daily = MaintenanceWindow {
+            recurrence: Recurrence::Daily,
+            until: Some(at(9, 0) + Duration::days(2)),
+            ..window.clone()
+        };
+        let day = Duration::days(1);
+        assert!(daily.active_at(at(10, 0) + day));
+        assert!(!daily.active_at(at(12, 0) + day));
+        assert_eq!(
+            daily.occurrence(at(12, 0)),
+            Some((at(9, 0) + day, at(11, 0) + day))
+        );
+        // Occurrences stop at `until`.
+        assert_eq!(daily.occurrence(at(12, 0) + day), None);
+
+        let expired = MaintenanceWindow {
+            expired_at: Some(at(10, 0)),
+            ..daily
+        };
+        assert!(expired.active_at(at(9, 30)));
+        assert!(!expired.active_at(at(10, 0)));
+        assert_eq!(expired.occurrence(at(12, 0)), None);
+    }
+
+    #[test]
+    fn test_maintenance_windows() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let now = Utc::now();
+            let service = NewService {
+                asset_group_id,
+                name: "camera",
+                address: "localhost:9551",
+                service_type: ServiceType::Input,
+                labels: vec!["site=lab".to_string()],
+                ..Default::default()
+            }
+            .insert(conn)?;
+            let active = MaintenanceWindowRepr {
+                label: Some("site=lab".to_string()),
+                starts_at: now - Duration::hours(1),
+                ends_at: now + Duration::hours(1),
+                ..repr("2021-12-20T09:00:00Z", "2021-12-20T11:00:00Z")
+            }
+            .insert(conn, asset_group_id)?;
+            let other = MaintenanceWindowRepr {
+                address: Some("localhost:9552".to_string()),
+                starts_at: now - Duration::hours(1),
+                ends_at: now + Duration::hours(1),
+                ..repr("2021-12-20T09:00:00Z", "2021-12-20T11:00:00Z")
+            }
+            .insert(conn, asset_group_id)?;
+            let ended = repr("2021-12-20T09:00:00Z", "2021-12-20T11:00:00Z")
+                .insert(conn, asset_group_id)?;
+            assert_eq!(ended.recurrence, Recurrence::Once);
+
+            assert_eq!(
+                MaintenanceWindow::get_group(conn, asset_group_id, false, now)?,
+                vec![active.clone(), other.clone()]
+            );
+            assert_eq!(
+                MaintenanceWindow::get_group(conn, asset_group_id, true, now)?,
+                vec![ended, active.clone(), other]
+            );
+            assert_eq!(
+                MaintenanceWindow::find_covering(conn, &service, now)?,
+                Some(active.clone())
+            );
+
+            let expired = active.expire(conn, now)?;
+            assert!(expired.expired_at.is_some());
+            assert_eq!(MaintenanceWindow::find_covering(conn, &service, now)?, None);
+            Ok(())
+        })
+    }
+}
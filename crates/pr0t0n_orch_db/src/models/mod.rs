@@ -1,17 +1,39 @@
+pub mod alert_states;
+pub mod alerts;
 pub mod asset_groups;
 pub mod assets;
 pub mod configs;
+pub mod dead_letters;
 mod enums;
+pub mod events;
+pub mod failovers;
 pub mod generic;
+pub mod maintenance_windows;
+pub mod pending_services;
+pub mod pools;
+pub mod reconciliation;
+pub mod responses;
+pub mod routing;
 pub mod service_edges;
 pub mod services;
 pub mod system;
 
+pub use alert_states::*;
+pub use alerts::*;
 pub use asset_groups::*;
 pub use assets::*;
 pub use configs::*;
+pub use dead_letters::*;
 pub use enums::*;
+pub use events::*;
+pub use failovers::*;
 pub use generic::*;
+pub use maintenance_windows::*;
+pub use pending_services::*;
+pub use pools::*;
+pub use reconciliation::*;
+pub use responses::*;
+pub use routing::*;
 pub use service_edges::*;
 pub use services::*;
 pub use system::*;
@@ -0,0 +1,140 @@
+use chrono::{DateTime, Utc};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, Queryable, RunQueryDsl};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    models::{generic::*, HealthStatus, NewService, Service, ServiceType},
+    schema::{pending_services, services},
+};
+
+/// An address that connected to an asset group with the `Pending` registration policy, held until
+/// an operator approves it as a service.
+#[derive(Queryable, Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct PendingService {
+    pub pending_service_id: i32,
+    pub asset_group_id: i32,
+    pub address: String,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+impl DbFind for PendingService {
+    type Table = pending_services::table;
+}
+impl DbDelete for PendingService {
+    type Table = pending_services::table;
+}
+impl PendingService {
+    /// Pending services of an asset group, in the order they first connected.
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        let pending = pending_services::table
+            .filter(pending_services::asset_group_id.eq(asset_group_id))
+            .order((
+                pending_services::first_seen_at,
+                pending_services::pending_service_id,
+            ))
+            .load(conn)?;
+        Ok(pending)
+    }
+
+    /// Record that `address` connected at `now`, holding it if it is not pending already.
+    pub fn upsert(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        address: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Self, Error> {
+        let pending = diesel::insert_into(pending_services::table)
+            .values((
+                pending_services::asset_group_id.eq(asset_group_id),
+                pending_services::address.eq(address),
+                pending_services::first_seen_at.eq(now),
+                pending_services::last_seen_at.eq(now),
+            ))
+            .on_conflict((pending_services::asset_group_id, pending_services::address))
+            .do_update()
+            .set(pending_services::last_seen_at.eq(now))
+            .get_result(conn)?;
+        Ok(pending)
+    }
+
+    /// Register the pending service as a declared service, returning it.
+    pub fn approve(
+        &self,
+        conn: &PgConnection,
+        approval: &ServiceApproval,
+        config_id: Option<i32>,
+    ) -> Result<Service, Error> {
+        conn.transaction(|| {
+            let new_service = NewService {
+                asset_group_id: self.asset_group_id,
+                name: &approval.name,
+                address: &self.address,
+                service_type: approval.service_type,
+                health_status: HealthStatus::Disconnected,
+                config_id,
+                labels: vec![],
+                failback: false,
+            };
+            let service = diesel::insert_into(services::table)
+                .values((
+                    new_service,
+                    services::last_connected_at.eq(self.last_seen_at),
+                ))
+                .get_result(conn)?;
+            Self::delete(conn, self.pending_service_id)?;
+            Ok(service)
+        })
+    }
+}
+
+/// Request body for approving a pending service.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Default, Clone, Debug)]
+pub struct ServiceApproval {
+    pub name: String,
+    pub service_type: ServiceType,
+    /// Name of the config to assign to the service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::testing::temp_asset_group_test;
+    use crate::{errors::Error, models::*};
+
+    #[test]
+    fn test_pending_services() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let now = Utc.with_ymd_and_hms(2022, 1, 3, 10, 0, 0).unwrap();
+            let first = PendingService::upsert(conn, asset_group_id, "localhost:9591", now)?;
+            PendingService::upsert(conn, asset_group_id, "localhost:9592", now)?;
+            let later = now + Duration::minutes(5);
+            let again = PendingService::upsert(conn, asset_group_id, "localhost:9591", later)?;
+            assert_eq!(again.pending_service_id, first.pending_service_id);
+            assert_eq!(again.first_seen_at, first.first_seen_at);
+            assert_eq!(again.last_seen_at, later);
+
+            let pending = PendingService::get_group(conn, asset_group_id)?;
+            let addresses: Vec<&str> = pending.iter().map(|p| p.address.as_str()).collect();
+            assert_eq!(addresses, vec!["localhost:9591", "localhost:9592"]);
+
+            let approval = ServiceApproval {
+                name: "camera".to_string(),
+                service_type: ServiceType::Input,
+                config: None,
+            };
+            let service = again.approve(conn, &approval, None)?;
+            assert_eq!(service.address, "localhost:9591");
+            assert_eq!(service.service_type, ServiceType::Input);
+            assert!(service.declared);
+            assert_eq!(service.last_connected_at, Some(later));
+            assert_eq!(PendingService::get_group(conn, asset_group_id)?.len(), 1);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use diesel::{
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, Queryable, RunQueryDsl,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    models::{generic::*, BalancingStrategy, Service},
+    schema::{pool_replicas, service_pools, services},
+};
+
+/// Metric a `LeastLoaded` pool compares when it does not name one.
+pub const DEFAULT_LOAD_METRIC: &str = "load";
+
+/// A processor whose work is spread over replica services. Its inputs send to one of the healthy
+/// replicas instead of the processor itself, and the replicas send on to its outputs.
+#[derive(Insertable, Queryable, PartialEq, Clone, Debug)]
+pub struct ServicePool {
+    pub service_id: i32,
+    pub asset_group_id: i32,
+    pub strategy: BalancingStrategy,
+    /// Metric the replicas of a `LeastLoaded` pool report their load as.
+    pub load_metric: Option<String>,
+}
+impl DbInsert for ServicePool {
+    type Table = service_pools::table;
+    type Return = Self;
+}
+impl ServicePool {
+    /// Get all pools for an asset_group_id.
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        let results: Vec<Self> = service_pools::table
+            .filter(service_pools::asset_group_id.eq(asset_group_id))
+            .get_results(conn)?;
+        Ok(results)
+    }
+
+    /// The pool of a service, if it is one.
+    pub fn try_find(conn: &PgConnection, service_id: i32) -> Result<Option<Self>, Error> {
+        let result = service_pools::table
+            .find(service_id)
+            .first(conn)
+            .optional()?;
+        Ok(result)
+    }
+
+    /// Pools a service is a replica of, with the services they belong to.
+    pub fn find_by_replica(
+        conn: &PgConnection,
+        replica_service_id: i32,
+    ) -> Result<Vec<(Self, Service)>, Error> {
+        let results: Vec<(Self, Service)> = service_pools::table
+            .inner_join(pool_replicas::table)
+            .inner_join(services::table)
+            .filter(pool_replicas::replica_service_id.eq(replica_service_id))
+            .select((service_pools::all_columns, services::all_columns))
+            .get_results(conn)?;
+        Ok(results)
+    }
+
+    /// Replicas of the pool, by address.
+    pub fn get_replicas(&self, conn: &PgConnection) -> Result<Vec<Service>, Error> {
+        let replica_ids = pool_replicas::table
+            .filter(pool_replicas::service_id.eq(self.service_id))
+            .select(pool_replicas::replica_service_id);
+        let results: Vec<Service> = services::table
+            .filter(services::service_id.eq_any(replica_ids))
+            .order(services::address)
+            .get_results(conn)?;
+        Ok(results)
+    }
+
+    /// Metric the replicas are compared on, if the pool balances by load.
+    pub fn load_metric(&self) -> Option<&str> {
+        match self.strategy {
+            BalancingStrategy::LeastLoaded => {
+                Some(self.load_metric.as_deref().unwrap_or(DEFAULT_LOAD_METRIC))
+            }
+            BalancingStrategy::RoundRobin | BalancingStrategy::StickyHash => None,
+        }
+    }
+
+    /// Replaces the pool of a service with the one declared in its representation, if any. Only
+    /// what changed is written, so an unchanged pool leaves the revision of the asset group alone.
+    pub fn sync_service(
+        conn: &PgConnection,
+        service: &Service,
+        addr_to_id: &HashMap<String, i32>,
+        repr: Option<&PoolRepr>,
+    ) -> Result<(), Error> {
+        let pool = repr.map(|repr| Self {
+            service_id: service.service_id,
+            asset_group_id: service.asset_group_id,
+            strategy: repr.strategy,
+            load_metric: repr.load_metric.clone(),
+        });
+        if Self::try_find(conn, service.service_id)? != pool {
+            // Pools have no update trigger, so a changed pool is replaced along with its replicas.
+            diesel::delete(service_pools::table.find(service.service_id)).execute(conn)?;
+            if let Some(pool) = &pool {
+                pool.insert(conn)?;
+            }
+        }
+        let repr = match repr {
+            Some(repr) => repr,
+            None => return Ok(()),
+        };
+
+        let mut replica_service_ids: Vec<i32> = Vec::with_capacity(repr.replica_addresses.len());
+        for replica_addr in &repr.replica_addresses {
+            if let Some(&replica_service_id) = addr_to_id.get(replica_addr) {
+                replica_service_ids.push(replica_service_id);
+            } else {
+                return Err(Error::DatabaseSyncError(format!(
+                    "Failed to find service with address '{}'",
+                    replica_addr
+                )));
+            }
+        }
+        let replicas =
+            pool_replicas::table.filter(pool_replicas::service_id.eq(service.service_id));
+        let existing: Vec<i32> = replicas
+            .select(pool_replicas::replica_service_id)
+            .get_results(conn)?;
+        diesel::delete(
+            replicas.filter(pool_replicas::replica_service_id.ne_all(&replica_service_ids)),
+        )
+        .execute(conn)?;
+        let new_replicas: Vec<PoolReplica> = replica_service_ids
+            .into_iter()
+            .filter(|replica_service_id| !existing.contains(replica_service_id))
+            .map(|replica_service_id| PoolReplica {
+                service_id: service.service_id,
+                replica_service_id,
+                asset_group_id: service.asset_group_id,
+            })
+            .collect();
+        new_replicas.insert_all(conn)?;
+        Ok(())
+    }
+}
+
+/// A service that does the work of a pool.
+#[derive(Insertable, Queryable, Debug, Default)]
+pub struct PoolReplica {
+    pub service_id: i32,
+    pub replica_service_id: i32,
+    pub asset_group_id: i32,
+}
+impl DbInsertAll for Vec<PoolReplica> {
+    type Table = pool_replicas::table;
+    type Return = PoolReplica;
+}
+impl PoolReplica {
+    /// Get all replicas for an asset_group_id.
+    pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Vec<Self>, Error> {
+        let results: Vec<Self> = pool_replicas::table
+            .filter(pool_replicas::asset_group_id.eq(asset_group_id))
+            .get_results(conn)?;
+        Ok(results)
+    }
+}
+
+/// A pool declared on a processor in a system file.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Default, Clone, Debug)]
+pub struct PoolRepr {
+    /// Addresses of the services that do the work of the pool, listed by address.
+    pub replica_addresses: Vec<String>,
+    #[serde(default)]
+    pub strategy: BalancingStrategy,
+    /// Metric the replicas report their load as, for `LeastLoaded`. Defaults to `load`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_metric: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::testing::temp_asset_group_test;
+    use crate::{errors::Error, models::*};
+
+    #[test]
+    fn test_pools() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let service =
+                |address: &str, service_type: ServiceType, outputs: &[&str]| ServiceRepr {
+                    address: address.to_string(),
+                    service_type,
+                    name: address.to_string(),
+                    output_addresses: outputs.iter().map(|output| output.to_string()).collect(),
+                    ..Default::default()
+                };
+            let (camera, detector, arm) = ("localhost:9651", "localhost:9652", "localhost:9655");
+            let (first, second) = ("localhost:9653", "localhost:9654");
+            let pool = PoolRepr {
+                replica_addresses: vec![first.to_string(), second.to_string()],
+                strategy: BalancingStrategy::LeastLoaded,
+                load_metric: None,
+            };
+            let mut reprs = vec![
+                service(camera, ServiceType::Input, &[detector]),
+                ServiceRepr {
+                    pool: Some(pool.clone()),
+                    ..service(detector, ServiceType::Processor, &[arm])
+                },
+                service(first, ServiceType::Processor, &[]),
+                service(second, ServiceType::Processor, &[]),
+                service(arm, ServiceType::Output, &[]),
+            ];
+            ServiceRepr::sync_db(conn, asset_group_id, &mut reprs.clone())?;
+            let synced = ServiceRepr::get_group(conn, asset_group_id)?;
+            assert_eq!(synced[1].pool, Some(pool.clone()));
+
+            // Syncing the same pool again leaves the revision alone, and changing it does not.
+            let revision = AssetGroup::get_revision(conn, asset_group_id)?;
+            ServiceRepr::sync_db(conn, asset_group_id, &mut reprs.clone())?;
+            assert_eq!(AssetGroup::get_revision(conn, asset_group_id)?, revision);
+            let mut changed = reprs.clone();
+            changed[1].pool = Some(PoolRepr {
+                replica_addresses: vec![first.to_string()],
+                ..pool.clone()
+            });
+            ServiceRepr::sync_db(conn, asset_group_id, &mut changed)?;
+            assert_ne!(AssetGroup::get_revision(conn, asset_group_id)?, revision);
+            ServiceRepr::sync_db(conn, asset_group_id, &mut reprs.clone())?;
+            let synced = ServiceRepr::get_group(conn, asset_group_id)?;
+            assert_eq!(synced[1].pool, Some(pool));
+
+            // Replicas are routed like the pool, and only healthy ones are routed to.
+            let detector_service = Service::find_by_addr(conn, detector)?;
+            let pool = ServicePool::try_find(conn, detector_service.service_id)?.unwrap();
+            assert_eq!(pool.load_metric(), Some(DEFAULT_LOAD_METRIC));
+            let first_service = Service::find_by_addr(conn, first)?;
+            let pools = ServicePool::find_by_replica(conn, first_service.service_id)?;
+            assert_eq!(pools.len(), 1);
+            assert_eq!(pools[0].0, pool);
+            assert_eq!(pools[0].1.service_id, detector_service.service_id);
+            let routing = Routing::get(conn, &first_service)?;
+            assert_eq!(routing.inputs, vec![camera]);
+            assert_eq!(routing.outputs, vec![arm]);
+            assert!(routing.pools.is_empty());
+            Service::set_health(conn, second, HealthStatus::Critical)?;
+            let routing = Routing::get(conn, &Service::find_by_addr(conn, camera)?)?;
+            assert_eq!(routing.outputs, vec![detector]);
+            assert_eq!(routing.pools[detector].replicas, vec![first]);
+
+            // Only replicas with a session are kept, least loaded first.
+            Service::set_health(conn, second, HealthStatus::Healthy)?;
+            let mut routing = Routing::get(conn, &Service::find_by_addr(conn, camera)?)?;
+            let mut sessions: BTreeMap<&str, BTreeMap<String, f64>> = BTreeMap::new();
+            let mut unconnected = routing.clone();
+            unconnected.apply_sessions(|address| sessions.get(address));
+            assert!(unconnected.pools[detector].replicas.is_empty());
+            sessions.insert(first, vec![("load".to_string(), 0.7)].into_iter().collect());
+            sessions.insert(
+                second,
+                vec![("load".to_string(), 0.2)].into_iter().collect(),
+            );
+            let mut loaded = routing.clone();
+            loaded.apply_sessions(|address| sessions.get(address));
+            assert_eq!(loaded.pools[detector].replicas, vec![second, first]);
+            assert_eq!(loaded.pools[detector].loads[first], 0.7);
+            sessions.remove(second);
+            routing.apply_sessions(|address| sessions.get(address));
+            assert_eq!(routing.pools[detector].replicas, vec![first]);
+
+            // Removing the pool leaves the replicas as plain services.
+            reprs[1].pool = None;
+            ServiceRepr::sync_db(conn, asset_group_id, &mut reprs)?;
+            assert!(ServicePool::get_group(conn, asset_group_id)?.is_empty());
+            Ok(())
+        })
+    }
+}
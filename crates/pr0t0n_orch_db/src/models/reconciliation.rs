@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use diesel::PgConnection;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    models::{assets::Asset, HealthStatus, Service},
+};
+
+/// How a service's declaration in the system compares with its sessions.
+#[derive(
+    Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug,
+)]
+pub enum Reconciliation {
+    /// Declared, and connected now.
+    Connected,
+    /// Declared, and connected before but not now.
+    Disconnected,
+    /// Declared, but never connected.
+    NeverSeen,
+    /// Connected without being declared.
+    Undeclared,
+}
+
+/// A service or session of an asset group, with how it reconciles.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct ReconciledService {
+    pub address: String,
+    pub state: Reconciliation,
+    /// ID of the service, unless it connected without being registered.
+    pub service_id: Option<i32>,
+    pub health_status: Option<HealthStatus>,
+    pub last_connected_at: Option<DateTime<Utc>>,
+}
+
+/// The services an asset group declares compared with the ones that connected.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct ReconciliationReport {
+    pub asset_group_id: i32,
+    /// Number of services in each state, leaving out empty ones.
+    pub counts: BTreeMap<Reconciliation, usize>,
+    /// Services by state, then address.
+    pub services: Vec<ReconciledService>,
+}
+impl ReconciliationReport {
+    /// Compare the services of an asset group with the addresses that have a session in it.
+    pub fn new(asset_group_id: i32, services: Vec<Service>, connected: &[String]) -> Self {
+        let mut reconciled: Vec<ReconciledService> = services
+            .into_iter()
+            .map(|service| {
+                let state = if !service.declared {
+                    Reconciliation::Undeclared
+                } else if connected.contains(&service.address) {
+                    Reconciliation::Connected
+                } else if service.last_connected_at.is_some() {
+                    Reconciliation::Disconnected
+                } else {
+                    Reconciliation::NeverSeen
+                };
+                ReconciledService {
+                    address: service.address,
+                    state,
+                    service_id: Some(service.service_id),
+                    health_status: Some(service.health_status),
+                    last_connected_at: service.last_connected_at,
+                }
+            })
+            .collect();
+        // Sessions of services that were never registered.
+        for address in connected {
+            if reconciled.iter().all(|service| service.address != *address) {
+                reconciled.push(ReconciledService {
+                    address: address.clone(),
+                    state: Reconciliation::Undeclared,
+                    service_id: None,
+                    health_status: None,
+                    last_connected_at: None,
+                });
+            }
+        }
+        reconciled.sort_by(|a, b| (a.state, &a.address).cmp(&(b.state, &b.address)));
+
+        let mut counts = BTreeMap::new();
+        for service in &reconciled {
+            *counts.entry(service.state).or_default() += 1;
+        }
+        Self {
+            asset_group_id,
+            counts,
+            services: reconciled,
+        }
+    }
+
+    /// Reconcile the services of an asset group in the database with the `connected` addresses.
+    pub fn get(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        connected: &[String],
+    ) -> Result<Self, Error> {
+        Ok(Self::new(
+            asset_group_id,
+            Service::get_group(conn, asset_group_id)?,
+            connected,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::testing::temp_asset_group_test;
+    use crate::{errors::Error, models::*};
+
+    #[test]
+    fn test_reconciliation() -> Result<(), Error> {
+        temp_asset_group_test(|conn, asset_group| {
+            let asset_group_id = asset_group.asset_group_id;
+            let declare = |address: &'static str| NewService {
+                asset_group_id,
+                name: "camera",
+                address,
+                service_type: ServiceType::Input,
+                ..Default::default()
+            };
+            declare("localhost:9571").insert(conn)?;
+            declare("localhost:9572").insert(conn)?;
+            let reconnected = declare("localhost:9573").insert(conn)?;
+            Service::upsert_healthy_address(conn, asset_group_id, "localhost:9573")?;
+            Service::upsert_healthy_address(conn, asset_group_id, "localhost:9574")?;
+            Service::disconnect_address(conn, "localhost:9573")?;
+
+            let connected = vec![
+                "localhost:9571".to_string(),
+                "localhost:9574".to_string(),
+                "localhost:9575".to_string(),
+            ];
+            let report = ReconciliationReport::get(conn, asset_group_id, &connected)?;
+            let states: Vec<(&str, Reconciliation)> = report
+                .services
+                .iter()
+                .map(|service| (service.address.as_str(), service.state))
+                .collect();
+            assert_eq!(
+                states,
+                vec![
+                    ("localhost:9571", Reconciliation::Connected),
+                    ("localhost:9573", Reconciliation::Disconnected),
+                    ("localhost:9572", Reconciliation::NeverSeen),
+                    ("localhost:9574", Reconciliation::Undeclared),
+                    ("localhost:9575", Reconciliation::Undeclared),
+                ]
+            );
+            assert_eq!(report.counts[&Reconciliation::Undeclared], 2);
+            assert_eq!(report.services[4].service_id, None);
+            let seen = report.services[1].last_connected_at.unwrap();
+            assert!(Utc::now() - seen < chrono::Duration::minutes(1));
+            assert_eq!(report.services[1].service_id, Some(reconnected.service_id));
+
+            // Syncing an undeclared service declares it.
+            let mut system = SystemRepr::get_group(conn, asset_group_id)?;
+            system.sync_db(conn)?;
+            let report = ReconciliationReport::get(conn, asset_group_id, &connected)?;
+            assert_eq!(report.counts.get(&Reconciliation::Undeclared), Some(&1));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,125 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Stable, machine-readable reason for an error response.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request could not be parsed.
+    BadRequest,
+    /// The requested resource does not exist.
+    NotFound,
+    /// A field of the request has an invalid value.
+    ValidationFailed,
+    /// The request is well-formed but could not be applied to the database.
+    SyncFailed,
+    /// A value is not a member of the expected enum.
+    InvalidEnumValue,
+    /// The request conflicts with the current state, e.g. deleting something still in use.
+    Conflict,
+    /// The asset group changed since the revision given in `If-Match`.
+    RevisionConflict,
+    /// A record with the same unique key already exists.
+    UniqueViolation,
+    UnsupportedMediaType,
+    Forbidden,
+    Internal,
+    /// A code this client does not know about yet. Servers never send it.
+    #[serde(other)]
+    #[schemars(skip)]
+    Unknown,
+}
+
+/// One error of an [`ErrorResponse`].
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct ErrorDetail {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Path of the offending field in the request body, e.g. `services/2/address`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+impl ErrorDetail {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            field: None,
+        }
+    }
+
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+}
+
+/// Body of every error response of the HTTP API.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, PartialEq)]
+pub struct ErrorResponse {
+    /// User-friendly error messages.
+    pub errors: Vec<String>,
+    /// The same errors with their codes and fields.
+    #[serde(default)]
+    pub details: Vec<ErrorDetail>,
+}
+impl ErrorResponse {
+    /// Code of the first error, if any.
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.details.first().map(|detail| detail.code)
+    }
+}
+impl From<ErrorDetail> for ErrorResponse {
+    fn from(detail: ErrorDetail) -> Self {
+        Self {
+            errors: vec![detail.message.clone()],
+            details: vec![detail],
+        }
+    }
+}
+impl From<Vec<ErrorDetail>> for ErrorResponse {
+    fn from(details: Vec<ErrorDetail>) -> Self {
+        Self {
+            errors: details
+                .iter()
+                .map(|detail| detail.message.clone())
+                .collect(),
+            details,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_response() {
+        let response = ErrorResponse::from(
+            ErrorDetail::new(ErrorCode::ValidationFailed, "Address must not be empty")
+                .with_field("services/0/address"),
+        );
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "errors": ["Address must not be empty"],
+                "details": [{
+                    "code": "validation_failed",
+                    "message": "Address must not be empty",
+                    "field": "services/0/address",
+                }],
+            })
+        );
+        assert_eq!(response.code(), Some(ErrorCode::ValidationFailed));
+
+        // Older servers only send messages, newer ones may send unknown codes.
+        let old: ErrorResponse = serde_json::from_str(r#"{"errors": ["Oops"]}"#).unwrap();
+        assert_eq!(old.code(), None);
+        let new: ErrorResponse = serde_json::from_str(
+            r#"{"errors": ["Oops"], "details": [{"code": "brand_new", "message": "Oops"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(new.code(), Some(ErrorCode::Unknown));
+    }
+}
@@ -0,0 +1,121 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    models::{BalancingStrategy, HealthStatus, Service, ServicePool},
+};
+
+/// Addresses a service exchanges data with, sent to its session as `{"routing": ...}` when it
+/// registers and whenever a failover or the replicas of a pool change them.
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, Debug)]
+pub struct Routing {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    /// Replicas to use for the inputs and outputs that are pools, by pool address.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pools: BTreeMap<String, PoolRouting>,
+}
+impl Routing {
+    /// Current routing of a service. Replicas of a pool are routed like the pool.
+    pub fn get(conn: &PgConnection, service: &Service) -> Result<Self, Error> {
+        let mut routed = vec![service.clone()];
+        for (_pool, pool_service) in ServicePool::find_by_replica(conn, service.service_id)? {
+            routed.push(pool_service);
+        }
+        let mut inputs: BTreeMap<String, Service> = BTreeMap::new();
+        let mut outputs: BTreeMap<String, Service> = BTreeMap::new();
+        for service in &routed {
+            for input in service.get_inputs(conn)? {
+                inputs.insert(input.address.clone(), input);
+            }
+            for output in service.get_outputs(conn)? {
+                outputs.insert(output.address.clone(), output);
+            }
+        }
+
+        let mut pools = BTreeMap::new();
+        for neighbour in inputs.values().chain(outputs.values()) {
+            if let Some(pool) = ServicePool::try_find(conn, neighbour.service_id)? {
+                pools.insert(neighbour.address.clone(), PoolRouting::get(conn, &pool)?);
+            }
+        }
+        Ok(Self {
+            inputs: inputs.into_keys().collect(),
+            outputs: outputs.into_keys().collect(),
+            pools,
+        })
+    }
+
+    /// Keeps the replicas that have a session, given by the metrics they last reported through
+    /// `metrics(address)`. The loads of `LeastLoaded` pools are filled in from those, least loaded
+    /// first, and replicas that have not reported a load go last.
+    pub fn apply_sessions<'a, F>(&mut self, metrics: F)
+    where
+        F: Fn(&str) -> Option<&'a BTreeMap<String, f64>>,
+    {
+        for pool in self.pools.values_mut() {
+            pool.replicas.retain(|replica| metrics(replica).is_some());
+            let metric = match &pool.load_metric {
+                Some(metric) => metric,
+                None => continue,
+            };
+            pool.loads = pool
+                .replicas
+                .iter()
+                .filter_map(|replica| Some((replica.clone(), *metrics(replica)?.get(metric)?)))
+                .collect();
+            let loads = &pool.loads;
+            pool.replicas
+                .sort_by(|a, b| match (loads.get(a), loads.get(b)) {
+                    (Some(a), Some(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                });
+        }
+    }
+
+    /// Text message that tells a session its routing.
+    pub fn to_message(&self) -> String {
+        serde_json::json!({ "routing": self }).to_string()
+    }
+}
+
+/// The replicas standing in for a pool in a routing.
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, Debug)]
+pub struct PoolRouting {
+    pub strategy: BalancingStrategy,
+    /// Addresses of the healthy replicas, by address, or least loaded first for `LeastLoaded`.
+    pub replicas: Vec<String>,
+    /// Metric the replicas are compared on, for `LeastLoaded`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_metric: Option<String>,
+    /// Load each replica last reported, for `LeastLoaded`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub loads: BTreeMap<String, f64>,
+}
+impl PoolRouting {
+    /// Current replicas of a pool. Replicas are healthy while they are `Healthy` or `Warning`.
+    pub fn get(conn: &PgConnection, pool: &ServicePool) -> Result<Self, Error> {
+        let replicas = pool
+            .get_replicas(conn)?
+            .into_iter()
+            .filter(|replica| {
+                matches!(
+                    replica.health_status,
+                    HealthStatus::Healthy | HealthStatus::Warning
+                )
+            })
+            .map(|replica| replica.address)
+            .collect();
+        Ok(Self {
+            strategy: pool.strategy,
+            replicas,
+            load_metric: pool.load_metric().map(str::to_string),
+            loads: BTreeMap::new(),
+        })
+    }
+}
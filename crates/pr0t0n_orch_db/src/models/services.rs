@@ -1,20 +1,105 @@
 use std::collections::{BTreeMap, HashMap};
 
+use chrono::{DateTime, Utc};
+
 use crate::{
     errors::Error,
     models::{
+        alerts::{AlertRule, AlertRuleRepr},
         configs::Config,
         enums::{HealthStatus, ServiceType},
+        failovers::{Failover, ServiceStandby},
         generic::{DbDelete, DbFind, DbInsert, DbInsertAll},
+        pools::{PoolReplica, PoolRepr, ServicePool},
         service_edges::ServiceEdge,
     },
-    schema::{service_edges, services},
+    schema::{service_edges, service_standbys, services},
+};
+use diesel::{
+    expression_methods::{PgArrayExpressionMethods, PgTextExpressionMethods},
+    BoolExpressionMethods, ExpressionMethods, JoinOnDsl, OptionalExtension, PgConnection, QueryDsl,
+    Queryable, RunQueryDsl,
 };
-use diesel::{ExpressionMethods, JoinOnDsl, PgConnection, QueryDsl, Queryable, RunQueryDsl};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::assets::{Asset, AssetRepr};
 
+/// Number of services in a page when the query does not give a limit.
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+/// Largest page of services a query may ask for.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// Labels are stored as `key=value` strings so they can be matched with the array index.
+fn labels_to_db(labels: &BTreeMap<String, String>) -> Vec<String> {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect()
+}
+
+fn labels_from_db(labels: &[String]) -> BTreeMap<String, String> {
+    labels
+        .iter()
+        .map(|label| match label.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (label.clone(), String::new()),
+        })
+        .collect()
+}
+
+/// Escape `LIKE` wildcards so a search matches them literally.
+fn escape_like(pattern: &str) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Column a page of services is sorted by. Ties are broken by service ID.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceSort {
+    #[default]
+    ServiceId,
+    Name,
+    Address,
+}
+
+/// Filters and pagination for listing the services of an asset group.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ServiceQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_type: Option<ServiceType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_status: Option<HealthStatus>,
+    /// Case-insensitive substring of the service name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Label the service must have, as `key=value`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub sort: ServiceSort,
+    /// `next_cursor` of the previous page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+}
+
+/// Cursors are the service ID and sort key of the last service on a page.
+fn parse_cursor(cursor: &str) -> Result<(i32, String), Error> {
+    cursor
+        .split_once(':')
+        .and_then(|(service_id, key)| Some((service_id.parse().ok()?, key.to_string())))
+        .ok_or_else(|| Error::InvalidQuery(format!("Invalid cursor '{}'", cursor)))
+}
+
 /// Public representation of a service with typed enums.
 #[derive(Queryable, AsChangeset, Debug, Default, Clone)]
 #[primary_key(service_id)]
@@ -26,8 +111,116 @@ pub struct Service {
     pub service_type: ServiceType,
     pub health_status: HealthStatus,
     pub config_id: Option<i32>,
+    /// Labels as `key=value` strings.
+    pub labels: Vec<String>,
+    /// Whether the service is in the system of its asset group, rather than registered by
+    /// connecting.
+    pub declared: bool,
+    /// When the service last opened a session, if ever.
+    pub last_connected_at: Option<DateTime<Utc>>,
+    /// Whether routing returns from a standby once the service reconnects.
+    pub failback: bool,
 }
 impl Service {
+    fn cursor(&self, sort: ServiceSort) -> String {
+        let key = match sort {
+            ServiceSort::ServiceId => "",
+            ServiceSort::Name => &self.name,
+            ServiceSort::Address => &self.address,
+        };
+        format!("{}:{}", self.service_id, key)
+    }
+
+    /// Get a page of the services in an asset group matching a query, along with the cursor of
+    /// the next page if there is one.
+    pub fn get_page(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        query: &ServiceQuery,
+    ) -> Result<(Vec<Self>, Option<String>), Error> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(Error::InvalidQuery(format!(
+                "limit must be between 1 and {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+
+        let mut select = services::table
+            .filter(services::asset_group_id.eq(asset_group_id))
+            .into_boxed();
+        if let Some(service_type) = query.service_type {
+            select = select.filter(services::service_type.eq(service_type));
+        }
+        if let Some(health_status) = query.health_status {
+            select = select.filter(services::health_status.eq(health_status));
+        }
+        if let Some(name) = &query.name {
+            select = select.filter(services::name.ilike(format!("%{}%", escape_like(name))));
+        }
+        if let Some(label) = &query.label {
+            if !label.contains('=') {
+                return Err(Error::InvalidQuery(format!(
+                    "Label '{}' must be given as key=value",
+                    label
+                )));
+            }
+            select = select.filter(services::labels.contains(vec![label.clone()]));
+        }
+        if let Some(cursor) = &query.cursor {
+            let (after_id, after_key) = parse_cursor(cursor)?;
+            select = match query.sort {
+                ServiceSort::ServiceId => select.filter(services::service_id.gt(after_id)),
+                ServiceSort::Name => select.filter(
+                    services::name.gt(after_key.clone()).or(services::name
+                        .eq(after_key)
+                        .and(services::service_id.gt(after_id))),
+                ),
+                ServiceSort::Address => select.filter(
+                    services::address.gt(after_key.clone()).or(services::address
+                        .eq(after_key)
+                        .and(services::service_id.gt(after_id))),
+                ),
+            };
+        }
+        select = match query.sort {
+            ServiceSort::ServiceId => select.order(services::service_id),
+            ServiceSort::Name => select.order((services::name, services::service_id)),
+            ServiceSort::Address => select.order((services::address, services::service_id)),
+        };
+
+        // Fetch one extra service to know whether there is a next page.
+        let mut results: Vec<Self> = select.limit(limit + 1).load(conn)?;
+        let next_cursor = if results.len() as i64 > limit {
+            results.truncate(limit as usize);
+            results.last().map(|service| service.cursor(query.sort))
+        } else {
+            None
+        };
+        Ok((results, next_cursor))
+    }
+
+    /// Get the output addresses of each of the given services, sorted by address.
+    pub fn get_output_addresses(
+        conn: &PgConnection,
+        service_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<String>>, Error> {
+        let results: Vec<(i32, String)> = service_edges::table
+            .filter(service_edges::input_service_id.eq_any(service_ids))
+            .inner_join(
+                services::table.on(services::service_id.eq(service_edges::output_service_id)),
+            )
+            .select((service_edges::input_service_id, services::address))
+            .order(services::address)
+            .get_results(conn)?;
+
+        let mut map: HashMap<i32, Vec<String>> = HashMap::new();
+        for (service_id, address) in results {
+            map.entry(service_id).or_default().push(address);
+        }
+        Ok(map)
+    }
+
     pub fn get_addr_to_id(
         conn: &PgConnection,
         asset_group_id: i32,
@@ -52,6 +245,14 @@ impl Service {
         Ok(result)
     }
 
+    /// Get the service at an address, or `None` if there is none.
+    pub fn try_find_by_addr(conn: &PgConnection, address: &str) -> Result<Option<Self>, Error> {
+        Ok(services::table
+            .filter(services::address.eq(address))
+            .get_result(conn)
+            .optional()?)
+    }
+
     /// Get all services for an asset_group_id.
     pub fn find_by_addrs(conn: &PgConnection, addresses: &[&str]) -> Result<Vec<Self>, Error> {
         let results: Vec<Service> = services::table
@@ -70,13 +271,14 @@ impl Service {
         Ok((services, edges))
     }
 
-    /// Given an address, if a service with that address already exists, create it.
-    /// Otherwise update it's connection status to healthy.
+    /// Mark the service at an address healthy and connected now. Addresses without a service
+    /// are registered as undeclared inputs.
     pub fn upsert_healthy_address(
         conn: &PgConnection,
         asset_group_id: i32,
         address: &str,
     ) -> Result<(), Error> {
+        let now = Utc::now();
         let new_service = NewService {
             asset_group_id,
             name: address,
@@ -86,10 +288,17 @@ impl Service {
             ..Default::default()
         };
         diesel::insert_into(services::table)
-            .values(new_service)
+            .values((
+                new_service,
+                services::declared.eq(false),
+                services::last_connected_at.eq(now),
+            ))
             .on_conflict(services::address)
             .do_update()
-            .set(services::health_status.eq(HealthStatus::Healthy))
+            .set((
+                services::health_status.eq(HealthStatus::Healthy),
+                services::last_connected_at.eq(now),
+            ))
             .execute(conn)?;
         Ok(())
     }
@@ -103,6 +312,43 @@ impl Service {
         Ok(())
     }
 
+    /// Set the health of the service at an address, as reported by the service.
+    pub fn set_health(
+        conn: &PgConnection,
+        address: &str,
+        health_status: HealthStatus,
+    ) -> Result<(), Error> {
+        diesel::update(services::table.filter(services::address.eq(address)))
+            .set(services::health_status.eq(health_status))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Mark the services at the given addresses disconnected.
+    pub fn disconnect_addresses(conn: &PgConnection, addresses: &[&str]) -> Result<(), Error> {
+        diesel::update(services::table.filter(services::address.eq_any(addresses)))
+            .set(services::health_status.eq(HealthStatus::Disconnected))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Set services in any of the `from` statuses to `to`, except those at the `connected`
+    /// addresses. Returns the updated services.
+    pub fn reconcile_health(
+        conn: &PgConnection,
+        from: &[HealthStatus],
+        to: HealthStatus,
+        connected: &[&str],
+    ) -> Result<Vec<Self>, Error> {
+        Ok(diesel::update(
+            services::table
+                .filter(services::health_status.eq_any(from))
+                .filter(services::address.ne_all(connected)),
+        )
+        .set(services::health_status.eq(to))
+        .get_results(conn)?)
+    }
+
     /// Get all output services for a given service.
     pub fn get_outputs(&self, conn: &PgConnection) -> Result<Vec<Self>, Error> {
         let results: Vec<(ServiceEdge, Service)> = service_edges::table
@@ -131,21 +377,32 @@ impl Service {
             .collect())
     }
 
+    pub fn delete_all(conn: &PgConnection, service_ids: &[i32]) -> Result<usize, Error> {
+        let num_deleted =
+            diesel::delete(services::table.filter(services::service_id.eq_any(service_ids)))
+                .execute(conn)?;
+        Ok(num_deleted)
+    }
+
     /// Custom update for service.
     pub fn update(&self, conn: &PgConnection) -> Result<usize, Error> {
-        let result: usize = diesel::update(services::table)
+        let result: usize = diesel::update(services::table.find(self.service_id))
             .set((
                 services::name.eq(self.name.clone()),
                 services::asset_group_id.eq(self.asset_group_id),
                 services::service_type.eq(self.service_type),
                 services::health_status.eq(self.health_status),
                 services::config_id.eq(self.config_id),
+                services::labels.eq(&self.labels),
+                services::declared.eq(self.declared),
+                services::failback.eq(self.failback),
             ))
             .execute(conn)?;
         Ok(result)
     }
 
-    /// Updates outputs based on respresentation.
+    /// Updates outputs based on respresentation. Only edges that were added or removed are
+    /// written, so the revision of the asset group is not bumped for unchanged outputs.
     pub fn update_outputs(
         &self,
         conn: &PgConnection,
@@ -153,16 +410,11 @@ impl Service {
         addr_to_id: &HashMap<String, i32>,
         repr: &ServiceRepr,
     ) -> Result<(), Error> {
-        // Update service edges.
         let input_service_id = self.service_id;
-        let mut new_edges: Vec<ServiceEdge> = Vec::with_capacity(repr.output_addresses.len());
+        let mut output_service_ids: Vec<i32> = Vec::with_capacity(repr.output_addresses.len());
         for output_addr in &repr.output_addresses {
             if let Some(&output_service_id) = addr_to_id.get(output_addr) {
-                new_edges.push(ServiceEdge {
-                    asset_group_id,
-                    input_service_id,
-                    output_service_id,
-                });
+                output_service_ids.push(output_service_id);
             } else {
                 return Err(Error::DatabaseSyncError(format!(
                     "Failed to find service with address '{}'",
@@ -170,10 +422,98 @@ impl Service {
                 )));
             }
         }
-        ServiceEdge::delete_outputs(conn, input_service_id)?;
+
+        let existing: Vec<i32> = service_edges::table
+            .filter(service_edges::input_service_id.eq(input_service_id))
+            .select(service_edges::output_service_id)
+            .get_results(conn)?;
+        diesel::delete(
+            service_edges::table
+                .filter(service_edges::input_service_id.eq(input_service_id))
+                .filter(service_edges::output_service_id.ne_all(&output_service_ids)),
+        )
+        .execute(conn)?;
+        let new_edges: Vec<ServiceEdge> = output_service_ids
+            .into_iter()
+            .filter(|output_service_id| !existing.contains(output_service_id))
+            .map(|output_service_id| ServiceEdge {
+                asset_group_id,
+                input_service_id,
+                output_service_id,
+            })
+            .collect();
         new_edges.insert_all(conn)?;
         Ok(())
     }
+
+    /// Get the standby services of a service, in the order they are failed over to.
+    pub fn get_standbys(&self, conn: &PgConnection) -> Result<Vec<Self>, Error> {
+        let results: Vec<(ServiceStandby, Service)> = service_standbys::table
+            .filter(service_standbys::service_id.eq(self.service_id))
+            .inner_join(
+                services::table.on(services::service_id.eq(service_standbys::standby_service_id)),
+            )
+            .order(service_standbys::priority)
+            .get_results(conn)?;
+        Ok(results
+            .into_iter()
+            .map(|(_standby, service)| service)
+            .collect())
+    }
+
+    /// Updates standbys based on representation. Only standbys that were added, removed or
+    /// reordered are written, so the revision of the asset group is not bumped otherwise.
+    pub fn update_standbys(
+        &self,
+        conn: &PgConnection,
+        asset_group_id: i32,
+        addr_to_id: &HashMap<String, i32>,
+        repr: &ServiceRepr,
+    ) -> Result<(), Error> {
+        let service_id = self.service_id;
+        let mut standbys: Vec<(i32, i32)> = Vec::with_capacity(repr.standby_addresses.len());
+        for (priority, standby_addr) in repr.standby_addresses.iter().enumerate() {
+            if let Some(&standby_service_id) = addr_to_id.get(standby_addr) {
+                standbys.push((standby_service_id, priority as i32));
+            } else {
+                return Err(Error::DatabaseSyncError(format!(
+                    "Failed to find service with address '{}'",
+                    standby_addr
+                )));
+            }
+        }
+
+        let existing: Vec<(i32, i32)> = service_standbys::table
+            .filter(service_standbys::service_id.eq(service_id))
+            .select((
+                service_standbys::standby_service_id,
+                service_standbys::priority,
+            ))
+            .get_results(conn)?;
+        let removed: Vec<i32> = existing
+            .iter()
+            .filter(|standby| !standbys.contains(standby))
+            .map(|&(standby_service_id, _priority)| standby_service_id)
+            .collect();
+        diesel::delete(
+            service_standbys::table
+                .filter(service_standbys::service_id.eq(service_id))
+                .filter(service_standbys::standby_service_id.eq_any(&removed)),
+        )
+        .execute(conn)?;
+        let new_standbys: Vec<ServiceStandby> = standbys
+            .into_iter()
+            .filter(|standby| !existing.contains(standby))
+            .map(|(standby_service_id, priority)| ServiceStandby {
+                service_id,
+                standby_service_id,
+                asset_group_id,
+                priority,
+            })
+            .collect();
+        new_standbys.insert_all(conn)?;
+        Ok(())
+    }
 }
 impl DbDelete for Service {
     type Table = services::table;
@@ -205,6 +545,8 @@ pub struct NewService<'a> {
     pub service_type: ServiceType,
     pub health_status: HealthStatus,
     pub config_id: Option<i32>,
+    pub labels: Vec<String>,
+    pub failback: bool,
 }
 impl DbInsert for NewService<'_> {
     type Table = services::table;
@@ -216,14 +558,30 @@ impl DbInsertAll for Vec<NewService<'_>> {
 }
 
 /// Service representation for ergonomic config.
-#[derive(Serialize, Deserialize, PartialEq, Default, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Default, Clone, Debug)]
 pub struct ServiceRepr {
     pub address: String,
     pub service_type: ServiceType,
-    pub health_status: HealthStatus,
+    /// Health as last reported. Left out of the system of an asset group, which only holds what
+    /// is declared, and only read on upload to set the health of new services.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_status: Option<HealthStatus>,
     pub name: String,
     pub output_addresses: Vec<String>,
     pub config_name: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<AlertRuleRepr>,
+    /// Processors that take over the routing of this one while it is disconnected, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub standby_addresses: Vec<String>,
+    /// Whether routing returns to this service from its standby once it reconnects.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub failback: bool,
+    /// Replicas that do the work of this processor, which then only stands for them in routing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolRepr>,
 
     /// Populated automatically based on `config_name`
     #[serde(skip)]
@@ -234,13 +592,19 @@ impl From<Service> for ServiceRepr {
         Self {
             address: service.address,
             service_type: service.service_type,
-            health_status: service.health_status,
+            health_status: Some(service.health_status),
             name: service.name,
             config_id: service.config_id,
+            labels: labels_from_db(&service.labels),
+            failback: service.failback,
             ..Default::default()
         }
     }
 }
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
 impl ServiceRepr {
     fn as_insertable(&self, asset_group_id: i32) -> NewService {
         NewService {
@@ -248,11 +612,260 @@ impl ServiceRepr {
             name: &self.name,
             address: &self.address,
             service_type: self.service_type,
-            health_status: self.health_status,
-            ..Default::default()
+            health_status: self.health_status.unwrap_or_default(),
+            config_id: self.config_id,
+            labels: labels_to_db(&self.labels),
+            failback: self.failback,
+        }
+    }
+
+    fn validate_labels(&self) -> Result<(), Error> {
+        for key in self.labels.keys() {
+            if key.is_empty() || key.contains('=') {
+                return Err(Error::DatabaseSyncError(format!(
+                    "Invalid label key '{}' on service '{}'",
+                    key, self.address
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_standbys(&self, reprs: &[Self]) -> Result<(), Error> {
+        if self.standby_addresses.is_empty() {
+            return Ok(());
+        }
+        if self.service_type != ServiceType::Processor {
+            return Err(Error::DatabaseSyncError(format!(
+                "Only processors can have standbys, but service '{}' is {:?}",
+                self.address, self.service_type
+            )));
+        }
+        for standby_addr in &self.standby_addresses {
+            if standby_addr == &self.address {
+                return Err(Error::DatabaseSyncError(format!(
+                    "Service '{}' cannot be its own standby",
+                    self.address
+                )));
+            }
+            // Standbys are idle until a failover, so they take no part in the routing.
+            let routed = reprs.iter().any(|repr| {
+                (repr.address == *standby_addr && !repr.output_addresses.is_empty())
+                    || repr.output_addresses.contains(standby_addr)
+            });
+            if routed {
+                return Err(Error::DatabaseSyncError(format!(
+                    "Standby '{}' of service '{}' cannot have inputs or outputs",
+                    standby_addr, self.address
+                )));
+            }
+            let processor = reprs.iter().all(|repr| {
+                repr.address != *standby_addr || repr.service_type == ServiceType::Processor
+            });
+            if !processor {
+                return Err(Error::DatabaseSyncError(format!(
+                    "Standby '{}' of service '{}' must be a processor",
+                    standby_addr, self.address
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_pool(&self, reprs: &[Self]) -> Result<(), Error> {
+        let pool = match &self.pool {
+            Some(pool) => pool,
+            None => return Ok(()),
+        };
+        if self.service_type != ServiceType::Processor {
+            return Err(Error::DatabaseSyncError(format!(
+                "Only processors can be pools, but service '{}' is {:?}",
+                self.address, self.service_type
+            )));
         }
+        if pool.replica_addresses.is_empty() || !self.standby_addresses.is_empty() {
+            return Err(Error::DatabaseSyncError(format!(
+                "Pool '{}' must have replicas and no standbys",
+                self.address
+            )));
+        }
+        for replica_addr in &pool.replica_addresses {
+            // Replicas are routed through the pool, so they take no part in the routing themselves.
+            let routed = *replica_addr == self.address
+                || reprs.iter().any(|repr| {
+                    (repr.address == *replica_addr
+                        && (!repr.output_addresses.is_empty()
+                            || !repr.standby_addresses.is_empty()
+                            || repr.pool.is_some()))
+                        || repr.output_addresses.contains(replica_addr)
+                });
+            if routed {
+                return Err(Error::DatabaseSyncError(format!(
+                    "Replica '{}' of pool '{}' cannot be a pool or have inputs, outputs or standbys",
+                    replica_addr, self.address
+                )));
+            }
+            let processor = reprs.iter().all(|repr| {
+                repr.address != *replica_addr || repr.service_type == ServiceType::Processor
+            });
+            if !processor {
+                return Err(Error::DatabaseSyncError(format!(
+                    "Replica '{}' of pool '{}' must be a processor",
+                    replica_addr, self.address
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_alerts(&self) -> Result<(), Error> {
+        for rule in &self.alerts {
+            if let Err((_, message)) = rule.validate() {
+                return Err(Error::DatabaseSyncError(format!(
+                    "{} on service '{}'",
+                    message, self.address
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Status a service reports over its websocket session, as a JSON text message. Fields that are
+/// left out are unchanged.
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceStatus {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_status: Option<HealthStatus>,
+    /// Name of the config the service is running.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_name: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, f64>,
+}
+
+/// A service and its ID, as listed by the services API.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct ServiceSummary {
+    pub service_id: i32,
+    #[serde(flatten)]
+    pub service: ServiceRepr,
+}
+
+/// A page of services, with the cursor to pass to get the next one.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct ServicePage {
+    pub services: Vec<ServiceSummary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+impl ServicePage {
+    pub fn get(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        query: &ServiceQuery,
+    ) -> Result<Self, Error> {
+        let (services, next_cursor) = Service::get_page(conn, asset_group_id, query)?;
+
+        let service_ids: Vec<i32> = services.iter().map(|service| service.service_id).collect();
+        let config_ids: Vec<i32> = services
+            .iter()
+            .filter_map(|service| service.config_id)
+            .collect();
+        let mut output_addresses = Service::get_output_addresses(conn, &service_ids)?;
+        let config_names = Config::get_names(conn, &config_ids)?;
+
+        let services = services
+            .into_iter()
+            .map(|service| {
+                let service_id = service.service_id;
+                let mut repr = ServiceRepr::from(service);
+                repr.config_name = repr.config_id.and_then(|id| config_names.get(&id).cloned());
+                repr.output_addresses = output_addresses.remove(&service_id).unwrap_or_default();
+                ServiceSummary {
+                    service_id,
+                    service: repr,
+                }
+            })
+            .collect();
+        Ok(Self {
+            services,
+            next_cursor,
+        })
+    }
+}
+
+/// A neighbouring service in the service graph.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct ServiceLink {
+    pub service_id: i32,
+    pub name: String,
+    pub address: String,
+    pub health_status: HealthStatus,
+}
+impl From<Service> for ServiceLink {
+    fn from(service: Service) -> Self {
+        Self {
+            service_id: service.service_id,
+            name: service.name,
+            address: service.address,
+            health_status: service.health_status,
+        }
+    }
+}
+
+/// A service with the services it receives from and sends to.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct ServiceDetail {
+    pub service_id: i32,
+    #[serde(flatten)]
+    pub service: ServiceRepr,
+    pub inputs: Vec<ServiceLink>,
+    pub outputs: Vec<ServiceLink>,
+}
+impl ServiceDetail {
+    /// Get a service of an asset group, or `None` if the group has no service with this ID.
+    pub fn get(
+        conn: &PgConnection,
+        asset_group_id: i32,
+        service_id: i32,
+    ) -> Result<Option<Self>, Error> {
+        let service: Option<Service> = services::table
+            .find(service_id)
+            .filter(services::asset_group_id.eq(asset_group_id))
+            .first(conn)
+            .optional()?;
+        let service = match service {
+            Some(service) => service,
+            None => return Ok(None),
+        };
+        let mut inputs = service.get_inputs(conn)?;
+        let mut outputs = service.get_outputs(conn)?;
+        inputs.sort_by(|a, b| a.address.cmp(&b.address));
+        outputs.sort_by(|a, b| a.address.cmp(&b.address));
+
+        let config_ids: Vec<i32> = service.config_id.into_iter().collect();
+        let mut config_names = Config::get_names(conn, &config_ids)?;
+        let mut repr = ServiceRepr::from(service);
+        repr.config_name = repr.config_id.and_then(|id| config_names.remove(&id));
+        repr.alerts = AlertRule::get_for_service(conn, service_id)?
+            .into_iter()
+            .map(|alert_rule| alert_rule.rule)
+            .collect();
+        repr.output_addresses = outputs
+            .iter()
+            .map(|output| output.address.clone())
+            .collect();
+        Ok(Some(Self {
+            service_id,
+            service: repr,
+            inputs: inputs.into_iter().map(ServiceLink::from).collect(),
+            outputs: outputs.into_iter().map(ServiceLink::from).collect(),
+        }))
     }
 }
+
 impl<'a> AssetRepr<'a> for ServiceRepr {
     type Asset = Service;
 
@@ -266,7 +879,17 @@ impl<'a> AssetRepr<'a> for ServiceRepr {
             if let Some(config_id) = service.config_id {
                 config_ids.push(config_id);
             }
-            service_map.insert(service.service_id, Self::from(service));
+            let service_id = service.service_id;
+            let mut repr = Self::from(service);
+            // Health changes on every connect, so it is listed with the services instead.
+            repr.health_status = None;
+            service_map.insert(service_id, repr);
+        }
+
+        // Attach alert rules.
+        let mut alerts = AlertRule::get_group_map(conn, asset_group_id)?;
+        for (service_id, repr) in service_map.iter_mut() {
+            repr.alerts = alerts.remove(service_id).unwrap_or_default();
         }
 
         // Populate config names.
@@ -280,8 +903,57 @@ impl<'a> AssetRepr<'a> for ServiceRepr {
             }
         }
 
+        // Attach standby addresses in priority order.
+        for standby in ServiceStandby::get_group(conn, asset_group_id)? {
+            let standby_address = match service_map.get(&standby.standby_service_id) {
+                Some(standby_service) => standby_service.address.clone(),
+                None => continue,
+            };
+            if let Some(repr) = service_map.get_mut(&standby.service_id) {
+                repr.standby_addresses.push(standby_address);
+            }
+        }
+
+        // Attach pools, with their replicas by address.
+        let mut replicas: HashMap<i32, Vec<String>> = HashMap::new();
+        for replica in PoolReplica::get_group(conn, asset_group_id)? {
+            if let Some(replica_service) = service_map.get(&replica.replica_service_id) {
+                replicas
+                    .entry(replica.service_id)
+                    .or_default()
+                    .push(replica_service.address.clone());
+            }
+        }
+        for pool in ServicePool::get_group(conn, asset_group_id)? {
+            let mut replica_addresses = replicas.remove(&pool.service_id).unwrap_or_default();
+            replica_addresses.sort();
+            if let Some(repr) = service_map.get_mut(&pool.service_id) {
+                repr.pool = Some(PoolRepr {
+                    replica_addresses,
+                    strategy: pool.strategy,
+                    load_metric: pool.load_metric,
+                });
+            }
+        }
+
+        // Routing moved to a standby is reported on the service it belongs to.
+        let failovers = Failover::get_active_group(conn, asset_group_id)?;
+        let rerouted: HashMap<i32, i32> = failovers
+            .iter()
+            .map(|failover| (failover.standby_service_id, failover.service_id))
+            .collect();
+
         // One pass over edges to attach addresses from outputs to the input services' list.
         for edge in edges {
+            let edge = ServiceEdge {
+                input_service_id: *rerouted
+                    .get(&edge.input_service_id)
+                    .unwrap_or(&edge.input_service_id),
+                output_service_id: *rerouted
+                    .get(&edge.output_service_id)
+                    .unwrap_or(&edge.output_service_id),
+                ..edge
+            };
             let output_address =
                 if let Some(output_service) = service_map.get(&edge.output_service_id) {
                     output_service.address.clone()
@@ -301,12 +973,18 @@ impl<'a> AssetRepr<'a> for ServiceRepr {
     fn try_merge_asset(&self, asset: &mut Self::Asset) -> Result<(), Error> {
         asset.name = self.name.clone();
         asset.address = self.address.clone();
+        asset.service_type = self.service_type;
+        asset.config_id = self.config_id;
+        asset.labels = labels_to_db(&self.labels);
+        asset.failback = self.failback;
+        // Syncing a service that registered by connecting declares it.
+        asset.declared = true;
         Ok(())
     }
 
-    /// Gets the identifier string for this item.
+    /// Gets the identifier string for this item. Must match `Service::get_string_id`.
     fn get_string_id(&self) -> &str {
-        &self.name
+        &self.address
     }
 
     fn sync_db(
@@ -314,49 +992,78 @@ impl<'a> AssetRepr<'a> for ServiceRepr {
         asset_group_id: i32,
         reprs: &mut Vec<Self>,
     ) -> Result<(), Error> {
+        // Resolve config names to ids before diffing so updates pick them up too.
+        let config_ids: HashMap<String, i32> = Config::get_ids(conn, asset_group_id)?;
+        for repr in reprs.iter_mut() {
+            repr.validate_labels()?;
+            repr.validate_alerts()?;
+            repr.config_id = match &repr.config_name {
+                Some(config_name) => match config_ids.get(config_name) {
+                    Some(&config_id) => Some(config_id),
+                    None => {
+                        return Err(Error::DatabaseSyncError(format!(
+                            "Failed to find config '{}'",
+                            config_name
+                        )))
+                    }
+                },
+                None => None,
+            };
+        }
+
+        for repr in reprs.iter() {
+            repr.validate_standbys(reprs)?;
+            repr.validate_pool(reprs)?;
+        }
+
+        // Routing is declared against primaries, so put any failed over edges back first.
+        let failovers = Failover::get_active_group(conn, asset_group_id)?;
+        for failover in &failovers {
+            failover.restore_edges(conn)?;
+        }
+
         let existing = Self::Asset::get_group_map(conn, asset_group_id)?;
         let (to_insert, to_update, to_delete) = Self::partition_diff(existing, reprs)?;
 
-        // Get config ids for names.
-        let config_ids: HashMap<String, i32> = Config::get_ids(conn, asset_group_id)?;
-        let mut new_services: Vec<NewService> = Vec::with_capacity(to_insert.len());
-        for repr in &to_insert {
-            let mut new_service = repr.as_insertable(asset_group_id);
-            if let Some(config_name) = &repr.config_name {
-                if let Some(&config_id) = config_ids.get(config_name) {
-                    new_service.config_id = Some(config_id);
-                } else {
-                    return Err(Error::DatabaseSyncError(format!(
-                        "Failed to find config '{}'",
-                        config_name
-                    )));
-                }
-            }
-            new_services.push(new_service);
-        }
+        let new_services: Vec<NewService> = to_insert
+            .iter()
+            .map(|repr| repr.as_insertable(asset_group_id))
+            .collect();
 
         // Insert new services.
 
         println!("Inserting new services: {:#?}", new_services);
         let inserted_services: Vec<Service> = new_services.insert_all(conn)?;
 
+        // Delete removed services before collecting addresses, so that edges to them fail.
+        let delete_ids: Vec<i32> = to_delete.iter().map(|asset| asset.service_id).collect();
+        Service::delete_all(conn, &delete_ids)?;
+
         // New service modifications are completed, so collect the address to ID map here.
         let addr_to_id: HashMap<String, i32> = Service::get_addr_to_id(conn, asset_group_id)?;
 
         // Connect new services to their outputs.
         for (repr, service) in to_insert.iter().zip(&inserted_services) {
             service.update_outputs(conn, asset_group_id, &addr_to_id, &repr)?;
+            service.update_standbys(conn, asset_group_id, &addr_to_id, repr)?;
+            ServicePool::sync_service(conn, service, &addr_to_id, repr.pool.as_ref())?;
+            AlertRule::sync_service(conn, service, &repr.alerts)?;
         }
 
         // Update existing services.
         for (repr, service) in &to_update {
             println!("Updating service: {:#?}", service);
             service.update_outputs(conn, asset_group_id, &addr_to_id, &repr)?;
+            service.update_standbys(conn, asset_group_id, &addr_to_id, repr)?;
+            ServicePool::sync_service(conn, service, &addr_to_id, repr.pool.as_ref())?;
             service.update(conn)?;
+            AlertRule::sync_service(conn, service, &repr.alerts)?;
         }
 
-        let delete_ids: Vec<i32> = to_delete.iter().map(|asset| asset.service_id).collect();
-        Config::delete_all(conn, &delete_ids)?;
+        // Failovers of deleted services are gone with them; the rest move their edges again.
+        for failover in Failover::get_active_group(conn, asset_group_id)? {
+            failover.apply_edges(conn)?;
+        }
 
         Ok(())
     }
@@ -533,6 +1240,107 @@ mod tests {
             Service::disconnect_address(conn, new_addr)?;
             new_service = Service::find_by_addr(conn, new_addr)?;
             assert_eq!(new_service.health_status, HealthStatus::Disconnected);
+
+            Service::upsert_healthy_address(conn, input_service.asset_group_id, new_addr)?;
+            Service::upsert_healthy_address(
+                conn,
+                input_service.asset_group_id,
+                &input_service.address,
+            )?;
+            Service::disconnect_addresses(conn, &[new_addr, &input_service.address])?;
+            for address in &[new_addr, &input_service.address] {
+                let service = Service::find_by_addr(conn, address)?;
+                assert_eq!(service.health_status, HealthStatus::Disconnected);
+            }
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_service_page() {
+        temp_asset_group_test(|conn: &PgConnection, asset_group: &AssetGroup| {
+            let asset_group_id = asset_group.asset_group_id;
+            let mut reprs: Vec<ServiceRepr> = ["cam_b", "cam_a", "arm_100%", "cam_c"]
+                .iter()
+                .enumerate()
+                .map(|(i, name)| ServiceRepr {
+                    address: format!("test_page{}:2222", i),
+                    name: name.to_string(),
+                    service_type: ServiceType::Input,
+                    labels: vec![("zone".to_string(), format!("{}", i % 2))]
+                        .into_iter()
+                        .collect(),
+                    ..Default::default()
+                })
+                .collect();
+            reprs[0].output_addresses = vec!["test_page2:2222".to_string()];
+            ServiceRepr::sync_db(conn, asset_group_id, &mut reprs)?;
+
+            // Walk the services sorted by name, two at a time.
+            let mut query = ServiceQuery {
+                sort: ServiceSort::Name,
+                limit: Some(2),
+                ..Default::default()
+            };
+            let first = ServicePage::get(conn, asset_group_id, &query)?;
+            let names: Vec<&str> = first
+                .services
+                .iter()
+                .map(|s| s.service.name.as_str())
+                .collect();
+            assert_eq!(names, vec!["arm_100%", "cam_a"]);
+            query.cursor = first.next_cursor.clone();
+            let second = ServicePage::get(conn, asset_group_id, &query)?;
+            let names: Vec<&str> = second
+                .services
+                .iter()
+                .map(|s| s.service.name.as_str())
+                .collect();
+            assert_eq!(names, vec!["cam_b", "cam_c"]);
+            assert_eq!(
+                second.services[0].service.output_addresses,
+                vec!["test_page2:2222".to_string()]
+            );
+            assert_eq!(second.next_cursor, None);
+
+            // Filters.
+            let by_label = ServicePage::get(
+                conn,
+                asset_group_id,
+                &ServiceQuery {
+                    label: Some("zone=1".to_string()),
+                    ..Default::default()
+                },
+            )?;
+            assert_eq!(by_label.services.len(), 2);
+            assert_eq!(by_label.services[0].service.labels["zone"], "1");
+            let by_name = ServicePage::get(
+                conn,
+                asset_group_id,
+                &ServiceQuery {
+                    name: Some("0%".to_string()),
+                    ..Default::default()
+                },
+            )?;
+            assert_eq!(by_name.services.len(), 1);
+            assert!(ServicePage::get(
+                conn,
+                asset_group_id,
+                &ServiceQuery {
+                    cursor: Some("not a cursor".to_string()),
+                    ..Default::default()
+                },
+            )
+            .is_err());
+
+            // Details include neighbouring services.
+            let arm_id = first.services[0].service_id;
+            let detail = ServiceDetail::get(conn, asset_group_id, arm_id)?.unwrap();
+            assert_eq!(detail.inputs.len(), 1);
+            assert_eq!(detail.inputs[0].name, "cam_b");
+            assert!(detail.outputs.is_empty());
+            assert!(ServiceDetail::get(conn, asset_group_id + 1, arm_id)?.is_none());
             Ok(())
         })
         .unwrap();
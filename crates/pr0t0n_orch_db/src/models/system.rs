@@ -1,4 +1,5 @@
 use diesel::PgConnection;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -6,17 +7,33 @@ use crate::{
     Error,
 };
 
+/// Strong ETag for the system of an asset group at a revision.
+pub fn revision_etag(asset_group_id: i32, revision: i64) -> String {
+    format!("\"{}-{}\"", asset_group_id, revision)
+}
+
 /// Model the entire database using a single serializable state struct.
-#[derive(Serialize, Deserialize, PartialEq, Default, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Default, Clone, Debug)]
 pub struct SystemRepr {
     pub asset_group_id: i32,
+    /// Revision of the asset group this was downloaded at. Uploads send it back as `If-Match`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<i64>,
     pub services: Vec<ServiceRepr>,
     pub configs: Vec<ConfigRepr>,
 }
+
+/// Revision of an asset group after an upload.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct SystemRevision {
+    pub asset_group_id: i32,
+    pub revision: i64,
+}
 impl SystemRepr {
     pub fn get_group(conn: &PgConnection, asset_group_id: i32) -> Result<Self, Error> {
         Ok(Self {
             asset_group_id,
+            revision: None,
             services: ServiceRepr::get_group(conn, asset_group_id)?,
             configs: ConfigRepr::get_group(conn, asset_group_id)?,
         })
@@ -46,6 +63,7 @@ mod tests {
             {
                 let system_repr = SystemRepr {
                     asset_group_id,
+                    revision: None,
                     services: vec![
                         ServiceRepr {
                             address: "localhost:123".to_string(),
@@ -75,17 +93,23 @@ mod tests {
 
                 let new_system_repr = SystemRepr::get_group(conn, asset_group_id)?;
                 assert_json_eq!(system_repr, new_system_repr);
+
+                // Re-applying an exported system must not change it.
+                new_system_repr.clone().sync_db(conn)?;
+                let reapplied_system_repr = SystemRepr::get_group(conn, asset_group_id)?;
+                assert_json_eq!(new_system_repr, reapplied_system_repr);
             }
 
             // Now delete the second node and make sure it's deleted in the database.
             {
                 let system_repr = SystemRepr {
                     asset_group_id,
+                    revision: None,
                     services: vec![ServiceRepr {
                         address: "localhost:123".to_string(),
                         service_type: ServiceType::Input,
                         name: "localhost:123".to_string(),
-                        output_addresses: vec!["localhost:234".to_string()],
+                        output_addresses: vec![],
                         config_name: Some("TestConfig".to_string()),
                         ..Default::default()
                     }],
@@ -98,8 +122,8 @@ mod tests {
                 };
                 system_repr.clone().sync_db(conn)?;
 
-                // let new_system_repr = SystemRepr::get_group(conn, asset_group_id)?;
-                // assert_json_eq!(system_repr, new_system_repr);
+                let new_system_repr = SystemRepr::get_group(conn, asset_group_id)?;
+                assert_json_eq!(system_repr, new_system_repr);
             }
             Ok(())
         })
@@ -1,8 +1,45 @@
+table! {
+    alert_rules (alert_rule_id) {
+        alert_rule_id -> Int4,
+        asset_group_id -> Int4,
+        service_id -> Int4,
+        condition -> Jsonb,
+        sender -> Varchar,
+        recipients -> Array<Text>,
+        channels -> Array<Text>,
+        dedup_key -> Nullable<Varchar>,
+        for_seconds -> Int4,
+        escalation -> Jsonb,
+    }
+}
+
+table! {
+    alert_states (alert_state_id) {
+        alert_state_id -> Int4,
+        asset_group_id -> Int4,
+        dedup_key -> Varchar,
+        alert_rule_id -> Int4,
+        address -> Varchar,
+        status -> Varchar,
+        message -> Text,
+        since -> Timestamptz,
+        fired_at -> Nullable<Timestamptz>,
+        resolved_at -> Nullable<Timestamptz>,
+        acknowledged_at -> Nullable<Timestamptz>,
+        acknowledged_by -> Nullable<Varchar>,
+        escalations -> Int4,
+        flapping -> Bool,
+        transitions -> Array<Timestamptz>,
+    }
+}
+
 table! {
     asset_groups (asset_group_id) {
         asset_group_id -> Int4,
         name -> Varchar,
         description -> Text,
+        revision -> Int8,
+        registration_policy -> Varchar,
     }
 }
 
@@ -17,13 +54,73 @@ table! {
 }
 
 table! {
-    event_logs (config_id) {
-        config_id -> Timestamp,
+    dead_letters (dead_letter_id) {
+        dead_letter_id -> Int4,
         asset_group_id -> Int4,
+        alert_rule_id -> Int4,
+        address -> Varchar,
+        channel -> Varchar,
+        payload -> Jsonb,
+        error -> Text,
+        attempts -> Int4,
+        failed_at -> Timestamptz,
+    }
+}
+
+table! {
+    event_logs (event_log_id) {
+        event_log_id -> Int4,
+        asset_group_id -> Int4,
+        logged_at -> Timestamptz,
         entry -> Jsonb,
     }
 }
 
+table! {
+    failovers (failover_id) {
+        failover_id -> Int4,
+        asset_group_id -> Int4,
+        service_id -> Int4,
+        standby_service_id -> Int4,
+        started_at -> Timestamptz,
+        ended_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    maintenance_windows (maintenance_window_id) {
+        maintenance_window_id -> Int4,
+        asset_group_id -> Int4,
+        address -> Nullable<Varchar>,
+        label -> Nullable<Varchar>,
+        reason -> Text,
+        starts_at -> Timestamptz,
+        ends_at -> Timestamptz,
+        recurrence -> Varchar,
+        until -> Nullable<Timestamptz>,
+        expired_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    pending_services (pending_service_id) {
+        pending_service_id -> Int4,
+        asset_group_id -> Int4,
+        address -> Varchar,
+        first_seen_at -> Timestamptz,
+        last_seen_at -> Timestamptz,
+    }
+}
+
+table! {
+    pool_replicas (service_id, replica_service_id) {
+        service_id -> Int4,
+        replica_service_id -> Int4,
+        asset_group_id -> Int4,
+    }
+}
+
 table! {
     service_edges (input_service_id, output_service_id) {
         input_service_id -> Int4,
@@ -32,6 +129,24 @@ table! {
     }
 }
 
+table! {
+    service_pools (service_id) {
+        service_id -> Int4,
+        asset_group_id -> Int4,
+        strategy -> Varchar,
+        load_metric -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    service_standbys (service_id, standby_service_id) {
+        service_id -> Int4,
+        standby_service_id -> Int4,
+        asset_group_id -> Int4,
+        priority -> Int4,
+    }
+}
+
 table! {
     services (service_id) {
         service_id -> Int4,
@@ -41,6 +156,10 @@ table! {
         service_type -> Varchar,
         health_status -> Varchar,
         config_id -> Nullable<Int4>,
+        labels -> Array<Text>,
+        declared -> Bool,
+        last_connected_at -> Nullable<Timestamptz>,
+        failback -> Bool,
     }
 }
 
@@ -51,17 +170,38 @@ table! {
     }
 }
 
+joinable!(alert_rules -> asset_groups (asset_group_id));
+joinable!(alert_rules -> services (service_id));
+joinable!(alert_states -> asset_groups (asset_group_id));
 joinable!(configs -> asset_groups (asset_group_id));
+joinable!(dead_letters -> asset_groups (asset_group_id));
 joinable!(event_logs -> asset_groups (asset_group_id));
+joinable!(failovers -> asset_groups (asset_group_id));
+joinable!(maintenance_windows -> asset_groups (asset_group_id));
+joinable!(pending_services -> asset_groups (asset_group_id));
+joinable!(pool_replicas -> asset_groups (asset_group_id));
+joinable!(pool_replicas -> service_pools (service_id));
 joinable!(service_edges -> asset_groups (asset_group_id));
+joinable!(service_pools -> asset_groups (asset_group_id));
+joinable!(service_pools -> services (service_id));
+joinable!(service_standbys -> asset_groups (asset_group_id));
 joinable!(services -> asset_groups (asset_group_id));
 joinable!(services -> configs (config_id));
 
 allow_tables_to_appear_in_same_query!(
+    alert_rules,
+    alert_states,
     asset_groups,
     configs,
+    dead_letters,
     event_logs,
+    failovers,
+    maintenance_windows,
+    pending_services,
+    pool_replicas,
     service_edges,
+    service_pools,
+    service_standbys,
     services,
     users,
 );